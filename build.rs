@@ -0,0 +1,83 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2019, the dump_ciede2000 contributors
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//  list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//  this list of conditions and the following disclaimer in the documentation
+//  and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+fn main() {
+    #[cfg(feature = "capi")]
+    capi::generate();
+}
+
+#[cfg(feature = "capi")]
+mod capi {
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+
+    // Both files are written next to the cdylib so that the build directory
+    // can be used directly as a prefix:
+    //   PKG_CONFIG_PATH=target/release pkg-config --cflags --libs dump_ciede2000
+    pub fn generate() {
+        println!("cargo:rerun-if-changed=src/capi.rs");
+        println!("cargo:rerun-if-changed=cbindgen.toml");
+        println!("cargo:rerun-if-env-changed=CAPI_PREFIX");
+
+        let crate_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+        // OUT_DIR is <target>/<profile>/build/<package>-<hash>/out
+        let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+        let profile_dir = out_dir.ancestors().nth(3).unwrap().to_path_buf();
+        let include_dir = profile_dir.join("include");
+        fs::create_dir_all(&include_dir).unwrap();
+
+        let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml")).unwrap();
+        cbindgen::Builder::new()
+            .with_crate(&crate_dir)
+            .with_config(config)
+            .generate()
+            .expect("Unable to generate C header")
+            .write_to_file(include_dir.join("dump_ciede2000.h"));
+
+        // Set CAPI_PREFIX when packaging for an install location.
+        let (prefix, libdir) = match env::var("CAPI_PREFIX") {
+            Ok(prefix) => (prefix, "${prefix}/lib".to_owned()),
+            Err(_) => (profile_dir.display().to_string(), "${prefix}".to_owned()),
+        };
+        let pc = format!(
+            "prefix={}\n\
+             libdir={}\n\
+             includedir=${{prefix}}/include\n\
+             \n\
+             Name: dump_ciede2000\n\
+             Description: Video quality metric based on CIEDE2000 color difference\n\
+             Version: {}\n\
+             Libs: -L${{libdir}} -ldump_ciede2000\n\
+             Libs.private: -lm -ldl -lpthread\n\
+             Cflags: -I${{includedir}}\n",
+            prefix,
+            libdir,
+            env::var("CARGO_PKG_VERSION").unwrap()
+        );
+        fs::write(profile_dir.join("dump_ciede2000.pc"), pc).unwrap();
+    }
+}
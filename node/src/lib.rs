@@ -0,0 +1,210 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2019, the dump_ciede2000 contributors
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//  list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//  this list of conditions and the following disclaimer in the documentation
+//  and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Node.js bindings. Build with `npm run build` in this directory.
+
+#[macro_use]
+extern crate napi_derive;
+
+use std::fs::File;
+use std::io::BufReader;
+
+use dump_ciede2000::*;
+use napi::bindgen_prelude::*;
+use napi::{Env, Task};
+
+/// Format of the frames passed to `scoreFrame`.
+#[napi(object)]
+pub struct FrameFormat {
+    pub width: u32,
+    pub height: u32,
+    /// 8, 10 or 12
+    pub bit_depth: u32,
+    /// log2 of the horizontal chroma subsampling: 0, 1 or 2 (4:1:1)
+    pub xdec: u32,
+    /// 1 if chroma is vertically subsampled
+    pub ydec: u32,
+    /// Whether vertically subsampled chroma is subsampled per field
+    pub interlaced: Option<bool>,
+}
+
+#[napi(object)]
+pub struct ScoreOptions {
+    /// Maximum number of frames to process
+    pub limit: Option<u32>,
+    /// Use SIMD when available. Defaults to true.
+    pub simd: Option<bool>,
+}
+
+#[napi(object)]
+pub struct Scores {
+    /// Score of each frame
+    pub frames: Vec<f64>,
+    /// Mean of the frame scores
+    pub total: f64,
+}
+
+pub struct ScoreFiles {
+    path1: String,
+    path2: String,
+    limit: Option<usize>,
+    simd: bool,
+}
+
+impl ScoreFiles {
+    fn run(&self) -> std::result::Result<Vec<f64>, String> {
+        let open = |path: &str| File::open(path).map_err(|e| format!("{}: {}", path, e));
+        let mut file1 = BufReader::new(open(&self.path1)?);
+        let mut file2 = BufReader::new(open(&self.path2)?);
+        let mut video1 = Y4mSource::new(&mut file1).map_err(|e| e.to_string())?;
+        let mut video2 = Y4mSource::new(&mut file2).map_err(|e| e.to_string())?;
+        let info = video1.info();
+        if info != video2.info() {
+            return Err("Video formats do not match".to_owned());
+        }
+        let mut scores = Vec::new();
+        loop {
+            let frame1 = video1.next_frame().map_err(|e| e.to_string())?;
+            let frame2 = video2.next_frame().map_err(|e| e.to_string())?;
+            let (frame1, frame2) = match (frame1, frame2) {
+                (Some(frame1), Some(frame2)) => (frame1, frame2),
+                _ => break,
+            };
+            scores.push(frame_score(&info, &frame1, &frame2, self.simd));
+            if Some(scores.len()) == self.limit {
+                break;
+            }
+        }
+        Ok(scores)
+    }
+}
+
+fn frame_score(info: &FrameInfo, frame1: &Frame, frame2: &Frame, simd: bool) -> f64 {
+    let sum = delta_e_frame(info, frame1, frame2, simd);
+    delta_e_to_score(sum / ((info.width * info.height) as f64))
+}
+
+impl Task for ScoreFiles {
+    type Output = Vec<f64>;
+    type JsValue = Scores;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        self.run().map_err(Error::from_reason)
+    }
+
+    fn resolve(&mut self, _env: Env, frames: Self::Output) -> Result<Self::JsValue> {
+        let total = frames.iter().sum::<f64>() / (frames.len() as f64);
+        Ok(Scores { frames, total })
+    }
+}
+
+/// Scores two YUV4MPEG2 files against each other.
+#[napi(ts_return_type = "Promise<Scores>")]
+pub fn score_files(
+    path1: String,
+    path2: String,
+    options: Option<ScoreOptions>,
+) -> AsyncTask<ScoreFiles> {
+    let options = options.unwrap_or(ScoreOptions {
+        limit: None,
+        simd: None,
+    });
+    AsyncTask::new(ScoreFiles {
+        path1,
+        path2,
+        limit: options.limit.map(|l| l as usize),
+        simd: options.simd.unwrap_or(true),
+    })
+}
+
+pub struct ScoreFrame {
+    info: FrameInfo,
+    frame1: Vec<u8>,
+    frame2: Vec<u8>,
+    simd: bool,
+}
+
+fn split_planes<'a>(info: &FrameInfo, buf: &'a [u8]) -> Result<Frame<'a>> {
+    info.split_planes(buf).ok_or_else(|| {
+        let (luma_len, chroma_len) = info.plane_lens();
+        Error::from_reason(format!(
+            "Frame buffer holds {} bytes, expected {}",
+            buf.len(),
+            luma_len + 2 * chroma_len
+        ))
+    })
+}
+
+impl Task for ScoreFrame {
+    type Output = f64;
+    type JsValue = f64;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        Ok(frame_score(
+            &self.info,
+            &split_planes(&self.info, &self.frame1)?,
+            &split_planes(&self.info, &self.frame2)?,
+            self.simd,
+        ))
+    }
+
+    fn resolve(&mut self, _env: Env, score: Self::Output) -> Result<Self::JsValue> {
+        Ok(score)
+    }
+}
+
+/// Scores a pair of frames stored as consecutive Y, U and V planes, like the
+/// frame data of a YUV4MPEG2 file.
+#[napi(ts_return_type = "Promise<number>")]
+pub fn score_frame(
+    format: FrameFormat,
+    reference: Buffer,
+    distorted: Buffer,
+    simd: Option<bool>,
+) -> Result<AsyncTask<ScoreFrame>> {
+    if ![8, 10, 12].contains(&format.bit_depth) || format.xdec > 2 || format.ydec > 1 {
+        return Err(Error::from_reason("Unsupported frame format".to_owned()));
+    }
+    let info = FrameInfo {
+        width: format.width as usize,
+        height: format.height as usize,
+        bit_depth: format.bit_depth as usize,
+        xdec: format.xdec as usize,
+        ydec: format.ydec as usize,
+        interlaced: format.interlaced.unwrap_or(false),
+        matrix: Matrix::Bt709,
+        range: Range::Limited,
+        transfer: Transfer::Sdr,
+        vision: Vision::Normal,
+    };
+    info.check_size().map_err(Error::from_reason)?;
+    Ok(AsyncTask::new(ScoreFrame {
+        info,
+        frame1: reference.to_vec(),
+        frame2: distorted.to_vec(),
+        simd: simd.unwrap_or(true),
+    }))
+}
@@ -0,0 +1,108 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2019, the dump_ciede2000 contributors
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//  list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//  this list of conditions and the following disclaimer in the documentation
+//  and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Running a command when the score stays low for some frames, to alert
+//! whoever is monitoring a live stream.
+
+use std::io;
+use std::process::{Child, Command, ExitStatus, Stdio};
+
+pub struct DropAlert {
+    command: String,
+    threshold: f64,
+    /// Frames in a row that must score below `threshold`
+    frames: usize,
+    /// Frames in a row scored below `threshold` so far
+    streak: usize,
+    /// Commands started that have not exited yet
+    running: Vec<Child>,
+}
+
+impl DropAlert {
+    pub fn new(command: String, threshold: f64, frames: usize) -> Self {
+        DropAlert {
+            command,
+            threshold,
+            frames,
+            streak: 0,
+            running: Vec::new(),
+        }
+    }
+
+    /// Takes the score of the next frame, running the command once each time
+    /// `frames` in a row have scored below the threshold. The command runs in
+    /// the background so that scoring keeps pace.
+    pub fn push(&mut self, frame: usize, score: f64) {
+        self.running.retain_mut(|child| match child.try_wait() {
+            Ok(Some(status)) => {
+                report(status);
+                false
+            }
+            Ok(None) => true,
+            Err(_) => false,
+        });
+        if score >= self.threshold {
+            self.streak = 0;
+            return;
+        }
+        self.streak += 1;
+        if self.streak != self.frames {
+            return;
+        }
+        match self.spawn(frame, score) {
+            Ok(child) => self.running.push(child),
+            Err(e) => eprintln!("Failed to run --on-drop command: {}", e),
+        }
+    }
+
+    /// Waits for the commands still running.
+    pub fn finish(self) {
+        for mut child in self.running {
+            if let Ok(status) = child.wait() {
+                report(status);
+            }
+        }
+    }
+
+    // The frame and score are $1 and $2 of the command. Its output goes to
+    // stderr, keeping stdout for the scores, and it cannot read stdin, which
+    // may be an input.
+    fn spawn(&self, frame: usize, score: f64) -> io::Result<Child> {
+        Command::new("sh")
+            .args(["-c", &self.command, "sh"])
+            .arg(frame.to_string())
+            .arg(format!("{:.4}", score))
+            .stdin(Stdio::null())
+            .stdout(io::stderr())
+            .spawn()
+    }
+}
+
+fn report(status: ExitStatus) {
+    if !status.success() {
+        eprintln!("--on-drop command failed: {}", status);
+    }
+}
@@ -0,0 +1,259 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2019, the dump_ciede2000 contributors
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//  list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//  this list of conditions and the following disclaimer in the documentation
+//  and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Reading inputs ahead, to realign them around frames that are missing from
+//! video2 with `--tolerate-drops`, to find the offset between them with
+//! `--auto-align` and to check that they run in the same order.
+
+use std::collections::VecDeque;
+
+use dump_ciede2000::{mean_luma, Frame, FrameInfo, FrameSource};
+
+use crate::baseline::InputHash;
+use crate::next_frame;
+
+/// Frames of an input, which can be read ahead and put back when buffered.
+/// Unbuffered queues hand out the frames of the source without copying once
+/// the frames read ahead are used up.
+pub struct FrameQueue<'s, 'a> {
+    source: &'s mut (dyn FrameSource + 'a),
+    name: &'static str,
+    info: FrameInfo,
+    buffered: bool,
+    /// Frames read ahead or put back
+    ahead: VecDeque<Vec<u8>>,
+    /// The frame last returned by `next`, when buffered
+    current: Vec<u8>,
+    /// Number of frames read from the source
+    read: usize,
+    /// Hash of every frame read from the source, if kept
+    pub hash: Option<InputHash>,
+}
+
+impl<'s, 'a> FrameQueue<'s, 'a> {
+    pub fn new(
+        source: &'s mut (dyn FrameSource + 'a),
+        name: &'static str,
+        buffered: bool,
+        hash: bool,
+    ) -> Self {
+        FrameQueue {
+            info: source.info(),
+            source,
+            name,
+            buffered,
+            ahead: VecDeque::new(),
+            current: Vec::new(),
+            read: 0,
+            hash: if hash { Some(InputHash::new()) } else { None },
+        }
+    }
+
+    // Reads a frame from the source into a buffer of its own.
+    fn read(&mut self) -> Option<Vec<u8>> {
+        let frame = next_frame(&mut *self.source, self.name)?;
+        self.read += 1;
+        if let Some(hash) = &mut self.hash {
+            hash.add(&frame);
+        }
+        Some([frame.y, frame.u, frame.v].concat())
+    }
+
+    pub fn next(&mut self) -> Option<Frame<'_>> {
+        if !self.buffered && self.ahead.is_empty() {
+            let frame = next_frame(&mut *self.source, self.name)?;
+            self.read += 1;
+            if let Some(hash) = &mut self.hash {
+                hash.add(&frame);
+            }
+            return Some(frame);
+        }
+        self.current = match self.ahead.pop_front() {
+            Some(frame) => frame,
+            None => self.read()?,
+        };
+        self.info.split_planes(&self.current)
+    }
+
+    /// Position in the source of the frame `next` returns next, counting
+    /// from 0 at the first frame read through the queue.
+    pub fn position(&self) -> usize {
+        // The frames ahead are always the last ones read
+        self.read - self.ahead.len()
+    }
+
+    /// The `n`th frame after the one last returned by `next`, counting from
+    /// 1. Unbuffered queues can only look ahead before the first `next`.
+    pub fn peek(&mut self, n: usize) -> Option<Frame<'_>> {
+        while self.ahead.len() < n {
+            let frame = self.read()?;
+            self.ahead.push_back(frame);
+        }
+        self.info.split_planes(&self.ahead[n - 1])
+    }
+
+    /// Drops the next `n` frames, which must have been peeked at.
+    pub fn discard(&mut self, n: usize) {
+        self.ahead.drain(..n);
+    }
+
+    /// Swaps the frame last returned by `next` into `buf`, which buffered
+    /// queues hold in a buffer of their own, and what `buf` held into the
+    /// queue.
+    pub fn swap_current(&mut self, buf: &mut Vec<u8>) {
+        std::mem::swap(&mut self.current, buf);
+    }
+
+    /// Makes `next` return the frame it last returned again.
+    pub fn unread(&mut self) {
+        self.ahead.push_front(std::mem::take(&mut self.current));
+    }
+}
+
+/// Frames at the start of the inputs compared by [`check_order`].
+const ORDER_WINDOW: usize = 12;
+/// Spread of the mean luma over the window, from 0 to 1, below which the
+/// frames are too alike to tell their order.
+const ORDER_MIN_DEVIATION: f64 = 0.005;
+/// Correlation of the two sequences of mean luma expected of inputs in the
+/// same order.
+const ORDER_CORRELATION: f64 = 0.5;
+
+/// Compares the mean luma of the first frames of the inputs, and describes
+/// how video2 seems to be ordered when it does not follow video1. Inputs too
+/// short or too still to tell pass.
+pub fn check_order(queue1: &mut FrameQueue, queue2: &mut FrameQueue) -> Option<String> {
+    let luma = [window_luma(queue1), window_luma(queue2)];
+    let len = luma[0].len().min(luma[1].len());
+    let (luma1, luma2) = (&luma[0][..len], &luma[1][..len]);
+    if len < 4 || deviation(luma1) < ORDER_MIN_DEVIATION {
+        return None;
+    }
+    let forward = correlation(luma1, luma2);
+    if forward >= ORDER_CORRELATION {
+        return None;
+    }
+    let reversed: Vec<f64> = luma2.iter().rev().copied().collect();
+    let backward = correlation(luma1, &reversed);
+    Some(if backward >= ORDER_CORRELATION {
+        format!(
+            "video2 seems to run backwards: the brightness of its first {} frames follows video1 in reverse (correlation {:.2}, forwards {:.2})",
+            len, backward, forward
+        )
+    } else {
+        format!(
+            "video2 seems to be out of order: the brightness of its first {} frames does not follow video1 (correlation {:.2})",
+            len, forward
+        )
+    })
+}
+
+// Mean luma of the frames of the window that the input has.
+fn window_luma(queue: &mut FrameQueue) -> Vec<f64> {
+    let info = queue.info;
+    (1..=ORDER_WINDOW)
+        .map_while(|n| queue.peek(n).map(|frame| mean_luma(&info, &frame)))
+        .collect()
+}
+
+/// Smallest number of frame pairs [`best_offset`] compares at an offset.
+const OFFSET_MIN_PAIRS: usize = 4;
+/// Correlation of the changes in luma between frames below which no offset
+/// is taken to match.
+const OFFSET_CORRELATION: f64 = 0.5;
+
+/// Finds the offset of video2 into video1, from `-max` to `max` frames, at
+/// which the changes of the luma signatures from one frame to the next
+/// correlate best. A positive offset pairs frame 0 of video2 with that frame
+/// of video1, and a negative one frame 0 of video1 with that frame of
+/// video2. Returns `None` when no offset correlates well, as for still
+/// inputs.
+pub fn best_offset(
+    signatures1: &[Vec<f64>],
+    signatures2: &[Vec<f64>],
+    max: usize,
+) -> Option<isize> {
+    // Changes from frame to frame, so a still picture does not match itself
+    // at every offset
+    let changes = |signatures: &[Vec<f64>]| -> Vec<Vec<f64>> {
+        signatures
+            .windows(2)
+            .map(|pair| pair[1].iter().zip(&pair[0]).map(|(b, a)| b - a).collect())
+            .collect()
+    };
+    let (changes1, changes2) = (changes(signatures1), changes(signatures2));
+    let max = max as isize;
+    let mut best: Option<(isize, f64)> = None;
+    for offset in -max..=max {
+        let (skip1, skip2) = (offset.max(0) as usize, (-offset).max(0) as usize);
+        let pairs = changes1
+            .len()
+            .saturating_sub(skip1)
+            .min(changes2.len().saturating_sub(skip2));
+        if pairs < OFFSET_MIN_PAIRS {
+            continue;
+        }
+        let flatten =
+            |changes: &[Vec<f64>], skip| -> Vec<f64> { changes[skip..][..pairs].concat() };
+        let r = correlation(&flatten(&changes1, skip1), &flatten(&changes2, skip2));
+        // Nearer offsets win ties
+        let better = match best {
+            Some((best, best_r)) => r > best_r || r == best_r && offset.abs() < best.abs(),
+            None => true,
+        };
+        if better {
+            best = Some((offset, r));
+        }
+    }
+    best.filter(|&(_, r)| r >= OFFSET_CORRELATION)
+        .map(|(offset, _)| offset)
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+fn deviation(values: &[f64]) -> f64 {
+    let mean = mean(values);
+    (values.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / values.len() as f64).sqrt()
+}
+
+// Pearson correlation, 0 when either sequence is constant.
+fn correlation(a: &[f64], b: &[f64]) -> f64 {
+    let (mean_a, mean_b) = (mean(a), mean(b));
+    let (mut ab, mut aa, mut bb) = (0., 0., 0.);
+    for (a, b) in a.iter().zip(b) {
+        let (a, b) = (a - mean_a, b - mean_b);
+        ab += a * b;
+        aa += a * a;
+        bb += b * b;
+    }
+    if aa == 0. || bb == 0. {
+        0.
+    } else {
+        ab / (aa * bb).sqrt()
+    }
+}
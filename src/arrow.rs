@@ -0,0 +1,111 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2019, the dump_ciede2000 contributors
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//  list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//  this list of conditions and the following disclaimer in the documentation
+//  and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Per-frame records written as an Arrow IPC stream, to load large corpora
+//! into polars or pandas without parsing text.
+
+use arrow_array::{ArrayRef, Float64Array, Int64Array, RecordBatch};
+use arrow_ipc::writer::StreamWriter;
+use arrow_schema::{ArrowError, DataType, Field, Schema};
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Frames buffered before they are written out as a record batch
+const BATCH_FRAMES: usize = 256;
+
+/// Writes the frame number, mean ΔE and scores of each frame as a row of an
+/// Arrow IPC stream, in batches of [`BATCH_FRAMES`].
+pub struct ArrowWriter {
+    writer: StreamWriter<Box<dyn Write>>,
+    schema: Arc<Schema>,
+    frames: Vec<i64>,
+    /// Mean ΔE, then the scores
+    values: Vec<Vec<f64>>,
+}
+
+impl ArrowWriter {
+    /// Writes to `path`, or to stdout if it is `-`, with a column for each
+    /// of `columns` after `frame` and `mean_delta_e`.
+    pub fn new(path: &Path, columns: &[&str]) -> io::Result<Self> {
+        let output: Box<dyn Write> = if path == Path::new("-") {
+            Box::new(io::stdout())
+        } else {
+            Box::new(io::BufWriter::new(File::create(path)?))
+        };
+        let mut fields = vec![
+            Field::new("frame", DataType::Int64, false),
+            Field::new("mean_delta_e", DataType::Float64, false),
+        ];
+        fields.extend(
+            columns
+                .iter()
+                .map(|name| Field::new(*name, DataType::Float64, false)),
+        );
+        let schema = Arc::new(Schema::new(fields));
+        let writer = StreamWriter::try_new(output, &schema).map_err(io::Error::other)?;
+        Ok(ArrowWriter {
+            writer,
+            schema,
+            frames: Vec::new(),
+            values: vec![Vec::new(); columns.len() + 1],
+        })
+    }
+
+    pub fn push(&mut self, frame: usize, mean_delta_e: f64, scores: &[f64]) -> io::Result<()> {
+        self.frames.push(frame as i64);
+        let values = std::iter::once(&mean_delta_e).chain(scores);
+        for (column, &value) in self.values.iter_mut().zip(values) {
+            column.push(value);
+        }
+        if self.frames.len() == BATCH_FRAMES {
+            self.write_batch().map_err(io::Error::other)?;
+        }
+        Ok(())
+    }
+
+    fn write_batch(&mut self) -> Result<(), ArrowError> {
+        let mut columns: Vec<ArrayRef> =
+            vec![Arc::new(Int64Array::from(std::mem::take(&mut self.frames)))];
+        for values in &mut self.values {
+            columns.push(Arc::new(Float64Array::from(std::mem::take(values))));
+        }
+        let batch = RecordBatch::try_new(self.schema.clone(), columns)?;
+        self.writer.write(&batch)?;
+        // Readers of a pipe get each batch as soon as it is scored
+        self.writer.flush()
+    }
+
+    /// Writes the frames left and the end of stream marker.
+    pub fn finish(mut self) -> io::Result<()> {
+        if !self.frames.is_empty() {
+            self.write_batch().map_err(io::Error::other)?;
+        }
+        self.writer.finish().map_err(io::Error::other)?;
+        self.writer.get_mut().flush()
+    }
+}
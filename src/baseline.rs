@@ -0,0 +1,151 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2019, the dump_ciede2000 contributors
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//  list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//  this list of conditions and the following disclaimer in the documentation
+//  and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Scores from an earlier `--json` run, to gate on regressions against.
+//!
+//! Baselines written by `--update-baseline` also hold a `baseline` object
+//! with a hash of the settings that change scores and of the frames of
+//! each input.
+
+use std::fs;
+use std::path::Path;
+
+use dump_ciede2000::Frame;
+
+use crate::json::{json_object, parse_json, JsonValue};
+
+/// 64-bit FNV-1a, which is enough to tell inputs and settings apart.
+pub struct InputHash(u64);
+
+impl InputHash {
+    pub fn new() -> InputHash {
+        InputHash(0xcbf2_9ce4_8422_2325)
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 = (self.0 ^ b as u64).wrapping_mul(0x100_0000_01b3);
+        }
+    }
+
+    pub fn add(&mut self, frame: &Frame) {
+        self.update(frame.y);
+        self.update(frame.u);
+        self.update(frame.v);
+    }
+
+    pub fn hex(&self) -> String {
+        format!("{:016x}", self.0)
+    }
+}
+
+/// Hash of the `--json` settings, leaving out the kernel since it does not
+/// change what is measured.
+pub fn config_hash(settings: &[(&str, String)]) -> String {
+    let settings: Vec<(&str, String)> = settings
+        .iter()
+        .filter(|(key, _)| *key != "simd")
+        .cloned()
+        .collect();
+    let mut hash = InputHash::new();
+    hash.update(json_object(&settings).as_bytes());
+    hash.hex()
+}
+
+pub struct Baseline {
+    /// The score of each frame, infinite for identical frames.
+    pub frames: Vec<f64>,
+    pub total: f64,
+    /// The settings hash, if written by `--update-baseline`.
+    pub config: Option<String>,
+}
+
+// `null` stands for the infinite score of identical frames.
+fn score(value: Option<&JsonValue>) -> Option<f64> {
+    match value? {
+        JsonValue::Null => Some(f64::INFINITY),
+        value => value.as_f64(),
+    }
+}
+
+impl Baseline {
+    pub fn load(path: &Path) -> Result<Baseline, String> {
+        let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let document = parse_json(&text)?;
+        let frames = document
+            .get("frames")
+            .and_then(JsonValue::as_array)
+            .ok_or("no frames array")?
+            .iter()
+            .enumerate()
+            .map(|(i, frame)| score(frame.get("score")).ok_or(format!("no score for frame {}", i)))
+            .collect::<Result<_, _>>()?;
+        let total = score(document.get("total").and_then(|total| total.get("score")))
+            .ok_or("no total score")?;
+        let config = document
+            .get("baseline")
+            .and_then(|baseline| baseline.get("config"))
+            .and_then(JsonValue::as_str)
+            .map(str::to_owned);
+        Ok(Baseline {
+            frames,
+            total,
+            config,
+        })
+    }
+
+    /// Lines describing every score more than `max_regression` below the
+    /// baseline, along with any difference in the number of frames.
+    pub fn regressions(&self, frames: &[f64], total: f64, max_regression: f64) -> Vec<String> {
+        let mut report = Vec::new();
+        if frames.len() != self.frames.len() {
+            report.push(format!(
+                "scored {} frames, baseline has {}",
+                frames.len(),
+                self.frames.len()
+            ));
+        }
+        let scores = frames
+            .iter()
+            .zip(&self.frames)
+            .enumerate()
+            .map(|(i, scores)| (format!("{:08}", i), scores))
+            .chain(Some(("Total".to_owned(), (&total, &self.total))));
+        for (name, (&score, &baseline)) in scores {
+            // Identical frames in both runs have an infinite difference of NaN
+            if baseline - score > max_regression {
+                report.push(format!(
+                    "{}: {:2.4}, baseline {:2.4} ({:+.4})",
+                    name,
+                    score,
+                    baseline,
+                    score - baseline
+                ));
+            }
+        }
+        report
+    }
+}
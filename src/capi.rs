@@ -0,0 +1,218 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2019, the dump_ciede2000 contributors
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//  list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//  this list of conditions and the following disclaimer in the documentation
+//  and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! C API. The header and pkg-config file are generated by the build script
+//! when the `capi` feature is enabled.
+
+use std::panic::{self, AssertUnwindSafe};
+use std::slice;
+
+use crate::{Budget, Frame, FrameInfo, Matrix, Range, Transfer, Vision};
+
+/// Format shared by the two frames being compared.
+#[repr(C)]
+pub struct Ciede2000FrameInfo {
+    pub width: usize,
+    pub height: usize,
+    /// 8, 10 or 12
+    pub bit_depth: usize,
+    /// log2 of the horizontal chroma subsampling: 0, 1 or 2 (4:1:1)
+    pub xdec: usize,
+    /// 1 if chroma is vertically subsampled
+    pub ydec: usize,
+    /// Whether vertically subsampled chroma is subsampled per field
+    pub interlaced: bool,
+}
+
+/// Plane pointers of a frame. Samples above 8 bits are 16-bit little-endian.
+#[repr(C)]
+pub struct Ciede2000Frame {
+    pub y: *const u8,
+    pub u: *const u8,
+    pub v: *const u8,
+}
+
+/// What the scoring functions return.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ciede2000Status {
+    /// The frames were scored
+    Ok,
+    /// `ciede2000_frame_sum_bounded` gave up once its limit was exceeded
+    OverBudget,
+    /// The format is unsupported or too large, a pointer is null or the block
+    /// size is 0
+    InvalidArgument,
+    /// Scoring failed unexpectedly
+    Internal,
+}
+
+impl Ciede2000FrameInfo {
+    fn to_info(&self) -> Result<FrameInfo, Ciede2000Status> {
+        if ![8, 10, 12].contains(&self.bit_depth) || self.xdec > 2 || self.ydec > 1 {
+            return Err(Ciede2000Status::InvalidArgument);
+        }
+        let info = FrameInfo {
+            width: self.width,
+            height: self.height,
+            bit_depth: self.bit_depth,
+            xdec: self.xdec,
+            ydec: self.ydec,
+            interlaced: self.interlaced,
+            matrix: Matrix::Bt709,
+            range: Range::Limited,
+            transfer: Transfer::Sdr,
+            vision: Vision::Normal,
+        };
+        info.check_size()
+            .map_err(|_| Ciede2000Status::InvalidArgument)?;
+        Ok(info)
+    }
+}
+
+unsafe fn to_frame<'a>(
+    info: &FrameInfo,
+    frame: *const Ciede2000Frame,
+) -> Result<Frame<'a>, Ciede2000Status> {
+    let frame = frame.as_ref().ok_or(Ciede2000Status::InvalidArgument)?;
+    if frame.y.is_null() || frame.u.is_null() || frame.v.is_null() {
+        return Err(Ciede2000Status::InvalidArgument);
+    }
+    let (luma_len, chroma_len) = info.plane_lens();
+    Ok(Frame {
+        y: slice::from_raw_parts(frame.y, luma_len),
+        u: slice::from_raw_parts(frame.u, chroma_len),
+        v: slice::from_raw_parts(frame.v, chroma_len),
+    })
+}
+
+// Runs `f`, returning `Ciede2000Status::Internal` rather than unwinding into
+// the caller if it panics.
+fn guard(f: impl FnOnce() -> Result<(), Ciede2000Status>) -> Ciede2000Status {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => Ciede2000Status::Ok,
+        Ok(Err(status)) => status,
+        Err(_) => Ciede2000Status::Internal,
+    }
+}
+
+unsafe fn read_info(info: *const Ciede2000FrameInfo) -> Result<FrameInfo, Ciede2000Status> {
+    info.as_ref()
+        .ok_or(Ciede2000Status::InvalidArgument)?
+        .to_info()
+}
+
+/// Stores the sum of the per-pixel ΔE between two frames in `sum`.
+///
+/// # Safety
+///
+/// All pointers must be valid and the planes must be at least as large as
+/// `info` describes.
+#[no_mangle]
+pub unsafe extern "C" fn ciede2000_frame_sum(
+    info: *const Ciede2000FrameInfo,
+    frame1: *const Ciede2000Frame,
+    frame2: *const Ciede2000Frame,
+    simd: bool,
+    sum: *mut f64,
+) -> Ciede2000Status {
+    guard(|| {
+        let info = read_info(info)?;
+        let (frame1, frame2) = (to_frame(&info, frame1)?, to_frame(&info, frame2)?);
+        let sum = sum.as_mut().ok_or(Ciede2000Status::InvalidArgument)?;
+        *sum = crate::delta_e_frame(&info, &frame1, &frame2, simd);
+        Ok(())
+    })
+}
+
+/// Which limit `ciede2000_frame_sum_bounded` enforces.
+#[repr(C)]
+pub enum Ciede2000BudgetKind {
+    /// Limit on the sum of the per-pixel ΔE
+    Sum,
+    /// Limit on the ΔE of any single pixel
+    Max,
+}
+
+/// Like `ciede2000_frame_sum`, but gives up once `limit` is exceeded and
+/// returns `Ciede2000Status_OverBudget`.
+///
+/// # Safety
+///
+/// All pointers must be valid and the planes must be at least as large as
+/// `info` describes.
+#[no_mangle]
+pub unsafe extern "C" fn ciede2000_frame_sum_bounded(
+    info: *const Ciede2000FrameInfo,
+    frame1: *const Ciede2000Frame,
+    frame2: *const Ciede2000Frame,
+    kind: Ciede2000BudgetKind,
+    limit: f64,
+    simd: bool,
+    sum: *mut f64,
+) -> Ciede2000Status {
+    guard(|| {
+        let info = read_info(info)?;
+        let (frame1, frame2) = (to_frame(&info, frame1)?, to_frame(&info, frame2)?);
+        let sum = sum.as_mut().ok_or(Ciede2000Status::InvalidArgument)?;
+        let budget = match kind {
+            Ciede2000BudgetKind::Sum => Budget::Sum(limit),
+            Ciede2000BudgetKind::Max => Budget::Max(limit as f32),
+        };
+        *sum = crate::delta_e_frame_bounded(&info, &frame1, &frame2, budget, simd)
+            .ok_or(Ciede2000Status::OverBudget)?;
+        Ok(())
+    })
+}
+
+/// Writes the mean ΔE of each `block_size`×`block_size` block to `scores` in
+/// raster order. `scores` must have room for
+/// `ceil(width / block_size) * ceil(height / block_size)` values.
+///
+/// # Safety
+///
+/// All pointers must be valid, the planes must be at least as large as `info`
+/// describes and `scores` must be large enough.
+#[no_mangle]
+pub unsafe extern "C" fn ciede2000_score_blocks(
+    info: *const Ciede2000FrameInfo,
+    reference: *const Ciede2000Frame,
+    distorted: *const Ciede2000Frame,
+    block_size: usize,
+    simd: bool,
+    scores: *mut f32,
+) -> Ciede2000Status {
+    guard(|| {
+        let info = read_info(info)?;
+        let (reference, distorted) = (to_frame(&info, reference)?, to_frame(&info, distorted)?);
+        if block_size == 0 || scores.is_null() {
+            return Err(Ciede2000Status::InvalidArgument);
+        }
+        let blocks = crate::score_blocks(&info, &reference, &distorted, block_size, simd);
+        slice::from_raw_parts_mut(scores, blocks.len()).copy_from_slice(&blocks);
+        Ok(())
+    })
+}
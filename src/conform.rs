@@ -0,0 +1,148 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2019, the dump_ciede2000 contributors
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//  list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//  this list of conditions and the following disclaimer in the documentation
+//  and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Conversion between chroma subsamplings.
+
+use crate::{Frame, FrameInfo};
+
+pub(crate) fn read(plane: &[u8], bytewidth: usize, i: usize) -> u32 {
+    if bytewidth == 1 {
+        plane[i] as u32
+    } else {
+        u16::from_le_bytes([plane[2 * i], plane[2 * i + 1]]) as u32
+    }
+}
+
+/// How [`conform_chroma_with`] upsamples chroma.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChromaFilter {
+    /// Repeats each chroma sample.
+    Nearest,
+    /// Interpolates linearly between the two nearest chroma samples in each
+    /// direction, taking samples to be centred on the luma samples they
+    /// cover. Interlaced chroma is still repeated vertically.
+    Bilinear,
+}
+
+// Source samples and integer weights making up each sample along one axis,
+// when upsampling by 2^`shift` to `to_len` samples.
+fn bilinear_taps(from_len: usize, to_len: usize, shift: usize) -> Vec<Vec<(usize, u32)>> {
+    // Positions in source samples are (2k + 1 - 2^shift) / 2^(shift + 1)
+    let den = 2i64 << shift;
+    (0..to_len)
+        .map(|k| {
+            let num = 2 * k as i64 + 1 - (1 << shift);
+            let (i, frac) = (num.div_euclid(den), num.rem_euclid(den));
+            let clamp = |i: i64| i.clamp(0, from_len as i64 - 1) as usize;
+            vec![(clamp(i), (den - frac) as u32), (clamp(i + 1), frac as u32)]
+        })
+        .collect()
+}
+
+/// Converts `frame` from the format `from` to the chroma subsampling and
+/// interlacing of `to`, writing the planes to `buf`. The frames are split back
+/// out with `to.split_planes(buf)`.
+///
+/// Each chroma sample of `to` is the mean of the samples of `from` that cover
+/// the same luma samples, which replicates samples when upsampling and box
+/// filters when downsampling.
+///
+/// Panics if the formats differ in dimensions or bit depth.
+pub fn conform_chroma(from: &FrameInfo, frame: &Frame, to: &FrameInfo, buf: &mut Vec<u8>) {
+    conform_chroma_with(from, frame, to, ChromaFilter::Nearest, buf);
+}
+
+/// [`conform_chroma`] upsampling with `filter`. Downsampling always averages
+/// the samples covering the same luma samples.
+pub fn conform_chroma_with(
+    from: &FrameInfo,
+    frame: &Frame,
+    to: &FrameInfo,
+    filter: ChromaFilter,
+    buf: &mut Vec<u8>,
+) {
+    assert_eq!(
+        (from.width, from.height, from.bit_depth),
+        (to.width, to.height, to.bit_depth),
+        "only the chroma layout can be conformed"
+    );
+    let bytewidth = from.bytewidth();
+    let (from_width, from_height) = from.chroma_dims();
+    let (to_width, to_height) = to.chroma_dims();
+    let bilinear = filter == ChromaFilter::Bilinear;
+    // Chroma rows and columns of `from` making up each chroma row and column
+    // of `to`, with their weights. By default these are the ones covering the
+    // same luma samples, weighted equally.
+    let rows = if bilinear && to.ydec < from.ydec && !from.interlaced && !to.interlaced {
+        bilinear_taps(from_height, to_height, from.ydec - to.ydec)
+    } else {
+        let mut rows = vec![Vec::new(); to_height];
+        for i in 0..from.height {
+            let row: &mut Vec<(usize, u32)> = &mut rows[to.chroma_row(i)];
+            if !row.contains(&(from.chroma_row(i), 1)) {
+                row.push((from.chroma_row(i), 1));
+            }
+        }
+        rows
+    };
+    let cols = if bilinear && to.xdec < from.xdec {
+        bilinear_taps(from_width, to_width, from.xdec - to.xdec)
+    } else {
+        let mut cols = vec![Vec::new(); to_width];
+        for x in 0..from.width {
+            let col: &mut Vec<(usize, u32)> = &mut cols[x >> to.xdec];
+            if !col.contains(&(x >> from.xdec, 1)) {
+                col.push((x >> from.xdec, 1));
+            }
+        }
+        cols
+    };
+
+    let (luma_len, chroma_len) = to.plane_lens();
+    buf.clear();
+    buf.reserve(luma_len + 2 * chroma_len);
+    buf.extend_from_slice(&frame.y[..luma_len]);
+    for plane in [frame.u, frame.v] {
+        for c_row in &rows {
+            for c_col in &cols {
+                let (mut sum, mut total) = (0, 0);
+                for &(r, row_weight) in c_row {
+                    for &(c, col_weight) in c_col {
+                        let weight = row_weight * col_weight;
+                        sum += weight * read(plane, bytewidth, r * from_width + c);
+                        total += weight;
+                    }
+                }
+                let sample = (sum + total / 2) / total;
+                if bytewidth == 1 {
+                    buf.push(sample as u8);
+                } else {
+                    buf.extend_from_slice(&(sample as u16).to_le_bytes());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,314 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2019, the dump_ciede2000 contributors
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//  list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//  this list of conditions and the following disclaimer in the documentation
+//  and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Decoding of compressed inputs by an ffmpeg process, whose output is read
+//! as Y4M.
+
+use std::cell::Cell;
+use std::ffi::{OsStr, OsString};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStderr, ChildStdout, Command, Stdio};
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+/// How ffmpeg decodes one input.
+#[derive(Clone, Debug, Default)]
+pub struct FfmpegOptions {
+    /// Arguments placed before `-i`, such as `-threads 4` or `-ss 10`
+    pub input_args: Vec<OsString>,
+    /// Hardware decoder for `-hwaccel`, such as `vaapi`, `cuda` or
+    /// `videotoolbox`
+    pub hwaccel: Option<String>,
+    /// Index among the video streams of the one to decode, in place of the
+    /// one ffmpeg picks
+    pub stream: Option<usize>,
+    /// Report the timestamp, picture type and key flag of each frame, with
+    /// the `showinfo` filter
+    pub frame_info: bool,
+}
+
+/// What ffmpeg reports of a frame it decoded.
+#[derive(Clone, Debug)]
+pub struct DecodedFrame {
+    /// Presentation timestamp in the time base of the stream, if it has one
+    pub pts: Option<i64>,
+    /// Presentation time in seconds
+    pub pts_time: Option<f64>,
+    /// Picture type, such as `I`, `P` or `B`, or `?` when unknown
+    pub pict_type: char,
+    pub key_frame: bool,
+}
+
+/// Parses the line `showinfo` logs for each frame, from `n:` on.
+fn parse_showinfo(message: &str) -> Option<DecodedFrame> {
+    // Fields are `key:value`, with spaces after the colon to align some
+    let mut n = None;
+    let mut frame = DecodedFrame {
+        pts: None,
+        pts_time: None,
+        pict_type: '?',
+        key_frame: false,
+    };
+    let mut tokens = message.split_whitespace();
+    while let Some(token) = tokens.next() {
+        let (key, value) = match token.split_once(':') {
+            Some((key, "")) => (key, tokens.next().unwrap_or("")),
+            Some(field) => field,
+            None => continue,
+        };
+        match key {
+            "n" => n = value.parse::<u64>().ok(),
+            "pts" => frame.pts = value.parse().ok(),
+            "pts_time" => frame.pts_time = value.parse().ok(),
+            "iskey" => frame.key_frame = value == "1",
+            "type" => frame.pict_type = value.chars().next().unwrap_or('?'),
+            _ => {}
+        }
+    }
+    // The other lines of showinfo, such as those on side data, have no `n:`
+    n.map(|_| frame)
+}
+
+/// Reads the log of an ffmpeg run at `level+info`, sending the frames
+/// `showinfo` reports and printing errors as `-v error` would.
+fn read_log(stderr: ChildStderr, frames: mpsc::Sender<DecodedFrame>) {
+    for line in BufReader::new(stderr).split(b'\n') {
+        let line = match line {
+            Ok(line) => String::from_utf8_lossy(&line).into_owned(),
+            Err(_) => break,
+        };
+        // Lines start with the contexts, if any, then the level, as in
+        // `[Parsed_showinfo_0 @ 0x5612] [info] n:   0 pts:      0 ...`
+        let mut contexts = Vec::new();
+        let mut level = "";
+        let mut message = line.as_str();
+        while let Some((group, rest)) = message
+            .strip_prefix('[')
+            .and_then(|message| message.split_once("] "))
+        {
+            message = rest;
+            if group.contains(" @ ") {
+                contexts.push(group);
+            } else {
+                level = group;
+                break;
+            }
+        }
+        match level {
+            "info" if contexts.iter().any(|c| c.starts_with("Parsed_showinfo")) => {
+                if let Some(frame) = parse_showinfo(message) {
+                    // Nothing is listening once scoring stops
+                    let _ = frames.send(frame);
+                }
+            }
+            "error" | "fatal" | "panic" => {
+                let contexts: String = contexts.iter().map(|c| format!("[{}] ", c)).collect();
+                let _ = writeln!(io::stderr(), "{}{}", contexts, message);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Frames reported by an ffmpeg started with `frame_info`, in the order it
+/// outputs them.
+pub struct FrameLog {
+    receiver: Receiver<DecodedFrame>,
+    frames: Vec<DecodedFrame>,
+    /// Set once a frame was not reported in time
+    lost: bool,
+}
+
+/// How long to wait for a frame to be reported after reading it, which
+/// ffmpeg logs before writing the frame out.
+const FRAME_LOG_TIMEOUT: Duration = Duration::from_secs(1);
+
+impl FrameLog {
+    /// Frame `n` of the output, counting from 0, or `None` if ffmpeg did not
+    /// report it.
+    pub fn get(&mut self, n: usize) -> Option<&DecodedFrame> {
+        while self.frames.len() <= n && !self.lost {
+            match self.receiver.recv_timeout(FRAME_LOG_TIMEOUT) {
+                Ok(frame) => self.frames.push(frame),
+                // Either ffmpeg exited or the log is not in the format
+                // expected, and waiting for each frame would stall scoring
+                Err(_) => self.lost = true,
+            }
+        }
+        self.frames.get(n)
+    }
+}
+
+/// Counts the video streams of `path` with ffprobe, or `None` if ffprobe
+/// could not tell.
+fn video_streams(path: &OsStr) -> Option<usize> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", "v"])
+        .args(["-show_entries", "stream=index", "-of", "csv=p=0"])
+        .arg(path)
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(
+        output
+            .stdout
+            .split(|&b| b == b'\n')
+            .filter(|line| !line.is_empty())
+            .count(),
+    )
+}
+
+/// Reads the Y4M output of ffmpeg, failing at the end of it if ffmpeg did.
+/// The errors ffmpeg prints go to stderr.
+pub struct FfmpegReader {
+    child: Child,
+    stdout: ChildStdout,
+    exited: bool,
+    failed: Rc<Cell<bool>>,
+    frame_log: Option<FrameLog>,
+}
+
+impl FfmpegReader {
+    /// Starts ffmpeg decoding `path`, or stdin for `-`. Files are first
+    /// checked to have the video stream to decode.
+    pub fn spawn(path: &OsStr, options: &FfmpegOptions) -> io::Result<Self> {
+        let stdin = path == OsStr::new("-");
+        if !stdin {
+            let stream = options.stream.unwrap_or(0);
+            match video_streams(path) {
+                Some(0) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "No video stream",
+                    ));
+                }
+                Some(count) if stream >= count => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("No video stream {}, the input has {}", stream, count),
+                    ));
+                }
+                _ => {}
+            }
+        }
+        let mut command = Command::new("ffmpeg");
+        if options.frame_info {
+            // Frames are logged at the info level, with the level of each
+            // line to tell errors apart
+            command.args(["-v", "level+info", "-hide_banner", "-nostats"]);
+        } else {
+            command.args(["-v", "error"]);
+        }
+        if !stdin {
+            // Keeps ffmpeg from reading keys from the terminal
+            command.arg("-nostdin");
+        }
+        if let Some(hwaccel) = &options.hwaccel {
+            // Without -hwaccel_output_format the frames are copied back to
+            // memory in the format of the surface, P010 for 10 bits, which
+            // ffmpeg converts to planar for Y4M without losing bits
+            command.args(["-hwaccel", hwaccel]);
+        }
+        command.args(&options.input_args);
+        if stdin {
+            command.args(["-i", "pipe:0"]);
+        } else {
+            command.arg("-i").arg(path);
+        }
+        if let Some(stream) = options.stream {
+            command.arg("-map").arg(format!("0:v:{}", stream));
+        }
+        if options.frame_info {
+            // Frames go out as decoded, so the nth frame logged is the nth
+            // read, rather than repeated or dropped to keep a constant rate
+            command.args(["-vf", "showinfo", "-fps_mode", "passthrough"]);
+            command.stderr(Stdio::piped());
+        }
+        // High bit depths need -strict -1 to go in Y4M
+        let mut child = command
+            .args(["-f", "yuv4mpegpipe", "-strict", "-1", "pipe:1"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| io::Error::new(e.kind(), format!("Failed to start ffmpeg: {}", e)))?;
+        let stdout = child.stdout.take().unwrap();
+        let frame_log = child.stderr.take().map(|stderr| {
+            let (sender, receiver) = mpsc::channel();
+            thread::spawn(move || read_log(stderr, sender));
+            FrameLog {
+                receiver,
+                frames: Vec::new(),
+                lost: false,
+            }
+        });
+        Ok(FfmpegReader {
+            child,
+            stdout,
+            exited: false,
+            failed: Rc::new(Cell::new(false)),
+            frame_log,
+        })
+    }
+
+    /// The frames reported with `frame_info`, which can be taken once.
+    pub fn take_frame_log(&mut self) -> Option<FrameLog> {
+        self.frame_log.take()
+    }
+
+    /// Whether ffmpeg has failed, set once its output ends.
+    pub fn failed(&self) -> Rc<Cell<bool>> {
+        self.failed.clone()
+    }
+}
+
+impl Read for FfmpegReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.stdout.read(buf)?;
+        if len == 0 && !self.exited {
+            self.exited = true;
+            let status = self.child.wait()?;
+            if !status.success() {
+                self.failed.set(true);
+                return Err(io::Error::other(format!("ffmpeg failed, {}", status)));
+            }
+        }
+        Ok(len)
+    }
+}
+
+impl Drop for FfmpegReader {
+    // Scoring can stop before the end, as with --limit
+    fn drop(&mut self) {
+        if !self.exited {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
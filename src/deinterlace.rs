@@ -0,0 +1,186 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2019, the dump_ciede2000 contributors
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//  list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//  this list of conditions and the following disclaimer in the documentation
+//  and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Splitting interlaced frames into fields.
+
+use crate::{Frame, FrameInfo, FrameSource};
+use std::io;
+
+/// Writes field `bottom` of `frame` as a progressive frame of the same
+/// format, interpolating the rows of the other field from the rows above and
+/// below. Chroma of 4:2:0 `info` is read per field, whether or not `info` is
+/// marked interlaced.
+pub fn bob_field(info: &FrameInfo, frame: &Frame, bottom: bool, buf: &mut Vec<u8>) {
+    let field = bottom as usize;
+    let bytewidth = info.bytewidth();
+    let (chroma_width, chroma_height) = info.chroma_dims();
+    let fields = FrameInfo {
+        interlaced: true,
+        ..*info
+    };
+    buf.clear();
+    bob_plane(
+        frame.y,
+        info.width * bytewidth,
+        info.height,
+        field,
+        bytewidth,
+        buf,
+    );
+    let stride = chroma_width * bytewidth;
+    for plane in [frame.u, frame.v] {
+        if info.ydec == 0 {
+            bob_plane(plane, stride, chroma_height, field, bytewidth, buf);
+            continue;
+        }
+        // Each chroma row of the field covers two of its luma rows, which
+        // become four rows of the output
+        for k in 0..chroma_height {
+            let c_row = fields.chroma_row(2 * k + field);
+            buf.extend_from_slice(&plane[c_row * stride..][..stride]);
+        }
+    }
+}
+
+fn bob_plane(
+    plane: &[u8],
+    stride: usize,
+    height: usize,
+    field: usize,
+    bytewidth: usize,
+    buf: &mut Vec<u8>,
+) {
+    let row = |y: usize| &plane[y * stride..][..stride];
+    for y in 0..height {
+        if y & 1 == field {
+            buf.extend_from_slice(row(y));
+            continue;
+        }
+        match (y.checked_sub(1), Some(y + 1).filter(|&y| y < height)) {
+            (Some(above), Some(below)) => {
+                let (above, below) = (row(above), row(below));
+                if bytewidth == 1 {
+                    buf.extend(
+                        above
+                            .iter()
+                            .zip(below)
+                            .map(|(&a, &b)| ((a as u16 + b as u16 + 1) >> 1) as u8),
+                    );
+                } else {
+                    for (a, b) in above.chunks_exact(2).zip(below.chunks_exact(2)) {
+                        let a = u16::from_le_bytes([a[0], a[1]]) as u32;
+                        let b = u16::from_le_bytes([b[0], b[1]]) as u32;
+                        buf.extend_from_slice(&(((a + b + 1) >> 1) as u16).to_le_bytes());
+                    }
+                }
+            }
+            (Some(y), None) | (None, Some(y)) => buf.extend_from_slice(row(y)),
+            // A single row has no other field to take from
+            (None, None) => buf.extend_from_slice(row(y)),
+        }
+    }
+}
+
+/// Frames of an interlaced source split into one frame per field, at twice
+/// the frame rate. The fields are read as top field first unless the source
+/// says otherwise.
+pub struct BobSource<'a> {
+    source: Box<dyn FrameSource + 'a>,
+    /// Format of the frames read, with chroma subsampled per field
+    fields: FrameInfo,
+    bottom_first: bool,
+    /// Whether the next frame is the second field of `frame`
+    second: bool,
+    frame: Vec<u8>,
+    buf: Vec<u8>,
+}
+
+impl<'a> BobSource<'a> {
+    /// Splits the frames of `source`, even if it is not marked interlaced.
+    pub fn new(source: Box<dyn FrameSource + 'a>) -> Self {
+        let fields = FrameInfo {
+            interlaced: true,
+            ..source.info()
+        };
+        let bottom_first = source.bottom_field_first();
+        BobSource {
+            source,
+            fields,
+            bottom_first,
+            second: false,
+            frame: Vec::new(),
+            buf: Vec::new(),
+        }
+    }
+}
+
+impl FrameSource for BobSource<'_> {
+    fn info(&self) -> FrameInfo {
+        FrameInfo {
+            interlaced: false,
+            ..self.fields
+        }
+    }
+
+    fn framerate(&self) -> Option<(usize, usize)> {
+        self.source.framerate().map(|(num, den)| (2 * num, den))
+    }
+
+    fn next_frame(&mut self) -> io::Result<Option<Frame<'_>>> {
+        if !self.second {
+            let frame = match self.source.next_frame()? {
+                Some(frame) => frame,
+                None => return Ok(None),
+            };
+            self.frame.clear();
+            for plane in [frame.y, frame.u, frame.v] {
+                self.frame.extend_from_slice(plane);
+            }
+        }
+        let frame = self.fields.split_planes(&self.frame).unwrap();
+        bob_field(
+            &self.fields,
+            &frame,
+            self.second != self.bottom_first,
+            &mut self.buf,
+        );
+        self.second = !self.second;
+        Ok(self.info().split_planes(&self.buf))
+    }
+
+    fn skipped_bytes(&self) -> u64 {
+        self.source.skipped_bytes()
+    }
+
+    fn frames_left(&self, stream_len: u64) -> Option<usize> {
+        let frames = self.source.frames_left(stream_len)?;
+        Some(2 * frames + self.second as usize)
+    }
+
+    fn grayscale(&self) -> bool {
+        self.source.grayscale()
+    }
+}
@@ -0,0 +1,51 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2019, the dump_ciede2000 contributors
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//  list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//  this list of conditions and the following disclaimer in the documentation
+//  and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use lab::Lab;
+
+pub struct DE94;
+
+impl DE94 {
+    /// Returns the CIE94 difference between two `Lab` colors, with the
+    /// weights for graphic arts. `color_1` is the reference, whose chroma
+    /// scales the chroma and hue differences.
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(color_1: Lab, color_2: Lab) -> f32 {
+        const K1: f32 = 0.045;
+        const K2: f32 = 0.015;
+        let c_1 = color_1.a.hypot(color_1.b);
+        let c_2 = color_2.a.hypot(color_2.b);
+        let delta_l = color_1.l - color_2.l;
+        let delta_c = c_1 - c_2;
+        // Rounding can leave ΔH² slightly below zero for colors of one hue
+        let delta_h_2 = ((color_1.a - color_2.a).powi(2) + (color_1.b - color_2.b).powi(2)
+            - delta_c.powi(2))
+        .max(0.);
+        let s_c = 1. + K1 * c_1;
+        let s_h = 1. + K2 * c_1;
+        (delta_l.powi(2) + (delta_c / s_c).powi(2) + delta_h_2 / s_h.powi(2)).sqrt()
+    }
+}
@@ -1,3 +1,9 @@
+mod cmc;
 mod de2000;
+mod de76;
+mod de94;
 
+pub use cmc::*;
 pub use de2000::*;
+pub use de76::*;
+pub use de94::*;
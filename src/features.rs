@@ -0,0 +1,192 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2019, the dump_ciede2000 contributors
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//  list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//  this list of conditions and the following disclaimer in the documentation
+//  and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Per-frame feature vectors for training quality models, written as Parquet.
+
+use dump_ciede2000::{Breakdown, DeltaEHistogram};
+use parquet::data_type::{DoubleType, Int64Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::parser::parse_message_type;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Percentiles of the per-pixel ΔE, with their column names
+const PERCENTILES: [(f64, &str); 4] = [(0.5, "p50"), (0.9, "p90"), (0.95, "p95"), (0.99, "p99")];
+
+/// Upper edges of the ΔE histogram bins, with the column of each bin. The
+/// last bin takes every ΔE of 10 and up.
+const HISTOGRAM: [(f32, &str); 8] = [
+    (0.5, "hist_0"),
+    (1., "hist_1"),
+    (2., "hist_2"),
+    (3., "hist_3"),
+    (5., "hist_4"),
+    (7., "hist_5"),
+    (10., "hist_6"),
+    (f32::INFINITY, "hist_7"),
+];
+
+/// Frames buffered before they are written out as a row group
+const ROW_GROUP_FRAMES: usize = 1024;
+
+/// Features of one frame pair.
+pub struct FrameFeatures<'a> {
+    pub score: f64,
+    pub mean_delta_e: f64,
+    pub histogram: &'a DeltaEHistogram,
+    pub breakdown: Breakdown,
+    /// Mean and standard deviation of the luma of video1, then of video2
+    pub luma: [(f64, f64); 2],
+}
+
+impl FrameFeatures<'_> {
+    fn values(&self) -> Vec<f64> {
+        let mut values = vec![self.score, self.mean_delta_e];
+        values.extend(
+            PERCENTILES
+                .iter()
+                .map(|&(p, _)| self.histogram.percentile(p) as f64),
+        );
+        values.push(self.histogram.max() as f64);
+        let mut below = 0.;
+        for &(edge, _) in &HISTOGRAM {
+            let fraction = if edge.is_finite() {
+                self.histogram.fraction_below(edge)
+            } else {
+                1.
+            };
+            values.push(fraction - below);
+            below = fraction;
+        }
+        values.extend([
+            self.breakdown.lightness,
+            self.breakdown.chroma,
+            self.breakdown.hue,
+        ]);
+        for (mean, deviation) in self.luma {
+            values.extend([mean, deviation]);
+        }
+        values
+    }
+}
+
+/// Columns after `frame`, in the order of [`FrameFeatures::values`].
+fn columns() -> Vec<&'static str> {
+    let mut columns = vec!["score", "mean_delta_e"];
+    columns.extend(PERCENTILES.iter().map(|(_, name)| name));
+    columns.push("max");
+    columns.extend(HISTOGRAM.iter().map(|(_, name)| name));
+    columns.extend([
+        "lightness_share",
+        "chroma_share",
+        "hue_share",
+        "luma1_mean",
+        "luma1_deviation",
+        "luma2_mean",
+        "luma2_deviation",
+    ]);
+    columns
+}
+
+/// Writes the features of each frame as a row of a Parquet file, in row
+/// groups of [`ROW_GROUP_FRAMES`].
+pub struct FeatureWriter {
+    writer: SerializedFileWriter<File>,
+    frames: Vec<i64>,
+    values: Vec<Vec<f64>>,
+}
+
+impl FeatureWriter {
+    pub fn new(path: &Path) -> io::Result<Self> {
+        let fields: Vec<String> = columns()
+            .iter()
+            .map(|name| format!("required double {};", name))
+            .collect();
+        let schema = parse_message_type(&format!(
+            "message features {{ required int64 frame; {} }}",
+            fields.join(" ")
+        ))
+        .map_err(io::Error::other)?;
+        let writer = SerializedFileWriter::new(
+            File::create(path)?,
+            Arc::new(schema),
+            Arc::new(WriterProperties::builder().build()),
+        )
+        .map_err(io::Error::other)?;
+        Ok(FeatureWriter {
+            writer,
+            frames: Vec::new(),
+            values: vec![Vec::new(); columns().len()],
+        })
+    }
+
+    pub fn push(&mut self, frame: usize, features: &FrameFeatures) -> io::Result<()> {
+        self.frames.push(frame as i64);
+        for (column, value) in self.values.iter_mut().zip(features.values()) {
+            column.push(value);
+        }
+        if self.frames.len() == ROW_GROUP_FRAMES {
+            self.write_row_group().map_err(io::Error::other)?;
+        }
+        Ok(())
+    }
+
+    fn write_row_group(&mut self) -> parquet::errors::Result<()> {
+        let mut row_group = self.writer.next_row_group()?;
+        if let Some(mut column) = row_group.next_column()? {
+            column
+                .typed::<Int64Type>()
+                .write_batch(&self.frames, None, None)?;
+            column.close()?;
+        }
+        for values in &self.values {
+            if let Some(mut column) = row_group.next_column()? {
+                column
+                    .typed::<DoubleType>()
+                    .write_batch(values, None, None)?;
+                column.close()?;
+            }
+        }
+        row_group.close()?;
+        self.frames.clear();
+        for values in &mut self.values {
+            values.clear();
+        }
+        Ok(())
+    }
+
+    /// Writes the frames left and the footer, without which the file can not
+    /// be read.
+    pub fn finish(mut self) -> io::Result<()> {
+        if !self.frames.is_empty() {
+            self.write_row_group().map_err(io::Error::other)?;
+        }
+        self.writer.close().map(|_| ()).map_err(io::Error::other)
+    }
+}
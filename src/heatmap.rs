@@ -0,0 +1,131 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2019, the dump_ciede2000 contributors
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//  list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//  this list of conditions and the following disclaimer in the documentation
+//  and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Images and videos of the per-pixel ΔE.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Color of a ΔE, going from black through red and yellow to white at
+/// `scale`.
+fn heat(delta_e: f32, scale: f32) -> [u8; 3] {
+    let t = (delta_e / scale).clamp(0., 1.) * 3.;
+    let channel = |offset: f32| ((t - offset).clamp(0., 1.) * 255.).round() as u8;
+    [channel(0.), channel(1.), channel(2.)]
+}
+
+/// Writes the ΔE of each pixel of a `width` by `height` frame as a colorized
+/// PNG.
+pub fn write_heatmap(
+    path: &Path,
+    width: usize,
+    height: usize,
+    delta_e: &[f32],
+    scale: f32,
+) -> io::Result<()> {
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(File::create(path)?),
+        width as u32,
+        height as u32,
+    );
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let pixels: Vec<u8> = delta_e[..width * height]
+        .iter()
+        .flat_map(|&delta_e| heat(delta_e, scale))
+        .collect();
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&pixels))
+        .map_err(io::Error::other)
+}
+
+/// A grayscale Y4M video of the per-pixel ΔE, with luma going from black at 0
+/// to white at `scale`. Chroma is 4:2:0 at the neutral value, which players
+/// support more widely than the `mono` colorspace.
+pub struct Y4mMapWriter {
+    writer: BufWriter<File>,
+    width: usize,
+    height: usize,
+    bit_depth: usize,
+    scale: f32,
+    buf: Vec<u8>,
+}
+
+impl Y4mMapWriter {
+    /// Creates `path` and writes the stream header. `bit_depth` is 8 or 10.
+    pub fn new(
+        path: &Path,
+        width: usize,
+        height: usize,
+        framerate: (usize, usize),
+        bit_depth: usize,
+        scale: f32,
+    ) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(
+            writer,
+            "YUV4MPEG2 W{} H{} F{}:{} Ip A1:1 C{} XCOLORRANGE=FULL",
+            width,
+            height,
+            framerate.0,
+            framerate.1,
+            if bit_depth == 8 { "420jpeg" } else { "420p10" }
+        )?;
+        Ok(Y4mMapWriter {
+            writer,
+            width,
+            height,
+            bit_depth,
+            scale,
+            buf: Vec::new(),
+        })
+    }
+
+    pub fn write_frame(&mut self, delta_e: &[f32]) -> io::Result<()> {
+        let max = ((1 << self.bit_depth) - 1) as f32;
+        let chroma_len = 2 * self.width.div_ceil(2) * self.height.div_ceil(2);
+        let neutral = 1u16 << (self.bit_depth - 1);
+        let scale = self.scale;
+        let codes = delta_e[..self.width * self.height]
+            .iter()
+            .map(|&delta_e| ((delta_e / scale).clamp(0., 1.) * max).round() as u16)
+            .chain((0..chroma_len).map(|_| neutral));
+        self.buf.clear();
+        if self.bit_depth == 8 {
+            self.buf.extend(codes.map(|code| code as u8));
+        } else {
+            self.buf.extend(codes.flat_map(u16::to_le_bytes));
+        }
+        self.writer.write_all(b"FRAME\n")?;
+        self.writer.write_all(&self.buf)
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
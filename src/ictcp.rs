@@ -0,0 +1,54 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2019, the dump_ciede2000 contributors
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//  list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//  this list of conditions and the following disclaimer in the documentation
+//  and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! ICtCp of BT.2100 and the ΔE-ITP of BT.2124 computed from it.
+
+use crate::rgbtolab::linear_to_pq;
+
+/// Linear BT.2020 RGB to the LMS cone responses of ICtCp
+pub const BT2020_TO_LMS: [[f32; 3]; 3] = [
+    [1688.0 / 4096.0, 2146.0 / 4096.0, 262.0 / 4096.0],
+    [683.0 / 4096.0, 2951.0 / 4096.0, 462.0 / 4096.0],
+    [99.0 / 4096.0, 309.0 / 4096.0, 3688.0 / 4096.0],
+];
+
+/// Converts linear LMS, with 1 as 10000 cd/m², to the I, T and P of BT.2124.
+/// T is half of Ct, so that distances in ITP are perceptually even.
+pub fn lms_to_itp(lms: &[f32; 3]) -> [f32; 3] {
+    let [l, m, s] = lms.map(linear_to_pq);
+    [
+        0.5 * l + 0.5 * m,
+        0.5 * (6610.0 * l - 13613.0 * m + 7003.0 * s) / 4096.0,
+        (17933.0 * l - 17390.0 * m - 543.0 * s) / 4096.0,
+    ]
+}
+
+/// ΔE-ITP between two colors in ITP, where 1 is about a just noticeable
+/// difference.
+pub fn delta_e_itp(itp1: &[f32; 3], itp2: &[f32; 3]) -> f32 {
+    let sum: f32 = itp1.iter().zip(itp2).map(|(a, b)| (a - b) * (a - b)).sum();
+    720.0 * sum.sqrt()
+}
@@ -0,0 +1,599 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2019, the dump_ciede2000 contributors
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//  list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//  this list of conditions and the following disclaimer in the documentation
+//  and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Decoding of still and animated images for the `image` subcommand.
+
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read};
+use std::path::Path;
+
+use dump_ciede2000::Transfer;
+use exr::prelude::traits::*;
+
+/// An image with channels from 0 to 1 encoded with `transfer`, or of linear
+/// light relative to 1 for white.
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<[f32; 3]>,
+    /// `Sdr` for integer formats, taken to be sRGB, and `Linear` for OpenEXR,
+    /// floating point TIFF and linear or logarithmic DPX
+    pub transfer: Transfer,
+}
+
+/// Browsers show frames of animations with a duration of 10 ms or less for
+/// 100 ms instead.
+const MIN_DURATION: f64 = 10.;
+const DEFAULT_DURATION: f64 = 100.;
+
+/// Reads the frames of a GIF, APNG or WebP animation, composited as they are
+/// shown, with their durations in milliseconds, or the one image of a PNG,
+/// binary PPM or PGM, JPEG, OpenEXR, TIFF, DPX or still WebP file, with a
+/// duration of 0. Formats are told apart by their first bytes. Alpha is
+/// ignored after compositing.
+pub fn read_frames(path: &Path) -> Result<Vec<(Image, f64)>, String> {
+    let mut data = Vec::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_end(&mut data))
+        .map_err(|e| e.to_string())?;
+    let frames = match data.get(..4) {
+        Some(b"GIF8") => read_gif(&data)?,
+        Some(b"RIFF") if data.get(8..12) == Some(b"WEBP") => read_webp(&data)?,
+        Some(b"\x89PNG") if is_apng(&data) => read_apng(&data)?,
+        _ => vec![(read_image(&data)?, 0.)],
+    };
+    match frames.first() {
+        None => Err("Animation has no frames".to_owned()),
+        Some((image, _)) if image.width == 0 || image.height == 0 => {
+            Err("Image is empty".to_owned())
+        }
+        _ => Ok(frames),
+    }
+}
+
+// Duration in milliseconds that a frame is shown for.
+fn shown_duration(duration: f64) -> f64 {
+    if duration <= MIN_DURATION {
+        DEFAULT_DURATION
+    } else {
+        duration
+    }
+}
+
+fn read_image(data: &[u8]) -> Result<Image, String> {
+    match data.get(..2) {
+        Some(b"\x89P") => read_png(data),
+        Some(b"P5") | Some(b"P6") => read_pnm(data),
+        Some(b"\xff\xd8") => read_jpeg(data),
+        Some(b"v/") => read_exr(data),
+        Some(b"II") | Some(b"MM") => read_tiff(data),
+        Some(b"SD") | Some(b"XP") => read_dpx(data),
+        _ => Err("Not a PNG, PPM, PGM, JPEG, OpenEXR, TIFF, DPX, GIF or WebP image".to_owned()),
+    }
+}
+
+fn read_png(data: &[u8]) -> Result<Image, String> {
+    let mut decoder = png::Decoder::new(data);
+    // Palettes are expanded to RGB and low bit depths to 8 bits
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = decoder.read_info().map_err(|e| e.to_string())?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).map_err(|e| e.to_string())?;
+    let channels = info.color_type.samples();
+    let samples = from_samples(
+        &buf[..info.buffer_size()],
+        info.bit_depth == png::BitDepth::Sixteen,
+    );
+    Ok(Image {
+        width: info.width as usize,
+        height: info.height as usize,
+        pixels: to_rgb(&samples, channels),
+        transfer: Transfer::Sdr,
+    })
+}
+
+fn read_pnm(data: &[u8]) -> Result<Image, String> {
+    let channels = if data[1] == b'6' { 3 } else { 1 };
+    // The magic number, width, height and maximum value, separated by
+    // whitespace and comments running to the end of the line
+    let mut fields = [0usize; 3];
+    let mut pos = 2;
+    for field in &mut fields {
+        loop {
+            match data.get(pos) {
+                Some(b'#') => {
+                    while data.get(pos).is_some_and(|&b| b != b'\n') {
+                        pos += 1;
+                    }
+                }
+                Some(b) if b.is_ascii_whitespace() => pos += 1,
+                _ => break,
+            }
+        }
+        let start = pos;
+        while data.get(pos).is_some_and(u8::is_ascii_digit) {
+            pos += 1;
+        }
+        *field = std::str::from_utf8(&data[start..pos])
+            .unwrap()
+            .parse()
+            .map_err(|_| "Invalid PNM header".to_owned())?;
+    }
+    let [width, height, max] = fields;
+    if !(1..=65535).contains(&max) || !data.get(pos).is_some_and(u8::is_ascii_whitespace) {
+        return Err("Invalid PNM header".to_owned());
+    }
+    let wide = max > 255;
+    let len = width
+        .checked_mul(height)
+        .and_then(|len| len.checked_mul(channels << wide as usize))
+        .ok_or("Invalid PNM header")?;
+    let data = data
+        .get(pos + 1..)
+        .and_then(|data| data.get(..len))
+        .ok_or("PNM image is truncated")?;
+    let scale = if wide { 65535. } else { 255. } / max as f32;
+    let samples: Vec<f32> = from_samples(data, wide)
+        .into_iter()
+        .map(|sample| sample * scale)
+        .collect();
+    Ok(Image {
+        width,
+        height,
+        pixels: to_rgb(&samples, channels),
+        transfer: Transfer::Sdr,
+    })
+}
+
+fn read_jpeg(data: &[u8]) -> Result<Image, String> {
+    let mut decoder = jpeg_decoder::Decoder::new(BufReader::new(data));
+    let buf = decoder.decode().map_err(|e| e.to_string())?;
+    let info = decoder.info().unwrap();
+    let channels = match info.pixel_format {
+        jpeg_decoder::PixelFormat::L8 => 1,
+        jpeg_decoder::PixelFormat::RGB24 => 3,
+        format => return Err(format!("Unsupported JPEG pixel format {:?}", format)),
+    };
+    Ok(Image {
+        width: info.width as usize,
+        height: info.height as usize,
+        pixels: to_rgb(&from_samples(&buf, false), channels),
+        transfer: Transfer::Sdr,
+    })
+}
+
+// Reads the RGB channels of the first layer, at its largest resolution.
+fn read_exr(data: &[u8]) -> Result<Image, String> {
+    let image = read()
+        .no_deep_data()
+        .largest_resolution_level()
+        .rgba_channels(
+            |size, _| (size.width(), vec![[0f32; 3]; size.area()]),
+            |(width, pixels): &mut (usize, Vec<[f32; 3]>),
+             position,
+             (r, g, b, _): (f32, f32, f32, f32)| {
+                pixels[position.y() * *width + position.x()] = [r, g, b];
+            },
+        )
+        .first_valid_layer()
+        .all_attributes()
+        .from_buffered(Cursor::new(data))
+        .map_err(|e| e.to_string())?;
+    let (width, pixels) = image.layer_data.channel_data.pixels;
+    Ok(Image {
+        width,
+        height: image.layer_data.size.height(),
+        pixels,
+        transfer: Transfer::Linear,
+    })
+}
+
+// An animation being composited, of straight alpha RGBA.
+struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<[f32; 4]>,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize) -> Self {
+        Canvas {
+            width,
+            height,
+            pixels: vec![[0.; 4]; width * height],
+        }
+    }
+
+    // Draws the `width` by `height` `pixels` at `x`, `y`, replacing what is
+    // there or, with `over`, blending them onto it by their alpha. Parts
+    // outside the canvas are dropped.
+    fn draw(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        pixels: &[[f32; 4]],
+        over: bool,
+    ) {
+        if width == 0 {
+            return;
+        }
+        for (row, src) in (y..self.height).zip(pixels.chunks_exact(width).take(height)) {
+            for (col, &src) in (x..self.width).zip(src) {
+                let dst = &mut self.pixels[row * self.width + col];
+                if !over {
+                    *dst = src;
+                    continue;
+                }
+                let alpha = src[3] + dst[3] * (1. - src[3]);
+                if alpha > 0. {
+                    for c in 0..3 {
+                        dst[c] = (src[c] * src[3] + dst[c] * dst[3] * (1. - src[3])) / alpha;
+                    }
+                }
+                dst[3] = alpha;
+            }
+        }
+    }
+
+    // Clears the `width` by `height` rectangle at `x`, `y` to transparent.
+    fn clear(&mut self, x: usize, y: usize, width: usize, height: usize) {
+        for row in y..(y + height).min(self.height) {
+            let start = row * self.width;
+            for pixel in
+                &mut self.pixels[start + x.min(self.width)..start + (x + width).min(self.width)]
+            {
+                *pixel = [0.; 4];
+            }
+        }
+    }
+
+    fn image(&self) -> Image {
+        Image {
+            width: self.width,
+            height: self.height,
+            pixels: self.pixels.iter().map(|&[r, g, b, _]| [r, g, b]).collect(),
+            transfer: Transfer::Sdr,
+        }
+    }
+}
+
+fn read_gif(data: &[u8]) -> Result<Vec<(Image, f64)>, String> {
+    let mut options = gif::DecodeOptions::new();
+    options.set_color_output(gif::ColorOutput::RGBA);
+    let mut decoder = options.read_info(data).map_err(|e| e.to_string())?;
+    let mut canvas = Canvas::new(decoder.width() as usize, decoder.height() as usize);
+    let mut frames = Vec::new();
+    while let Some(frame) = decoder.read_next_frame().map_err(|e| e.to_string())? {
+        let (x, y) = (frame.left as usize, frame.top as usize);
+        let (width, height) = (frame.width as usize, frame.height as usize);
+        let previous = match frame.dispose {
+            gif::DisposalMethod::Previous => Some(canvas.pixels.clone()),
+            _ => None,
+        };
+        // Transparent pixels have an alpha of 0, leaving the canvas as it was
+        let pixels = to_rgba(&from_samples(&frame.buffer, false), 4);
+        canvas.draw(x, y, width, height, &pixels, true);
+        frames.push((canvas.image(), shown_duration(frame.delay as f64 * 10.)));
+        match (frame.dispose, previous) {
+            (gif::DisposalMethod::Background, _) => canvas.clear(x, y, width, height),
+            (_, Some(previous)) => canvas.pixels = previous,
+            _ => {}
+        }
+    }
+    Ok(frames)
+}
+
+fn is_apng(data: &[u8]) -> bool {
+    png::Decoder::new(data)
+        .read_info()
+        .is_ok_and(|reader| reader.info().animation_control.is_some())
+}
+
+fn read_apng(data: &[u8]) -> Result<Vec<(Image, f64)>, String> {
+    let mut decoder = png::Decoder::new(data);
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = decoder.read_info().map_err(|e| e.to_string())?;
+    let info = reader.info();
+    let num_frames = info.animation_control.unwrap().num_frames as usize;
+    let mut canvas = Canvas::new(info.width as usize, info.height as usize);
+    // The image is only the first frame when a frame control comes before it
+    let mut skip_image = info.frame_control.is_none();
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let mut frames = Vec::new();
+    while frames.len() < num_frames {
+        let output = reader.next_frame(&mut buf).map_err(|e| e.to_string())?;
+        if skip_image {
+            skip_image = false;
+            continue;
+        }
+        let control = reader.info().frame_control.unwrap();
+        let (x, y) = (control.x_offset as usize, control.y_offset as usize);
+        let (width, height) = (control.width as usize, control.height as usize);
+        let samples = from_samples(
+            &buf[..output.buffer_size()],
+            output.bit_depth == png::BitDepth::Sixteen,
+        );
+        let pixels = to_rgba(&samples, output.color_type.samples());
+        // Restoring before the first frame clears the canvas
+        let previous = match control.dispose_op {
+            png::DisposeOp::Previous if !frames.is_empty() => Some(canvas.pixels.clone()),
+            _ => None,
+        };
+        let over = control.blend_op == png::BlendOp::Over;
+        canvas.draw(x, y, width, height, &pixels, over);
+        let den = match control.delay_den {
+            0 => 100.,
+            den => den as f64,
+        };
+        let duration = control.delay_num as f64 * 1000. / den;
+        frames.push((canvas.image(), shown_duration(duration)));
+        match (control.dispose_op, previous) {
+            (_, Some(previous)) => canvas.pixels = previous,
+            (png::DisposeOp::None, _) => {}
+            _ => canvas.clear(x, y, width, height),
+        }
+    }
+    Ok(frames)
+}
+
+// Reads a still or animated WebP image, which the decoder composites.
+fn read_webp(data: &[u8]) -> Result<Vec<(Image, f64)>, String> {
+    let mut decoder = image_webp::WebPDecoder::new(Cursor::new(data)).map_err(|e| e.to_string())?;
+    let (width, height) = decoder.dimensions();
+    let channels = if decoder.has_alpha() { 4 } else { 3 };
+    let mut buf = vec![
+        0;
+        decoder
+            .output_buffer_size()
+            .ok_or("WebP image is too large")?
+    ];
+    let image = |buf: &[u8]| Image {
+        width: width as usize,
+        height: height as usize,
+        pixels: to_rgb(&from_samples(buf, false), channels),
+        transfer: Transfer::Sdr,
+    };
+    if !decoder.is_animated() {
+        decoder.read_image(&mut buf).map_err(|e| e.to_string())?;
+        return Ok(vec![(image(&buf), 0.)]);
+    }
+    let mut frames = Vec::new();
+    for _ in 0..decoder.num_frames() {
+        let duration = decoder.read_frame(&mut buf).map_err(|e| e.to_string())?;
+        frames.push((image(&buf), shown_duration(duration as f64)));
+    }
+    Ok(frames)
+}
+
+// Reads the first image of a TIFF file. Floating point samples are taken to
+// be linear light, as from renderers and scanners, and integers to be sRGB.
+fn read_tiff(data: &[u8]) -> Result<Image, String> {
+    use tiff::decoder::{Decoder, DecodingResult};
+    use tiff::ColorType;
+
+    let mut decoder = Decoder::new(Cursor::new(data)).map_err(|e| e.to_string())?;
+    let (width, height) = decoder.dimensions().map_err(|e| e.to_string())?;
+    let channels = match decoder.colortype().map_err(|e| e.to_string())? {
+        ColorType::Gray(_) => 1,
+        ColorType::GrayA(_) => 2,
+        ColorType::RGB(_) => 3,
+        ColorType::RGBA(_) => 4,
+        color_type => return Err(format!("Unsupported TIFF color type {:?}", color_type)),
+    };
+    let (samples, transfer): (Vec<f32>, _) = match decoder
+        .read_image()
+        .map_err(|e| e.to_string())?
+    {
+        DecodingResult::U8(buf) => (
+            buf.iter().map(|&s| s as f32 / 255.).collect(),
+            Transfer::Sdr,
+        ),
+        DecodingResult::U16(buf) => (
+            buf.iter().map(|&s| s as f32 / 65535.).collect(),
+            Transfer::Sdr,
+        ),
+        DecodingResult::F16(buf) => (buf.iter().map(|s| s.to_f32()).collect(), Transfer::Linear),
+        DecodingResult::F32(buf) => (buf, Transfer::Linear),
+        _ => return Err("Unsupported TIFF sample format".to_owned()),
+    };
+    Ok(Image {
+        width: width as usize,
+        height: height as usize,
+        pixels: to_rgb(&samples, channels),
+        transfer,
+    })
+}
+
+// Cineon printing density of black and white, in 10-bit code values, and the
+// density of a code value step with the film gamma of 0.6.
+const DPX_LOG_BLACK: f32 = 95.;
+const DPX_LOG_WHITE: f32 = 685.;
+const DPX_LOG_STEP: f32 = 0.002 / 0.6;
+
+// Reads the first image element of a DPX file. Logarithmic elements are
+// converted from printing density to linear light with 1 at white, and linear
+// ones are scaled to 1 at their reference high code value. Video transfers
+// are taken to be sRGB, as for the other integer formats.
+fn read_dpx(data: &[u8]) -> Result<Image, String> {
+    let header = data.get(..832).ok_or("DPX header is truncated")?;
+    let big_endian = &header[..4] == b"SDPX";
+    let read_u16 = |bytes: &[u8]| {
+        let bytes = [bytes[0], bytes[1]];
+        if big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        }
+    };
+    let read_u32 = |bytes: &[u8]| {
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        if big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        }
+    };
+    let u16_at = |pos: usize| read_u16(&header[pos..]);
+    let u32_at = |pos: usize| read_u32(&header[pos..]);
+    let orientation = u16_at(768);
+    let width = u32_at(772) as usize;
+    let height = u32_at(776) as usize;
+    let channels = match header[800] {
+        6 => 1,
+        50 => 3,
+        51 => 4,
+        descriptor => return Err(format!("Unsupported DPX descriptor {}", descriptor)),
+    };
+    let transfer_characteristic = header[801];
+    let bit_depth = header[803] as u32;
+    let packing = u16_at(804);
+    let offset = u32_at(808) as usize;
+    let line_padding = match u32_at(812) {
+        0xffffffff => 0,
+        padding => padding as usize,
+    };
+    if width == 0 || height == 0 {
+        return Err("Image is empty".to_owned());
+    }
+    if orientation != 0 {
+        return Err("Only top to bottom, left to right DPX images are supported".to_owned());
+    }
+    if u16_at(806) != 0 {
+        return Err("Run-length encoded DPX images are not supported".to_owned());
+    }
+
+    // Lines are padded to 32-bit words. 10-bit samples fill the words three
+    // at a time from the most significant bits with method A (packing 1) or
+    // the least with method B (packing 2), and 12-bit ones fill 16 bits.
+    let line_samples = width.checked_mul(channels).ok_or("Invalid DPX header")?;
+    let per_word = match (bit_depth, packing) {
+        (8, _) => 4,
+        (16, _) | (12, 1) | (12, 2) => 2,
+        (10, 1) | (10, 2) => 3,
+        _ => {
+            return Err(format!(
+                "Unsupported DPX bit depth {} with packing {}",
+                bit_depth, packing
+            ))
+        }
+    };
+    let line_len = line_samples
+        .div_ceil(per_word)
+        .checked_mul(4)
+        .and_then(|len| len.checked_add(line_padding))
+        .ok_or("Invalid DPX header")?;
+    let len = line_len.checked_mul(height).ok_or("Invalid DPX header")?;
+    let data = offset
+        .checked_add(len)
+        .and_then(|end| data.get(offset..end))
+        .ok_or("DPX image is truncated")?;
+    let max = ((1u32 << bit_depth) - 1) as f32;
+    // Reference black and white code values
+    let (low, high) = match (u32_at(784), u32_at(792)) {
+        (low, high) if low < high && high as f32 <= max => (low as f32, high as f32),
+        _ if transfer_characteristic == 3 => {
+            let scale = max / 1023.;
+            (DPX_LOG_BLACK * scale, DPX_LOG_WHITE * scale)
+        }
+        _ => (0., max),
+    };
+    let step = DPX_LOG_STEP * 1023. / max;
+    let black = 10f32.powf((low - high) * step);
+    let mut samples = Vec::with_capacity(line_samples * height);
+    for line in data.chunks_exact(line_len) {
+        for i in 0..line_samples {
+            let code = match bit_depth {
+                8 => line[i] as u32,
+                10 => {
+                    let shift = if packing == 1 { 22 } else { 20 } - 10 * (i % 3) as u32;
+                    (read_u32(&line[i / 3 * 4..]) >> shift) & 0x3ff
+                }
+                _ => {
+                    let unit = read_u16(&line[2 * i..]) as u32;
+                    match (bit_depth, packing) {
+                        (12, 1) => unit >> 4,
+                        (12, _) => unit & 0xfff,
+                        _ => unit,
+                    }
+                }
+            } as f32;
+            samples.push(match transfer_characteristic {
+                3 => (10f32.powf((code - high) * step) - black) / (1. - black),
+                2 => (code - low) / (high - low),
+                _ => code / max,
+            });
+        }
+    }
+    let transfer = match transfer_characteristic {
+        2 | 3 => Transfer::Linear,
+        _ => Transfer::Sdr,
+    };
+    Ok(Image {
+        width,
+        height,
+        pixels: to_rgb(&samples, channels),
+        transfer,
+    })
+}
+
+/// Samples from 0 to 1 of 8-bit or big-endian 16-bit data.
+fn from_samples(data: &[u8], wide: bool) -> Vec<f32> {
+    if wide {
+        data.chunks_exact(2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]) as f32 / 65535.)
+            .collect()
+    } else {
+        data.iter().map(|&b| b as f32 / 255.).collect()
+    }
+}
+
+/// RGBA pixels of interleaved samples, as in [`to_rgb`], opaque when there is
+/// no alpha.
+fn to_rgba(samples: &[f32], channels: usize) -> Vec<[f32; 4]> {
+    samples
+        .chunks_exact(channels)
+        .map(|pixel| match channels {
+            1 => [pixel[0], pixel[0], pixel[0], 1.],
+            2 => [pixel[0], pixel[0], pixel[0], pixel[1]],
+            3 => [pixel[0], pixel[1], pixel[2], 1.],
+            _ => [pixel[0], pixel[1], pixel[2], pixel[3]],
+        })
+        .collect()
+}
+
+/// Pixels of interleaved samples, `channels` to a pixel. Gray has 1 or 2
+/// channels and color 3 or 4, the last of 2 or 4 being alpha.
+fn to_rgb(samples: &[f32], channels: usize) -> Vec<[f32; 3]> {
+    samples
+        .chunks_exact(channels)
+        .map(|pixel| match channels {
+            1 | 2 => [pixel[0]; 3],
+            _ => [pixel[0], pixel[1], pixel[2]],
+        })
+        .collect()
+}
@@ -0,0 +1,140 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2019, the dump_ciede2000 contributors
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//  list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//  this list of conditions and the following disclaimer in the documentation
+//  and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+//! Bringing the frames of an input to the format they are scored in.
+
+use crate::{
+    conform_chroma_with, crop_frame, lut_frame, scale_frame, transform_frame, ChromaFilter, Crop,
+    Frame, FrameInfo, Lut, ScaleFilter, ScaleLight, Transform,
+};
+
+/// How the frames of one input are brought to the format they are scored in.
+#[derive(Clone, Copy)]
+pub struct Ingest<'l> {
+    /// The format read and the LUT applied to it
+    pub lut: Option<(FrameInfo, &'l Lut)>,
+    /// The format read and the transform applied to it
+    pub transform: Option<(FrameInfo, Transform)>,
+    /// The format read and the format its chroma is conformed to
+    pub conform: Option<(FrameInfo, FrameInfo)>,
+    /// The formats before and after rescaling, and its filter and light
+    pub rescale: Option<(FrameInfo, FrameInfo, ScaleFilter, ScaleLight)>,
+    /// The format before cropping and the region kept
+    pub crop: Option<(FrameInfo, Crop)>,
+    /// The filter `conform` resamples chroma with
+    pub filter: ChromaFilter,
+}
+
+impl Ingest<'_> {
+    /// Applies the LUT to `frame`, transforms, rescales, conforms and crops it
+    /// using `bufs`, then replaces its chroma with `neutral`, if any.
+    pub fn frame<'a>(
+        &self,
+        frame: Frame<'a>,
+        neutral: Option<&'a [u8]>,
+        bufs: &'a mut [Vec<u8>; 5],
+    ) -> Frame<'a> {
+        let [lut_buf, transform_buf, rescale_buf, conform_buf, crop_buf] = bufs;
+        let frame = match self.lut {
+            Some((info, lut)) => {
+                lut_frame(&info, &frame, lut, lut_buf);
+                info.split_planes(lut_buf).unwrap()
+            }
+            None => frame,
+        };
+        let frame = match self.transform {
+            Some((info, transform)) => {
+                transform_frame(&info, &frame, transform, transform_buf);
+                let to = transform.apply_to(&info).unwrap();
+                to.split_planes(transform_buf).unwrap()
+            }
+            None => frame,
+        };
+        let frame = match self.rescale {
+            Some((from, to, filter, light)) => {
+                scale_frame(&from, &frame, &to, filter, light, rescale_buf);
+                to.split_planes(rescale_buf).unwrap()
+            }
+            None => frame,
+        };
+        let frame = conform_frame(frame, self.conform, self.filter, conform_buf);
+        let frame = match self.crop {
+            Some((info, crop)) => {
+                crop_frame(&info, &frame, crop, crop_buf);
+                let to = crop.apply_to(&info).unwrap();
+                to.split_planes(crop_buf).unwrap()
+            }
+            None => frame,
+        };
+        neutralize(frame, neutral)
+    }
+
+    /// The buffer of `bufs` that `frame` left the frame in, before any neutral
+    /// chroma, or `None` if it was left as read.
+    pub fn output<'b>(&self, bufs: &'b mut [Vec<u8>; 5]) -> Option<&'b mut Vec<u8>> {
+        let [lut_buf, transform_buf, rescale_buf, conform_buf, crop_buf] = bufs;
+        if self.crop.is_some() {
+            Some(crop_buf)
+        } else if self.conform.is_some() {
+            Some(conform_buf)
+        } else if self.rescale.is_some() {
+            Some(rescale_buf)
+        } else if self.transform.is_some() {
+            Some(transform_buf)
+        } else if self.lut.is_some() {
+            Some(lut_buf)
+        } else {
+            None
+        }
+    }
+}
+
+// Converts `frame` to the second format of `conform`, if any.
+fn conform_frame<'a>(
+    frame: Frame<'a>,
+    conform: Option<(FrameInfo, FrameInfo)>,
+    filter: ChromaFilter,
+    buf: &'a mut Vec<u8>,
+) -> Frame<'a> {
+    match conform {
+        Some((from, to)) => {
+            conform_chroma_with(&from, &frame, &to, filter, buf);
+            to.split_planes(buf).unwrap()
+        }
+        None => frame,
+    }
+}
+
+/// Replaces the chroma planes of `frame` with `chroma`, if any.
+pub fn neutralize<'a>(frame: Frame<'a>, chroma: Option<&'a [u8]>) -> Frame<'a> {
+    match chroma {
+        Some(chroma) => Frame {
+            y: frame.y,
+            u: chroma,
+            v: chroma,
+        },
+        None => frame,
+    }
+}
@@ -0,0 +1,2892 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2019, the dump_ciede2000 contributors
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//  list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//  this list of conditions and the following disclaimer in the documentation
+//  and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! CIEDE2000 color difference between YUV videos, as a library.
+//!
+//! Frames are read with a [`FrameSource`] such as [`Y4mSource`] or
+//! [`RawSource`], described by a [`FrameInfo`] and scored with
+//! [`delta_e_frame`], which returns the sum of the per-pixel ΔE.
+//! [`delta_e_to_score`] turns the mean ΔE into the score printed by the
+//! `dump_ciede2000` binary:
+//!
+//! ```no_run
+//! use dump_ciede2000::*;
+//! use std::fs::File;
+//! use std::io::BufReader;
+//!
+//! let mut file1 = BufReader::new(File::open("reference.y4m").unwrap());
+//! let mut file2 = BufReader::new(File::open("distorted.y4m").unwrap());
+//! let mut video1 = Y4mSource::new(&mut file1).unwrap();
+//! let mut video2 = Y4mSource::new(&mut file2).unwrap();
+//! let info = video1.info();
+//! assert_eq!(info, video2.info());
+//! while let (Some(frame1), Some(frame2)) =
+//!     (video1.next_frame().unwrap(), video2.next_frame().unwrap())
+//! {
+//!     let sum = delta_e_frame(&info, &frame1, &frame2, true);
+//!     println!("{}", delta_e_to_score(sum / (info.width * info.height) as f64));
+//! }
+//! ```
+//!
+//! [`Ingest`] brings frames to a common format first, with the LUT,
+//! transform, rescaling, chroma conforming and cropping the binary applies.
+//! [`delta_e_frame_pooled`] pools the per-pixel ΔE some other way, such as a
+//! percentile, with any [`FramePooler`]. Lower level callers can pick a row
+//! kernel with [`get_delta_e_row_fn`] and pool rows themselves with
+//! [`delta_e_row_pool`]. [`delta_e::DE2000`] and
+//! [`rgbtolab`] hold the per-color conversions.
+
+// There are no stable RVV intrinsics, target feature or runtime detection
+#![cfg_attr(
+    all(feature = "rvv", target_arch = "riscv64"),
+    feature(riscv_target_feature, stdarch_riscv_feature_detection)
+)]
+
+#[cfg_attr(
+    any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        all(feature = "rvv", target_arch = "riscv64")
+    ),
+    macro_use
+)]
+extern crate itertools;
+
+pub mod delta_e;
+pub mod ictcp;
+pub mod rgbtolab;
+
+#[cfg(feature = "capi")]
+pub mod capi;
+
+mod conform;
+mod deinterlace;
+mod ingest;
+mod lut;
+mod pooling;
+mod reference;
+mod scale;
+mod source;
+mod trace;
+mod transform;
+pub use conform::*;
+pub use deinterlace::*;
+pub use ingest::*;
+pub use lut::*;
+pub use pooling::*;
+pub use reference::*;
+pub use scale::*;
+pub use source::*;
+pub use trace::*;
+pub use transform::*;
+
+use delta_e::*;
+use ictcp::*;
+use rgbtolab::*;
+
+use lab::Lab;
+
+use rayon::prelude::*;
+use std::marker::PhantomData;
+use std::time::{Duration, Instant};
+
+/// Arguments for delta e, from
+/// "Color Image Quality Assessment Based on CIEDE2000"
+/// Yang Yang, Jun Ming and Nenghai Yu, 2012
+/// http://dx.doi.org/10.1155/2012/273723
+pub const K_SUB: KSubArgs = KSubArgs {
+    l: 0.65,
+    c: 1.0,
+    h: 4.0,
+};
+
+/// Weights of textbook CIEDE2000.
+pub const K_SUB_STANDARD: KSubArgs = KSubArgs {
+    l: 1.0,
+    c: 1.0,
+    h: 1.0,
+};
+
+pub struct FrameRow<'a> {
+    pub y: &'a [u8],
+    pub u: &'a [u8],
+    pub v: &'a [u8],
+    pub to_rgb: YuvToRgb,
+}
+
+/// Matrix used to derive RGB from YUV.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Matrix {
+    Bt601,
+    Bt709,
+    Bt2020,
+    /// No matrix: the planes hold G, B and R, as in the gbrp formats of
+    /// ffmpeg
+    Identity,
+}
+
+/// Range of the YUV samples.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Range {
+    /// 16-235 for luma and 16-240 for chroma, scaled up for higher bit depths
+    Limited,
+    /// Every code value, as in JPEG
+    Full,
+}
+
+/// Transfer function the RGB is encoded with.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Transfer {
+    /// The sRGB curve, for SDR content
+    Sdr,
+    /// SMPTE ST 2084, for HDR10 content. RGB is taken to have BT.2020
+    /// primaries.
+    Pq,
+    /// None: the samples are linear light with sRGB primaries, for checking
+    /// the later stages against a reference
+    Linear,
+}
+
+/// Color difference scored by the kernels.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Metric {
+    /// CIEDE2000
+    De2000,
+    /// CIE76, the distance in Lab
+    De76,
+    /// CIE94 with the weights for graphic arts
+    De94,
+    /// CMC l:c, with l and c taken from the kL and kC weights
+    Cmc,
+    /// ΔE-ITP of BT.2124, from ICtCp
+    Itp,
+}
+
+/// Stage of the conversion to Lab.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Stage {
+    /// Nonlinear RGB, from the YUV to RGB matrix
+    Rgb,
+    /// Linear RGB, from the transfer function
+    Linear,
+    /// XYZ (D65), from the RGB to XYZ matrix of the primaries and any
+    /// simulated vision
+    Xyz,
+    Lab,
+}
+
+/// Colour vision the comparison is made for.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Vision {
+    Normal,
+    Protanopia,
+    Deuteranopia,
+    Tritanopia,
+}
+
+impl Vision {
+    /// Linear RGB to XYZ as seen with this vision. Dichromacy is simulated
+    /// with the severity 1 matrices of Machado et al. (2009).
+    pub fn rgb_to_xyz(self) -> [[f32; 3]; 3] {
+        let sim = match self {
+            Vision::Normal => return SRGB_TO_XYZ,
+            Vision::Protanopia => [
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ],
+            Vision::Deuteranopia => [
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ],
+            Vision::Tritanopia => [
+                [1.255528, -0.076749, -0.178779],
+                [-0.078411, 0.930809, 0.147602],
+                [0.004733, 0.691367, 0.303900],
+            ],
+        };
+        mat_mul(&SRGB_TO_XYZ, &sim)
+    }
+}
+
+/// Offsets, scales and matrix coefficients converting the samples of a row to
+/// nonlinear RGB, and the transfer function and matrix taking it on to XYZ.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct YuvToRgb {
+    y_offset: f32,
+    y_scale: f32,
+    uv_offset: f32,
+    uv_scale: f32,
+    /// Whether the planes hold G, B and R, for [`Matrix::Identity`]
+    gbr: bool,
+    r_v: f32,
+    g_u: f32,
+    g_v: f32,
+    b_u: f32,
+    transfer: Transfer,
+    rgb_to_xyz: [[f32; 3]; 3],
+    /// Linear RGB to the LMS of ICtCp, with 1 as 10000 cd/m²
+    rgb_to_lms: [[f32; 3]; 3],
+}
+
+impl YuvToRgb {
+    pub fn new(
+        bit_depth: usize,
+        matrix: Matrix,
+        range: Range,
+        transfer: Transfer,
+        vision: Vision,
+    ) -> Self {
+        let scale = (1 << (bit_depth - 8)) as f32;
+        let (y_offset, y_scale, uv_scale) = match range {
+            Range::Limited => (16. * scale, 1. / (219. * scale), 1. / (224. * scale)),
+            Range::Full => {
+                let max = ((1 << bit_depth) - 1) as f32;
+                (0., 1. / max, 1. / max)
+            }
+        };
+        // The chroma coefficients are those of analog YUV, for which the
+        // BT.709 conversion was originally written, derived from each
+        // matrix's Kr and Kb.
+        let (r_v, g_u, g_v, b_u) = match matrix {
+            Matrix::Bt601 => (1.13984, 0.39465, 0.58060, 2.03211),
+            Matrix::Bt709 => (1.28033, 0.21482, 0.38059, 2.12798),
+            Matrix::Bt2020 => (1.19886, 0.18871, 0.46451, 2.15757),
+            Matrix::Identity => (0., 0., 0., 0.),
+        };
+        let gbr = matrix == Matrix::Identity;
+        // Each plane of RGB has the range of luma
+        let (uv_offset, uv_scale) = if gbr {
+            (y_offset, y_scale)
+        } else {
+            (128. * scale, uv_scale)
+        };
+        let rgb_to_xyz = match transfer {
+            Transfer::Sdr | Transfer::Linear => vision.rgb_to_xyz(),
+            Transfer::Pq if vision == Vision::Normal => BT2020_TO_XYZ,
+            // Dichromacy is simulated on sRGB primaries
+            Transfer::Pq => mat_mul(&vision.rgb_to_xyz(), &mat_mul(&XYZ_TO_SRGB, &BT2020_TO_XYZ)),
+        };
+        // Light is relative to the reference white, which is taken to be at
+        // the same luminance for SDR
+        let rgb_to_lms = mat_mul(&BT2020_TO_LMS, &mat_mul(&XYZ_TO_BT2020, &rgb_to_xyz))
+            .map(|row| row.map(|m| m * (PQ_REFERENCE_WHITE / 10000.)));
+        YuvToRgb {
+            y_offset,
+            y_scale,
+            uv_offset,
+            uv_scale,
+            gbr,
+            r_v,
+            g_u,
+            g_v,
+            b_u,
+            transfer,
+            rgb_to_xyz,
+            rgb_to_lms,
+        }
+    }
+
+    fn lab(&self, rgb: &[f32; 3]) -> Lab {
+        match self.transfer {
+            Transfer::Sdr => rgb_to_lab_with(rgb, &self.rgb_to_xyz),
+            Transfer::Pq => pq_rgb_to_lab_with(rgb, &self.rgb_to_xyz),
+            Transfer::Linear => linear_rgb_to_lab_with(rgb, &self.rgb_to_xyz),
+        }
+    }
+
+    // Sample values of Y, U and V for nonlinear RGB, the inverse of the
+    // conversion of the kernels before rounding.
+    fn rgb_to_yuv(&self, rgb: &[f32; 3]) -> [f32; 3] {
+        let [r, g, b] = *rgb;
+        let (y, u, v) = if self.gbr {
+            (g, b, r)
+        } else {
+            let (g_b, g_r) = (self.g_u / self.b_u, self.g_v / self.r_v);
+            let y = (g + g_b * b + g_r * r) / (1. + g_b + g_r);
+            (y, (b - y) / self.b_u, (r - y) / self.r_v)
+        };
+        [
+            y / self.y_scale + self.y_offset,
+            u / self.uv_scale + self.uv_offset,
+            v / self.uv_scale + self.uv_offset,
+        ]
+    }
+
+    // The I, T and P of BT.2124 in place of L*, a* and b*.
+    fn itp(&self, rgb: &[f32; 3]) -> Lab {
+        let [l, a, b] = lms_to_itp(&linear_to_xyz(&self.linear(rgb), &self.rgb_to_lms));
+        Lab { l, a, b }
+    }
+
+    fn linear(&self, rgb: &[f32; 3]) -> [f32; 3] {
+        match self.transfer {
+            Transfer::Sdr => rgb.map(srgb_to_linear),
+            Transfer::Pq => rgb.map(pq_to_linear),
+            Transfer::Linear => *rgb,
+        }
+    }
+
+    // The inverse of `linear`.
+    fn nonlinear(&self, linear: &[f32; 3]) -> [f32; 3] {
+        match self.transfer {
+            Transfer::Sdr => linear.map(linear_to_srgb),
+            Transfer::Pq => linear.map(|y| linear_to_pq(y * (PQ_REFERENCE_WHITE / 10000.))),
+            Transfer::Linear => *linear,
+        }
+    }
+
+    // Takes nonlinear RGB on to `stage`.
+    fn stage(&self, rgb: &[f32; 3], stage: Stage) -> [f32; 3] {
+        let linear = self.linear(rgb);
+        match stage {
+            Stage::Rgb => *rgb,
+            Stage::Linear => linear,
+            Stage::Xyz => linear_to_xyz(&linear, &self.rgb_to_xyz),
+            Stage::Lab => {
+                let lab = self.lab(rgb);
+                [lab.l, lab.a, lab.b]
+            }
+        }
+    }
+}
+
+fn mat_mul(a: &[[f32; 3]; 3], b: &[[f32; 3]; 3]) -> [[f32; 3]; 3] {
+    let mut m = [[0.; 3]; 3];
+    for (i, row) in m.iter_mut().enumerate() {
+        for (j, out) in row.iter_mut().enumerate() {
+            *out = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    m
+}
+
+/// The planes of a single frame. Samples with a bit depth above 8 are stored
+/// as two little-endian bytes, like in y4m.
+pub struct Frame<'a> {
+    pub y: &'a [u8],
+    pub u: &'a [u8],
+    pub v: &'a [u8],
+}
+
+/// Largest width or height accepted by [`FrameInfo::check_size`]. The largest
+/// video formats in use are 15360 pixels wide.
+pub const MAX_DIMENSION: usize = 1 << 16;
+
+/// Format shared by the two frames being compared.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FrameInfo {
+    pub width: usize,
+    pub height: usize,
+    pub bit_depth: usize,
+    /// log2 of the horizontal chroma subsampling, up to 2 for 4:1:1
+    pub xdec: usize,
+    /// log2 of the vertical chroma subsampling, up to 1
+    pub ydec: usize,
+    /// Chroma of vertically subsampled material is subsampled per field, so
+    /// chroma rows alternate between the two fields like luma rows do
+    pub interlaced: bool,
+    pub matrix: Matrix,
+    pub range: Range,
+    pub transfer: Transfer,
+    pub vision: Vision,
+}
+
+impl FrameInfo {
+    fn bytewidth(&self) -> usize {
+        if self.bit_depth > 8 {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// Chroma plane dimensions. Odd luma dimensions round up, as in y4m.
+    fn chroma_dims(&self) -> (usize, usize) {
+        (
+            (self.width + (1 << self.xdec) - 1) >> self.xdec,
+            (self.height + (1 << self.ydec) - 1) >> self.ydec,
+        )
+    }
+
+    /// Chroma row holding the samples for luma row `i`.
+    fn chroma_row(&self, i: usize) -> usize {
+        if self.interlaced && self.ydec == 1 {
+            let c_row = ((i >> 2) << 1) | (i & 1);
+            // With a height that is not a multiple of 4 the last chroma row of
+            // one field is missing, so reuse the one above it in that field.
+            // Frames under 4 rows only have the first field's chroma.
+            if c_row >= self.chroma_dims().1 {
+                c_row.saturating_sub(2)
+            } else {
+                c_row
+            }
+        } else {
+            i >> self.ydec
+        }
+    }
+
+    /// Checks that the dimensions are nonzero and at most [`MAX_DIMENSION`],
+    /// and, where the available memory is known, that a frame fits in it.
+    /// Formats parsed from untrusted headers should be checked before frame
+    /// buffers are allocated.
+    pub fn check_size(&self) -> Result<(), String> {
+        if self.width == 0
+            || self.height == 0
+            || self.width > MAX_DIMENSION
+            || self.height > MAX_DIMENSION
+        {
+            return Err(format!(
+                "Invalid dimensions {}x{}, each must be between 1 and {}",
+                self.width, self.height, MAX_DIMENSION
+            ));
+        }
+        // In u64 so the size cannot overflow on 32-bit targets
+        let (chroma_width, chroma_height) = self.chroma_dims();
+        let frame_len = (self.width as u64 * self.height as u64
+            + 2 * chroma_width as u64 * chroma_height as u64)
+            * self.bytewidth() as u64;
+        if frame_len > usize::MAX as u64 {
+            return Err(format!(
+                "A {}x{} frame does not fit in the address space",
+                self.width, self.height
+            ));
+        }
+        if let Some(available) = available_memory() {
+            if frame_len > available {
+                return Err(format!(
+                    "A {}x{} frame needs {} MiB, but only {} MiB of memory is available",
+                    self.width,
+                    self.height,
+                    frame_len >> 20,
+                    available >> 20
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of bytes in the luma plane and in each of the chroma planes.
+    pub fn plane_lens(&self) -> (usize, usize) {
+        let luma_len = self.width * self.height * self.bytewidth();
+        let (chroma_width, chroma_height) = self.chroma_dims();
+        (luma_len, chroma_width * chroma_height * self.bytewidth())
+    }
+
+    /// A chroma plane of the neutral value, as used for grayscale input.
+    pub fn neutral_chroma(&self) -> Vec<u8> {
+        let neutral = 1u16 << (self.bit_depth - 1);
+        let chroma_len = self.plane_lens().1;
+        if self.bytewidth() == 1 {
+            vec![neutral as u8; chroma_len]
+        } else {
+            neutral.to_le_bytes().repeat(chroma_len / 2)
+        }
+    }
+
+    /// Splits consecutive Y, U and V planes, as in the frame data of a y4m
+    /// file. Returns `None` if `buf` is too small.
+    pub fn split_planes<'a>(&self, buf: &'a [u8]) -> Option<Frame<'a>> {
+        let (luma_len, chroma_len) = self.plane_lens();
+        if buf.len() < luma_len + 2 * chroma_len {
+            return None;
+        }
+        let (y, chroma) = buf.split_at(luma_len);
+        let (u, chroma) = chroma.split_at(chroma_len);
+        Some(Frame {
+            y,
+            u,
+            v: &chroma[..chroma_len],
+        })
+    }
+
+    /// Slice row `i` out of `frame`. Panics if the planes are too small.
+    pub fn row<'a>(&self, frame: &Frame<'a>, i: usize) -> FrameRow<'a> {
+        // luma stride
+        let y_stride = self.width * self.bytewidth();
+        // chroma stride
+        let c_stride = self.chroma_dims().0 * self.bytewidth();
+        let c_row = self.chroma_row(i);
+        FrameRow {
+            y: &frame.y[i * y_stride..][..y_stride],
+            u: &frame.u[c_row * c_stride..][..c_stride],
+            v: &frame.v[c_row * c_stride..][..c_stride],
+            to_rgb: YuvToRgb::new(
+                self.bit_depth,
+                self.matrix,
+                self.range,
+                self.transfer,
+                self.vision,
+            ),
+        }
+    }
+
+    /// Returns columns `x..x + len` of a row returned by [`FrameInfo::row`].
+    /// `x` must be a multiple of the horizontal chroma subsampling.
+    pub fn row_tile<'a>(&self, row: &FrameRow<'a>, x: usize, len: usize) -> FrameRow<'a> {
+        let bw = self.bytewidth();
+        let c_start = ((x >> self.xdec) * bw).min(row.u.len());
+        let c_end = (((x + len + (1 << self.xdec) - 1) >> self.xdec) * bw).min(row.u.len());
+        FrameRow {
+            y: &row.y[x * bw..(x + len) * bw],
+            u: &row.u[c_start..c_end],
+            v: &row.v[c_start..c_end],
+            to_rgb: row.to_rgb,
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn available_memory() -> Option<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let line = meminfo
+        .lines()
+        .find(|line| line.starts_with("MemAvailable:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib << 10)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn available_memory() -> Option<u64> {
+    None
+}
+
+/// Converts the mean ΔE of a frame into the score reported by the CLI.
+pub fn delta_e_to_score(mean_delta_e: f64) -> f64 {
+    45. - 20. * mean_delta_e.log10()
+}
+
+/// Limit on how much difference is of interest when scoring a frame with
+/// [`delta_e_frame_bounded`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Budget {
+    /// Give up once the sum of the per-pixel ΔE exceeds this value.
+    Sum(f64),
+    /// Give up once the ΔE of any single pixel exceeds this value.
+    Max(f32),
+}
+
+/// Default tile width for [`delta_e_row_pool`].
+pub const TILE_WIDTH: usize = 1024;
+
+/// Sum and maximum of the per-pixel ΔE over part of a frame.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Pool {
+    pub sum: f64,
+    pub max: f32,
+}
+
+/// Pools the per-pixel ΔE of a row, scoring `tile.len()` pixels at a time so
+/// each tile is pooled while it is still in cache rather than after the whole
+/// row has been written out. The tile width should be a multiple of 8 so SIMD
+/// kernels are not split mid-chunk.
+///
+/// # Safety
+///
+/// Same requirements as `row_fn`.
+pub unsafe fn delta_e_row_pool(
+    row_fn: DeltaERowFn,
+    info: &FrameInfo,
+    row1: FrameRow,
+    row2: FrameRow,
+    ksub: KSubArgs,
+    tile: &mut [f32],
+) -> Pool {
+    let mut pool = Pool { sum: 0., max: 0. };
+    for x in (0..info.width).step_by(tile.len()) {
+        let len = tile.len().min(info.width - x);
+        row_fn(
+            info.row_tile(&row1, x, len),
+            info.row_tile(&row2, x, len),
+            ksub,
+            &mut tile[..len],
+        );
+        for delta_e in &tile[..len] {
+            pool.sum += *delta_e as f64;
+            pool.max = pool.max.max(*delta_e);
+        }
+    }
+    pool
+}
+
+/// Like [`delta_e_row_pool`], but the sum is of each pixel's ΔE times its
+/// entry in `weights`. The maximum is unweighted.
+///
+/// # Safety
+///
+/// Same requirements as `row_fn`.
+pub unsafe fn delta_e_row_pool_weighted(
+    row_fn: DeltaERowFn,
+    info: &FrameInfo,
+    row1: FrameRow,
+    row2: FrameRow,
+    ksub: KSubArgs,
+    tile: &mut [f32],
+    weights: &[f32],
+) -> Pool {
+    let mut pool = Pool { sum: 0., max: 0. };
+    for x in (0..info.width).step_by(tile.len()) {
+        let len = tile.len().min(info.width - x);
+        row_fn(
+            info.row_tile(&row1, x, len),
+            info.row_tile(&row2, x, len),
+            ksub,
+            &mut tile[..len],
+        );
+        for (delta_e, weight) in tile[..len].iter().zip(&weights[x..x + len]) {
+            pool.sum += (*delta_e * *weight) as f64;
+            pool.max = pool.max.max(*delta_e);
+        }
+    }
+    pool
+}
+
+/// Histogram of per-pixel ΔE in bins of 1/64, for percentiles.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeltaEHistogram {
+    /// The last bin holds every ΔE of 128 and up
+    bins: Vec<u64>,
+    count: u64,
+    max: f32,
+}
+
+impl DeltaEHistogram {
+    const BINS_PER_UNIT: f32 = 64.;
+
+    pub fn new() -> Self {
+        DeltaEHistogram {
+            bins: vec![0; 128 * Self::BINS_PER_UNIT as usize + 1],
+            count: 0,
+            max: 0.,
+        }
+    }
+
+    pub fn add(&mut self, delta_e: f32) {
+        let bin = ((delta_e * Self::BINS_PER_UNIT) as usize).min(self.bins.len() - 1);
+        self.bins[bin] += 1;
+        self.count += 1;
+        self.max = self.max.max(delta_e);
+    }
+
+    pub fn merge(&mut self, other: &DeltaEHistogram) {
+        for (bin, other) in self.bins.iter_mut().zip(&other.bins) {
+            *bin += other;
+        }
+        self.count += other.count;
+        self.max = self.max.max(other.max);
+    }
+
+    /// Returns the ΔE that fraction `p` of the pixels are at or below, rounded
+    /// up to the next bin edge, or 0 for an empty histogram.
+    pub fn percentile(&self, p: f64) -> f32 {
+        let rank = (p * self.count as f64).ceil().max(1.) as u64;
+        let mut seen = 0;
+        for (i, &bin) in self.bins.iter().enumerate() {
+            seen += bin;
+            if seen >= rank {
+                return if i == self.bins.len() - 1 {
+                    self.max
+                } else {
+                    ((i + 1) as f32 / Self::BINS_PER_UNIT).min(self.max)
+                };
+            }
+        }
+        0.
+    }
+
+    pub fn max(&self) -> f32 {
+        self.max
+    }
+
+    /// Returns the fraction of the pixels with a ΔE below `delta_e`, rounded
+    /// down to the bin edge, or 0 for an empty histogram.
+    pub fn fraction_below(&self, delta_e: f32) -> f64 {
+        let bins = ((delta_e * Self::BINS_PER_UNIT) as usize).min(self.bins.len() - 1);
+        let below: u64 = self.bins[..bins].iter().sum();
+        below as f64 / self.count.max(1) as f64
+    }
+}
+
+impl Default for DeltaEHistogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Like [`delta_e_row_pool`], also adding each pixel's ΔE to `histogram`. With
+/// `weights` the sum is weighted as in [`delta_e_row_pool_weighted`].
+///
+/// # Safety
+///
+/// Same requirements as `row_fn`.
+#[allow(clippy::too_many_arguments)]
+pub unsafe fn delta_e_row_pool_histogram(
+    row_fn: DeltaERowFn,
+    info: &FrameInfo,
+    row1: FrameRow,
+    row2: FrameRow,
+    ksub: KSubArgs,
+    tile: &mut [f32],
+    weights: Option<&[f32]>,
+    histogram: &mut DeltaEHistogram,
+) -> Pool {
+    let mut pool = Pool { sum: 0., max: 0. };
+    for x in (0..info.width).step_by(tile.len()) {
+        let len = tile.len().min(info.width - x);
+        row_fn(
+            info.row_tile(&row1, x, len),
+            info.row_tile(&row2, x, len),
+            ksub,
+            &mut tile[..len],
+        );
+        for (i, delta_e) in tile[..len].iter().enumerate() {
+            let weight = weights.map_or(1., |weights| weights[x + i]);
+            pool.sum += (*delta_e * weight) as f64;
+            pool.max = pool.max.max(*delta_e);
+            histogram.add(*delta_e);
+        }
+    }
+    pool
+}
+
+/// Writes `weight` for the pixels of `row` whose color is a likely skin tone
+/// and 1 for the others. Skin tones are taken to be those in an ellipsoid
+/// around L* 60, a* 16, b* 20 with semi-axes of 35, 10 and 12, which covers
+/// light to dark skin under daylight.
+pub fn skin_weights(info: &FrameInfo, row: &FrameRow, weight: f32, out: &mut [f32]) {
+    for (x, out) in out[..info.width].iter_mut().enumerate() {
+        let lab = pixel_lab(info, row, x);
+        let d = ((lab.l - 60.) / 35.).powi(2)
+            + ((lab.a - 16.) / 10.).powi(2)
+            + ((lab.b - 20.) / 12.).powi(2);
+        *out = if d <= 1. { weight } else { 1. };
+    }
+}
+
+/// Writes the L*, a* and b* that each pixel of a row returned by
+/// [`FrameInfo::row`] is scored with to `out`.
+pub fn row_lab(info: &FrameInfo, row: &FrameRow, out: &mut [[f32; 3]]) {
+    row_stage(info, row, Stage::Lab, out);
+}
+
+/// Like [`row_lab`], writing the values of each pixel at `stage` of the
+/// conversion instead.
+pub fn row_stage(info: &FrameInfo, row: &FrameRow, stage: Stage, out: &mut [[f32; 3]]) {
+    for (x, out) in out[..info.width].iter_mut().enumerate() {
+        *out = row.to_rgb.stage(&pixel_rgb(info, row, x), stage);
+    }
+}
+
+/// Returns the sum of the per-pixel ΔE between two frames, or `None` as soon
+/// as `budget` is exceeded.
+///
+/// The budget is checked after every row, so the cost of a frame that goes
+/// over budget is proportional to how far into the frame that happens. This is
+/// intended for encoders making mode decisions, where the exact difference
+/// beyond some bound does not matter.
+pub fn delta_e_frame_bounded(
+    info: &FrameInfo,
+    frame1: &Frame,
+    frame2: &Frame,
+    budget: Budget,
+    simd: impl Into<SimdLevel>,
+) -> Option<f64> {
+    let delta_e_row_fn = get_delta_e_row_fn(info.bit_depth, info.xdec, simd);
+    let mut tile: Vec<f32> = vec![0.0; TILE_WIDTH.min(info.width)];
+    let mut sum = 0f64;
+    for i in 0..info.height {
+        let pool = unsafe {
+            delta_e_row_pool(
+                delta_e_row_fn,
+                info,
+                info.row(frame1, i),
+                info.row(frame2, i),
+                K_SUB,
+                &mut tile,
+            )
+        };
+        sum += pool.sum;
+        let exceeded = match budget {
+            Budget::Sum(limit) => sum > limit,
+            Budget::Max(limit) => pool.max > limit,
+        };
+        if exceeded {
+            return None;
+        }
+    }
+    Some(sum)
+}
+
+/// Returns the sum of the per-pixel ΔE between two frames.
+pub fn delta_e_frame(
+    info: &FrameInfo,
+    frame1: &Frame,
+    frame2: &Frame,
+    simd: impl Into<SimdLevel>,
+) -> f64 {
+    delta_e_frame_bounded(info, frame1, frame2, Budget::Sum(f64::INFINITY), simd).unwrap()
+}
+
+/// Backend settings picked by [`autotune`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Tuning {
+    pub simd: SimdLevel,
+    pub threads: usize,
+    pub tile_width: usize,
+}
+
+/// Tile widths tried by [`autotune`].
+const TUNED_TILE_WIDTHS: [usize; 5] = [256, 512, 1024, 2048, 4096];
+
+// The shortest of 3 times taken to score a frame pair with `row_fn` on
+// `threads` threads, in bands of rows pooled in tiles of `tile_width`.
+fn time_frame(
+    info: &FrameInfo,
+    frame1: &Frame,
+    frame2: &Frame,
+    row_fn: DeltaERowFn,
+    threads: usize,
+    tile_width: usize,
+) -> Duration {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .unwrap();
+    // Bands as small as those of the command line tool
+    let band_height = info.height.div_ceil(4 * threads);
+    let score = || {
+        (0..info.height.div_ceil(band_height))
+            .into_par_iter()
+            .map(|band| {
+                let mut tile = vec![0.; tile_width.min(info.width)];
+                let rows = band * band_height..((band + 1) * band_height).min(info.height);
+                rows.map(|i| unsafe {
+                    let (row1, row2) = (info.row(frame1, i), info.row(frame2, i));
+                    delta_e_row_pool(row_fn, info, row1, row2, K_SUB, &mut tile).sum
+                })
+                .sum::<f64>()
+            })
+            .sum::<f64>()
+    };
+    pool.install(|| {
+        (0..3)
+            .map(|_| {
+                let start = Instant::now();
+                score();
+                start.elapsed()
+            })
+            .min()
+            .unwrap()
+    })
+}
+
+/// Picks the fastest SIMD level for a frame pair by timing the kernel
+/// `row_fn` gives for each level the CPU has, on one thread.
+pub fn autotune_simd(
+    info: &FrameInfo,
+    frame1: &Frame,
+    frame2: &Frame,
+    row_fn: impl Fn(SimdLevel) -> DeltaERowFn,
+) -> SimdLevel {
+    let best = SimdLevel::detect(info.xdec);
+    [
+        SimdLevel::Off,
+        SimdLevel::Neon,
+        SimdLevel::Rvv,
+        SimdLevel::Sse41,
+        SimdLevel::Avx2,
+        SimdLevel::Avx512,
+    ]
+    .iter()
+    .copied()
+    .filter(|&level| level <= best && level.supported())
+    .min_by_key(|&level| time_frame(info, frame1, frame2, row_fn(level), 1, TILE_WIDTH))
+    .unwrap()
+}
+
+/// Picks the fastest SIMD level, then the fastest thread count up to
+/// `max_threads` with it, then the fastest tile width, by timing the kernels
+/// `row_fn` gives on a frame pair. Thread counts are tried in powers of 2.
+pub fn autotune(
+    info: &FrameInfo,
+    frame1: &Frame,
+    frame2: &Frame,
+    row_fn: impl Fn(SimdLevel) -> DeltaERowFn,
+    max_threads: usize,
+) -> Tuning {
+    let simd = autotune_simd(info, frame1, frame2, &row_fn);
+    let row_fn = row_fn(simd);
+    let threads = (0..)
+        .map(|shift| 1 << shift)
+        .take_while(|&threads| threads < max_threads)
+        .chain([max_threads])
+        .min_by_key(|&threads| time_frame(info, frame1, frame2, row_fn, threads, TILE_WIDTH))
+        .unwrap();
+    // Widths past that of the frame would all time the same
+    let tile_width = TUNED_TILE_WIDTHS
+        .iter()
+        .copied()
+        .filter(|&width| width == TUNED_TILE_WIDTHS[0] || width / 2 < info.width)
+        .min_by_key(|&width| time_frame(info, frame1, frame2, row_fn, threads, width))
+        .unwrap();
+    Tuning {
+        simd,
+        threads,
+        tile_width,
+    }
+}
+
+/// Returns the mean ΔE of each `block_size`×`block_size` block in raster
+/// order. Blocks on the right and bottom edges are clipped to the frame.
+pub fn score_blocks(
+    info: &FrameInfo,
+    reference: &Frame,
+    distorted: &Frame,
+    block_size: usize,
+    simd: impl Into<SimdLevel>,
+) -> Vec<f32> {
+    assert!(block_size > 0, "block size must be nonzero");
+    let blocks_wide = info.width.div_ceil(block_size);
+    let blocks_high = info.height.div_ceil(block_size);
+    let delta_e_row_fn = get_delta_e_row_fn(info.bit_depth, info.xdec, simd);
+    let mut delta_e_row: Vec<f32> = vec![0.0; info.width];
+    let mut sums = vec![0f64; blocks_wide * blocks_high];
+    for i in 0..info.height {
+        unsafe {
+            delta_e_row_fn(
+                info.row(reference, i),
+                info.row(distorted, i),
+                K_SUB,
+                &mut delta_e_row,
+            );
+        }
+        let block_row = &mut sums[(i / block_size) * blocks_wide..][..blocks_wide];
+        for (sum, chunk) in block_row.iter_mut().zip(delta_e_row.chunks(block_size)) {
+            *sum += chunk.iter().map(|x| *x as f64).sum::<f64>();
+        }
+    }
+    sums.iter()
+        .enumerate()
+        .map(|(n, sum)| {
+            let w = block_size.min(info.width - (n % blocks_wide) * block_size);
+            let h = block_size.min(info.height - (n / blocks_wide) * block_size);
+            (sum / ((w * h) as f64)) as f32
+        })
+        .collect()
+}
+
+/// Shares of the summed squared ΔE of a frame that come from each of the
+/// DE2000 terms. The rest comes from the rotation term, which may be negative.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Breakdown {
+    pub lightness: f64,
+    pub chroma: f64,
+    pub hue: f64,
+}
+
+/// Converts pixel `x` of a row returned by [`FrameInfo::row`] to Lab.
+fn pixel_lab(info: &FrameInfo, row: &FrameRow, x: usize) -> Lab {
+    row.to_rgb.lab(&pixel_rgb(info, row, x))
+}
+
+/// Converts pixel `x` of a row returned by [`FrameInfo::row`] to nonlinear
+/// RGB.
+fn pixel_rgb(info: &FrameInfo, row: &FrameRow, x: usize) -> [f32; 3] {
+    let read = |plane: &[u8], i: usize| {
+        if info.bytewidth() == 1 {
+            plane[i] as u16
+        } else {
+            u16::from_le_bytes([plane[2 * i], plane[2 * i + 1]])
+        }
+    };
+    let c = x >> info.xdec;
+    let yuv = (read(row.y, x), read(row.u, c), read(row.v, c));
+    // The conversion comes from `to_rgb`, so any kernel will do
+    Kernel::<8, 0>::yuv_to_rgb_scalar(&row.to_rgb, yuv)
+}
+
+/// Returns the [`Breakdown`] of the ΔE between two frames.
+pub fn delta_e_breakdown(
+    info: &FrameInfo,
+    frame1: &Frame,
+    frame2: &Frame,
+    ksub: KSubArgs,
+) -> Breakdown {
+    let (mut lightness, mut chroma, mut hue, mut total) = (0f64, 0f64, 0f64, 0f64);
+    for i in 0..info.height {
+        let row1 = info.row(frame1, i);
+        let row2 = info.row(frame2, i);
+        for x in 0..info.width {
+            let terms = DE2000::terms(pixel_lab(info, &row1, x), pixel_lab(info, &row2, x), ksub);
+            lightness += terms.lightness.powi(2) as f64;
+            chroma += terms.chroma.powi(2) as f64;
+            hue += terms.hue.powi(2) as f64;
+            total += terms.delta_e().powi(2) as f64;
+        }
+    }
+    if total == 0. {
+        return Breakdown::default();
+    }
+    Breakdown {
+        lightness: lightness / total,
+        chroma: chroma / total,
+        hue: hue / total,
+    }
+}
+
+/// Writes 1 for the pixels of row `i` of a frame that lie on a smooth luma
+/// gradient or plateau, where banding is most visible, and 0 for the others.
+/// A pixel is smooth if luma changes by at most one 8-bit code to each of its
+/// four neighbors.
+pub fn smooth_mask(info: &FrameInfo, frame: &Frame, i: usize, out: &mut [f32]) {
+    let read = |row: usize, x: usize| {
+        let i = row * info.width + x;
+        if info.bytewidth() == 1 {
+            frame.y[i] as i32
+        } else {
+            u16::from_le_bytes([frame.y[2 * i], frame.y[2 * i + 1]]) as i32
+        }
+    };
+    let max_step = 1 << (info.bit_depth - 8);
+    let above = i.saturating_sub(1);
+    let below = (i + 1).min(info.height - 1);
+    for (x, out) in out[..info.width].iter_mut().enumerate() {
+        let y = read(i, x);
+        let smooth = [
+            read(i, x.saturating_sub(1)),
+            read(i, (x + 1).min(info.width - 1)),
+            read(above, x),
+            read(below, x),
+        ]
+        .iter()
+        .all(|&n| (n - y).abs() <= max_step);
+        *out = smooth as u32 as f32;
+    }
+}
+
+/// Returns the mean absolute luma step across the edges of a grid of
+/// `block_size` blocks over the mean step between other neighboring pixels.
+/// Frames without block artifacts score about 1. Both means are in 8-bit
+/// codes and have one code added, so that flat frames score 1.
+pub fn blockiness(info: &FrameInfo, frame: &Frame, block_size: usize) -> f64 {
+    let read = |i: usize| {
+        if info.bytewidth() == 1 {
+            frame.y[i] as i32
+        } else {
+            u16::from_le_bytes([frame.y[2 * i], frame.y[2 * i + 1]]) as i32
+        }
+    };
+    // Sums and counts of the steps across block edges and inside blocks
+    let mut edge = (0u64, 0u64);
+    let mut inner = (0u64, 0u64);
+    for i in 0..info.height {
+        for x in 0..info.width {
+            let y = read(i * info.width + x);
+            if x > 0 {
+                let step = (y - read(i * info.width + x - 1)).unsigned_abs() as u64;
+                let sum = if x % block_size == 0 {
+                    &mut edge
+                } else {
+                    &mut inner
+                };
+                *sum = (sum.0 + step, sum.1 + 1);
+            }
+            if i > 0 {
+                let step = (y - read((i - 1) * info.width + x)).unsigned_abs() as u64;
+                let sum = if i % block_size == 0 {
+                    &mut edge
+                } else {
+                    &mut inner
+                };
+                *sum = (sum.0 + step, sum.1 + 1);
+            }
+        }
+    }
+    let scale = (1 << (info.bit_depth - 8)) as f64;
+    let mean = |(sum, count): (u64, u64)| sum as f64 / scale / count.max(1) as f64 + 1.;
+    mean(edge) / mean(inner)
+}
+
+/// Returns the mean luma of a frame, from 0 for black to 1 for white.
+pub fn mean_luma(info: &FrameInfo, frame: &Frame) -> f64 {
+    let samples = info.width * info.height;
+    let sum: u64 = luma_samples(info, frame).map(|y| y as u64).sum();
+    normalize_luma(info, sum as f64 / samples as f64)
+}
+
+/// Returns the mean luma of each cell of a `cells`×`cells` grid over a frame,
+/// in raster order, on the scale of [`mean_luma`]. Cells split the frame as
+/// evenly as whole pixels allow.
+pub fn luma_grid(info: &FrameInfo, frame: &Frame, cells: usize) -> Vec<f64> {
+    let mut sums = vec![(0u64, 0u64); cells * cells];
+    for (i, y) in luma_samples(info, frame).enumerate() {
+        let (row, col) = (i / info.width, i % info.width);
+        let cell = &mut sums[row * cells / info.height * cells + col * cells / info.width];
+        cell.0 += y as u64;
+        cell.1 += 1;
+    }
+    sums.iter()
+        .map(|&(sum, count)| normalize_luma(info, sum as f64 / count.max(1) as f64))
+        .collect()
+}
+
+// Maps a mean luma sample value to 0 for black and 1 for white.
+fn normalize_luma(info: &FrameInfo, mean: f64) -> f64 {
+    let scale = (1 << (info.bit_depth - 8)) as f64;
+    let luma = match info.range {
+        Range::Limited => (mean - 16. * scale) / (219. * scale),
+        Range::Full => mean / ((1 << info.bit_depth) - 1) as f64,
+    };
+    luma.clamp(0., 1.)
+}
+
+/// Returns the standard deviation of the luma of a frame, on the scale of
+/// [`mean_luma`].
+pub fn luma_deviation(info: &FrameInfo, frame: &Frame) -> f64 {
+    let samples = (info.width * info.height) as f64;
+    let (sum, sum_sq) = luma_samples(info, frame).fold((0f64, 0f64), |(sum, sum_sq), y| {
+        let y = y as f64;
+        (sum + y, sum_sq + y * y)
+    });
+    let mean = sum / samples;
+    let deviation = (sum_sq / samples - mean * mean).max(0.).sqrt();
+    let scale = (1 << (info.bit_depth - 8)) as f64;
+    match info.range {
+        Range::Limited => deviation / (219. * scale),
+        Range::Full => deviation / ((1 << info.bit_depth) - 1) as f64,
+    }
+}
+
+fn luma_samples<'a>(info: &FrameInfo, frame: &Frame<'a>) -> impl Iterator<Item = u16> + 'a {
+    let samples = info.width * info.height;
+    let (bytewidth, y) = (info.bytewidth(), frame.y);
+    y[..bytewidth * samples]
+        .chunks_exact(bytewidth)
+        .map(|y| match *y {
+            [y] => y as u16,
+            [lo, hi] => u16::from_le_bytes([lo, hi]),
+            _ => unreachable!(),
+        })
+}
+
+/// Returns the mean ΔE over the chroma samples of two frames. Each chroma
+/// sample is paired with the mean of the luma samples it covers instead of
+/// upsampling chroma to the luma grid, so the result does not depend on an
+/// upsampling filter. For 4:4:4 this is the regular mean ΔE.
+pub fn delta_e_chroma_grid(
+    info: &FrameInfo,
+    frame1: &Frame,
+    frame2: &Frame,
+    ksub: KSubArgs,
+) -> f64 {
+    let (chroma_width, chroma_height) = info.chroma_dims();
+    let read = |plane: &[u8], i: usize| {
+        if info.bytewidth() == 1 {
+            plane[i] as u32
+        } else {
+            u16::from_le_bytes([plane[2 * i], plane[2 * i + 1]]) as u32
+        }
+    };
+    // Sums of the luma samples covered by each chroma sample, and their count
+    let mut luma = vec![(0u32, 0u32, 0u32); chroma_width * chroma_height];
+    for i in 0..info.height {
+        let c_row = &mut luma[info.chroma_row(i) * chroma_width..][..chroma_width];
+        for x in 0..info.width {
+            let (sum1, sum2, count) = &mut c_row[x >> info.xdec];
+            *sum1 += read(frame1.y, i * info.width + x);
+            *sum2 += read(frame2.y, i * info.width + x);
+            *count += 1;
+        }
+    }
+    let to_rgb = YuvToRgb::new(
+        info.bit_depth,
+        info.matrix,
+        info.range,
+        info.transfer,
+        info.vision,
+    );
+    let mut sum = 0f64;
+    let mut samples = 0;
+    for (c, &(sum1, sum2, count)) in luma.iter().enumerate() {
+        if count == 0 {
+            continue;
+        }
+        let mean = |sum: u32| ((sum + count / 2) / count) as u16;
+        let yuv1 = (
+            mean(sum1),
+            read(frame1.u, c) as u16,
+            read(frame1.v, c) as u16,
+        );
+        let yuv2 = (
+            mean(sum2),
+            read(frame2.u, c) as u16,
+            read(frame2.v, c) as u16,
+        );
+        // The conversion comes from `to_rgb`, so any kernel will do
+        sum += Kernel::<8, 0>::delta_e_scalar(&to_rgb, yuv1, &to_rgb, yuv2, ksub) as f64;
+        samples += 1;
+    }
+    sum / samples as f64
+}
+
+/// Computes the ΔE of each pixel in a row.
+///
+/// The rows must be the same width and hold samples of the bit depth and
+/// chroma decimation the function was obtained for. The unsafe part is
+/// `target_feature`: functions returned by [`get_delta_e_row_fn`] only use
+/// instruction sets that were detected at runtime.
+pub type DeltaERowFn = unsafe fn(FrameRow, FrameRow, KSubArgs, &mut [f32]);
+
+// The SIMD kernel `$f` of `$wrap<Kernel<..>>`, or of `Kernel<..>`, for the bit
+// depth and chroma decimation. SIMD kernels cover 4:4:4 and chroma subsampled
+// by 2 horizontally.
+#[cfg(any(
+    target_arch = "x86",
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    all(feature = "rvv", target_arch = "riscv64")
+))]
+macro_rules! simd_row_fn {
+    ($wrap:ident :: $f:ident, $bit_depth:expr, $xdec:expr) => {
+        match ($bit_depth, $xdec) {
+            (8, 1) => $wrap::<Kernel<8, 1>>::$f,
+            (10, 1) => $wrap::<Kernel<10, 1>>::$f,
+            (12, 1) => $wrap::<Kernel<12, 1>>::$f,
+            (8, 0) => $wrap::<Kernel<8, 0>>::$f,
+            (10, 0) => $wrap::<Kernel<10, 0>>::$f,
+            (12, 0) => $wrap::<Kernel<12, 0>>::$f,
+            _ => unreachable!(),
+        }
+    };
+    ($f:ident, $bit_depth:expr, $xdec:expr) => {
+        match ($bit_depth, $xdec) {
+            (8, 1) => Kernel::<8, 1>::$f,
+            (10, 1) => Kernel::<10, 1>::$f,
+            (12, 1) => Kernel::<12, 1>::$f,
+            (8, 0) => Kernel::<8, 0>::$f,
+            (10, 0) => Kernel::<10, 0>::$f,
+            (12, 0) => Kernel::<12, 0>::$f,
+            _ => unreachable!(),
+        }
+    };
+}
+
+pub fn get_delta_e_row_fn(
+    bit_depth: usize,
+    xdec: usize,
+    simd: impl Into<SimdLevel>,
+) -> DeltaERowFn {
+    let simd = simd.into().min(SimdLevel::detect(xdec));
+    #[cfg(not(any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        all(feature = "rvv", target_arch = "riscv64")
+    )))]
+    let _ = simd;
+    #[cfg(target_arch = "aarch64")]
+    if simd == SimdLevel::Neon {
+        return simd_row_fn!(delta_e_row_neon, bit_depth, xdec);
+    }
+    #[cfg(all(feature = "rvv", target_arch = "riscv64"))]
+    if simd == SimdLevel::Rvv {
+        return simd_row_fn!(delta_e_row_rvv, bit_depth, xdec);
+    }
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    match simd {
+        SimdLevel::Avx512 => {
+            return simd_row_fn!(delta_e_row_avx512, bit_depth, xdec);
+        }
+        SimdLevel::Avx2 => {
+            return simd_row_fn!(delta_e_row_avx2, bit_depth, xdec);
+        }
+        SimdLevel::Sse41 => {
+            return simd_row_fn!(delta_e_row_sse41, bit_depth, xdec);
+        }
+        _ => {}
+    }
+    match (bit_depth, xdec) {
+        (8, 1) => Kernel::<8, 1>::delta_e_row_scalar,
+        (10, 1) => Kernel::<10, 1>::delta_e_row_scalar,
+        (12, 1) => Kernel::<12, 1>::delta_e_row_scalar,
+        (8, 0) => Kernel::<8, 0>::delta_e_row_scalar,
+        (10, 0) => Kernel::<10, 0>::delta_e_row_scalar,
+        (12, 0) => Kernel::<12, 0>::delta_e_row_scalar,
+        (8, 2) => Kernel::<8, 2>::delta_e_row_scalar,
+        (10, 2) => Kernel::<10, 2>::delta_e_row_scalar,
+        (12, 2) => Kernel::<12, 2>::delta_e_row_scalar,
+        _ => unreachable!(),
+    }
+}
+
+/// Same as [`get_delta_e_row_fn`], but the returned kernel uses
+/// [`DE2000::new_approx`](delta_e::DE2000::new_approx).
+pub fn get_delta_e_row_fn_approx(
+    bit_depth: usize,
+    xdec: usize,
+    simd: impl Into<SimdLevel>,
+) -> DeltaERowFn {
+    let simd = simd.into().min(SimdLevel::detect(xdec));
+    #[cfg(not(any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        all(feature = "rvv", target_arch = "riscv64")
+    )))]
+    let _ = simd;
+    #[cfg(target_arch = "aarch64")]
+    if simd == SimdLevel::Neon {
+        return simd_row_fn!(Approx::delta_e_row_neon, bit_depth, xdec);
+    }
+    #[cfg(all(feature = "rvv", target_arch = "riscv64"))]
+    if simd == SimdLevel::Rvv {
+        return simd_row_fn!(Approx::delta_e_row_rvv, bit_depth, xdec);
+    }
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    match simd {
+        SimdLevel::Avx512 => {
+            return simd_row_fn!(Approx::delta_e_row_avx512, bit_depth, xdec);
+        }
+        SimdLevel::Avx2 => {
+            return simd_row_fn!(Approx::delta_e_row_avx2, bit_depth, xdec);
+        }
+        SimdLevel::Sse41 => {
+            return simd_row_fn!(Approx::delta_e_row_sse41, bit_depth, xdec);
+        }
+        _ => {}
+    }
+    match (bit_depth, xdec) {
+        (8, 1) => Approx::<Kernel<8, 1>>::delta_e_row_scalar,
+        (10, 1) => Approx::<Kernel<10, 1>>::delta_e_row_scalar,
+        (12, 1) => Approx::<Kernel<12, 1>>::delta_e_row_scalar,
+        (8, 0) => Approx::<Kernel<8, 0>>::delta_e_row_scalar,
+        (10, 0) => Approx::<Kernel<10, 0>>::delta_e_row_scalar,
+        (12, 0) => Approx::<Kernel<12, 0>>::delta_e_row_scalar,
+        (8, 2) => Approx::<Kernel<8, 2>>::delta_e_row_scalar,
+        (10, 2) => Approx::<Kernel<10, 2>>::delta_e_row_scalar,
+        (12, 2) => Approx::<Kernel<12, 2>>::delta_e_row_scalar,
+        _ => unreachable!(),
+    }
+}
+
+/// Same as [`get_delta_e_row_fn`], but the returned kernel scores `metric`.
+/// The weights it is given only apply to [`Metric::De2000`] and
+/// [`Metric::Cmc`].
+pub fn get_delta_e_row_fn_metric(
+    metric: Metric,
+    bit_depth: usize,
+    xdec: usize,
+    simd: impl Into<SimdLevel>,
+) -> DeltaERowFn {
+    let simd = simd.into().min(SimdLevel::detect(xdec));
+    macro_rules! kernels {
+        ($metric:ident) => {{
+            #[cfg(not(any(
+                target_arch = "x86",
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                all(feature = "rvv", target_arch = "riscv64")
+            )))]
+            let _ = simd;
+            #[cfg(target_arch = "aarch64")]
+            if simd == SimdLevel::Neon {
+                return simd_row_fn!($metric::delta_e_row_neon, bit_depth, xdec);
+            }
+            #[cfg(all(feature = "rvv", target_arch = "riscv64"))]
+            if simd == SimdLevel::Rvv {
+                return simd_row_fn!($metric::delta_e_row_rvv, bit_depth, xdec);
+            }
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            match simd {
+                SimdLevel::Avx512 => {
+                    return simd_row_fn!($metric::delta_e_row_avx512, bit_depth, xdec);
+                }
+                SimdLevel::Avx2 => {
+                    return simd_row_fn!($metric::delta_e_row_avx2, bit_depth, xdec);
+                }
+                SimdLevel::Sse41 => {
+                    return simd_row_fn!($metric::delta_e_row_sse41, bit_depth, xdec);
+                }
+                _ => {}
+            }
+            match (bit_depth, xdec) {
+                (8, 1) => $metric::<Kernel<8, 1>>::delta_e_row_scalar,
+                (10, 1) => $metric::<Kernel<10, 1>>::delta_e_row_scalar,
+                (12, 1) => $metric::<Kernel<12, 1>>::delta_e_row_scalar,
+                (8, 0) => $metric::<Kernel<8, 0>>::delta_e_row_scalar,
+                (10, 0) => $metric::<Kernel<10, 0>>::delta_e_row_scalar,
+                (12, 0) => $metric::<Kernel<12, 0>>::delta_e_row_scalar,
+                (8, 2) => $metric::<Kernel<8, 2>>::delta_e_row_scalar,
+                (10, 2) => $metric::<Kernel<10, 2>>::delta_e_row_scalar,
+                (12, 2) => $metric::<Kernel<12, 2>>::delta_e_row_scalar,
+                _ => unreachable!(),
+            }
+        }};
+    }
+    match metric {
+        Metric::De2000 => get_delta_e_row_fn(bit_depth, xdec, simd),
+        Metric::De76 => kernels!(De76),
+        Metric::De94 => kernels!(De94),
+        Metric::Cmc => kernels!(Cmc),
+        Metric::Itp => kernels!(Itp),
+    }
+}
+
+/// Returns a scalar kernel converting YUV to RGB in fixed point, for targets
+/// with weak floating point or SIMD. Only 8-bit input with horizontally
+/// subsampled chroma is supported.
+///
+/// Over all 8-bit YUV values the RGB differs from the floating point version
+/// by at most 1.0e-4, which is a ΔE of at most 0.018 (mean 0.003) between the
+/// two conversions of the same pixel.
+pub fn get_delta_e_row_fn_fixed(
+    bit_depth: usize,
+    xdec: usize,
+    approx_trig: bool,
+) -> Option<DeltaERowFn> {
+    match (bit_depth, xdec, approx_trig) {
+        (8, 1, false) => Some(Fixed8::delta_e_row_scalar),
+        (8, 1, true) => Some(Approx::<Fixed8>::delta_e_row_scalar),
+        _ => None,
+    }
+}
+
+/// Whether the SIMD row kernels cover chroma subsampled by `1 << xdec`
+/// horizontally on this CPU.
+pub fn simd_available(xdec: usize) -> bool {
+    SimdLevel::detect(xdec) != SimdLevel::Off
+}
+
+/// Instruction sets the row kernels may use, from least to most capable.
+/// Requesting a level the CPU or format lacks falls back to the best one
+/// below it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SimdLevel {
+    Off,
+    /// 4 pixels at a time, on aarch64
+    Neon,
+    /// 16 pixels at a time, on riscv64 with the V extension, when built with
+    /// the `rvv` feature
+    Rvv,
+    /// 4 pixels at a time, for x86 without AVX2
+    Sse41,
+    /// 8 pixels at a time
+    Avx2,
+    /// 16 pixels at a time
+    Avx512,
+}
+
+impl SimdLevel {
+    /// The most capable level this CPU has kernels for, for chroma subsampled
+    /// by `1 << xdec` horizontally.
+    pub fn detect(xdec: usize) -> SimdLevel {
+        if xdec > 1 {
+            return SimdLevel::Off;
+        }
+        [
+            SimdLevel::Avx512,
+            SimdLevel::Avx2,
+            SimdLevel::Sse41,
+            SimdLevel::Neon,
+            SimdLevel::Rvv,
+        ]
+        .iter()
+        .copied()
+        .find(|level| level.supported())
+        .unwrap_or(SimdLevel::Off)
+    }
+
+    /// Whether this CPU has the instructions of the level.
+    pub fn supported(self) -> bool {
+        match self {
+            SimdLevel::Off => true,
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            SimdLevel::Sse41 => is_x86_feature_detected!("sse4.1"),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            SimdLevel::Avx2 => is_x86_feature_detected!("avx2"),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            SimdLevel::Avx512 => is_x86_feature_detected!("avx512f"),
+            #[cfg(target_arch = "aarch64")]
+            SimdLevel::Neon => std::arch::is_aarch64_feature_detected!("neon"),
+            #[cfg(all(feature = "rvv", target_arch = "riscv64"))]
+            SimdLevel::Rvv => std::arch::is_riscv_feature_detected!("v"),
+            #[allow(unreachable_patterns)]
+            _ => false,
+        }
+    }
+}
+
+/// `true` is the best level available, `false` is [`SimdLevel::Off`].
+impl From<bool> for SimdLevel {
+    fn from(simd: bool) -> SimdLevel {
+        if simd {
+            SimdLevel::Avx512
+        } else {
+            SimdLevel::Off
+        }
+    }
+}
+
+pub trait Colorspace {
+    const BIT_DEPTH: u32;
+    const X_DECIMATION: u32;
+    /// Use [`DE2000::new_approx`] instead of [`DE2000::new`]
+    const APPROX_TRIG: bool = false;
+    /// Color difference scored
+    const METRIC: Metric = Metric::De2000;
+}
+
+/// Kernels for a bit depth and horizontal chroma decimation
+struct Kernel<const BIT_DEPTH: u32, const X_DECIMATION: u32>;
+
+/// `C` with approximate trigonometry in DE2000
+struct Approx<C>(PhantomData<C>);
+
+/// `C` scoring CIE76
+struct De76<C>(PhantomData<C>);
+
+/// `C` scoring CIE94
+struct De94<C>(PhantomData<C>);
+
+/// `C` scoring CMC l:c
+struct Cmc<C>(PhantomData<C>);
+
+/// `C` scoring ΔE-ITP
+struct Itp<C>(PhantomData<C>);
+
+/// 8-bit subsampled chroma with YUV to RGB in fixed point
+struct Fixed8;
+
+impl<const BD: u32, const XDEC: u32> Colorspace for Kernel<BD, XDEC> {
+    const BIT_DEPTH: u32 = BD;
+    const X_DECIMATION: u32 = XDEC;
+}
+impl Colorspace for Fixed8 {
+    const BIT_DEPTH: u32 = 8;
+    const X_DECIMATION: u32 = 1;
+}
+impl<C: Colorspace> Colorspace for Approx<C> {
+    const BIT_DEPTH: u32 = C::BIT_DEPTH;
+    const X_DECIMATION: u32 = C::X_DECIMATION;
+    const APPROX_TRIG: bool = true;
+}
+impl<C: Colorspace> Colorspace for De76<C> {
+    const BIT_DEPTH: u32 = C::BIT_DEPTH;
+    const X_DECIMATION: u32 = C::X_DECIMATION;
+    const METRIC: Metric = Metric::De76;
+}
+impl<C: Colorspace> Colorspace for De94<C> {
+    const BIT_DEPTH: u32 = C::BIT_DEPTH;
+    const X_DECIMATION: u32 = C::X_DECIMATION;
+    const METRIC: Metric = Metric::De94;
+}
+impl<C: Colorspace> Colorspace for Cmc<C> {
+    const BIT_DEPTH: u32 = C::BIT_DEPTH;
+    const X_DECIMATION: u32 = C::X_DECIMATION;
+    const METRIC: Metric = Metric::Cmc;
+}
+impl<C: Colorspace> Colorspace for Itp<C> {
+    const BIT_DEPTH: u32 = C::BIT_DEPTH;
+    const X_DECIMATION: u32 = C::X_DECIMATION;
+    const METRIC: Metric = Metric::Itp;
+}
+
+/// Pixels converted to Lab at a time by the scalar kernel.
+const BATCH: usize = 64;
+
+/// Lab values of a batch of pixels, one array per component.
+struct LabBatch {
+    l: [f32; BATCH],
+    a: [f32; BATCH],
+    b: [f32; BATCH],
+}
+
+impl LabBatch {
+    fn new() -> Self {
+        LabBatch {
+            l: [0.; BATCH],
+            a: [0.; BATCH],
+            b: [0.; BATCH],
+        }
+    }
+
+    fn get(&self, i: usize) -> Lab {
+        Lab {
+            l: self.l[i],
+            a: self.a[i],
+            b: self.b[i],
+        }
+    }
+}
+
+pub trait DeltaEScalar: Colorspace {
+    fn yuv_to_rgb_scalar(c: &YuvToRgb, yuv: (u16, u16, u16)) -> [f32; 3] {
+        let y = (yuv.0 as f32 - c.y_offset) * c.y_scale;
+        let u = (yuv.1 as f32 - c.uv_offset) * c.uv_scale;
+        let v = (yuv.2 as f32 - c.uv_offset) * c.uv_scale;
+        if c.gbr {
+            return [v, y, u];
+        }
+
+        // For limited range BT.709: [-0.804677, 1.81723]
+        let r = y + c.r_v * v;
+        // [−0.316650, 1.09589]
+        let g = y - c.g_u * u - c.g_v * v;
+        // [-1.28905, 2.29781]
+        let b = y + c.b_u * u;
+
+        [r, g, b]
+    }
+
+    /// Converts nonlinear RGB to Lab, or to ITP packed in the same fields.
+    fn lab(c: &YuvToRgb, rgb: &[f32; 3]) -> Lab {
+        if Self::METRIC == Metric::Itp {
+            c.itp(rgb)
+        } else {
+            c.lab(rgb)
+        }
+    }
+
+    fn delta_e(lab1: Lab, lab2: Lab, ksub: KSubArgs) -> f32 {
+        match Self::METRIC {
+            Metric::De2000 if Self::APPROX_TRIG => DE2000::new_approx(lab1, lab2, ksub),
+            Metric::De2000 => DE2000::new(lab1, lab2, ksub),
+            Metric::De76 => DE76::new(lab1, lab2),
+            Metric::De94 => DE94::new(lab1, lab2),
+            Metric::Cmc => CMC::new(lab1, lab2, ksub.l, ksub.c),
+            Metric::Itp => delta_e_itp(&[lab1.l, lab1.a, lab1.b], &[lab2.l, lab2.a, lab2.b]),
+        }
+    }
+
+    fn delta_e_scalar(
+        c1: &YuvToRgb,
+        yuv1: (u16, u16, u16),
+        c2: &YuvToRgb,
+        yuv2: (u16, u16, u16),
+        ksub: KSubArgs,
+    ) -> f32 {
+        Self::delta_e(
+            Self::lab(c1, &Self::yuv_to_rgb_scalar(c1, yuv1)),
+            Self::lab(c2, &Self::yuv_to_rgb_scalar(c2, yuv2)),
+            ksub,
+        )
+    }
+
+    /// Returns the samples of pixel `i` of a row.
+    fn sample(row: &FrameRow, i: usize) -> (u16, u16, u16) {
+        let c = i >> Self::X_DECIMATION;
+        if Self::BIT_DEPTH == 8 {
+            (row.y[i] as u16, row.u[c] as u16, row.v[c] as u16)
+        } else {
+            let read =
+                |plane: &[u8], i: usize| u16::from_le_bytes([plane[2 * i], plane[2 * i + 1]]);
+            (read(row.y, i), read(row.u, c), read(row.v, c))
+        }
+    }
+
+    /// # Safety
+    ///
+    /// Has no requirements beyond those of [`DeltaERowFn`].
+    unsafe fn delta_e_row_scalar(
+        row1: FrameRow,
+        row2: FrameRow,
+        ksub: KSubArgs,
+        res_row: &mut [f32],
+    ) {
+        let len = row_pixels::<Self>(&row1, &row2, res_row.len());
+        let mut lab1 = LabBatch::new();
+        let mut lab2 = LabBatch::new();
+        for start in (0..len).step_by(BATCH) {
+            let batch = BATCH.min(len - start);
+            lab_batch::<Self>(&row1, start, batch, &mut lab1);
+            lab_batch::<Self>(&row2, start, batch, &mut lab2);
+            for (i, res) in res_row[start..start + batch].iter_mut().enumerate() {
+                *res = Self::delta_e(lab1.get(i), lab2.get(i), ksub);
+            }
+        }
+    }
+}
+
+/// Returns the pixels of two rows with samples in every plane, up to `max`.
+fn row_pixels<C: Colorspace + ?Sized>(row1: &FrameRow, row2: &FrameRow, max: usize) -> usize {
+    let bytewidth = if C::BIT_DEPTH == 8 { 1 } else { 2 };
+    [row1.y.len(), row2.y.len()]
+        .iter()
+        .map(|len| len / bytewidth)
+        .chain(
+            [row1.u.len(), row1.v.len(), row2.u.len(), row2.v.len()]
+                .iter()
+                .map(|len| (len / bytewidth) << C::X_DECIMATION),
+        )
+        .fold(max, usize::min)
+}
+
+/// Converts pixels `start..start + len` of a row to Lab.
+fn lab_batch<C: DeltaEScalar + ?Sized>(
+    row: &FrameRow,
+    start: usize,
+    len: usize,
+    out: &mut LabBatch,
+) {
+    for i in 0..len {
+        let lab = C::lab(
+            &row.to_rgb,
+            &C::yuv_to_rgb_scalar(&row.to_rgb, C::sample(row, start + i)),
+        );
+        out.l[i] = lab.l;
+        out.a[i] = lab.a;
+        out.b[i] = lab.b;
+    }
+}
+
+impl<const BD: u32, const XDEC: u32> DeltaEScalar for Kernel<BD, XDEC> {}
+impl<C: DeltaEScalar> DeltaEScalar for Approx<C> {
+    fn yuv_to_rgb_scalar(c: &YuvToRgb, yuv: (u16, u16, u16)) -> [f32; 3] {
+        C::yuv_to_rgb_scalar(c, yuv)
+    }
+}
+impl<C: DeltaEScalar> DeltaEScalar for De76<C> {
+    fn yuv_to_rgb_scalar(c: &YuvToRgb, yuv: (u16, u16, u16)) -> [f32; 3] {
+        C::yuv_to_rgb_scalar(c, yuv)
+    }
+}
+impl<C: DeltaEScalar> DeltaEScalar for De94<C> {
+    fn yuv_to_rgb_scalar(c: &YuvToRgb, yuv: (u16, u16, u16)) -> [f32; 3] {
+        C::yuv_to_rgb_scalar(c, yuv)
+    }
+}
+impl<C: DeltaEScalar> DeltaEScalar for Cmc<C> {
+    fn yuv_to_rgb_scalar(c: &YuvToRgb, yuv: (u16, u16, u16)) -> [f32; 3] {
+        C::yuv_to_rgb_scalar(c, yuv)
+    }
+}
+impl<C: DeltaEScalar> DeltaEScalar for Itp<C> {
+    fn yuv_to_rgb_scalar(c: &YuvToRgb, yuv: (u16, u16, u16)) -> [f32; 3] {
+        C::yuv_to_rgb_scalar(c, yuv)
+    }
+}
+
+impl DeltaEScalar for Fixed8 {
+    fn yuv_to_rgb_scalar(_: &YuvToRgb, yuv: (u16, u16, u16)) -> [f32; 3] {
+        // Coefficients of the floating point version for limited range BT.709
+        // in Q20
+        let y = (yuv.0 as i32 - 16) * 4788;
+        let u = yuv.1 as i32 - 128;
+        let v = yuv.2 as i32 - 128;
+        let scale = 1. / (1 << 20) as f32;
+        [
+            (y + 5993 * v) as f32 * scale,
+            (y - 1006 * u - 1782 * v) as f32 * scale,
+            (y + 9961 * u) as f32 * scale,
+        ]
+    }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+use self::avx2::*;
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod avx2 {
+    use super::*;
+
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::*;
+
+    #[target_feature(enable = "avx2")]
+    unsafe fn to_lab_avx2<C: Colorspace + ?Sized>(c: &YuvToRgb, rgb: &[__m256; 3]) -> [Lab; 8] {
+        if C::METRIC == Metric::Itp {
+            // The PQ curve is applied to each lane in turn
+            let mut lanes = [[0f32; 8]; 3];
+            for (lane, &v) in lanes.iter_mut().zip(rgb) {
+                _mm256_storeu_ps(lane.as_mut_ptr(), v);
+            }
+            return std::array::from_fn(|i| c.itp(&[lanes[0][i], lanes[1][i], lanes[2][i]]));
+        }
+        match c.transfer {
+            Transfer::Sdr => rgb_to_lab_with_avx2(rgb, &c.rgb_to_xyz),
+            Transfer::Pq => pq_rgb_to_lab_with_avx2(rgb, &c.rgb_to_xyz),
+            Transfer::Linear => linear_rgb_to_lab_with_avx2(rgb, &c.rgb_to_xyz),
+        }
+    }
+
+    /// The L*, a* and b* of 8 colors, one register per component.
+    #[target_feature(enable = "avx2")]
+    unsafe fn lab_lanes(lab: &[Lab; 8]) -> [__m256; 3] {
+        let lanes = |f: fn(&Lab) -> f32| {
+            _mm256_setr_ps(
+                f(&lab[0]),
+                f(&lab[1]),
+                f(&lab[2]),
+                f(&lab[3]),
+                f(&lab[4]),
+                f(&lab[5]),
+                f(&lab[6]),
+                f(&lab[7]),
+            )
+        };
+        [lanes(|lab| lab.l), lanes(|lab| lab.a), lanes(|lab| lab.b)]
+    }
+
+    /// Bytes past each chunk that the `prefetch` feature fetches, 64 chunks
+    /// ahead in 8-bit luma.
+    const PREFETCH_DISTANCE: usize = 512;
+
+    // With the `prefetch` feature, fetches the samples PREFETCH_DISTANCE bytes
+    // ahead of each chunk into cache, once for every cache line of luma the
+    // chunks start.
+    #[target_feature(enable = "avx2")]
+    unsafe fn prefetch(chunks: [&[u8]; 6]) {
+        if cfg!(feature = "prefetch") && chunks[0].as_ptr() as usize % 64 < chunks[0].len() {
+            for chunk in chunks {
+                let ahead = chunk.as_ptr().wrapping_add(PREFETCH_DISTANCE);
+                _mm_prefetch::<_MM_HINT_T0>(ahead as *const i8);
+            }
+        }
+    }
+
+    pub trait DeltaEAVX2: Colorspace + DeltaEScalar {
+        #[target_feature(enable = "avx2")]
+        unsafe fn yuv_to_rgb(
+            c: &YuvToRgb,
+            yuv: (__m256, __m256, __m256),
+        ) -> (__m256, __m256, __m256) {
+            #[target_feature(enable = "avx2")]
+            unsafe fn set1(val: f32) -> __m256 {
+                _mm256_set1_ps(val)
+            }
+            let y = _mm256_mul_ps(_mm256_sub_ps(yuv.0, set1(c.y_offset)), set1(c.y_scale));
+            let u = _mm256_mul_ps(_mm256_sub_ps(yuv.1, set1(c.uv_offset)), set1(c.uv_scale));
+            let v = _mm256_mul_ps(_mm256_sub_ps(yuv.2, set1(c.uv_offset)), set1(c.uv_scale));
+            if c.gbr {
+                return (v, y, u);
+            }
+
+            let r = _mm256_add_ps(y, _mm256_mul_ps(v, set1(c.r_v)));
+            let g = _mm256_add_ps(
+                _mm256_add_ps(y, _mm256_mul_ps(u, set1(-c.g_u))),
+                _mm256_mul_ps(v, set1(-c.g_v)),
+            );
+            let b = _mm256_add_ps(y, _mm256_mul_ps(u, set1(c.b_u)));
+
+            (r, g, b)
+        }
+
+        #[target_feature(enable = "avx2")]
+        unsafe fn delta_e_avx2(
+            c: (&YuvToRgb, &YuvToRgb),
+            yuv1: (__m256, __m256, __m256),
+            yuv2: (__m256, __m256, __m256),
+            ksub: KSubArgs,
+            res_chunk: &mut [f32],
+        ) {
+            let (r1, g1, b1) = Self::yuv_to_rgb(c.0, yuv1);
+            let (r2, g2, b2) = Self::yuv_to_rgb(c.1, yuv2);
+
+            let lab1 = to_lab_avx2::<Self>(c.0, &[r1, g1, b1]);
+            let lab2 = to_lab_avx2::<Self>(c.1, &[r2, g2, b2]);
+            // The approximate DE2000 is computed on vectors, the exact one
+            // with libm like the other backends
+            if Self::METRIC == Metric::De2000 && Self::APPROX_TRIG {
+                let delta_e = de2000_avx2(&lab_lanes(&lab1), &lab_lanes(&lab2), ksub);
+                _mm256_storeu_ps(res_chunk.as_mut_ptr(), delta_e);
+                return;
+            }
+            for i in 0..8 {
+                res_chunk[i] = Self::delta_e(lab1[i], lab2[i], ksub);
+            }
+        }
+
+        #[target_feature(enable = "avx2")]
+        unsafe fn delta_e_row_avx2(
+            row1: FrameRow,
+            row2: FrameRow,
+            ksub: KSubArgs,
+            res_row: &mut [f32],
+        ) {
+            // Only one version should be compiled for each trait
+            if Self::BIT_DEPTH == 8 {
+                for (chunk1_y, chunk1_u, chunk1_v, chunk2_y, chunk2_u, chunk2_v, res_chunk) in izip!(
+                    row1.y.chunks(8),
+                    row1.u.chunks(8 >> Self::X_DECIMATION),
+                    row1.v.chunks(8 >> Self::X_DECIMATION),
+                    row2.y.chunks(8),
+                    row2.u.chunks(8 >> Self::X_DECIMATION),
+                    row2.v.chunks(8 >> Self::X_DECIMATION),
+                    res_row.chunks_mut(8)
+                ) {
+                    if chunk1_y.len() == 8 {
+                        prefetch([chunk1_y, chunk1_u, chunk1_v, chunk2_y, chunk2_u, chunk2_v]);
+
+                        #[target_feature(enable = "avx2")]
+                        unsafe fn load_luma(chunk: &[u8]) -> __m256 {
+                            let tmp = _mm_loadl_epi64(chunk.as_ptr() as *const _);
+                            _mm256_cvtepi32_ps(_mm256_cvtepu8_epi32(tmp))
+                        }
+
+                        #[target_feature(enable = "avx2")]
+                        unsafe fn load_chroma(chunk: &[u8]) -> __m256 {
+                            let tmp =
+                                _mm_cvtsi32_si128((chunk.as_ptr() as *const i32).read_unaligned());
+                            _mm256_cvtepi32_ps(_mm256_cvtepu8_epi32(_mm_unpacklo_epi8(tmp, tmp)))
+                        }
+
+                        // 4:4:4 chroma lines up with luma as it is
+                        let load_chroma: unsafe fn(&[u8]) -> __m256 = if Self::X_DECIMATION == 0 {
+                            load_luma
+                        } else {
+                            load_chroma
+                        };
+
+                        Self::delta_e_avx2(
+                            (&row1.to_rgb, &row2.to_rgb),
+                            (
+                                load_luma(chunk1_y),
+                                load_chroma(chunk1_u),
+                                load_chroma(chunk1_v),
+                            ),
+                            (
+                                load_luma(chunk2_y),
+                                load_chroma(chunk2_u),
+                                load_chroma(chunk2_v),
+                            ),
+                            ksub,
+                            res_chunk,
+                        );
+                    } else {
+                        Self::delta_e_row_scalar(
+                            FrameRow {
+                                y: chunk1_y,
+                                u: chunk1_u,
+                                v: chunk1_v,
+                                to_rgb: row1.to_rgb,
+                            },
+                            FrameRow {
+                                y: chunk2_y,
+                                u: chunk2_u,
+                                v: chunk2_v,
+                                to_rgb: row2.to_rgb,
+                            },
+                            ksub,
+                            res_chunk,
+                        );
+                    }
+                }
+            } else {
+                for (chunk1_y, chunk1_u, chunk1_v, chunk2_y, chunk2_u, chunk2_v, res_chunk) in izip!(
+                    row1.y.chunks(16),
+                    row1.u.chunks(16 >> Self::X_DECIMATION),
+                    row1.v.chunks(16 >> Self::X_DECIMATION),
+                    row2.y.chunks(16),
+                    row2.u.chunks(16 >> Self::X_DECIMATION),
+                    row2.v.chunks(16 >> Self::X_DECIMATION),
+                    res_row.chunks_mut(8)
+                ) {
+                    if chunk1_y.len() == 16 {
+                        prefetch([chunk1_y, chunk1_u, chunk1_v, chunk2_y, chunk2_u, chunk2_v]);
+
+                        #[target_feature(enable = "avx2")]
+                        unsafe fn load_luma(chunk: &[u8]) -> __m256 {
+                            let tmp = _mm_loadu_si128(chunk.as_ptr() as *const _);
+                            _mm256_cvtepi32_ps(_mm256_cvtepu16_epi32(tmp))
+                        }
+
+                        #[target_feature(enable = "avx2")]
+                        unsafe fn load_chroma(chunk: &[u8]) -> __m256 {
+                            let tmp = _mm_loadl_epi64(chunk.as_ptr() as *const _);
+                            _mm256_cvtepi32_ps(_mm256_cvtepu16_epi32(_mm_unpacklo_epi16(tmp, tmp)))
+                        }
+
+                        let load_chroma: unsafe fn(&[u8]) -> __m256 = if Self::X_DECIMATION == 0 {
+                            load_luma
+                        } else {
+                            load_chroma
+                        };
+
+                        Self::delta_e_avx2(
+                            (&row1.to_rgb, &row2.to_rgb),
+                            (
+                                load_luma(chunk1_y),
+                                load_chroma(chunk1_u),
+                                load_chroma(chunk1_v),
+                            ),
+                            (
+                                load_luma(chunk2_y),
+                                load_chroma(chunk2_u),
+                                load_chroma(chunk2_v),
+                            ),
+                            ksub,
+                            res_chunk,
+                        );
+                    } else {
+                        Self::delta_e_row_scalar(
+                            FrameRow {
+                                y: chunk1_y,
+                                u: chunk1_u,
+                                v: chunk1_v,
+                                to_rgb: row1.to_rgb,
+                            },
+                            FrameRow {
+                                y: chunk2_y,
+                                u: chunk2_u,
+                                v: chunk2_v,
+                                to_rgb: row2.to_rgb,
+                            },
+                            ksub,
+                            res_chunk,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    impl<const BD: u32> DeltaEAVX2 for Kernel<BD, 0> {}
+    impl<const BD: u32> DeltaEAVX2 for Kernel<BD, 1> {}
+    impl<C: DeltaEAVX2> DeltaEAVX2 for Approx<C> {}
+    impl<C: DeltaEAVX2> DeltaEAVX2 for De76<C> {}
+    impl<C: DeltaEAVX2> DeltaEAVX2 for De94<C> {}
+    impl<C: DeltaEAVX2> DeltaEAVX2 for Cmc<C> {}
+    impl<C: DeltaEAVX2> DeltaEAVX2 for Itp<C> {}
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+use self::avx512::*;
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod avx512 {
+    use super::*;
+
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::*;
+
+    #[target_feature(enable = "avx512f")]
+    unsafe fn to_lab_avx512<C: Colorspace + ?Sized>(c: &YuvToRgb, rgb: &[__m512; 3]) -> [Lab; 16] {
+        if C::METRIC == Metric::Itp {
+            // The PQ curve is applied to each lane in turn
+            let mut lanes = [[0f32; 16]; 3];
+            for (lane, &v) in lanes.iter_mut().zip(rgb) {
+                _mm512_storeu_ps(lane.as_mut_ptr(), v);
+            }
+            return std::array::from_fn(|i| c.itp(&[lanes[0][i], lanes[1][i], lanes[2][i]]));
+        }
+        match c.transfer {
+            Transfer::Sdr => rgb_to_lab_with_avx512(rgb, &c.rgb_to_xyz),
+            Transfer::Pq => pq_rgb_to_lab_with_avx512(rgb, &c.rgb_to_xyz),
+            Transfer::Linear => linear_rgb_to_lab_with_avx512(rgb, &c.rgb_to_xyz),
+        }
+    }
+
+    /// The L*, a* and b* of 16 colors, one register per component.
+    #[target_feature(enable = "avx512f")]
+    unsafe fn lab_lanes(lab: &[Lab; 16]) -> [__m512; 3] {
+        let lanes = |f: fn(&Lab) -> f32| {
+            let lane: [f32; 16] = std::array::from_fn(|i| f(&lab[i]));
+            _mm512_loadu_ps(lane.as_ptr())
+        };
+        [lanes(|lab| lab.l), lanes(|lab| lab.a), lanes(|lab| lab.b)]
+    }
+
+    pub trait DeltaEAVX512: DeltaEAVX2 {
+        #[target_feature(enable = "avx512f")]
+        unsafe fn yuv_to_rgb(
+            c: &YuvToRgb,
+            yuv: (__m512, __m512, __m512),
+        ) -> (__m512, __m512, __m512) {
+            #[target_feature(enable = "avx512f")]
+            unsafe fn set1(val: f32) -> __m512 {
+                _mm512_set1_ps(val)
+            }
+            let y = _mm512_mul_ps(_mm512_sub_ps(yuv.0, set1(c.y_offset)), set1(c.y_scale));
+            let u = _mm512_mul_ps(_mm512_sub_ps(yuv.1, set1(c.uv_offset)), set1(c.uv_scale));
+            let v = _mm512_mul_ps(_mm512_sub_ps(yuv.2, set1(c.uv_offset)), set1(c.uv_scale));
+            if c.gbr {
+                return (v, y, u);
+            }
+
+            let r = _mm512_add_ps(y, _mm512_mul_ps(v, set1(c.r_v)));
+            let g = _mm512_add_ps(
+                _mm512_add_ps(y, _mm512_mul_ps(u, set1(-c.g_u))),
+                _mm512_mul_ps(v, set1(-c.g_v)),
+            );
+            let b = _mm512_add_ps(y, _mm512_mul_ps(u, set1(c.b_u)));
+
+            (r, g, b)
+        }
+
+        #[target_feature(enable = "avx512f")]
+        unsafe fn delta_e_avx512(
+            c: (&YuvToRgb, &YuvToRgb),
+            yuv1: (__m512, __m512, __m512),
+            yuv2: (__m512, __m512, __m512),
+            ksub: KSubArgs,
+            res_chunk: &mut [f32],
+        ) {
+            let (r1, g1, b1) = <Self as DeltaEAVX512>::yuv_to_rgb(c.0, yuv1);
+            let (r2, g2, b2) = <Self as DeltaEAVX512>::yuv_to_rgb(c.1, yuv2);
+
+            let lab1 = to_lab_avx512::<Self>(c.0, &[r1, g1, b1]);
+            let lab2 = to_lab_avx512::<Self>(c.1, &[r2, g2, b2]);
+            if Self::METRIC == Metric::De2000 && Self::APPROX_TRIG {
+                let delta_e = de2000_avx512(&lab_lanes(&lab1), &lab_lanes(&lab2), ksub);
+                _mm512_storeu_ps(res_chunk.as_mut_ptr(), delta_e);
+                return;
+            }
+            for i in 0..16 {
+                res_chunk[i] = Self::delta_e(lab1[i], lab2[i], ksub);
+            }
+        }
+
+        #[target_feature(enable = "avx512f")]
+        unsafe fn delta_e_row_avx512(
+            row1: FrameRow,
+            row2: FrameRow,
+            ksub: KSubArgs,
+            res_row: &mut [f32],
+        ) {
+            // Luma and chroma bytes in 16 pixels
+            let luma = if Self::BIT_DEPTH == 8 { 16 } else { 32 };
+            let chroma = luma >> Self::X_DECIMATION;
+            for (chunk1_y, chunk1_u, chunk1_v, chunk2_y, chunk2_u, chunk2_v, res_chunk) in izip!(
+                row1.y.chunks(luma),
+                row1.u.chunks(chroma),
+                row1.v.chunks(chroma),
+                row2.y.chunks(luma),
+                row2.u.chunks(chroma),
+                row2.v.chunks(chroma),
+                res_row.chunks_mut(16)
+            ) {
+                let chunk_row1 = FrameRow {
+                    y: chunk1_y,
+                    u: chunk1_u,
+                    v: chunk1_v,
+                    to_rgb: row1.to_rgb,
+                };
+                let chunk_row2 = FrameRow {
+                    y: chunk2_y,
+                    u: chunk2_u,
+                    v: chunk2_v,
+                    to_rgb: row2.to_rgb,
+                };
+                if chunk1_y.len() < luma {
+                    // The rest of the row, 8 pixels at a time
+                    Self::delta_e_row_avx2(chunk_row1, chunk_row2, ksub, res_chunk);
+                    continue;
+                }
+                // Only one version should be compiled for each trait. Chroma
+                // samples are doubled to line up with luma, unless 4:4:4.
+                let load = if Self::BIT_DEPTH == 8 {
+                    #[target_feature(enable = "avx512f")]
+                    unsafe fn load(row: &FrameRow, subsampled: bool) -> (__m512, __m512, __m512) {
+                        let luma = |chunk: &[u8]| {
+                            let tmp = _mm_loadu_si128(chunk.as_ptr() as *const _);
+                            _mm512_cvtepi32_ps(_mm512_cvtepu8_epi32(tmp))
+                        };
+                        let chroma = |chunk: &[u8]| {
+                            if !subsampled {
+                                return luma(chunk);
+                            }
+                            let tmp = _mm_loadl_epi64(chunk.as_ptr() as *const _);
+                            _mm512_cvtepi32_ps(_mm512_cvtepu8_epi32(_mm_unpacklo_epi8(tmp, tmp)))
+                        };
+                        (luma(row.y), chroma(row.u), chroma(row.v))
+                    }
+                    load
+                } else {
+                    #[target_feature(enable = "avx512f")]
+                    unsafe fn load(row: &FrameRow, subsampled: bool) -> (__m512, __m512, __m512) {
+                        let luma = |chunk: &[u8]| {
+                            let tmp = _mm256_loadu_si256(chunk.as_ptr() as *const _);
+                            _mm512_cvtepi32_ps(_mm512_cvtepu16_epi32(tmp))
+                        };
+                        let chroma = |chunk: &[u8]| {
+                            if !subsampled {
+                                return luma(chunk);
+                            }
+                            let tmp = _mm_loadu_si128(chunk.as_ptr() as *const _);
+                            let doubled = _mm256_set_m128i(
+                                _mm_unpackhi_epi16(tmp, tmp),
+                                _mm_unpacklo_epi16(tmp, tmp),
+                            );
+                            _mm512_cvtepi32_ps(_mm512_cvtepu16_epi32(doubled))
+                        };
+                        (luma(row.y), chroma(row.u), chroma(row.v))
+                    }
+                    load
+                };
+                Self::delta_e_avx512(
+                    (&row1.to_rgb, &row2.to_rgb),
+                    load(&chunk_row1, Self::X_DECIMATION == 1),
+                    load(&chunk_row2, Self::X_DECIMATION == 1),
+                    ksub,
+                    res_chunk,
+                );
+            }
+        }
+    }
+
+    impl<const BD: u32> DeltaEAVX512 for Kernel<BD, 0> {}
+    impl<const BD: u32> DeltaEAVX512 for Kernel<BD, 1> {}
+    impl<C: DeltaEAVX512> DeltaEAVX512 for Approx<C> {}
+    impl<C: DeltaEAVX512> DeltaEAVX512 for De76<C> {}
+    impl<C: DeltaEAVX512> DeltaEAVX512 for De94<C> {}
+    impl<C: DeltaEAVX512> DeltaEAVX512 for Cmc<C> {}
+    impl<C: DeltaEAVX512> DeltaEAVX512 for Itp<C> {}
+}
+
+#[cfg(target_arch = "aarch64")]
+use self::neon::*;
+
+#[cfg(target_arch = "aarch64")]
+mod neon {
+    use super::*;
+
+    use std::arch::aarch64::*;
+
+    #[target_feature(enable = "neon")]
+    unsafe fn to_lab_neon<C: Colorspace + ?Sized>(
+        c: &YuvToRgb,
+        rgb: &[float32x4_t; 3],
+    ) -> [Lab; 4] {
+        if C::METRIC == Metric::Itp {
+            // The PQ curve is applied to each lane in turn
+            let mut lanes = [[0f32; 4]; 3];
+            for (lane, &v) in lanes.iter_mut().zip(rgb) {
+                vst1q_f32(lane.as_mut_ptr(), v);
+            }
+            return std::array::from_fn(|i| c.itp(&[lanes[0][i], lanes[1][i], lanes[2][i]]));
+        }
+        match c.transfer {
+            Transfer::Sdr => rgb_to_lab_with_neon(rgb, &c.rgb_to_xyz),
+            Transfer::Pq => pq_rgb_to_lab_with_neon(rgb, &c.rgb_to_xyz),
+            Transfer::Linear => linear_rgb_to_lab_with_neon(rgb, &c.rgb_to_xyz),
+        }
+    }
+
+    // The 8 samples as two vectors of 4
+    #[inline]
+    #[target_feature(enable = "neon")]
+    unsafe fn widen(samples: uint16x8_t) -> [float32x4_t; 2] {
+        [
+            vcvtq_f32_u32(vmovl_u16(vget_low_u16(samples))),
+            vcvtq_f32_u32(vmovl_high_u16(samples)),
+        ]
+    }
+
+    pub trait DeltaENeon: Colorspace + DeltaEScalar {
+        #[target_feature(enable = "neon")]
+        unsafe fn yuv_to_rgb(
+            c: &YuvToRgb,
+            yuv: (float32x4_t, float32x4_t, float32x4_t),
+        ) -> (float32x4_t, float32x4_t, float32x4_t) {
+            #[target_feature(enable = "neon")]
+            unsafe fn set1(val: f32) -> float32x4_t {
+                vdupq_n_f32(val)
+            }
+            let y = vmulq_f32(vsubq_f32(yuv.0, set1(c.y_offset)), set1(c.y_scale));
+            let u = vmulq_f32(vsubq_f32(yuv.1, set1(c.uv_offset)), set1(c.uv_scale));
+            let v = vmulq_f32(vsubq_f32(yuv.2, set1(c.uv_offset)), set1(c.uv_scale));
+            if c.gbr {
+                return (v, y, u);
+            }
+
+            let r = vaddq_f32(y, vmulq_f32(v, set1(c.r_v)));
+            let g = vaddq_f32(
+                vaddq_f32(y, vmulq_f32(u, set1(-c.g_u))),
+                vmulq_f32(v, set1(-c.g_v)),
+            );
+            let b = vaddq_f32(y, vmulq_f32(u, set1(c.b_u)));
+
+            (r, g, b)
+        }
+
+        #[target_feature(enable = "neon")]
+        unsafe fn delta_e_neon(
+            c: (&YuvToRgb, &YuvToRgb),
+            yuv1: (float32x4_t, float32x4_t, float32x4_t),
+            yuv2: (float32x4_t, float32x4_t, float32x4_t),
+            ksub: KSubArgs,
+            res_chunk: &mut [f32],
+        ) {
+            let (r1, g1, b1) = Self::yuv_to_rgb(c.0, yuv1);
+            let (r2, g2, b2) = Self::yuv_to_rgb(c.1, yuv2);
+
+            let lab1 = to_lab_neon::<Self>(c.0, &[r1, g1, b1]);
+            let lab2 = to_lab_neon::<Self>(c.1, &[r2, g2, b2]);
+            for i in 0..4 {
+                res_chunk[i] = Self::delta_e(lab1[i], lab2[i], ksub);
+            }
+        }
+
+        #[target_feature(enable = "neon")]
+        unsafe fn delta_e_row_neon(
+            row1: FrameRow,
+            row2: FrameRow,
+            ksub: KSubArgs,
+            res_row: &mut [f32],
+        ) {
+            // Luma and chroma bytes in 8 pixels
+            let luma = if Self::BIT_DEPTH == 8 { 8 } else { 16 };
+            let chroma = luma >> Self::X_DECIMATION;
+            for (chunk1_y, chunk1_u, chunk1_v, chunk2_y, chunk2_u, chunk2_v, res_chunk) in izip!(
+                row1.y.chunks(luma),
+                row1.u.chunks(chroma),
+                row1.v.chunks(chroma),
+                row2.y.chunks(luma),
+                row2.u.chunks(chroma),
+                row2.v.chunks(chroma),
+                res_row.chunks_mut(8)
+            ) {
+                let chunk_row1 = FrameRow {
+                    y: chunk1_y,
+                    u: chunk1_u,
+                    v: chunk1_v,
+                    to_rgb: row1.to_rgb,
+                };
+                let chunk_row2 = FrameRow {
+                    y: chunk2_y,
+                    u: chunk2_u,
+                    v: chunk2_v,
+                    to_rgb: row2.to_rgb,
+                };
+                if chunk1_y.len() < luma {
+                    Self::delta_e_row_scalar(chunk_row1, chunk_row2, ksub, res_chunk);
+                    continue;
+                }
+                // Only one version should be compiled for each trait. Chroma
+                // samples are doubled to line up with luma, unless 4:4:4.
+                let load = if Self::BIT_DEPTH == 8 {
+                    #[target_feature(enable = "neon")]
+                    unsafe fn load(row: &FrameRow, subsampled: bool) -> [[float32x4_t; 2]; 3] {
+                        let luma = |chunk: &[u8]| widen(vmovl_u8(vld1_u8(chunk.as_ptr())));
+                        let chroma = |chunk: &[u8]| {
+                            if !subsampled {
+                                return luma(chunk);
+                            }
+                            let tmp = (chunk.as_ptr() as *const u32).read_unaligned();
+                            let tmp = vreinterpret_u8_u32(vdup_n_u32(tmp));
+                            widen(vmovl_u8(vzip1_u8(tmp, tmp)))
+                        };
+                        [luma(row.y), chroma(row.u), chroma(row.v)]
+                    }
+                    load
+                } else {
+                    #[target_feature(enable = "neon")]
+                    unsafe fn load(row: &FrameRow, subsampled: bool) -> [[float32x4_t; 2]; 3] {
+                        let luma = |chunk: &[u8]| widen(vld1q_u16(chunk.as_ptr() as *const u16));
+                        let chroma = |chunk: &[u8]| {
+                            if !subsampled {
+                                return luma(chunk);
+                            }
+                            let tmp = vld1_u16(chunk.as_ptr() as *const u16);
+                            widen(vcombine_u16(vzip1_u16(tmp, tmp), vzip2_u16(tmp, tmp)))
+                        };
+                        [luma(row.y), chroma(row.u), chroma(row.v)]
+                    }
+                    load
+                };
+                let [y1, u1, v1] = load(&chunk_row1, Self::X_DECIMATION == 1);
+                let [y2, u2, v2] = load(&chunk_row2, Self::X_DECIMATION == 1);
+                for (half, res_half) in res_chunk.chunks_mut(4).enumerate() {
+                    Self::delta_e_neon(
+                        (&row1.to_rgb, &row2.to_rgb),
+                        (y1[half], u1[half], v1[half]),
+                        (y2[half], u2[half], v2[half]),
+                        ksub,
+                        res_half,
+                    );
+                }
+            }
+        }
+    }
+
+    impl<const BD: u32> DeltaENeon for Kernel<BD, 0> {}
+    impl<const BD: u32> DeltaENeon for Kernel<BD, 1> {}
+    impl<C: DeltaENeon> DeltaENeon for Approx<C> {}
+    impl<C: DeltaENeon> DeltaENeon for De76<C> {}
+    impl<C: DeltaENeon> DeltaENeon for De94<C> {}
+    impl<C: DeltaENeon> DeltaENeon for Cmc<C> {}
+    impl<C: DeltaENeon> DeltaENeon for Itp<C> {}
+}
+
+#[cfg(all(feature = "rvv", target_arch = "riscv64"))]
+use self::rvv::*;
+
+#[cfg(all(feature = "rvv", target_arch = "riscv64"))]
+mod rvv {
+    use super::*;
+
+    #[inline(always)]
+    fn to_lab_rvv<C: Colorspace + ?Sized>(c: &YuvToRgb, rgb: &[Lanes; 3]) -> [Lab; 16] {
+        if C::METRIC == Metric::Itp {
+            // The PQ curve is applied to each lane in turn
+            return std::array::from_fn(|i| c.itp(&[rgb[0][i], rgb[1][i], rgb[2][i]]));
+        }
+        match c.transfer {
+            Transfer::Sdr => rgb_to_lab_with_rvv(rgb, &c.rgb_to_xyz),
+            Transfer::Pq => pq_rgb_to_lab_with_rvv(rgb, &c.rgb_to_xyz),
+            Transfer::Linear => linear_rgb_to_lab_with_rvv(rgb, &c.rgb_to_xyz),
+        }
+    }
+
+    pub trait DeltaERvv: Colorspace + DeltaEScalar {
+        // The scalar conversion has the same operations as the SIMD ones
+        #[inline(always)]
+        fn yuv_to_rgb_rvv(c: &YuvToRgb, row: &FrameRow) -> [Lanes; 3] {
+            let mut rgb = [[0.; 16]; 3];
+            for i in 0..16 {
+                let pixel = Self::yuv_to_rgb_scalar(c, Self::sample(row, i));
+                for (lanes, value) in rgb.iter_mut().zip(pixel) {
+                    lanes[i] = value;
+                }
+            }
+            rgb
+        }
+
+        /// # Safety
+        ///
+        /// The CPU must support the V extension.
+        #[target_feature(enable = "v")]
+        unsafe fn delta_e_row_rvv(
+            row1: FrameRow,
+            row2: FrameRow,
+            ksub: KSubArgs,
+            res_row: &mut [f32],
+        ) {
+            // Luma and chroma bytes in 16 pixels
+            let luma = if Self::BIT_DEPTH == 8 { 16 } else { 32 };
+            let chroma = luma >> Self::X_DECIMATION;
+            for (chunk1_y, chunk1_u, chunk1_v, chunk2_y, chunk2_u, chunk2_v, res_chunk) in izip!(
+                row1.y.chunks(luma),
+                row1.u.chunks(chroma),
+                row1.v.chunks(chroma),
+                row2.y.chunks(luma),
+                row2.u.chunks(chroma),
+                row2.v.chunks(chroma),
+                res_row.chunks_mut(16)
+            ) {
+                let chunk_row1 = FrameRow {
+                    y: chunk1_y,
+                    u: chunk1_u,
+                    v: chunk1_v,
+                    to_rgb: row1.to_rgb,
+                };
+                let chunk_row2 = FrameRow {
+                    y: chunk2_y,
+                    u: chunk2_u,
+                    v: chunk2_v,
+                    to_rgb: row2.to_rgb,
+                };
+                if chunk1_y.len() < luma {
+                    Self::delta_e_row_scalar(chunk_row1, chunk_row2, ksub, res_chunk);
+                    continue;
+                }
+                let lab1 = to_lab_rvv::<Self>(
+                    &row1.to_rgb,
+                    &Self::yuv_to_rgb_rvv(&row1.to_rgb, &chunk_row1),
+                );
+                let lab2 = to_lab_rvv::<Self>(
+                    &row2.to_rgb,
+                    &Self::yuv_to_rgb_rvv(&row2.to_rgb, &chunk_row2),
+                );
+                for i in 0..16 {
+                    res_chunk[i] = Self::delta_e(lab1[i], lab2[i], ksub);
+                }
+            }
+        }
+    }
+
+    impl<const BD: u32> DeltaERvv for Kernel<BD, 0> {}
+    impl<const BD: u32> DeltaERvv for Kernel<BD, 1> {}
+    impl<C: DeltaERvv> DeltaERvv for Approx<C> {}
+    impl<C: DeltaERvv> DeltaERvv for De76<C> {}
+    impl<C: DeltaERvv> DeltaERvv for De94<C> {}
+    impl<C: DeltaERvv> DeltaERvv for Cmc<C> {}
+    impl<C: DeltaERvv> DeltaERvv for Itp<C> {}
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+use self::sse41::*;
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod sse41 {
+    use super::*;
+
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::*;
+
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn to_lab_sse41<C: Colorspace + ?Sized>(c: &YuvToRgb, rgb: &[__m128; 3]) -> [Lab; 4] {
+        if C::METRIC == Metric::Itp {
+            // The PQ curve is applied to each lane in turn
+            let mut lanes = [[0f32; 4]; 3];
+            for (lane, &v) in lanes.iter_mut().zip(rgb) {
+                _mm_storeu_ps(lane.as_mut_ptr(), v);
+            }
+            return std::array::from_fn(|i| c.itp(&[lanes[0][i], lanes[1][i], lanes[2][i]]));
+        }
+        match c.transfer {
+            Transfer::Sdr => rgb_to_lab_with_sse41(rgb, &c.rgb_to_xyz),
+            Transfer::Pq => pq_rgb_to_lab_with_sse41(rgb, &c.rgb_to_xyz),
+            Transfer::Linear => linear_rgb_to_lab_with_sse41(rgb, &c.rgb_to_xyz),
+        }
+    }
+
+    // The 8 bytes in the low half as two vectors of 4
+    #[inline]
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn widen_u8(samples: __m128i) -> [__m128; 2] {
+        [
+            _mm_cvtepi32_ps(_mm_cvtepu8_epi32(samples)),
+            _mm_cvtepi32_ps(_mm_cvtepu8_epi32(_mm_srli_si128(samples, 4))),
+        ]
+    }
+
+    // The 8 words as two vectors of 4
+    #[inline]
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn widen_u16(samples: __m128i) -> [__m128; 2] {
+        [
+            _mm_cvtepi32_ps(_mm_cvtepu16_epi32(samples)),
+            _mm_cvtepi32_ps(_mm_cvtepu16_epi32(_mm_srli_si128(samples, 8))),
+        ]
+    }
+
+    pub trait DeltaESSE41: Colorspace + DeltaEScalar {
+        #[target_feature(enable = "sse4.1")]
+        unsafe fn yuv_to_rgb(
+            c: &YuvToRgb,
+            yuv: (__m128, __m128, __m128),
+        ) -> (__m128, __m128, __m128) {
+            #[target_feature(enable = "sse4.1")]
+            unsafe fn set1(val: f32) -> __m128 {
+                _mm_set1_ps(val)
+            }
+            let y = _mm_mul_ps(_mm_sub_ps(yuv.0, set1(c.y_offset)), set1(c.y_scale));
+            let u = _mm_mul_ps(_mm_sub_ps(yuv.1, set1(c.uv_offset)), set1(c.uv_scale));
+            let v = _mm_mul_ps(_mm_sub_ps(yuv.2, set1(c.uv_offset)), set1(c.uv_scale));
+            if c.gbr {
+                return (v, y, u);
+            }
+
+            let r = _mm_add_ps(y, _mm_mul_ps(v, set1(c.r_v)));
+            let g = _mm_add_ps(
+                _mm_add_ps(y, _mm_mul_ps(u, set1(-c.g_u))),
+                _mm_mul_ps(v, set1(-c.g_v)),
+            );
+            let b = _mm_add_ps(y, _mm_mul_ps(u, set1(c.b_u)));
+
+            (r, g, b)
+        }
+
+        #[target_feature(enable = "sse4.1")]
+        unsafe fn delta_e_sse41(
+            c: (&YuvToRgb, &YuvToRgb),
+            yuv1: (__m128, __m128, __m128),
+            yuv2: (__m128, __m128, __m128),
+            ksub: KSubArgs,
+            res_chunk: &mut [f32],
+        ) {
+            let (r1, g1, b1) = Self::yuv_to_rgb(c.0, yuv1);
+            let (r2, g2, b2) = Self::yuv_to_rgb(c.1, yuv2);
+
+            let lab1 = to_lab_sse41::<Self>(c.0, &[r1, g1, b1]);
+            let lab2 = to_lab_sse41::<Self>(c.1, &[r2, g2, b2]);
+            for i in 0..4 {
+                res_chunk[i] = Self::delta_e(lab1[i], lab2[i], ksub);
+            }
+        }
+
+        #[target_feature(enable = "sse4.1")]
+        unsafe fn delta_e_row_sse41(
+            row1: FrameRow,
+            row2: FrameRow,
+            ksub: KSubArgs,
+            res_row: &mut [f32],
+        ) {
+            // Luma and chroma bytes in 8 pixels
+            let luma = if Self::BIT_DEPTH == 8 { 8 } else { 16 };
+            let chroma = luma >> Self::X_DECIMATION;
+            for (chunk1_y, chunk1_u, chunk1_v, chunk2_y, chunk2_u, chunk2_v, res_chunk) in izip!(
+                row1.y.chunks(luma),
+                row1.u.chunks(chroma),
+                row1.v.chunks(chroma),
+                row2.y.chunks(luma),
+                row2.u.chunks(chroma),
+                row2.v.chunks(chroma),
+                res_row.chunks_mut(8)
+            ) {
+                let chunk_row1 = FrameRow {
+                    y: chunk1_y,
+                    u: chunk1_u,
+                    v: chunk1_v,
+                    to_rgb: row1.to_rgb,
+                };
+                let chunk_row2 = FrameRow {
+                    y: chunk2_y,
+                    u: chunk2_u,
+                    v: chunk2_v,
+                    to_rgb: row2.to_rgb,
+                };
+                if chunk1_y.len() < luma {
+                    Self::delta_e_row_scalar(chunk_row1, chunk_row2, ksub, res_chunk);
+                    continue;
+                }
+                // Only one version should be compiled for each trait. Chroma
+                // samples are doubled to line up with luma, unless 4:4:4.
+                let load = if Self::BIT_DEPTH == 8 {
+                    #[target_feature(enable = "sse4.1")]
+                    unsafe fn load(row: &FrameRow, subsampled: bool) -> [[__m128; 2]; 3] {
+                        let luma =
+                            |chunk: &[u8]| widen_u8(_mm_loadl_epi64(chunk.as_ptr() as *const _));
+                        let chroma = |chunk: &[u8]| {
+                            if !subsampled {
+                                return luma(chunk);
+                            }
+                            let tmp =
+                                _mm_cvtsi32_si128((chunk.as_ptr() as *const i32).read_unaligned());
+                            widen_u8(_mm_unpacklo_epi8(tmp, tmp))
+                        };
+                        [luma(row.y), chroma(row.u), chroma(row.v)]
+                    }
+                    load
+                } else {
+                    #[target_feature(enable = "sse4.1")]
+                    unsafe fn load(row: &FrameRow, subsampled: bool) -> [[__m128; 2]; 3] {
+                        let luma =
+                            |chunk: &[u8]| widen_u16(_mm_loadu_si128(chunk.as_ptr() as *const _));
+                        let chroma = |chunk: &[u8]| {
+                            if !subsampled {
+                                return luma(chunk);
+                            }
+                            let tmp = _mm_loadl_epi64(chunk.as_ptr() as *const _);
+                            widen_u16(_mm_unpacklo_epi16(tmp, tmp))
+                        };
+                        [luma(row.y), chroma(row.u), chroma(row.v)]
+                    }
+                    load
+                };
+                let [y1, u1, v1] = load(&chunk_row1, Self::X_DECIMATION == 1);
+                let [y2, u2, v2] = load(&chunk_row2, Self::X_DECIMATION == 1);
+                for (half, res_half) in res_chunk.chunks_mut(4).enumerate() {
+                    Self::delta_e_sse41(
+                        (&row1.to_rgb, &row2.to_rgb),
+                        (y1[half], u1[half], v1[half]),
+                        (y2[half], u2[half], v2[half]),
+                        ksub,
+                        res_half,
+                    );
+                }
+            }
+        }
+    }
+
+    impl<const BD: u32> DeltaESSE41 for Kernel<BD, 0> {}
+    impl<const BD: u32> DeltaESSE41 for Kernel<BD, 1> {}
+    impl<C: DeltaESSE41> DeltaESSE41 for Approx<C> {}
+    impl<C: DeltaESSE41> DeltaESSE41 for De76<C> {}
+    impl<C: DeltaESSE41> DeltaESSE41 for De94<C> {}
+    impl<C: DeltaESSE41> DeltaESSE41 for Cmc<C> {}
+    impl<C: DeltaESSE41> DeltaESSE41 for Itp<C> {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Random rows of `width` pixels, with the samples of a fixed LCG kept
+    /// within the bit depth.
+    fn random_row(state: &mut u64, bit_depth: usize, width: usize, xdec: usize) -> [Vec<u8>; 3] {
+        let mut plane = |len: usize| {
+            let mut bytes = Vec::new();
+            for _ in 0..len {
+                *state = state
+                    .wrapping_mul(6_364_136_223_846_793_005)
+                    .wrapping_add(1_442_695_040_888_963_407);
+                let sample = (*state >> 40) as u16 & ((1 << bit_depth) - 1);
+                if bit_depth == 8 {
+                    bytes.push(sample as u8);
+                } else {
+                    bytes.extend_from_slice(&sample.to_le_bytes());
+                }
+            }
+            bytes
+        };
+        [plane(width), plane(width >> xdec), plane(width >> xdec)]
+    }
+
+    /// Scores random rows with every SIMD level the CPU has and compares
+    /// them with the scalar kernel. The exact kernels must agree to the bit,
+    /// the approximate ones to 2e-4 of the score.
+    #[test]
+    fn simd_rows_match_scalar() {
+        let ksub = KSubArgs {
+            l: 1.,
+            c: 1.,
+            h: 1.,
+        };
+        let levels = [
+            SimdLevel::Neon,
+            SimdLevel::Rvv,
+            SimdLevel::Sse41,
+            SimdLevel::Avx2,
+            SimdLevel::Avx512,
+        ];
+        let mut state = 1;
+        for level in levels.iter().copied().filter(|level| level.supported()) {
+            for &(bit_depth, xdec, transfer) in &[
+                (8, 1, Transfer::Sdr),
+                (8, 0, Transfer::Sdr),
+                (10, 1, Transfer::Sdr),
+                (10, 1, Transfer::Pq),
+                (12, 0, Transfer::Linear),
+            ] {
+                let to_rgb = YuvToRgb::new(
+                    bit_depth,
+                    Matrix::Bt709,
+                    Range::Limited,
+                    transfer,
+                    Vision::Normal,
+                );
+                // Not a multiple of any vector width, for the tails
+                let width = 1000 + 2 * 7;
+                for _ in 0..5 {
+                    let [y1, u1, v1] = random_row(&mut state, bit_depth, width, xdec);
+                    let [y2, u2, v2] = random_row(&mut state, bit_depth, width, xdec);
+                    let row1 = || FrameRow {
+                        y: &y1,
+                        u: &u1,
+                        v: &v1,
+                        to_rgb,
+                    };
+                    let row2 = || FrameRow {
+                        y: &y2,
+                        u: &u2,
+                        v: &v2,
+                        to_rgb,
+                    };
+                    for approx in [false, true] {
+                        let get = if approx {
+                            get_delta_e_row_fn_approx
+                        } else {
+                            get_delta_e_row_fn
+                        };
+                        let mut scalar = vec![0.; width];
+                        let mut simd = vec![0.; width];
+                        unsafe {
+                            get(bit_depth, xdec, SimdLevel::Off)(row1(), row2(), ksub, &mut scalar);
+                            get(bit_depth, xdec, level)(row1(), row2(), ksub, &mut simd);
+                        }
+                        let tolerance = if approx { 2e-4 } else { 0. };
+                        for (i, (a, b)) in scalar.iter().zip(&simd).enumerate() {
+                            assert!(
+                                (a - b).abs() <= tolerance * a.max(1.),
+                                "{:?} {}-bit xdec {} {:?} approx {}: pixel {} {} against {}",
+                                level,
+                                bit_depth,
+                                xdec,
+                                transfer,
+                                approx,
+                                i,
+                                b,
+                                a
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Scaling a flat frame in linear light gives back its samples, which
+    /// checks that the RGB is encoded back with the inverse of the
+    /// conversion it was decoded with.
+    #[test]
+    fn linear_light_scale_keeps_flat_frames() {
+        for (bit_depth, transfer, yuv) in [
+            (8, Transfer::Sdr, [180u16, 90, 160]),
+            (10, Transfer::Pq, [520, 430, 610]),
+        ] {
+            let from = FrameInfo {
+                width: 8,
+                height: 6,
+                bit_depth,
+                xdec: 1,
+                ydec: 1,
+                interlaced: false,
+                matrix: Matrix::Bt709,
+                range: Range::Limited,
+                transfer,
+                vision: Vision::Normal,
+            };
+            let to = FrameInfo {
+                width: 5,
+                height: 3,
+                ..from
+            };
+            let plane = |sample: u16, len: usize| -> Vec<u8> {
+                (0..len)
+                    .flat_map(|_| sample.to_le_bytes()[..from.bytewidth()].to_vec())
+                    .collect()
+            };
+            let (y, u, v) = (plane(yuv[0], 48), plane(yuv[1], 12), plane(yuv[2], 12));
+            let frame = Frame {
+                y: &y,
+                u: &u,
+                v: &v,
+            };
+            let mut buf = Vec::new();
+            scale_frame(
+                &from,
+                &frame,
+                &to,
+                ScaleFilter::Lanczos,
+                ScaleLight::Linear,
+                &mut buf,
+            );
+            let scaled = to.split_planes(&buf).unwrap();
+            for (plane, &sample) in [scaled.y, scaled.u, scaled.v].iter().zip(&yuv) {
+                for i in 0..plane.len() / from.bytewidth() {
+                    let read = conform::read(plane, from.bytewidth(), i) as i32;
+                    assert!(
+                        (read - sample as i32).abs() <= 1,
+                        "{} != {} at {} bits",
+                        read,
+                        sample,
+                        bit_depth
+                    );
+                }
+            }
+        }
+    }
+}
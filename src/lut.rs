@@ -0,0 +1,200 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2019, the dump_ciede2000 contributors
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//  list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//  this list of conditions and the following disclaimer in the documentation
+//  and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Color lookup tables in the .cube format of Adobe and Resolve, applied to
+//! the RGB of frames.
+
+use crate::{pixel_rgb, Frame, FrameInfo};
+
+/// A 1D or 3D lookup table mapping nonlinear RGB to nonlinear RGB.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lut {
+    /// Entries along each axis
+    pub size: usize,
+    pub three_d: bool,
+    domain_min: [f32; 3],
+    domain_max: [f32; 3],
+    /// Outputs in file order: by input for 1D, and with red changing fastest,
+    /// then green, then blue, for 3D
+    table: Vec<[f32; 3]>,
+}
+
+impl Lut {
+    /// Parses the text of a .cube file.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut size = None;
+        let mut domain_min = [0.; 3];
+        let mut domain_max = [1.; 3];
+        let mut table = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let error = |msg: &str| format!("Line {}: {}", number + 1, msg);
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with("TITLE") {
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let keyword = fields.next().unwrap();
+            let values: Result<Vec<f32>, _> = fields.map(str::parse::<f32>).collect();
+            let values = values.map_err(|_| error("Invalid number"))?;
+            let triple = || match values[..] {
+                [a, b, c] if values.iter().all(|v| v.is_finite()) => Ok([a, b, c]),
+                _ => Err(error("Expected 3 numbers")),
+            };
+            match keyword {
+                "LUT_1D_SIZE" | "LUT_3D_SIZE" => {
+                    let three_d = keyword == "LUT_3D_SIZE";
+                    let max = if three_d { 256 } else { 65536 };
+                    match values[..] {
+                        [n] if n.fract() == 0. && n >= 2. && n <= max as f32 => {
+                            size = Some((n as usize, three_d))
+                        }
+                        _ => return Err(error(&format!("Size must be from 2 to {}", max))),
+                    }
+                }
+                "DOMAIN_MIN" => domain_min = triple()?,
+                "DOMAIN_MAX" => domain_max = triple()?,
+                // The Resolve form of the domain, the same for each channel
+                "LUT_1D_INPUT_RANGE" | "LUT_3D_INPUT_RANGE" => match values[..] {
+                    [min, max] => {
+                        domain_min = [min; 3];
+                        domain_max = [max; 3];
+                    }
+                    _ => return Err(error("Expected 2 numbers")),
+                },
+                _ => match (keyword.parse::<f32>(), &values[..]) {
+                    (Ok(r), &[g, b]) if r.is_finite() && g.is_finite() && b.is_finite() => {
+                        table.push([r, g, b])
+                    }
+                    _ => return Err(error(&format!("Unknown keyword {}", keyword))),
+                },
+            }
+        }
+        let (size, three_d) = size.ok_or("Missing LUT_1D_SIZE or LUT_3D_SIZE")?;
+        let expected = if three_d { size * size * size } else { size };
+        if table.len() != expected {
+            return Err(format!(
+                "Expected {} entries, found {}",
+                expected,
+                table.len()
+            ));
+        }
+        if (0..3).any(|c| domain_max[c] <= domain_min[c]) {
+            return Err("DOMAIN_MAX must be above DOMAIN_MIN".to_owned());
+        }
+        Ok(Lut {
+            size,
+            three_d,
+            domain_min,
+            domain_max,
+            table,
+        })
+    }
+
+    /// Looks up `rgb`, interpolating linearly between entries. Inputs outside
+    /// the domain are clamped to it.
+    pub fn apply(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let last = (self.size - 1) as f32;
+        // Index of the entry at or below each input, and the weight of the
+        // one after it
+        let mut index = [0; 3];
+        let mut weight = [0.; 3];
+        for c in 0..3 {
+            let t = (rgb[c] - self.domain_min[c]) / (self.domain_max[c] - self.domain_min[c]);
+            let t = (t * last).clamp(0., last);
+            index[c] = (t as usize).min(self.size - 2);
+            weight[c] = t - index[c] as f32;
+        }
+        if !self.three_d {
+            let mut out = [0.; 3];
+            for c in 0..3 {
+                let (a, b) = (self.table[index[c]][c], self.table[index[c] + 1][c]);
+                out[c] = a + (b - a) * weight[c];
+            }
+            return out;
+        }
+        let n = self.size;
+        let mut out = [0.; 3];
+        for corner in 0..8 {
+            let (dr, dg, db) = (corner & 1, corner >> 1 & 1, corner >> 2);
+            let w = [dr, dg, db]
+                .iter()
+                .zip(weight)
+                .map(|(&d, w)| if d == 1 { w } else { 1. - w })
+                .product::<f32>();
+            if w == 0. {
+                continue;
+            }
+            let entry = self.table[(index[0] + dr) + (index[1] + dg) * n + (index[2] + db) * n * n];
+            for c in 0..3 {
+                out[c] += entry[c] * w;
+            }
+        }
+        out
+    }
+}
+
+/// Applies `lut` to the RGB of `frame`, writing a frame of the same format to
+/// `buf`. Subsampled chroma is the mean of that of the pixels sharing it.
+pub fn lut_frame(info: &FrameInfo, frame: &Frame, lut: &Lut, buf: &mut Vec<u8>) {
+    let bytewidth = info.bytewidth();
+    let (luma_len, chroma_len) = info.plane_lens();
+    let chroma_width = info.chroma_dims().0;
+    let max = ((1 << info.bit_depth) - 1) as f32;
+    let push = |buf: &mut Vec<u8>, sample: f32| {
+        let sample = sample.round().clamp(0., max) as u16;
+        if bytewidth == 1 {
+            buf.push(sample as u8);
+        } else {
+            buf.extend_from_slice(&sample.to_le_bytes());
+        }
+    };
+    buf.clear();
+    buf.reserve(luma_len + 2 * chroma_len);
+    // Sums of the U and V of the pixels of each chroma sample, and their count
+    let mut chroma = vec![[0f32; 3]; chroma_len / bytewidth];
+    for i in 0..info.height {
+        let row = info.row(frame, i);
+        let chroma_row = &mut chroma[info.chroma_row(i) * chroma_width..][..chroma_width];
+        for x in 0..info.width {
+            let [y, u, v] = row.to_rgb.rgb_to_yuv(&lut.apply(pixel_rgb(info, &row, x)));
+            push(buf, y);
+            let sums = &mut chroma_row[x >> info.xdec];
+            sums[0] += u;
+            sums[1] += v;
+            sums[2] += 1.;
+        }
+    }
+    for (plane, c) in [(frame.u, 0), (frame.v, 1)] {
+        for (i, sums) in chroma.iter().enumerate() {
+            if sums[2] > 0. {
+                push(buf, sums[c] / sums[2]);
+            } else {
+                // Chroma rows no luma row reads from are kept
+                buf.extend_from_slice(&plane[i * bytewidth..][..bytewidth]);
+            }
+        }
+    }
+}
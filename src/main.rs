@@ -26,27 +26,254 @@
 
 extern crate clap;
 
-#[macro_use]
-extern crate itertools;
-
-use clap::{App, Arg};
-use std::fs::File;
+use clap::{App, Arg, ArgMatches};
+use std::cell::{Cell, RefCell};
+use std::f64::consts::PI;
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, File};
+use std::io;
 use std::io::prelude::*;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
 
 use std::process::exit;
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant};
 
-mod rgbtolab;
-use rgbtolab::*;
+use alert::DropAlert;
+use align::{best_offset, check_order, FrameQueue};
+use arrow::ArrowWriter;
+use baseline::{config_hash, Baseline};
+use decode::{DecodedFrame, FfmpegOptions, FfmpegReader, FrameLog};
+use dump_ciede2000::delta_e::{KSubArgs, DE2000};
+use dump_ciede2000::rgbtolab::{
+    linear_rgb_to_lab_with, pq_rgb_to_lab_with, rgb_to_lab, BT2020_TO_XYZ, SRGB_TO_XYZ,
+};
+use dump_ciede2000::*;
+use ed25519_dalek::SigningKey;
+use features::{FeatureWriter, FrameFeatures};
+use json::{json_array, json_f64, json_object, json_str};
+use maparchive::{MapArchiveReader, MapArchiveWriter, Region};
+use progress::Progress;
+use rayon::prelude::*;
+use retry::RetryReader;
+use roi::RoiBoxes;
+use rolling::{RollingScores, WINDOWS};
+use sign::{digest_hex, public_key_hex, DigestReader, InputDigest};
+use stage::{StageReader, StagedCopy};
+use tiff::LabFormat;
 
-mod delta_e;
-use delta_e::*;
+mod alert;
+mod align;
+mod arrow;
+mod baseline;
+mod decode;
+mod features;
+mod heatmap;
+mod image;
+mod json;
+mod maparchive;
+mod ocio;
+mod probe;
+mod progress;
+mod retry;
+mod roi;
+mod rolling;
+mod server;
+mod shm;
+mod sign;
+mod stage;
+mod tiff;
 
 struct CliOptions {
-    pub input1: Box<dyn Read>,
-    pub input2: Box<dyn Read>,
+    pub input1: Option<Box<dyn Read>>,
+    pub input2: Option<Box<dyn Read>>,
+    /// Further references for video2, from --reference
+    pub references: Vec<Box<dyn Read>>,
+    /// Name of each input with the number of reads retried on it so far
+    pub read_retries: Vec<(String, Rc<Cell<u64>>)>,
+    /// Name of each input being copied by --stage-to with the copy, once
+    /// complete
+    pub staged: Vec<(String, Rc<RefCell<Option<StagedCopy>>>)>,
+    /// Key for --sign and the file the signature goes to
+    pub sign: Option<(SigningKey, PathBuf)>,
+    /// Name of each input with the hash of what was read of it, for --sign
+    pub input_digests: Vec<(String, InputDigest)>,
+    /// Whether the decoder of each --decode input failed
+    pub decode_failed: Vec<Rc<Cell<bool>>>,
+    /// Frames the decoders of video1 and video2 reported, with --frame-info
+    pub frame_logs: [Option<FrameLog>; 2],
     pub summary: bool,
+    pub progress: bool,
+    /// Lengths of the inputs that are regular files, to estimate the frames
+    /// in them for --progress
+    pub input_lens: [Option<u64>; 2],
     pub limit: Option<usize>,
-    pub simd: bool,
+    /// Frames of each input read past before scoring, which frame numbers in
+    /// the output start from
+    pub skip: usize,
+    /// Frames per second to keep up with, sampling rows of frames that would
+    /// fall behind
+    pub realtime: Option<f64>,
+    pub rolling: bool,
+    /// The command, score and number of frames of --on-drop
+    pub on_drop: Option<(String, f64, usize)>,
+    pub tolerate_drops: usize,
+    /// Match repeated frames of video2 to the frame of video1 they repeat
+    pub dup_aware: bool,
+    /// Largest offset searched by --auto-align
+    pub auto_align: Option<usize>,
+    /// Check that the first frames of the inputs run in the same order
+    pub order_check: bool,
+    pub simd: SimdLevel,
+    pub metric: Metric,
+    pub approx_trig: bool,
+    pub fast_int: bool,
+    /// Score with the f64 reference kernel
+    pub precision_f64: bool,
+    pub autotune: bool,
+    pub tile_width: usize,
+    pub stdio_server: bool,
+    pub unix_socket: Option<PathBuf>,
+    pub shm: Option<PathBuf>,
+    pub probe_raw: Option<PathBuf>,
+    pub replay_trace: Option<PathBuf>,
+    pub image: Option<ImageOptions>,
+    pub map_stats: Option<MapStatsOptions>,
+    pub trace_tolerance: f32,
+    pub raw: Option<FrameInfo>,
+    pub assume_range: Option<Range>,
+    /// Sample ranges of video1 and video2 alone, taking precedence over
+    /// `assume_range`
+    pub range1: Option<Range>,
+    pub range2: Option<Range>,
+    pub transform1: Option<Transform>,
+    pub transform2: Option<Transform>,
+    /// The .cube files of --lut1 and --lut2
+    pub lut1: Option<(PathBuf, Lut)>,
+    pub lut2: Option<(PathBuf, Lut)>,
+    /// The part of both inputs that is scored
+    pub crop: Option<Crop>,
+    pub deinterlace: Deinterlace,
+    /// Deinterlace inputs not marked interlaced too
+    pub force_deinterlace: bool,
+    pub assume_matrix: Option<Matrix>,
+    pub transfer: Option<Transfer>,
+    pub assume_bit_depth: Option<usize>,
+    /// DE2000 weights of the main score
+    pub ksub: KSubArgs,
+    pub compare: Option<CompareConfig>,
+    pub chroma_grid: bool,
+    pub breakdown: bool,
+    pub skin_weight: Option<f32>,
+    /// Weight rows by latitude, for equirectangular 360° video
+    pub equirect: bool,
+    pub scene_summary: bool,
+    pub banding: bool,
+    pub blockiness: Option<usize>,
+    pub stereo: Option<Stereo>,
+    pub roi_boxes: Option<RoiBoxes>,
+    pub conform_to: Option<ConformTo>,
+    pub conform_filter: ChromaFilter,
+    /// Filter for scaling video2 to the dimensions of video1, if they differ
+    pub rescale: Option<ScaleFilter>,
+    pub scale_colorspace: ScaleLight,
+    pub resync: bool,
+    pub threads: Option<usize>,
+    pub json: bool,
+    /// Whether the scores are printed as text, which --json and --arrow to
+    /// stdout replace
+    pub text: bool,
+    pub table: bool,
+    pub heatmaps: Option<PathBuf>,
+    pub heatmap_scale: f32,
+    pub dump_lab: Option<PathBuf>,
+    /// Inclusive ranges of the frames to dump, or all of them
+    pub dump_lab_frames: Option<Vec<(usize, usize)>>,
+    pub dump_lab_format: LabFormat,
+    pub dump_stage: Stage,
+    pub record_trace: Option<PathBuf>,
+    /// Inclusive ranges of the frames to record, or all of them
+    pub record_trace_frames: Option<Vec<(usize, usize)>>,
+    pub map_output: Option<PathBuf>,
+    pub map_bit_depth: usize,
+    pub map_scale: f32,
+    pub map_archive: Option<PathBuf>,
+    pub csv: Option<PathBuf>,
+    /// Path of the Arrow IPC stream, or `-` for stdout
+    pub arrow: Option<PathBuf>,
+    pub features: Option<PathBuf>,
+    pub baseline: Option<Baseline>,
+    pub update_baseline: Option<PathBuf>,
+    pub max_regression: f64,
+    pub note: Option<String>,
+    /// Total score a run must reach to pass
+    pub verdict_threshold: Option<f64>,
+    pub rating: Option<Vec<(f64, String)>>,
+    pub vision: Vision,
+}
+
+/// Which input's chroma layout the other is converted to when they differ.
+#[derive(Clone, Copy)]
+enum ConformTo {
+    Reference,
+    Distorted,
+    /// Whichever has more chroma samples, so no chroma detail is discarded
+    Best,
+}
+
+/// How the two views of stereo frames are packed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Stereo {
+    /// Left view in the left half
+    SideBySide,
+    /// Left view in the top half
+    TopAndBottom,
+}
+
+/// How the fields of interlaced inputs are scored.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Deinterlace {
+    Off,
+    /// Both fields together, as one frame
+    Weave,
+    /// Each field line doubled into a frame of its own
+    Bob,
+}
+
+/// Settings of the second column of --compare-configs that differ from the
+/// first.
+#[derive(Clone, Copy, Default)]
+struct CompareConfig {
+    pub matrix: Option<Matrix>,
+    pub range: Option<Range>,
+    pub weights: Option<KSubArgs>,
+}
+
+/// Settings of the `image` subcommand.
+struct ImageOptions {
+    image1: PathBuf,
+    image2: PathBuf,
+    heatmap: Option<PathBuf>,
+    heatmap_scale: f32,
+    ksub: KSubArgs,
+    /// Conversion into the scoring space baked from --ocio
+    ocio: Option<Lut>,
+    /// Factor taking the linear light of OpenEXR, float TIFF and linear or log
+    /// DPX images to 1 for white, from
+    /// --exposure and --white or --nits
+    linear_scale: f32,
+    /// Take integer images to be PQ encoded BT.2020
+    pq: bool,
+}
+
+/// Settings of the `map-stats` subcommand.
+struct MapStatsOptions {
+    archive: PathBuf,
+    /// Inclusive ranges of the frames to query, or all of them
+    frames: Option<Vec<(usize, usize)>>,
+    region: Option<Region>,
 }
 
 fn parse_cli() -> CliOptions {
@@ -54,13 +281,36 @@ fn parse_cli() -> CliOptions {
         .about("Video quality metric based off color difference instead of just luma or chroma")
         .arg(
             Arg::with_name("video1")
-                .help("Uncompressed YUV4MPEG2 video input")
-                .required(true),
+                .help("Uncompressed YUV4MPEG2 video input, or - for stdin")
+                .allow_invalid_utf8(true)
+                .required_unless_present_any([
+                    "STDIO_SERVER",
+                    "UNIX_SOCKET",
+                    "SHM",
+                    "PROBE_RAW",
+                    "REPLAY_TRACE",
+                ]),
         )
         .arg(
             Arg::with_name("video2")
-                .help("Uncompressed YUV4MPEG2 video input")
-                .required(true),
+                .help("Uncompressed YUV4MPEG2 video input, or - for stdin")
+                .allow_invalid_utf8(true)
+                .required_unless_present_any([
+                    "STDIO_SERVER",
+                    "UNIX_SOCKET",
+                    "SHM",
+                    "PROBE_RAW",
+                    "REPLAY_TRACE",
+                ]),
+        )
+        .arg(
+            Arg::with_name("REFERENCE")
+                .help("Another reference for video2, in the format of video1. Each pixel is scored against the closest reference.")
+                .long("reference")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .allow_invalid_utf8(true)
+                .value_name("FILE"),
         )
         .arg(
             Arg::with_name("LIMIT")
@@ -69,538 +319,3888 @@ fn parse_cli() -> CliOptions {
                 .long("limit")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("SKIP")
+                .help("Number of frames of each input to skip before scoring")
+                .long("skip")
+                .takes_value(true)
+                .value_name("N"),
+        )
+        .arg(
+            Arg::with_name("FRAMES")
+                .help("Only score frames FIRST to LAST of each input")
+                .long("frames")
+                .takes_value(true)
+                .value_name("FIRST-LAST")
+                .conflicts_with_all(&["SKIP", "LIMIT"]),
+        )
+        .arg(
+            Arg::with_name("REALTIME")
+                .help("Score at most FPS frames per second, scoring only some rows of frames that would fall behind")
+                .long("realtime")
+                .takes_value(true)
+                .value_name("FPS")
+                .conflicts_with_all(&[
+                    "TOLERATE_DROPS",
+                    "TABLE",
+                    "DUMP_HEATMAPS",
+                    "MAP_OUTPUT",
+                    "MAP_ARCHIVE",
+                    "STEREO",
+                    "ROI_BOXES",
+                ]),
+        )
+        .arg(
+            Arg::with_name("ROLLING")
+                .help("Print the mean scores of the last 10 seconds, minute and 5 minutes every second")
+                .long("rolling")
+                .requires("REALTIME")
+                .conflicts_with("JSON"),
+        )
+        .arg(
+            Arg::with_name("ON_DROP")
+                .help("Run CMD with sh, given the frame and score as $1 and $2, when --alert-frames frames in a row score below --alert-below")
+                .long("on-drop")
+                .takes_value(true)
+                .value_name("CMD")
+                .requires_all(&["REALTIME", "ALERT_BELOW"]),
+        )
+        .arg(
+            Arg::with_name("ALERT_BELOW")
+                .help("Score below which frames count towards --on-drop")
+                .long("alert-below")
+                .takes_value(true)
+                .value_name("SCORE")
+                .requires("ON_DROP"),
+        )
+        .arg(
+            Arg::with_name("ALERT_FRAMES")
+                .help("Frames in a row that must score below --alert-below to run --on-drop")
+                .long("alert-frames")
+                .takes_value(true)
+                .value_name("N")
+                .default_value("1"),
+        )
+        .arg(
+            Arg::with_name("TOLERATE_DROPS")
+                .help("Realign up to N frames of video1 that are missing from video2")
+                .long("tolerate-drops")
+                .takes_value(true)
+                .value_name("N")
+                .conflicts_with("REFERENCE"),
+        )
+        .arg(
+            Arg::with_name("DUP_AWARE")
+                .help("Score frames of video2 that repeat the one before against the closer of the last two frames of video1")
+                .long("dup-aware")
+                .conflicts_with_all(&["TOLERATE_DROPS", "REFERENCE"]),
+        )
+        .arg(
+            Arg::with_name("AUTO_ALIGN")
+                .help("Find the offset between the inputs from the changes in their luma and skip the frames before it")
+                .long("auto-align")
+                .conflicts_with_all(&["REALTIME", "DUP_AWARE", "REFERENCE"]),
+        )
+        .arg(
+            Arg::with_name("ALIGN_WINDOW")
+                .help("Largest offset in frames --auto-align searches either way")
+                .long("align-window")
+                .takes_value(true)
+                .value_name("N")
+                .default_value("10"),
+        )
+        .arg(
+            Arg::with_name("NO_ORDER_CHECK")
+                .help("Score inputs whose first frames do not seem to run in the same order")
+                .long("no-order-check"),
+        )
         .arg(
             Arg::with_name("SUMMARY")
                 .help("Only output the summary line")
                 .short('s')
                 .long("summary"),
         )
+        .arg(
+            Arg::with_name("PROGRESS")
+                .help("Show the frames scored, frames per second and time left on stderr")
+                .long("progress"),
+        )
         .arg(
             Arg::with_name("SIMD")
-                .help("Set simd feature level")
+                .help("Set simd feature level, native being the best the CPU has")
                 .long("simd")
                 .takes_value(true)
-                .possible_values(&["off", "native"])
+                .possible_values(["off", "native", "neon", "rvv", "sse4.1", "avx2", "avx512"])
                 .default_value("native"),
         )
+        .arg(
+            Arg::with_name("METRIC")
+                .help("Score the ΔE of CIEDE2000, the cheaper CIE76 or CIE94, CMC l:c, or the ΔE-ITP of BT.2124 for HDR and wide color gamut")
+                .long("metric")
+                .takes_value(true)
+                .possible_values(["de2000", "de76", "de94", "cmc", "itp"])
+                .default_value("de2000"),
+        )
+        .arg(
+            Arg::with_name("CMC_RATIO")
+                .help("Lightness to chroma weight ratio l:c of --metric cmc, 2:1 for acceptability or 1:1 for perceptibility")
+                .long("cmc-ratio")
+                .takes_value(true)
+                .value_name("L:C")
+                .default_value("2:1"),
+        )
+        .arg(
+            Arg::with_name("TRIG")
+                .help("Use exact or polynomial approximations of trigonometry in DE2000")
+                .long("trig")
+                .takes_value(true)
+                .possible_values(["exact", "approx"])
+                .default_value("exact"),
+        )
+        .arg(
+            Arg::with_name("PRECISION")
+                .help("Score in f32 with the fast kernels, or in f64 with exact powers and trigonometry as a reference (scalar only)")
+                .long("precision")
+                .takes_value(true)
+                .possible_values(["f32", "f64"])
+                .default_value("f32"),
+        )
+        .arg(
+            Arg::with_name("FAST_INT")
+                .help("Convert YUV to RGB in fixed point (8-bit 4:2:0 and 4:2:2 only, no SIMD)")
+                .long("fast-int")
+                .conflicts_with_all(&["SIMD", "AUTOTUNE"]),
+        )
+        .arg(
+            Arg::with_name("AUTOTUNE")
+                .help("Choose the fastest backend, thread count and tile width by timing the first frames, overriding --simd and --tile-width")
+                .long("autotune"),
+        )
+        .arg(
+            Arg::with_name("TILE_WIDTH")
+                .help("Advanced: score rows in tiles of this many pixels (multiple of 8)")
+                .long("tile-width")
+                .takes_value(true)
+                .default_value("1024"),
+        )
+        .arg(
+            Arg::with_name("JSON")
+                .help("Print the scores and settings as one JSON document at the end")
+                .long("json"),
+        )
+        .arg(
+            Arg::with_name("SIGN")
+                .help("Sign the --json report with an Ed25519 private key in PEM")
+                .long("sign")
+                .takes_value(true)
+                .allow_invalid_utf8(true)
+                .value_name("KEY")
+                .requires_all(&["JSON", "SIGNATURE"]),
+        )
+        .arg(
+            Arg::with_name("SIGNATURE")
+                .help("Write the detached signature of --sign to FILE")
+                .long("signature")
+                .takes_value(true)
+                .allow_invalid_utf8(true)
+                .value_name("FILE")
+                .requires("SIGN"),
+        )
+        .arg(
+            Arg::with_name("TABLE")
+                .help("Print scores as an aligned table with the time, 95th percentile and maximum ΔE of each frame")
+                .long("table"),
+        )
+        .arg(
+            Arg::with_name("DUMP_HEATMAPS")
+                .help("Write the ΔE of each frame as a colorized PNG in DIR")
+                .long("dump-heatmaps")
+                .takes_value(true)
+                .allow_invalid_utf8(true)
+                .value_name("DIR"),
+        )
+        .arg(
+            Arg::with_name("DUMP_LAB")
+                .help("Write the L*, a* and b* planes of both inputs as TIFFs in DIR")
+                .long("dump-lab")
+                .takes_value(true)
+                .allow_invalid_utf8(true)
+                .value_name("DIR"),
+        )
+        .arg(
+            Arg::with_name("DUMP_LAB_FRAMES")
+                .help("Frames to write with --dump-lab, as comma separated numbers and FIRST-LAST ranges [default: all]")
+                .long("dump-lab-frames")
+                .takes_value(true)
+                .value_name("LIST")
+                .requires("DUMP_LAB"),
+        )
+        .arg(
+            Arg::with_name("DUMP_LAB_FORMAT")
+                .help("Samples of --dump-lab: f32 as is, or u16 scaled from L* 0 to 100 and a* and b* -128 to 128")
+                .long("dump-lab-format")
+                .takes_value(true)
+                .possible_values(["f32", "u16"])
+                .default_value("f32"),
+        )
+        .arg(
+            Arg::with_name("DUMP_STAGE")
+                .help("Stage of the conversion written by --dump-lab: nonlinear rgb, linear rgb, xyz or lab")
+                .long("dump-stage")
+                .takes_value(true)
+                .possible_values(["rgb", "linear", "xyz", "lab"])
+                .default_value("lab"),
+        )
+        .arg(
+            Arg::with_name("RECORD_TRACE")
+                .help("Write the samples of each row with the ΔE of each pixel to FILE, for --replay-trace")
+                .long("record-trace")
+                .takes_value(true)
+                .allow_invalid_utf8(true)
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::with_name("RECORD_TRACE_FRAMES")
+                .help("Frames to write with --record-trace, as comma separated numbers and FIRST-LAST ranges [default: all]")
+                .long("record-trace-frames")
+                .takes_value(true)
+                .value_name("LIST")
+                .requires("RECORD_TRACE"),
+        )
+        .arg(
+            Arg::with_name("HEATMAP_SCALE")
+                .help("ΔE drawn as white in heatmaps")
+                .long("heatmap-scale")
+                .takes_value(true)
+                .default_value("10"),
+        )
+        .arg(
+            Arg::with_name("BASELINE")
+                .help("Fail if scores drop below those of a --json run saved in FILE")
+                .long("baseline")
+                .takes_value(true)
+                .allow_invalid_utf8(true)
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::with_name("UPDATE_BASELINE")
+                .help("Write the scores of this run to the --baseline file instead of checking them")
+                .long("update-baseline")
+                .requires("BASELINE"),
+        )
+        .arg(
+            Arg::with_name("MAX_REGRESSION")
+                .help("Largest drop below the baseline score tolerated")
+                .long("max-regression")
+                .takes_value(true)
+                .requires("BASELINE")
+                .default_value("0"),
+        )
+        .arg(
+            Arg::with_name("NOTE")
+                .help("Include TEXT in the report, such as who ran it and why")
+                .long("note")
+                .takes_value(true)
+                .value_name("TEXT"),
+        )
+        .arg(
+            Arg::with_name("VERDICT_FROM_THRESHOLD")
+                .help("Report a pass when the total score reaches SCORE and a fail otherwise, exiting with status 1 on a fail")
+                .long("verdict-from-threshold")
+                .takes_value(true)
+                .value_name("SCORE"),
+        )
+        .arg(
+            Arg::with_name("MAP_OUTPUT")
+                .help("Write the ΔE of each frame as a grayscale Y4M video to FILE")
+                .long("map-output")
+                .takes_value(true)
+                .allow_invalid_utf8(true)
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::with_name("MAP_BIT_DEPTH")
+                .help("Bit depth of the --map-output video")
+                .long("map-bit-depth")
+                .takes_value(true)
+                .possible_values(["8", "10"])
+                .default_value("8"),
+        )
+        .arg(
+            Arg::with_name("MAP_SCALE")
+                .help("ΔE drawn as white in the --map-output video")
+                .long("map-scale")
+                .takes_value(true)
+                .default_value("10"),
+        )
+        .arg(
+            Arg::with_name("MAP_ARCHIVE")
+                .help("Write the ΔE of each frame to FILE as zstd-compressed tiles, to query with map-stats")
+                .long("map-archive")
+                .takes_value(true)
+                .allow_invalid_utf8(true)
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::with_name("CSV")
+                .help("Also write the frame number, mean ΔE and scores of each frame to FILE")
+                .long("csv")
+                .takes_value(true)
+                .allow_invalid_utf8(true)
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::with_name("ARROW")
+                .help("Write the frame number, mean ΔE and scores of each frame to FILE as an Arrow IPC stream, or to stdout if FILE is -")
+                .long("arrow")
+                .takes_value(true)
+                .allow_invalid_utf8(true)
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::with_name("FEATURES")
+                .help("Write ΔE percentiles, histogram, breakdown and luma stats of each frame to FILE as Parquet")
+                .long("features")
+                .takes_value(true)
+                .allow_invalid_utf8(true)
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::with_name("RATING")
+                .help("Label scores with rating bands")
+                .long("rating"),
+        )
+        .arg(
+            Arg::with_name("RATING_BANDS")
+                .help(
+                    "Label scores with these bands, given as comma separated MIN=LABEL \
+                     [default: 40=excellent,35=good,30=fair,-inf=poor]",
+                )
+                .long("rating-bands")
+                .takes_value(true)
+                .value_name("BANDS"),
+        )
         .arg(
             Arg::with_name("THREADS")
-                .help("Set threadpool size (unimplemented)")
+                .help("Number of threads scoring the rows of a frame [default: one per core]")
                 .long("threads")
                 .takes_value(true),
         )
-        .get_matches();
-    CliOptions {
-        input1: Box::new(File::open(matches.value_of("video1").unwrap()).unwrap()) as Box<dyn Read>,
-        input2: Box::new(File::open(matches.value_of("video2").unwrap()).unwrap()) as Box<dyn Read>,
-        summary: matches.is_present("SUMMARY"),
-        limit: matches
-            .value_of("LIMIT")
-            .map(|v| v.parse().expect("Limit must be a positive number")),
-        simd: match matches.value_of("SIMD").unwrap() {
-            "off" => false,
-            "native" => true,
-            &_ => unreachable!(),
-        },
-    }
-}
-
-// Taken from rav1e
-#[derive(Copy, Clone, Debug, PartialEq)]
-enum ChromaSampling {
-    Cs420,
-    Cs422,
-    Cs444,
-    Cs400,
-}
-
-// Taken from rav1e
-fn map_y4m_color_space(color_space: y4m::Colorspace) -> ChromaSampling {
-    use y4m::Colorspace::*;
-    use ChromaSampling::*;
-    match color_space {
-        Cmono => Cs400,
-        C420jpeg | C420paldv => Cs420,
-        C420mpeg2 => Cs420,
-        C420 | C420p10 | C420p12 => Cs420,
-        C422 | C422p10 | C422p12 => Cs422,
-        C444 | C444p10 | C444p12 => Cs444,
-    }
-}
-
-fn main() {
-    let mut cli = parse_cli();
-    let mut video1 = y4m::decode(&mut cli.input1).unwrap();
-    let mut video2 = y4m::decode(&mut cli.input2).unwrap();
-    let (width, height) = {
-        let dimension1 = (video1.get_width(), video1.get_height());
-        let dimension2 = (video2.get_width(), video2.get_height());
-
-        if dimension1 != dimension2 {
-            eprintln!(
-                "Video dimensions do not match: {}x{} != {}x{}",
-                dimension1.0, dimension1.1, dimension2.0, dimension2.1
-            );
-            exit(1);
-        }
-        dimension1
-    };
-    let (bit_depth, bytewidth, xdec, ydec) = {
-        let colorspace1 = video1.get_colorspace();
-        let colorspace2 = video2.get_colorspace();
-        let bit_depth1 = colorspace1.get_bit_depth();
-        let bit_depth2 = colorspace2.get_bit_depth();
-        if bit_depth1 != bit_depth2 {
-            eprintln!("Bit depths do not match: {} != {}", bit_depth1, bit_depth2);
-            exit(1);
-        }
-        let sampling1 = map_y4m_color_space(colorspace1);
-        let sampling2 = map_y4m_color_space(colorspace2);
-        if sampling1 != sampling2 {
-            eprintln!("Sub sampling does not match. Mismatched subsampling is not supported.");
-            exit(1);
-        }
-        if sampling1 == ChromaSampling::Cs400 {
-            eprintln!("Grayscale is unsupported.")
-        }
-        let (xdec, ydec) = {
-            use self::ChromaSampling::*;
-            match sampling1 {
-                Cs420 => (1, 1),
-                Cs422 => (1, 0),
-                Cs444 => (0, 0),
-                Cs400 => (1, 1),
-            }
-        };
-        (bit_depth1, video1.get_bytes_per_sample(), xdec, ydec)
-    };
-    {
-        let framerate1 = video1.get_framerate();
-        let framerate2 = video2.get_framerate();
-        if framerate1.num * framerate2.den != framerate2.num * framerate1.den {
-            eprintln!(
-                "Warning - Framerates do not match: {} != {}",
-                framerate1, framerate2
-            );
-        }
-    }
-
-    // luma stride
-    let y_stride = width * bytewidth;
-    // chroma stride
-    let c_stride = (width >> xdec) * bytewidth;
-    let delta_e_row_fn = get_delta_e_row_fn(bit_depth, xdec, cli.simd);
-    let mut num_frames: usize = 0;
-    let mut total: f64 = 0f64;
-    loop {
-        match (video1.read_frame(), video2.read_frame()) {
-            (Ok(pic1), Ok(pic2)) => {
-                let mut delta_e_vec: Vec<f32> = vec![0.0; width * height];
-                let y_plane1 = pic1.get_y_plane();
-                let u_plane1 = pic1.get_u_plane();
-                let v_plane1 = pic1.get_v_plane();
-                let y_plane2 = pic2.get_y_plane();
-                let u_plane2 = pic2.get_u_plane();
-                let v_plane2 = pic2.get_v_plane();
-                for i in 0..height {
-                    unsafe {
-                        delta_e_row_fn(
-                            FrameRow {
-                                y: &y_plane1[i * y_stride..][..y_stride],
-                                u: &u_plane1[(i >> ydec) * c_stride..][..c_stride],
-                                v: &v_plane1[(i >> ydec) * c_stride..][..c_stride],
-                            },
-                            FrameRow {
-                                y: &y_plane2[i * y_stride..][..y_stride],
-                                u: &u_plane2[(i >> ydec) * c_stride..][..c_stride],
-                                v: &v_plane2[(i >> ydec) * c_stride..][..c_stride],
-                            },
-                            &mut delta_e_vec[i * width..][..width],
-                        );
-                    }
-                }
-                let score = 45.
-                    - 20.
-                        * (delta_e_vec.iter().map(|x| *x as f64).sum::<f64>()
-                            / ((width * height) as f64))
-                            .log10();
-                total += score;
-                if !cli.summary {
-                    println!("{:08}: {:2.4}", num_frames, score);
-                }
-                num_frames += 1;
-                if let Some(limit) = cli.limit {
-                    if num_frames >= limit {
-                        break;
-                    }
-                }
-            }
-            _ => {
-                break;
-            }
-        }
-    }
-    println!("Total: {:2.4}", total / (num_frames as f64));
-}
-
-// Arguments for delta e
-// "Color Image Quality Assessment Based on CIEDE2000"
-// Yang Yang, Jun Ming and Nenghai Yu, 2012
-// http://dx.doi.org/10.1155/2012/273723
-const K_SUB: KSubArgs = KSubArgs {
-    l: 0.65,
-    c: 1.0,
-    h: 4.0,
-};
-
-pub struct FrameRow<'a> {
-    y: &'a [u8],
-    u: &'a [u8],
-    v: &'a [u8],
-}
-
-type DeltaERowFn = unsafe fn(FrameRow, FrameRow, &mut [f32]);
-
-fn get_delta_e_row_fn(bit_depth: usize, xdec: usize, simd: bool) -> DeltaERowFn {
-    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
-    {
-        if is_x86_feature_detected!("avx2") && xdec == 1 && simd {
-            return match bit_depth {
-                8 => BD8::delta_e_row_avx2,
-                10 => BD10::delta_e_row_avx2,
-                12 => BD12::delta_e_row_avx2,
-                _ => unreachable!(),
-            };
-        }
-    }
-    match (bit_depth, xdec) {
-        (8, 1) => BD8::delta_e_row_scalar,
-        (10, 1) => BD10::delta_e_row_scalar,
-        (12, 1) => BD12::delta_e_row_scalar,
-        (8, 0) => BD8_444::delta_e_row_scalar,
-        (10, 0) => BD10_444::delta_e_row_scalar,
-        (12, 0) => BD12_444::delta_e_row_scalar,
-        _ => unreachable!(),
-    }
-}
-
-pub trait Colorspace {
-    const BIT_DEPTH: u32;
-    const X_DECIMATION: u32;
-}
-
-struct BD8;
-struct BD10;
-struct BD12;
-
-struct BD8_444;
-struct BD10_444;
-struct BD12_444;
-
-impl Colorspace for BD8 {
-    const BIT_DEPTH: u32 = 8;
-    const X_DECIMATION: u32 = 1;
-}
-impl Colorspace for BD10 {
-    const BIT_DEPTH: u32 = 10;
-    const X_DECIMATION: u32 = 1;
-}
-impl Colorspace for BD12 {
-    const BIT_DEPTH: u32 = 12;
-    const X_DECIMATION: u32 = 1;
-}
-impl Colorspace for BD8_444 {
-    const BIT_DEPTH: u32 = 8;
-    const X_DECIMATION: u32 = 0;
-}
-impl Colorspace for BD10_444 {
-    const BIT_DEPTH: u32 = 10;
-    const X_DECIMATION: u32 = 0;
-}
-impl Colorspace for BD12_444 {
-    const BIT_DEPTH: u32 = 12;
-    const X_DECIMATION: u32 = 0;
-}
-
-fn twice<T>(
-    i: T,
-) -> itertools::Interleave<<T as IntoIterator>::IntoIter, <T as IntoIterator>::IntoIter>
-where
-    T: IntoIterator + Clone,
-{
-    itertools::interleave(i.clone(), i)
-}
-
-pub trait DeltaEScalar: Colorspace {
-    fn delta_e_scalar(yuv1: (u16, u16, u16), yuv2: (u16, u16, u16)) -> f32 {
-        let scale = (1 << (Self::BIT_DEPTH - 8)) as f32;
-        let yuv_to_rgb = |yuv: (u16, u16, u16)| {
-            // Assumes BT.709
-            let y = (yuv.0 as f32 - 16. * scale) * (1. / (219. * scale));
-            let u = (yuv.1 as f32 - 128. * scale) * (1. / (224. * scale));
-            let v = (yuv.2 as f32 - 128. * scale) * (1. / (224. * scale));
-
-            // [-0.804677, 1.81723]
-            let r = y + 1.28033 * v;
-            // [−0.316650, 1.09589]
-            let g = y - 0.21482 * u - 0.38059 * v;
-            // [-1.28905, 2.29781]
-            let b = y + 2.12798 * u;
-
-            (r, g, b)
-        };
-
-        let (r1, g1, b1) = yuv_to_rgb(yuv1);
-        let (r2, g2, b2) = yuv_to_rgb(yuv2);
-        DE2000::new(rgb_to_lab(&[r1, g1, b1]), rgb_to_lab(&[r2, g2, b2]), K_SUB)
-    }
-
-    unsafe fn delta_e_row_scalar(row1: FrameRow, row2: FrameRow, res_row: &mut [f32]) {
-        // Only one version should be compiled for each trait
-        if Self::BIT_DEPTH == 8 {
-            if Self::X_DECIMATION == 1 {
-                for (y1, u1, v1, y2, u2, v2, res) in izip!(
-                    row1.y,
-                    twice(row1.u),
-                    twice(row1.v),
-                    row2.y,
-                    twice(row2.u),
-                    twice(row2.v),
-                    res_row
-                ) {
-                    *res = Self::delta_e_scalar(
-                        (*y1 as u16, *u1 as u16, *v1 as u16),
-                        (*y2 as u16, *u2 as u16, *v2 as u16),
-                    );
-                }
-            } else {
-                for (y1, u1, v1, y2, u2, v2, res) in
-                    izip!(row1.y, row1.u, row1.v, row2.y, row2.u, row2.v, res_row)
-                {
-                    *res = Self::delta_e_scalar(
-                        (*y1 as u16, *u1 as u16, *v1 as u16),
-                        (*y2 as u16, *u2 as u16, *v2 as u16),
-                    );
-                }
-            }
-        } else {
-            if Self::X_DECIMATION == 1 {
-                for (y1, u1, v1, y2, u2, v2, res) in izip!(
-                    row1.y.chunks(2),
-                    twice(row1.u.chunks(2)),
-                    twice(row1.v.chunks(2)),
-                    row2.y.chunks(2),
-                    twice(row2.u.chunks(2)),
-                    twice(row2.v.chunks(2)),
-                    res_row
-                ) {
-                    let to_u16 =
-                        |input: &[u8]| -> u16 { ((input[1] as u16) << 8) | (input[0] as u16) };
-                    *res = Self::delta_e_scalar(
-                        (to_u16(&*y1), to_u16(&*u1), to_u16(&*v1)),
-                        (to_u16(&*y2), to_u16(&*u2), to_u16(&*v2)),
-                    );
-                }
-            } else {
-                for (y1, u1, v1, y2, u2, v2, res) in izip!(
-                    row1.y.chunks(2),
-                    row1.u.chunks(2),
-                    row1.v.chunks(2),
-                    row2.y.chunks(2),
-                    row2.u.chunks(2),
-                    row2.v.chunks(2),
-                    res_row
-                ) {
-                    let to_u16 =
-                        |input: &[u8]| -> u16 { ((input[1] as u16) << 8) | (input[0] as u16) };
-                    *res = Self::delta_e_scalar(
-                        (to_u16(&*y1), to_u16(&*u1), to_u16(&*v1)),
-                        (to_u16(&*y2), to_u16(&*u2), to_u16(&*v2)),
-                    );
-                }
-            }
-        }
-    }
-}
-
-impl DeltaEScalar for BD8 {}
-impl DeltaEScalar for BD10 {}
-impl DeltaEScalar for BD12 {}
-impl DeltaEScalar for BD8_444 {}
-impl DeltaEScalar for BD10_444 {}
-impl DeltaEScalar for BD12_444 {}
-
-#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
-use self::avx2::*;
-
-#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
-mod avx2 {
-    use super::*;
-
-    #[cfg(target_arch = "x86")]
-    use std::arch::x86::*;
-    #[cfg(target_arch = "x86_64")]
-    use std::arch::x86_64::*;
-
-    pub trait DeltaEAVX2: Colorspace + DeltaEScalar {
-        #[target_feature(enable = "avx2")]
-        unsafe fn yuv_to_rgb(yuv: (__m256, __m256, __m256)) -> (__m256, __m256, __m256) {
-            let scale: f32 = (1 << (Self::BIT_DEPTH - 8)) as f32;
-            #[target_feature(enable = "avx2")]
-            unsafe fn set1(val: f32) -> __m256 {
-                _mm256_set1_ps(val)
-            };
-            let y = _mm256_mul_ps(
-                _mm256_sub_ps(yuv.0, set1(16. * scale)),
-                set1(1. / (219. * scale)),
-            );
-            let u = _mm256_mul_ps(
-                _mm256_sub_ps(yuv.1, set1(128. * scale)),
-                set1(1. / (224. * scale)),
-            );
-            let v = _mm256_mul_ps(
-                _mm256_sub_ps(yuv.2, set1(128. * scale)),
-                set1(1. / (224. * scale)),
-            );
-
-            let r = _mm256_add_ps(y, _mm256_mul_ps(v, set1(1.28033)));
-            let g = _mm256_add_ps(
-                _mm256_add_ps(y, _mm256_mul_ps(u, set1(-0.21482))),
-                _mm256_mul_ps(v, set1(-0.38059)),
-            );
-            let b = _mm256_add_ps(y, _mm256_mul_ps(u, set1(2.12798)));
-
-            (r, g, b)
-        }
-
-        #[target_feature(enable = "avx2")]
-        unsafe fn delta_e_avx2(
-            yuv1: (__m256, __m256, __m256),
-            yuv2: (__m256, __m256, __m256),
-            res_chunk: &mut [f32],
-        ) {
-            let (r1, g1, b1) = Self::yuv_to_rgb(yuv1);
-            let (r2, g2, b2) = Self::yuv_to_rgb(yuv2);
-
-            let lab1 = rgb_to_lab_avx2(&[r1, g1, b1]);
-            let lab2 = rgb_to_lab_avx2(&[r2, g2, b2]);
-            for i in 0..8 {
-                res_chunk[i] = DE2000::new(lab1[i], lab2[i], K_SUB);
-            }
-        }
-
-        #[target_feature(enable = "avx2")]
-        unsafe fn delta_e_row_avx2(row1: FrameRow, row2: FrameRow, res_row: &mut [f32]) {
-            // Only one version should be compiled for each trait
-            if Self::BIT_DEPTH == 8 {
-                for (chunk1_y, chunk1_u, chunk1_v, chunk2_y, chunk2_u, chunk2_v, res_chunk) in izip!(
-                    row1.y.chunks(8),
-                    row1.u.chunks(4),
-                    row1.v.chunks(4),
-                    row2.y.chunks(8),
-                    row2.u.chunks(4),
-                    row2.v.chunks(4),
-                    res_row.chunks_mut(8)
-                ) {
-                    if chunk1_y.len() == 8 {
-                        #[target_feature(enable = "avx2")]
-                        unsafe fn load_luma(chunk: &[u8]) -> __m256 {
-                            let tmp = _mm_loadl_epi64(chunk.as_ptr() as *const _);
-                            _mm256_cvtepi32_ps(_mm256_cvtepu8_epi32(tmp))
-                        };
+        .arg(
+            Arg::with_name("RAW")
+                .help("Read inputs without a y4m header as raw frames of this size")
+                .long("raw")
+                .takes_value(true)
+                .value_name("WIDTHxHEIGHT"),
+        )
+        .arg(
+            Arg::with_name("BIT_DEPTH")
+                .help("Bit depth of raw inputs")
+                .long("bit-depth")
+                .takes_value(true)
+                .possible_values(["8", "10", "12"])
+                .default_value("8")
+                .requires("RAW"),
+        )
+        .arg(
+            Arg::with_name("SUBSAMPLING")
+                .help("Chroma subsampling of raw inputs")
+                .long("subsampling")
+                .takes_value(true)
+                .possible_values(["420", "422", "444", "411"])
+                .default_value("420")
+                .requires("RAW"),
+        )
+        .arg(
+            Arg::with_name("INTERLACED")
+                .help("Raw 4:2:0 inputs have chroma subsampled per field")
+                .long("interlaced")
+                .requires("RAW"),
+        )
+        .arg(
+            Arg::with_name("ASSUME_RANGE")
+                .help("Override the sample range of both inputs")
+                .long("assume-range")
+                .visible_alias("range")
+                .takes_value(true)
+                .possible_values(["limited", "full"]),
+        )
+        .arg(
+            Arg::with_name("RANGE1")
+                .help("Override the sample range of video1 and any --reference")
+                .long("range1")
+                .takes_value(true)
+                .possible_values(["limited", "full"]),
+        )
+        .arg(
+            Arg::with_name("TRANSFORM1")
+                .help("Rotate or flip video1 and any --reference as they are read")
+                .long("transform1")
+                .takes_value(true)
+                .possible_values(["rot90", "rot180", "hflip", "vflip"]),
+        )
+        .arg(
+            Arg::with_name("TRANSFORM2")
+                .help("Rotate or flip video2 as it is read")
+                .long("transform2")
+                .takes_value(true)
+                .possible_values(["rot90", "rot180", "hflip", "vflip"]),
+        )
+        .arg(
+            Arg::with_name("LUT1")
+                .help("Apply a 1D or 3D .cube LUT to the RGB of video1 and any --reference as they are read")
+                .long("lut1")
+                .takes_value(true)
+                .value_name("FILE")
+                .allow_invalid_utf8(true),
+        )
+        .arg(
+            Arg::with_name("LUT2")
+                .help("Apply a 1D or 3D .cube LUT to the RGB of video2 as it is read")
+                .long("lut2")
+                .takes_value(true)
+                .value_name("FILE")
+                .allow_invalid_utf8(true),
+        )
+        .arg(
+            Arg::with_name("CROP")
+                .help(
+                    "Only score the region of width W and height H at X, Y of both inputs, \
+                     after any transform",
+                )
+                .long("crop")
+                .takes_value(true)
+                .value_name("X:Y:W:H"),
+        )
+        .arg(
+            Arg::with_name("DEINTERLACE")
+                .help(
+                    "Deinterlace interlaced inputs: bob scores each field as a frame of its \
+                     own at twice the frame rate, weave scores the fields of a frame together",
+                )
+                .long("deinterlace")
+                .takes_value(true)
+                .possible_values(["bob", "weave", "off"])
+                .default_value("off"),
+        )
+        .arg(
+            Arg::with_name("FORCE_DEINTERLACE")
+                .help("Deinterlace inputs not flagged as interlaced too, as top field first")
+                .long("force-deinterlace")
+                .requires("DEINTERLACE"),
+        )
+        .arg(
+            Arg::with_name("RANGE2")
+                .help("Override the sample range of video2")
+                .long("range2")
+                .takes_value(true)
+                .possible_values(["limited", "full"]),
+        )
+        .arg(
+            Arg::with_name("ASSUME_MATRIX")
+                .help("Override the YUV to RGB matrix of both inputs")
+                .long("assume-matrix")
+                .visible_alias("matrix")
+                .takes_value(true)
+                .possible_values(["bt601", "bt709", "bt2020", "identity"]),
+        )
+        .arg(
+            Arg::with_name("TRANSFER")
+                .help(
+                    "Override the transfer function of both inputs: sdr for the sRGB curve, \
+                     pq for HDR10 with BT.2020 primaries, linear for linear light",
+                )
+                .long("transfer")
+                .takes_value(true)
+                .possible_values(["sdr", "pq", "linear"]),
+        )
+        .arg(
+            Arg::with_name("ASSUME_BIT_DEPTH")
+                .help("Override the bit depth of both inputs, keeping the sample size")
+                .long("assume-bit-depth")
+                .takes_value(true)
+                .possible_values(["8", "10", "12"]),
+        )
+        .arg(
+            Arg::with_name("KL")
+                .help("Lightness weight kL of DE2000 [default: 0.65]")
+                .long("kl")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("KC")
+                .help("Chroma weight kC of DE2000 [default: 1]")
+                .long("kc")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("KH")
+                .help("Hue weight kH of DE2000 [default: 4]")
+                .long("kh")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("STANDARD")
+                .help("Score with the weights of textbook CIEDE2000, kL = kC = kH = 1")
+                .long("standard")
+                .conflicts_with_all(&["KL", "KC", "KH"]),
+        )
+        .arg(
+            Arg::with_name("COMPARE_CONFIGS")
+                .help(
+                    "Also score under a second configuration, given as comma separated \
+                     matrix=bt601|bt709|bt2020, range=limited|full or weights=video|standard",
+                )
+                .long("compare-configs")
+                .takes_value(true)
+                .value_name("CONFIG"),
+        )
+        .arg(
+            Arg::with_name("CHROMA_GRID")
+                .help("Also score chroma at its native resolution, paired with averaged luma")
+                .long("chroma-grid"),
+        )
+        .arg(
+            Arg::with_name("BREAKDOWN")
+                .help("Also print the shares of squared ΔE from lightness, chroma and hue")
+                .long("breakdown"),
+        )
+        .arg(
+            Arg::with_name("SKIN_WEIGHT")
+                .help("Weight the ΔE of likely skin tones in the reference by W when pooling")
+                .long("skin-weight")
+                .takes_value(true)
+                .value_name("W"),
+        )
+        .arg(
+            Arg::with_name("ROI_BOXES")
+                .help("Also print the mean ΔE inside and outside the boxes of each frame listed in FILE")
+                .long("roi-boxes")
+                .takes_value(true)
+                .allow_invalid_utf8(true)
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::with_name("PROJECTION")
+                .help("Weight the ΔE of each row by the area it covers in this projection when pooling")
+                .long("projection")
+                .takes_value(true)
+                .possible_values(["equirect"])
+                .conflicts_with("STEREO"),
+        )
+        .arg(
+            Arg::with_name("SCENE_SUMMARY")
+                .help("Also summarize dark, mid and bright frames of the reference separately")
+                .long("scene-summary"),
+        )
+        .arg(
+            Arg::with_name("BANDING")
+                .help("Also print the mean ΔE over smooth regions of the reference, where banding shows")
+                .long("banding"),
+        )
+        .arg(
+            Arg::with_name("BLOCKINESS")
+                .help("Also print the blockiness of video2 on a grid of SIZE blocks")
+                .long("blockiness")
+                .takes_value(true)
+                .value_name("SIZE")
+                .possible_values(["4", "8"]),
+        )
+        .arg(
+            Arg::with_name("STEREO")
+                .help("Also score the left and right views of side-by-side or top-and-bottom stereo frames")
+                .long("stereo")
+                .takes_value(true)
+                .possible_values(["sbs", "tab"])
+                .conflicts_with("SKIN_WEIGHT"),
+        )
+        .arg(
+            Arg::with_name("CONFORM_TO")
+                .help(
+                    "Convert inputs with different chroma subsampling to the format of \
+                     video1 (ref), video2 (dist) or the one with more chroma (best)",
+                )
+                .long("conform-to")
+                .takes_value(true)
+                .possible_values(["ref", "dist", "best"]),
+        )
+        .arg(
+            Arg::with_name("CONFORM_FILTER")
+                .help(
+                    "Filter for upsampling chroma with --conform-to [default: nearest]. \
+                     Implies --conform-to best when given alone.",
+                )
+                .long("conform-filter")
+                .takes_value(true)
+                .possible_values(["nearest", "bilinear"]),
+        )
+        .arg(
+            Arg::with_name("RESCALE")
+                .help("Scale video2 to the dimensions of video1 when they differ")
+                .long("rescale"),
+        )
+        .arg(
+            Arg::with_name("RESCALE_FILTER")
+                .help(
+                    "Filter for scaling with --rescale [default: bicubic]. \
+                     Implies --rescale when given alone.",
+                )
+                .long("rescale-filter")
+                .takes_value(true)
+                .possible_values(["bilinear", "bicubic", "lanczos"]),
+        )
+        .arg(
+            Arg::with_name("SCALE_COLORSPACE")
+                .help(
+                    "Scale with --rescale in linear light or on the coded samples [default: linear]. \
+                     Implies --rescale when given alone.",
+                )
+                .long("scale-colorspace")
+                .takes_value(true)
+                .possible_values(["linear", "gamma"]),
+        )
+        .arg(
+            Arg::with_name("SIMULATE_CVD")
+                .help(
+                    "Experimental: score as seen by a protanope, deuteranope or tritanope, \
+                     simulating their color vision before ΔE",
+                )
+                .long("simulate-cvd")
+                .takes_value(true)
+                .possible_values(["protan", "deutan", "tritan"]),
+        )
+        .arg(
+            Arg::with_name("RESYNC")
+                .help(
+                    "Skip to the next FRAME marker after a corrupted y4m frame instead of stopping",
+                )
+                .long("resync"),
+        )
+        .arg(
+            Arg::with_name("STDIO_SERVER")
+                .help("Score length-prefixed frame pairs from stdin, writing JSON lines to stdout")
+                .long("stdio-server")
+                .conflicts_with_all(&["video1", "video2"]),
+        )
+        .arg(
+            Arg::with_name("UNIX_SOCKET")
+                .help("Serve the --stdio-server protocol on a Unix domain socket")
+                .long("unix-socket")
+                .takes_value(true)
+                .allow_invalid_utf8(true)
+                .value_name("PATH")
+                .conflicts_with_all(&["video1", "video2", "STDIO_SERVER"]),
+        )
+        .arg(
+            Arg::with_name("SHM")
+                .help("Score frame pairs from a ring buffer in a shared memory file")
+                .long("shm")
+                .takes_value(true)
+                .allow_invalid_utf8(true)
+                .value_name("PATH")
+                .conflicts_with_all(&["video1", "video2", "STDIO_SERVER", "UNIX_SOCKET"]),
+        )
+        .arg(
+            Arg::with_name("PROBE_RAW")
+                .help("Guess the format of a headerless YUV file")
+                .long("probe-raw")
+                .takes_value(true)
+                .allow_invalid_utf8(true)
+                .value_name("PATH")
+                .conflicts_with_all(&["video1", "video2", "STDIO_SERVER", "UNIX_SOCKET", "SHM"]),
+        )
+        .arg(
+            Arg::with_name("REPLAY_TRACE")
+                .help("Score the rows of a trace written by --record-trace again, failing if any ΔE changed")
+                .long("replay-trace")
+                .takes_value(true)
+                .allow_invalid_utf8(true)
+                .value_name("FILE")
+                .conflicts_with_all(&["video1", "video2", "STDIO_SERVER", "UNIX_SOCKET", "SHM"]),
+        )
+        .arg(
+            Arg::with_name("DECODE")
+                .help("Decode compressed inputs, such as MP4, MKV or IVF, with this decoder")
+                .long("decode")
+                .takes_value(true)
+                .possible_values(["ffmpeg"])
+                .conflicts_with("STAGE_TO"),
+        )
+        .arg(
+            Arg::with_name("HWACCEL")
+                .help("Decode with this ffmpeg hardware decoder, such as vaapi, cuda, videotoolbox or auto")
+                .long("hwaccel")
+                .takes_value(true)
+                .value_name("METHOD")
+                .requires("DECODE"),
+        )
+        .arg(
+            Arg::with_name("STREAM1")
+                .help("Decode video stream N of video1, counting from 0, in place of the one ffmpeg picks")
+                .long("stream1")
+                .takes_value(true)
+                .value_name("N")
+                .requires("DECODE"),
+        )
+        .arg(
+            Arg::with_name("STREAM2")
+                .help("Decode video stream N of video2, counting from 0, in place of the one ffmpeg picks")
+                .long("stream2")
+                .takes_value(true)
+                .value_name("N")
+                .requires("DECODE"),
+        )
+        .arg(
+            Arg::with_name("DEC_ARGS1")
+                .help("Pass these space separated arguments to the decoder of video1, before its input")
+                .long("dec-args1")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .value_name("ARGS")
+                .requires("DECODE"),
+        )
+        .arg(
+            Arg::with_name("DEC_ARGS2")
+                .help("Pass these space separated arguments to the decoder of video2, before its input")
+                .long("dec-args2")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .value_name("ARGS")
+                .requires("DECODE"),
+        )
+        .arg(
+            Arg::with_name("FRAME_INFO")
+                .help("Give the timestamp, picture type and key flag ffmpeg decoded each frame with")
+                .long("frame-info")
+                .requires("DECODE"),
+        )
+        .arg(
+            Arg::with_name("STAGE_TO")
+                .help("Copy inputs to DIR as they are read, and read the copies in later runs on the same inputs")
+                .long("stage-to")
+                .takes_value(true)
+                .allow_invalid_utf8(true)
+                .value_name("DIR"),
+        )
+        .arg(
+            Arg::with_name("READ_RETRIES")
+                .help("Retry a failed read of an input file up to N times, resuming where it stopped")
+                .long("read-retries")
+                .takes_value(true)
+                .value_name("N")
+                .default_value("0"),
+        )
+        .arg(
+            Arg::with_name("TRACE_TOLERANCE")
+                .help("Largest change in the ΔE of a pixel accepted by --replay-trace")
+                .long("trace-tolerance")
+                .takes_value(true)
+                .default_value("0.001"),
+        )
+        .subcommand_negates_reqs(true)
+        .args_conflicts_with_subcommands(true)
+        .subcommand(
+            App::new("map-stats")
+                .about("Print the mean and maximum ΔE of each frame of a --map-archive")
+                .arg(
+                    Arg::with_name("archive")
+                        .help("Archive written by --map-archive")
+                        .allow_invalid_utf8(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("FRAMES")
+                        .help("Frames to query, as a comma separated list of frames and ranges such as 0,10-20")
+                        .long("frames")
+                        .takes_value(true)
+                        .value_name("LIST"),
+                )
+                .arg(
+                    Arg::with_name("REGION")
+                        .help("Only query the region of width W and height H at X, Y")
+                        .long("region")
+                        .takes_value(true)
+                        .value_name("X:Y:W:H"),
+                ),
+        )
+        .subcommand(
+            App::new("image")
+                .about("Compare two PNG, PPM, JPEG, OpenEXR, TIFF or DPX images, or GIF, APNG or WebP animations")
+                .arg(
+                    Arg::with_name("image1")
+                        .help("Reference image")
+                        .allow_invalid_utf8(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("image2")
+                        .help("Image compared to image1, of the same size")
+                        .allow_invalid_utf8(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("HEATMAP")
+                        .help("Write the ΔE of each pixel as a colorized PNG")
+                        .long("heatmap")
+                        .takes_value(true)
+                        .allow_invalid_utf8(true)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::with_name("HEATMAP_SCALE")
+                        .help("ΔE drawn as white in the heatmap")
+                        .long("heatmap-scale")
+                        .takes_value(true)
+                        .default_value("10"),
+                )
+                .arg(
+                    Arg::with_name("STANDARD")
+                        .help("Score with the weights of textbook CIEDE2000, kL = kC = kH = 1")
+                        .long("standard"),
+                )
+                .arg(
+                    Arg::with_name("EXPOSURE")
+                        .help("Stops to brighten linear light images, such as OpenEXR, by")
+                        .long("exposure")
+                        .takes_value(true)
+                        .value_name("EV")
+                        .allow_hyphen_values(true)
+                        .default_value("0"),
+                )
+                .arg(
+                    Arg::with_name("WHITE")
+                        .help("Value of linear light images taken as reference white")
+                        .long("white")
+                        .takes_value(true)
+                        .default_value("1"),
+                )
+                .arg(
+                    Arg::with_name("NITS")
+                        .help("Luminance in cd/m² of the value 1 in linear light images, for absolute light")
+                        .long("nits")
+                        .takes_value(true)
+                        .conflicts_with("WHITE"),
+                )
+                .arg(
+                    Arg::with_name("PQ")
+                        .help("Take images otherwise read as sRGB to be PQ encoded BT.2020")
+                        .long("pq"),
+                )
+                .arg(
+                    Arg::with_name("OCIO")
+                        .help("OpenColorIO config converting the images to the scoring space, with ociobakelut")
+                        .long("ocio")
+                        .takes_value(true)
+                        .allow_invalid_utf8(true)
+                        .value_name("CONFIG")
+                        .requires("INPUT_COLORSPACE"),
+                )
+                .arg(
+                    Arg::with_name("INPUT_COLORSPACE")
+                        .help("Color space of the config the images are in")
+                        .long("input-colorspace")
+                        .takes_value(true)
+                        .value_name("NAME")
+                        .requires("OCIO"),
+                )
+                .arg(
+                    Arg::with_name("SCORING_COLORSPACE")
+                        .help("sRGB encoded color space of the config the images are scored in")
+                        .long("scoring-colorspace")
+                        .takes_value(true)
+                        .value_name("NAME")
+                        .default_value("color_picking"),
+                ),
+        )
+        .get_matches();
+    let max_retries: usize = match matches.value_of("READ_RETRIES").unwrap().parse() {
+        Ok(max_retries) => max_retries,
+        Err(_) => {
+            eprintln!("Read retries must be a whole number");
+            exit(1);
+        }
+    };
+    let stage_dir = matches.value_of_os("STAGE_TO").map(Path::new);
+    if let Some(dir) = stage_dir {
+        if let Err(e) = fs::create_dir_all(dir) {
+            eprintln!("Failed to create {}: {}", dir.display(), e);
+            exit(1);
+        }
+    }
+    let mut read_retries = Vec::new();
+    let mut staged = Vec::new();
+    let mut stdin_taken = false;
+    let mut decode_failed = Vec::new();
+    let mut frame_logs = [None, None];
+    let sign = matches.value_of_os("SIGN").map(|path| {
+        let path = Path::new(path);
+        match sign::read_key(path) {
+            Ok(key) => key,
+            Err(e) => {
+                eprintln!("Failed to read key {}: {}", path.display(), e);
+                exit(1);
+            }
+        }
+    });
+    let signing = sign.is_some();
+    let arrow_stdout = matches.value_of_os("ARROW") == Some(OsStr::new("-"));
+    if arrow_stdout && matches.is_present("JSON") {
+        eprintln!("--arrow - can not share stdout with --json");
+        exit(1);
+    }
+    let decode = matches.is_present("DECODE");
+    let frame_info = matches.is_present("FRAME_INFO");
+    let decode_options = |name: &str| {
+        let (args, stream) = match name {
+            "video1" => (matches.value_of("DEC_ARGS1"), matches.value_of("STREAM1")),
+            "video2" => (matches.value_of("DEC_ARGS2"), matches.value_of("STREAM2")),
+            _ => (None, None),
+        };
+        FfmpegOptions {
+            input_args: args.map_or_else(Vec::new, |args| {
+                args.split_whitespace().map(OsString::from).collect()
+            }),
+            hwaccel: matches.value_of("HWACCEL").map(str::to_owned),
+            stream: stream.map(|stream| {
+                stream.parse().unwrap_or_else(|_| {
+                    eprintln!("Stream must be a non-negative integer");
+                    exit(1);
+                })
+            }),
+            frame_info: frame_info && (name == "video1" || name == "video2"),
+        }
+    };
+    let mut open_reader = |path: &OsStr, name: String| -> Box<dyn Read> {
+        // Piped input is read once as it comes, so it is neither retried
+        // nor staged
+        if path == OsStr::new("-") {
+            if stdin_taken {
+                eprintln!("Only one input can be read from stdin");
+                exit(1);
+            }
+            stdin_taken = true;
+            if !decode {
+                return Box::new(io::stdin());
+            }
+        }
+        // The decoder reads the input itself, so it is neither retried nor
+        // staged
+        if decode {
+            return match FfmpegReader::spawn(path, &decode_options(&name)) {
+                Ok(mut reader) => {
+                    decode_failed.push(reader.failed());
+                    if let Some(log) = reader.take_frame_log() {
+                        frame_logs[(name == "video2") as usize] = Some(log);
+                    }
+                    Box::new(reader)
+                }
+                Err(e) => {
+                    eprintln!("{}: {}", name, e);
+                    exit(1);
+                }
+            };
+        }
+        let staged_path = stage_dir.and_then(|dir| stage::staged_path(dir, Path::new(path)).ok());
+        if let Some(staged_path) = staged_path.as_ref().filter(|path| path.is_file()) {
+            eprintln!("{}: Reading staged copy {}", name, staged_path.display());
+            let reader = open_input(staged_path.as_os_str(), max_retries);
+            read_retries.push((name, reader.retries()));
+            return Box::new(reader);
+        }
+        let reader = open_input(path, max_retries);
+        read_retries.push((name.clone(), reader.retries()));
+        match staged_path {
+            Some(staged_path) => match StageReader::new(reader, staged_path) {
+                Ok(reader) => {
+                    staged.push((name, reader.staged()));
+                    Box::new(reader)
+                }
+                Err(e) => {
+                    eprintln!("Failed to stage {}: {}", Path::new(path).display(), e);
+                    exit(1);
+                }
+            },
+            None => Box::new(reader),
+        }
+    };
+    let mut input_digests = Vec::new();
+    let mut open = |path: &OsStr, name: String| -> Box<dyn Read> {
+        let reader = open_reader(path, name.clone());
+        if !signing {
+            return reader;
+        }
+        let reader = DigestReader::new(reader);
+        input_digests.push((name, reader.digest()));
+        Box::new(reader)
+    };
+    // --frames is --skip and --limit in one
+    let (skip, limit) = match matches.value_of("FRAMES") {
+        Some(spec) => match parse_frame_list(spec)[..] {
+            [(first, last)] => (first, Some(last - first + 1)),
+            _ => {
+                eprintln!("Frames must be a single range FIRST-LAST");
+                exit(1);
+            }
+        },
+        None => (
+            matches.value_of("SKIP").map_or(0, |skip| {
+                skip.parse().unwrap_or_else(|_| {
+                    eprintln!("Skipped frames must be a non-negative integer");
+                    exit(1);
+                })
+            }),
+            matches
+                .value_of("LIMIT")
+                .map(|v| v.parse().expect("Limit must be a positive number")),
+        ),
+    };
+    // Piped and decoded inputs have no length to go by
+    let input_len = |name| {
+        matches
+            .value_of_os(name)
+            .filter(|&path| path != OsStr::new("-") && !decode)
+            .and_then(|path| fs::metadata(path).ok())
+            .filter(|metadata| metadata.is_file())
+            .map(|metadata| metadata.len())
+    };
+    let (input1_len, input2_len) = (input_len("video1"), input_len("video2"));
+    let input1 = matches
+        .value_of_os("video1")
+        .map(|path| open(path, "video1".to_owned()));
+    let input2 = matches
+        .value_of_os("video2")
+        .map(|path| open(path, "video2".to_owned()));
+    let references = matches
+        .values_of_os("REFERENCE")
+        .map_or(Vec::new(), |paths| {
+            paths
+                .enumerate()
+                .map(|(i, path)| open(path, format!("reference{}", i + 1)))
+                .collect()
+        });
+    CliOptions {
+        input1,
+        input2,
+        references,
+        read_retries,
+        staged,
+        sign: sign.map(|key| {
+            (
+                key,
+                PathBuf::from(matches.value_of_os("SIGNATURE").unwrap()),
+            )
+        }),
+        input_digests,
+        decode_failed,
+        frame_logs,
+        summary: matches.is_present("SUMMARY"),
+        progress: matches.is_present("PROGRESS"),
+        input_lens: [input1_len, input2_len],
+        limit,
+        skip,
+        realtime: matches
+            .value_of("REALTIME")
+            .map(|fps| match fps.parse::<f64>() {
+                Ok(fps) if fps > 0. && fps.is_finite() => fps,
+                _ => {
+                    eprintln!("Realtime framerate must be a positive number");
+                    exit(1);
+                }
+            }),
+        rolling: matches.is_present("ROLLING"),
+        on_drop: matches.value_of("ON_DROP").map(|command| {
+            let threshold = match matches.value_of("ALERT_BELOW").unwrap().parse::<f64>() {
+                Ok(threshold) if threshold.is_finite() => threshold,
+                _ => {
+                    eprintln!("Alert threshold must be a number");
+                    exit(1);
+                }
+            };
+            let frames = match matches.value_of("ALERT_FRAMES").unwrap().parse::<usize>() {
+                Ok(frames) if frames > 0 => frames,
+                _ => {
+                    eprintln!("Alert frames must be a positive integer");
+                    exit(1);
+                }
+            };
+            (command.to_owned(), threshold, frames)
+        }),
+        tolerate_drops: matches.value_of("TOLERATE_DROPS").map_or(0, |drops| {
+            drops.parse().unwrap_or_else(|_| {
+                eprintln!("Tolerated drops must be a non-negative integer");
+                exit(1);
+            })
+        }),
+        dup_aware: matches.is_present("DUP_AWARE"),
+        auto_align: matches.is_present("AUTO_ALIGN").then(|| {
+            match matches.value_of("ALIGN_WINDOW").unwrap().parse() {
+                Ok(window) if window > 0 => window,
+                _ => {
+                    eprintln!("Align window must be a positive integer");
+                    exit(1);
+                }
+            }
+        }),
+        order_check: !matches.is_present("NO_ORDER_CHECK"),
+        simd: match matches.value_of("SIMD").unwrap() {
+            "off" => SimdLevel::Off,
+            "native" => SimdLevel::Avx512,
+            level => {
+                let level = match level {
+                    "neon" => SimdLevel::Neon,
+                    "rvv" => SimdLevel::Rvv,
+                    "sse4.1" => SimdLevel::Sse41,
+                    "avx2" => SimdLevel::Avx2,
+                    _ => SimdLevel::Avx512,
+                };
+                if !level.supported() {
+                    eprintln!("This CPU does not support --simd {}", level_name(level));
+                    exit(1);
+                }
+                level
+            }
+        },
+        stdio_server: matches.is_present("STDIO_SERVER"),
+        unix_socket: matches.value_of_os("UNIX_SOCKET").map(PathBuf::from),
+        shm: matches.value_of_os("SHM").map(PathBuf::from),
+        probe_raw: matches.value_of_os("PROBE_RAW").map(PathBuf::from),
+        replay_trace: matches.value_of_os("REPLAY_TRACE").map(PathBuf::from),
+        image: matches
+            .subcommand_matches("image")
+            .map(|matches| ImageOptions {
+                image1: PathBuf::from(matches.value_of_os("image1").unwrap()),
+                image2: PathBuf::from(matches.value_of_os("image2").unwrap()),
+                heatmap: matches.value_of_os("HEATMAP").map(PathBuf::from),
+                heatmap_scale: parse_heatmap_scale(matches.value_of("HEATMAP_SCALE").unwrap()),
+                ksub: if matches.is_present("STANDARD") {
+                    K_SUB_STANDARD
+                } else {
+                    K_SUB
+                },
+                ocio: matches.value_of_os("OCIO").map(|config| {
+                    let input = matches.value_of("INPUT_COLORSPACE").unwrap();
+                    let output = matches.value_of("SCORING_COLORSPACE").unwrap();
+                    ocio::bake_lut(Path::new(config), input, output).unwrap_or_else(|e| {
+                        eprintln!("Failed to convert {} to {}: {}", input, output, e);
+                        exit(1);
+                    })
+                }),
+                linear_scale: parse_linear_scale(matches),
+                pq: matches.is_present("PQ"),
+            }),
+        map_stats: matches
+            .subcommand_matches("map-stats")
+            .map(|matches| MapStatsOptions {
+                archive: PathBuf::from(matches.value_of_os("archive").unwrap()),
+                frames: matches.value_of("FRAMES").map(parse_frame_list),
+                region: matches.value_of("REGION").map(parse_region),
+            }),
+        trace_tolerance: match matches.value_of("TRACE_TOLERANCE").unwrap().parse() {
+            Ok(tolerance) if tolerance >= 0. => tolerance,
+            _ => {
+                eprintln!("Trace tolerance must be a number of at least 0");
+                exit(1);
+            }
+        },
+        metric: match matches.value_of("METRIC").unwrap() {
+            "de76" => Metric::De76,
+            "de94" => Metric::De94,
+            "cmc" => Metric::Cmc,
+            "itp" => Metric::Itp,
+            _ => Metric::De2000,
+        },
+        approx_trig: matches.value_of("TRIG").unwrap() == "approx",
+        fast_int: matches.is_present("FAST_INT"),
+        precision_f64: matches.value_of("PRECISION").unwrap() == "f64",
+        autotune: matches.is_present("AUTOTUNE"),
+        tile_width: match matches.value_of("TILE_WIDTH").unwrap().parse() {
+            Ok(tile_width) if tile_width > 0 && tile_width % 8 == 0 => tile_width,
+            _ => {
+                eprintln!("Tile width must be a positive multiple of 8");
+                exit(1);
+            }
+        },
+        raw: matches.value_of("RAW").map(|dimensions| {
+            parse_raw_format(
+                dimensions,
+                matches.value_of("BIT_DEPTH").unwrap(),
+                matches.value_of("SUBSAMPLING").unwrap(),
+                matches.is_present("INTERLACED"),
+            )
+        }),
+        assume_range: matches.value_of("ASSUME_RANGE").map(parse_range),
+        range1: matches.value_of("RANGE1").map(parse_range),
+        range2: matches.value_of("RANGE2").map(parse_range),
+        transform1: matches.value_of("TRANSFORM1").map(parse_transform),
+        transform2: matches.value_of("TRANSFORM2").map(parse_transform),
+        lut1: matches.value_of_os("LUT1").map(read_lut),
+        lut2: matches.value_of_os("LUT2").map(read_lut),
+        crop: matches.value_of("CROP").map(parse_crop),
+        deinterlace: match matches.value_of("DEINTERLACE").unwrap() {
+            "bob" => Deinterlace::Bob,
+            "weave" => Deinterlace::Weave,
+            _ => Deinterlace::Off,
+        },
+        force_deinterlace: matches.is_present("FORCE_DEINTERLACE"),
+        assume_matrix: matches
+            .value_of("ASSUME_MATRIX")
+            .map(|matrix| match matrix {
+                "bt601" => Matrix::Bt601,
+                "bt709" => Matrix::Bt709,
+                "bt2020" => Matrix::Bt2020,
+                "identity" => Matrix::Identity,
+                &_ => unreachable!(),
+            }),
+        transfer: matches.value_of("TRANSFER").map(|transfer| match transfer {
+            "sdr" => Transfer::Sdr,
+            "pq" => Transfer::Pq,
+            "linear" => Transfer::Linear,
+            &_ => unreachable!(),
+        }),
+        assume_bit_depth: matches
+            .value_of("ASSUME_BIT_DEPTH")
+            .map(|bit_depth| bit_depth.parse().unwrap()),
+        // CMC takes its l and c in place of kL and kC
+        ksub: if matches.value_of("METRIC") == Some("cmc") {
+            parse_cmc_ratio(matches.value_of("CMC_RATIO").unwrap())
+        } else if matches.is_present("STANDARD") {
+            K_SUB_STANDARD
+        } else {
+            KSubArgs {
+                l: parse_k(&matches, "KL", K_SUB.l),
+                c: parse_k(&matches, "KC", K_SUB.c),
+                h: parse_k(&matches, "KH", K_SUB.h),
+            }
+        },
+        compare: matches
+            .value_of("COMPARE_CONFIGS")
+            .map(parse_compare_config),
+        chroma_grid: matches.is_present("CHROMA_GRID"),
+        breakdown: matches.is_present("BREAKDOWN"),
+        scene_summary: matches.is_present("SCENE_SUMMARY"),
+        banding: matches.is_present("BANDING"),
+        blockiness: matches
+            .value_of("BLOCKINESS")
+            .map(|block_size| block_size.parse().unwrap()),
+        roi_boxes: matches.value_of_os("ROI_BOXES").map(|path| {
+            let path = Path::new(path);
+            RoiBoxes::load(path).unwrap_or_else(|e| {
+                eprintln!("Failed to read boxes {}: {}", path.display(), e);
+                exit(1);
+            })
+        }),
+        stereo: matches.value_of("STEREO").map(|stereo| match stereo {
+            "sbs" => Stereo::SideBySide,
+            _ => Stereo::TopAndBottom,
+        }),
+        equirect: matches.value_of("PROJECTION") == Some("equirect"),
+        skin_weight: matches
+            .value_of("SKIN_WEIGHT")
+            .map(|weight| match weight.parse() {
+                Ok(weight) if weight > 0. && f32::is_finite(weight) => weight,
+                _ => {
+                    eprintln!("Skin weight must be a positive number");
+                    exit(1);
+                }
+            }),
+        resync: matches.is_present("RESYNC"),
+        json: matches.is_present("JSON"),
+        text: !matches.is_present("JSON") && !arrow_stdout,
+        table: matches.is_present("TABLE"),
+        heatmaps: matches.value_of_os("DUMP_HEATMAPS").map(PathBuf::from),
+        heatmap_scale: parse_heatmap_scale(matches.value_of("HEATMAP_SCALE").unwrap()),
+        dump_lab: matches.value_of_os("DUMP_LAB").map(PathBuf::from),
+        dump_lab_frames: matches.value_of("DUMP_LAB_FRAMES").map(parse_frame_list),
+        dump_lab_format: match matches.value_of("DUMP_LAB_FORMAT").unwrap() {
+            "u16" if matches.value_of("DUMP_STAGE") != Some("lab") => {
+                eprintln!("--dump-lab-format u16 can only hold the lab stage");
+                exit(1);
+            }
+            "u16" => LabFormat::U16,
+            _ => LabFormat::F32,
+        },
+        dump_stage: match matches.value_of("DUMP_STAGE").unwrap() {
+            "rgb" => Stage::Rgb,
+            "linear" => Stage::Linear,
+            "xyz" => Stage::Xyz,
+            _ => Stage::Lab,
+        },
+        record_trace: matches.value_of_os("RECORD_TRACE").map(PathBuf::from),
+        record_trace_frames: matches
+            .value_of("RECORD_TRACE_FRAMES")
+            .map(parse_frame_list),
+        map_output: matches.value_of_os("MAP_OUTPUT").map(PathBuf::from),
+        map_bit_depth: matches.value_of("MAP_BIT_DEPTH").unwrap().parse().unwrap(),
+        map_scale: match matches.value_of("MAP_SCALE").unwrap().parse() {
+            Ok(scale) if scale > 0. && f32::is_finite(scale) => scale,
+            _ => {
+                eprintln!("Map scale must be a positive number");
+                exit(1);
+            }
+        },
+        map_archive: matches.value_of_os("MAP_ARCHIVE").map(PathBuf::from),
+        csv: matches.value_of_os("CSV").map(PathBuf::from),
+        arrow: matches.value_of_os("ARROW").map(PathBuf::from),
+        features: matches.value_of_os("FEATURES").map(PathBuf::from),
+        baseline: matches
+            .value_of_os("BASELINE")
+            .filter(|_| !matches.is_present("UPDATE_BASELINE"))
+            .map(|path| {
+                let path = Path::new(path);
+                Baseline::load(path).unwrap_or_else(|e| {
+                    eprintln!("Failed to read baseline {}: {}", path.display(), e);
+                    exit(1);
+                })
+            }),
+        update_baseline: matches
+            .value_of_os("BASELINE")
+            .filter(|_| matches.is_present("UPDATE_BASELINE"))
+            .map(PathBuf::from),
+        note: matches.value_of("NOTE").map(str::to_owned),
+        verdict_threshold: matches.value_of("VERDICT_FROM_THRESHOLD").map(|threshold| {
+            match threshold.parse::<f64>() {
+                Ok(threshold) if !threshold.is_nan() => threshold,
+                _ => {
+                    eprintln!("Verdict threshold must be a number");
+                    exit(1);
+                }
+            }
+        }),
+        max_regression: match matches.value_of("MAX_REGRESSION").unwrap().parse() {
+            Ok(max) if max >= 0. => max,
+            _ => {
+                eprintln!("Maximum regression must be a non-negative number");
+                exit(1);
+            }
+        },
+        rating: match matches.value_of("RATING_BANDS") {
+            Some(spec) => Some(parse_rating_bands(spec)),
+            None if matches.is_present("RATING") => Some(parse_rating_bands(DEFAULT_RATING_BANDS)),
+            None => None,
+        },
+        threads: matches
+            .value_of("THREADS")
+            .map(|threads| match threads.parse() {
+                Ok(threads) if threads > 0 => threads,
+                _ => {
+                    eprintln!("Thread count must be a positive integer");
+                    exit(1);
+                }
+            }),
+        vision: match matches.value_of("SIMULATE_CVD") {
+            Some("protan") => Vision::Protanopia,
+            Some("deutan") => Vision::Deuteranopia,
+            Some("tritan") => Vision::Tritanopia,
+            _ => Vision::Normal,
+        },
+        conform_to: match matches.value_of("CONFORM_TO") {
+            Some("ref") => Some(ConformTo::Reference),
+            Some("dist") => Some(ConformTo::Distorted),
+            Some("best") => Some(ConformTo::Best),
+            Some(_) => unreachable!(),
+            None if matches.is_present("CONFORM_FILTER") => Some(ConformTo::Best),
+            None => None,
+        },
+        conform_filter: match matches.value_of("CONFORM_FILTER") {
+            Some("bilinear") => ChromaFilter::Bilinear,
+            _ => ChromaFilter::Nearest,
+        },
+        rescale: match matches.value_of("RESCALE_FILTER") {
+            Some("bilinear") => Some(ScaleFilter::Bilinear),
+            Some("lanczos") => Some(ScaleFilter::Lanczos),
+            Some(_) => Some(ScaleFilter::Bicubic),
+            None if matches.is_present("RESCALE") || matches.is_present("SCALE_COLORSPACE") => {
+                Some(ScaleFilter::Bicubic)
+            }
+            None => None,
+        },
+        scale_colorspace: match matches.value_of("SCALE_COLORSPACE") {
+            Some("gamma") => ScaleLight::Gamma,
+            _ => ScaleLight::Linear,
+        },
+    }
+}
+
+// Parses frame numbers and inclusive FIRST-LAST ranges separated by commas.
+fn parse_frame_list(spec: &str) -> Vec<(usize, usize)> {
+    spec.split(',')
+        .map(|item| {
+            let (first, last) = item.split_once('-').unwrap_or((item, item));
+            match (first.trim().parse(), last.trim().parse()) {
+                (Ok(first), Ok(last)) if first <= last => (first, last),
+                _ => {
+                    eprintln!("Invalid frame or range: {}", item);
+                    exit(1);
+                }
+            }
+        })
+        .collect()
+}
+
+// Whether `frame` is in a list parsed by `parse_frame_list`, taking no list
+// as all frames.
+fn in_frame_list(frames: &Option<Vec<(usize, usize)>>, frame: usize) -> bool {
+    frames.as_ref().is_none_or(|frames| {
+        frames
+            .iter()
+            .any(|&(first, last)| (first..=last).contains(&frame))
+    })
+}
+
+fn parse_region(spec: &str) -> Region {
+    let fields: Vec<_> = spec.split(':').map(str::parse::<usize>).collect();
+    match fields[..] {
+        [Ok(x), Ok(y), Ok(w), Ok(h)] if w > 0 && h > 0 => (x, y, w, h),
+        _ => {
+            eprintln!("Region must be X:Y:W:H with a positive width and height");
+            exit(1);
+        }
+    }
+}
+
+fn parse_crop(spec: &str) -> Crop {
+    let fields: Vec<_> = spec.split(':').map(str::parse::<usize>).collect();
+    match fields[..] {
+        [Ok(x), Ok(y), Ok(width), Ok(height)] if width > 0 && height > 0 => Crop {
+            x,
+            y,
+            width,
+            height,
+        },
+        _ => {
+            eprintln!("Crop must be X:Y:W:H with a positive width and height");
+            exit(1);
+        }
+    }
+}
+
+// Reads one of the DE2000 weights, which must be positive.
+fn parse_k(matches: &ArgMatches, name: &str, default: f32) -> f32 {
+    match matches.value_of(name).map(str::parse::<f32>) {
+        None => default,
+        Some(Ok(k)) if k > 0. && k.is_finite() => k,
+        Some(_) => {
+            eprintln!("--{} must be a positive number", name.to_lowercase());
+            exit(1);
+        }
+    }
+}
+
+// Names that open a device instead of a file in any directory on Windows
+fn is_reserved_device_name(path: &Path) -> bool {
+    let name = match path.file_name().and_then(OsStr::to_str) {
+        Some(name) => name,
+        None => return false,
+    };
+    // The extension is ignored, as is trailing whitespace before it
+    let stem = name
+        .split('.')
+        .next()
+        .unwrap()
+        .trim_end()
+        .to_ascii_uppercase();
+    match stem.as_str() {
+        "CON" | "PRN" | "AUX" | "NUL" | "CONIN$" | "CONOUT$" => true,
+        _ => {
+            (stem.starts_with("COM") || stem.starts_with("LPT"))
+                && stem.len() == 4
+                && stem.as_bytes()[3].is_ascii_digit()
+                && stem.as_bytes()[3] != b'0'
+        }
+    }
+}
+
+// Reads the factor taking linear light images to 1 for white.
+fn parse_linear_scale(matches: &ArgMatches) -> f32 {
+    let parse = |name: &str, positive: bool| match matches.value_of(name).map(str::parse::<f32>) {
+        Some(Ok(value)) if value.is_finite() && (value > 0. || !positive) => Some(value),
+        None => None,
+        _ => {
+            eprintln!("Invalid --{}", name.to_ascii_lowercase());
+            exit(1);
+        }
+    };
+    let exposure = parse("EXPOSURE", false).unwrap().exp2();
+    match parse("NITS", true) {
+        // Absolute light is relative to the reference white that PQ light is
+        // also taken relative to
+        Some(nits) => exposure * nits / REFERENCE_WHITE_NITS,
+        None => exposure / parse("WHITE", true).unwrap(),
+    }
+}
+
+fn parse_heatmap_scale(scale: &str) -> f32 {
+    match scale.parse() {
+        Ok(scale) if scale > 0. && f32::is_finite(scale) => scale,
+        _ => {
+            eprintln!("Heatmap scale must be a positive number");
+            exit(1);
+        }
+    }
+}
+
+fn compare_images(options: &ImageOptions) {
+    let read = |path: &Path| match image::read_frames(path) {
+        Ok(frames) => frames,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", path.display(), e);
+            exit(1);
+        }
+    };
+    let (mut frames1, mut frames2) = (read(&options.image1), read(&options.image2));
+    for (image, _) in frames1.iter_mut().chain(&mut frames2) {
+        match image.transfer {
+            Transfer::Linear => {
+                for pixel in &mut image.pixels {
+                    *pixel = pixel.map(|c| c * options.linear_scale);
+                }
+            }
+            _ if options.pq => image.transfer = Transfer::Pq,
+            _ => {}
+        }
+        if let Some(lut) = &options.ocio {
+            for pixel in &mut image.pixels {
+                *pixel = lut.apply(*pixel);
+            }
+            image.transfer = Transfer::Sdr;
+        }
+    }
+    let (image1, image2) = (&frames1[0].0, &frames2[0].0);
+    if (image1.width, image1.height) != (image2.width, image2.height) {
+        eprintln!(
+            "Images differ in size, {}x{} and {}x{}",
+            image1.width, image1.height, image2.width, image2.height
+        );
+        exit(1);
+    }
+    let (width, height) = (image1.width, image1.height);
+    // Pairs of frames shown at the same time, and for how long
+    let pairs = if frames1.len() == 1 && frames2.len() == 1 {
+        vec![(0, 0, 1.)]
+    } else {
+        let durations1: Vec<f64> = frames1.iter().map(|&(_, duration)| duration).collect();
+        let durations2: Vec<f64> = frames2.iter().map(|&(_, duration)| duration).collect();
+        let (total1, total2) = (
+            durations1.iter().sum::<f64>(),
+            durations2.iter().sum::<f64>(),
+        );
+        println!(
+            "Frames: {} and {}, {:.0} ms and {:.0} ms",
+            frames1.len(),
+            frames2.len(),
+            total1,
+            total2
+        );
+        if frames1.len() > 1 && frames2.len() > 1 && total1 != total2 {
+            eprintln!(
+                "Warning - Durations do not match: {:.0} ms != {:.0} ms. Scoring the first {:.0} ms.",
+                total1,
+                total2,
+                total1.min(total2)
+            );
+        }
+        pair_frames(&durations1, &durations2)
+    };
+    let mut delta_e_sum = vec![0f64; width * height];
+    let (mut weighted, mut total_duration, mut max) = (0f64, 0f64, 0f32);
+    for (i, &(frame1, frame2, duration)) in pairs.iter().enumerate() {
+        let (image1, image2) = (&frames1[frame1].0, &frames2[frame2].0);
+        let delta_e = image_delta_e(image1, image2, options.ksub);
+        let mean =
+            delta_e.iter().map(|&delta_e| delta_e as f64).sum::<f64>() / delta_e.len() as f64;
+        if pairs.len() > 1 {
+            println!(
+                "{:08}: {:.4}, frames {} and {} for {:.0} ms",
+                i,
+                delta_e_to_score(mean),
+                frame1,
+                frame2,
+                duration
+            );
+        }
+        for (sum, &delta_e) in delta_e_sum.iter_mut().zip(&delta_e) {
+            *sum += delta_e as f64 * duration;
+        }
+        weighted += mean * duration;
+        total_duration += duration;
+        max = delta_e.iter().fold(max, |max, &delta_e| max.max(delta_e));
+    }
+    let mean = weighted / total_duration;
+    println!("Mean ΔE: {:.4}", mean);
+    println!("Max ΔE: {:.4}", max);
+    println!("Score: {:.4}", delta_e_to_score(mean));
+    if let Some(path) = &options.heatmap {
+        let delta_e: Vec<f32> = delta_e_sum
+            .iter()
+            .map(|&sum| (sum / total_duration) as f32)
+            .collect();
+        let result = heatmap::write_heatmap(path, width, height, &delta_e, options.heatmap_scale);
+        if let Err(e) = result {
+            eprintln!("Failed to write {}: {}", path.display(), e);
+            exit(1);
+        }
+    }
+}
+
+// ΔE of each pixel of two images of the same size.
+fn image_delta_e(image1: &image::Image, image2: &image::Image, ksub: KSubArgs) -> Vec<f32> {
+    let lab = |transfer, rgb: &[f32; 3]| match transfer {
+        Transfer::Sdr => rgb_to_lab(rgb),
+        Transfer::Pq => pq_rgb_to_lab_with(rgb, &BT2020_TO_XYZ),
+        Transfer::Linear => linear_rgb_to_lab_with(rgb, &SRGB_TO_XYZ),
+    };
+    let (transfer1, transfer2) = (image1.transfer, image2.transfer);
+    image1
+        .pixels
+        .par_iter()
+        .zip(&image2.pixels)
+        .map(|(rgb1, rgb2)| DE2000::new(lab(transfer1, rgb1), lab(transfer2, rgb2), ksub))
+        .collect()
+}
+
+// Frames of two animations with the frame `durations`, shown at the same
+// time, and for how long. A still image is shown for the whole of the other,
+// and an animation longer than the other is cut short.
+fn pair_frames(durations1: &[f64], durations2: &[f64]) -> Vec<(usize, usize, f64)> {
+    let end = |durations: &[f64], i: usize, start: f64| match durations.len() {
+        1 => f64::INFINITY,
+        _ => start + durations[i],
+    };
+    let mut pairs = Vec::new();
+    let (mut i, mut j, mut time) = (0, 0, 0.);
+    let (mut start1, mut start2) = (0., 0.);
+    while i < durations1.len() && j < durations2.len() {
+        let (end1, end2) = (end(durations1, i, start1), end(durations2, j, start2));
+        let until = end1.min(end2);
+        if until.is_infinite() {
+            break;
+        }
+        if until > time {
+            pairs.push((i, j, until - time));
+        }
+        time = until;
+        // Ends a microsecond apart are taken to be the same, so fractional
+        // durations do not leave slivers
+        if end1 - until < 1e-3 {
+            i += 1;
+            start1 = end1;
+        }
+        if end2 - until < 1e-3 {
+            j += 1;
+            start2 = end2;
+        }
+    }
+    pairs
+}
+
+fn map_stats(options: &MapStatsOptions) {
+    let path = &options.archive;
+    let mut archive = match MapArchiveReader::open(path) {
+        Ok(archive) => archive,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", path.display(), e);
+            exit(1);
+        }
+    };
+    let region = options
+        .region
+        .unwrap_or((0, 0, archive.width, archive.height));
+    let (x, y, w, h) = region;
+    if x + w > archive.width || y + h > archive.height {
+        eprintln!(
+            "Region {}:{}:{}:{} is not inside the {}x{} frames",
+            x, y, w, h, archive.width, archive.height
+        );
+        exit(1);
+    }
+    let frames: Vec<usize> = match &options.frames {
+        Some(ranges) => ranges
+            .iter()
+            .flat_map(|&(first, last)| first..=last.min(archive.frames.saturating_sub(1)))
+            .collect(),
+        None => (0..archive.frames).collect(),
+    };
+    let (mut sum, mut max) = (0f64, 0f32);
+    for &frame in &frames {
+        let stats = match archive.region_stats(frame, region) {
+            Ok(stats) => stats,
+            Err(e) => {
+                eprintln!("Failed to read {}: {}", path.display(), e);
+                exit(1);
+            }
+        };
+        println!("{:08}: mean {:.4}, max {:.4}", frame, stats.mean, stats.max);
+        sum += stats.mean;
+        max = max.max(stats.max);
+    }
+    if !frames.is_empty() {
+        println!(
+            "Total: mean {:.4}, max {:.4} ({} frames)",
+            sum / frames.len() as f64,
+            max,
+            frames.len()
+        );
+    }
+}
+
+fn open_input(path: &OsStr, max_retries: usize) -> RetryReader {
+    let path = Path::new(path);
+    if cfg!(windows) && is_reserved_device_name(path) {
+        eprintln!(
+            "{} is a reserved device name on Windows, not a file",
+            path.display()
+        );
+        exit(1);
+    }
+    // Paths longer than MAX_PATH only open with the \\?\ prefix, which
+    // canonicalize adds. UNC shares get \\?\UNC\.
+    let resolved = if cfg!(windows) && path.as_os_str().len() >= 260 {
+        fs::canonicalize(path).unwrap_or_else(|_| path.to_owned())
+    } else {
+        path.to_owned()
+    };
+    match File::open(&resolved) {
+        Ok(file) => RetryReader::new(file, resolved, max_retries),
+        Err(e) => {
+            eprintln!("Failed to open {}: {}", path.display(), e);
+            exit(1);
+        }
+    }
+}
+
+fn parse_raw_format(
+    dimensions: &str,
+    bit_depth: &str,
+    subsampling: &str,
+    interlaced: bool,
+) -> FrameInfo {
+    let (width, height) = match dimensions
+        .split_once('x')
+        .map(|(w, h)| (w.parse::<usize>(), h.parse::<usize>()))
+    {
+        Some((Ok(width), Ok(height))) if width > 0 && height > 0 => (width, height),
+        _ => {
+            eprintln!("Raw dimensions must be WIDTHxHEIGHT, got {}", dimensions);
+            exit(1);
+        }
+    };
+    let (xdec, ydec) = match subsampling {
+        "420" => (1, 1),
+        "422" => (1, 0),
+        "444" => (0, 0),
+        "411" => (2, 0),
+        &_ => unreachable!(),
+    };
+    FrameInfo {
+        width,
+        height,
+        bit_depth: bit_depth.parse().unwrap(),
+        xdec,
+        ydec,
+        interlaced,
+        matrix: Matrix::Bt709,
+        range: Range::Limited,
+        transfer: Transfer::Sdr,
+        vision: Vision::Normal,
+    }
+}
+
+// y4m streams are recognized by their signature. Anything else is read as raw
+// frames when a raw format was given.
+fn open_source<'a, R: BufRead>(
+    input: &'a mut R,
+    raw: Option<FrameInfo>,
+    resync: bool,
+    name: &str,
+) -> Box<dyn FrameSource + 'a> {
+    let is_y4m = match input.fill_buf() {
+        Ok(buf) => buf.starts_with(b"YUV4MPEG2 "),
+        Err(e) => {
+            eprintln!("{}: {}", name, e);
+            exit(1);
+        }
+    };
+    let source: io::Result<Box<dyn FrameSource + 'a>> = match raw {
+        Some(info) if !is_y4m => RawSource::new(input, info).map(|source| Box::new(source) as _),
+        None if !is_y4m => {
+            eprintln!("{}: Not a y4m stream, use --raw for headerless input", name);
+            exit(1);
+        }
+        _ => Y4mSource::new(input).map(|mut source| {
+            source.set_resync(resync);
+            Box::new(source) as _
+        }),
+    };
+    source.unwrap_or_else(|e| {
+        eprintln!("{}: {}", name, e);
+        exit(1);
+    })
+}
+
+// Splits each frame of `source` into its fields for --deinterlace bob.
+// Returns whether --deinterlace applies to `source`.
+fn deinterlace<'a>(
+    cli: &CliOptions,
+    source: Box<dyn FrameSource + 'a>,
+) -> (Box<dyn FrameSource + 'a>, bool) {
+    let applies =
+        cli.deinterlace != Deinterlace::Off && (cli.force_deinterlace || source.info().interlaced);
+    if applies && cli.deinterlace == Deinterlace::Bob {
+        (Box::new(BobSource::new(source)), true)
+    } else {
+        (source, applies)
+    }
+}
+
+fn next_frame<'a>(source: &'a mut dyn FrameSource, name: &str) -> Option<Frame<'a>> {
+    match source.next_frame() {
+        Ok(frame) => frame,
+        Err(e) => {
+            eprintln!("{}: {}", name, e);
+            None
+        }
+    }
+}
+
+fn subsampling_name(xdec: usize, ydec: usize) -> &'static str {
+    match (xdec, ydec) {
+        (1, 1) => "420",
+        (1, 0) => "422",
+        (0, 0) => "444",
+        (2, 0) => "411",
+        _ => unreachable!(),
+    }
+}
+
+fn range_name(range: Range) -> &'static str {
+    match range {
+        Range::Limited => "limited",
+        Range::Full => "full",
+    }
+}
+
+fn matrix_name(matrix: Matrix) -> &'static str {
+    match matrix {
+        Matrix::Bt601 => "bt601",
+        Matrix::Bt709 => "bt709",
+        Matrix::Bt2020 => "bt2020",
+        Matrix::Identity => "identity",
+    }
+}
+
+fn weights_name(ksub: KSubArgs) -> String {
+    if ksub == K_SUB {
+        "video".to_owned()
+    } else if ksub == K_SUB_STANDARD {
+        "standard".to_owned()
+    } else {
+        format!("kl={},kc={},kh={}", ksub.l, ksub.c, ksub.h)
+    }
+}
+
+fn metric_name(metric: Metric) -> &'static str {
+    match metric {
+        Metric::De2000 => "de2000",
+        Metric::De76 => "de76",
+        Metric::De94 => "de94",
+        Metric::Cmc => "cmc",
+        Metric::Itp => "itp",
+    }
+}
+
+fn transfer_name(transfer: Transfer) -> &'static str {
+    match transfer {
+        Transfer::Sdr => "sdr",
+        Transfer::Pq => "pq",
+        Transfer::Linear => "linear",
+    }
+}
+
+fn parse_cmc_ratio(ratio: &str) -> KSubArgs {
+    let parsed: Option<Vec<f32>> = ratio.split(':').map(|k| k.parse().ok()).collect();
+    match parsed.as_deref() {
+        Some(&[l, c]) if l > 0. && c > 0. && l.is_finite() && c.is_finite() => {
+            KSubArgs { l, c, h: 1. }
+        }
+        _ => {
+            eprintln!("CMC ratio must be given as L:C, like 2:1, got {}", ratio);
+            exit(1);
+        }
+    }
+}
+
+fn parse_compare_config(spec: &str) -> CompareConfig {
+    let mut config = CompareConfig::default();
+    for setting in spec.split(',') {
+        match setting.split_once('=') {
+            Some(("matrix", "bt601")) => config.matrix = Some(Matrix::Bt601),
+            Some(("matrix", "bt709")) => config.matrix = Some(Matrix::Bt709),
+            Some(("matrix", "bt2020")) => config.matrix = Some(Matrix::Bt2020),
+            Some(("matrix", "identity")) => config.matrix = Some(Matrix::Identity),
+            Some(("range", "limited")) => config.range = Some(Range::Limited),
+            Some(("range", "full")) => config.range = Some(Range::Full),
+            Some(("weights", "video")) => config.weights = Some(K_SUB),
+            Some(("weights", "standard")) => config.weights = Some(K_SUB_STANDARD),
+            _ => {
+                eprintln!("Invalid --compare-configs setting: {}", setting);
+                exit(1);
+            }
+        }
+    }
+    config
+}
+
+fn parse_range(range: &str) -> Range {
+    match range {
+        "limited" => Range::Limited,
+        "full" => Range::Full,
+        &_ => unreachable!(),
+    }
+}
+
+fn parse_transform(transform: &str) -> Transform {
+    match transform {
+        "rot90" => Transform::Rot90,
+        "rot180" => Transform::Rot180,
+        "hflip" => Transform::HFlip,
+        "vflip" => Transform::VFlip,
+        &_ => unreachable!(),
+    }
+}
+
+fn read_lut(path: &OsStr) -> (PathBuf, Lut) {
+    let path = PathBuf::from(path);
+    match fs::read_to_string(&path) {
+        Ok(text) => match Lut::parse(&text) {
+            Ok(lut) => (path, lut),
+            Err(e) => {
+                eprintln!("Invalid LUT {}: {}", path.display(), e);
+                exit(1);
+            }
+        },
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", path.display(), e);
+            exit(1);
+        }
+    }
+}
+
+// Luma signatures of the first `len` frames of an input, as scored.
+fn luma_signatures(
+    queue: &mut FrameQueue,
+    ingest: &Ingest,
+    info: &FrameInfo,
+    neutral: Option<&[u8]>,
+    len: usize,
+) -> Vec<Vec<f64>> {
+    let mut bufs = Default::default();
+    let mut signatures = Vec::new();
+    for n in 1..=len {
+        match queue.peek(n) {
+            Some(frame) => {
+                let frame = ingest.frame(frame, neutral, &mut bufs);
+                signatures.push(luma_grid(info, &frame, SIGNATURE_CELLS));
+            }
+            None => break,
+        }
+    }
+    signatures
+}
+
+fn transform_name(transform: Transform) -> &'static str {
+    match transform {
+        Transform::Rot90 => "rot90",
+        Transform::Rot180 => "rot180",
+        Transform::HFlip => "hflip",
+        Transform::VFlip => "vflip",
+    }
+}
+
+fn scale_filter_name(filter: ScaleFilter) -> &'static str {
+    match filter {
+        ScaleFilter::Bilinear => "bilinear",
+        ScaleFilter::Bicubic => "bicubic",
+        ScaleFilter::Lanczos => "lanczos",
+    }
+}
+
+// Overrides header values that are known to be wrong. The bit depth can only
+// change between depths stored in the same number of bytes. `range`
+// overrides the range of this input alone.
+fn apply_assumptions(cli: &CliOptions, info: &mut FrameInfo, range: Option<Range>, name: &str) {
+    if let Some(range) = range.or(cli.assume_range) {
+        info.range = range;
+    }
+    if let Some(matrix) = cli.assume_matrix {
+        info.matrix = matrix;
+    }
+    if let Some(transfer) = cli.transfer {
+        info.transfer = transfer;
+    }
+    // Weaving a frame that is not marked interlaced reads its chroma per field
+    if cli.force_deinterlace && cli.deinterlace == Deinterlace::Weave {
+        info.interlaced = true;
+    }
+    if let Some(bit_depth) = cli.assume_bit_depth {
+        if (bit_depth > 8) != (info.bit_depth > 8) {
+            eprintln!(
+                "{}: Cannot assume {}-bit samples for {}-bit input",
+                name, bit_depth, info.bit_depth
+            );
+            exit(1);
+        }
+        info.bit_depth = bit_depth;
+    }
+}
+
+/// Per-row parts of the sums behind a frame's scores.
+#[derive(Clone, Copy, Default)]
+struct RowSums {
+    sum: f64,
+    compare: f64,
+    /// Sum of the --skin-weight weights
+    weight: f64,
+    /// Sum of the ΔE over the --banding mask, and the pixels in it
+    smooth: f64,
+    smooth_pixels: f64,
+}
+
+/// Buffers reused between the rows scored by one thread.
+struct RowScratch {
+    tile: Vec<f32>,
+    weights: Vec<f32>,
+    mask: Vec<f32>,
+    histogram: Option<DeltaEHistogram>,
+    /// ΔE of each pixel of the band
+    map: Option<Vec<f32>>,
+    /// ΔE of a row against one of the --reference inputs
+    reference: Vec<f32>,
+}
+
+/// With --tolerate-drops, how far a frame's score must fall below the last
+/// one's to be checked for a drop, and rise at a later frame of video1 to be
+/// realigned to it.
+const DROP_STEP: f64 = 3.;
+/// Frames of video1 looked ahead for a better match.
+const DROP_WINDOW: usize = 3;
+
+/// With --dup-aware, the mean ΔE below which a frame of video2 is taken to
+/// repeat the one before it, a just noticeable difference.
+const DUP_DELTA_E: f64 = 1.;
+
+/// Luminance in cd/m² of reference white, that of BT.2408, for --nits.
+const REFERENCE_WHITE_NITS: f32 = 203.;
+
+/// Cells across and down of the luma grid --auto-align compares frames by.
+const SIGNATURE_CELLS: usize = 4;
+
+/// Buckets of --scene-summary with the mean reference luma they end before
+const SCENES: [(&str, f64); 3] = [("Dark", 0.25), ("Mid", 0.65), ("Bright", f64::INFINITY)];
+
+const DEFAULT_RATING_BANDS: &str = "40=excellent,35=good,30=fair,-inf=poor";
+
+// Returns the bands sorted from the highest minimum score down.
+fn parse_rating_bands(spec: &str) -> Vec<(f64, String)> {
+    let mut bands: Vec<(f64, String)> = spec
+        .split(',')
+        .map(|band| match band.split_once('=') {
+            Some((min, label)) if !label.is_empty() => match min.parse::<f64>() {
+                Ok(min) if !min.is_nan() => (min, label.to_owned()),
+                _ => {
+                    eprintln!("Invalid rating band minimum: {}", band);
+                    exit(1);
+                }
+            },
+            _ => {
+                eprintln!("Rating bands must be given as MIN=LABEL, got {}", band);
+                exit(1);
+            }
+        })
+        .collect();
+    bands.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+    bands
+}
+
+/// The label of the highest band `score` reaches, if any.
+fn rating(bands: &[(f64, String)], score: f64) -> Option<&str> {
+    bands
+        .iter()
+        .find(|(min, _)| score >= *min)
+        .map(|(_, label)| label.as_str())
+}
+
+fn level_name(level: SimdLevel) -> &'static str {
+    match level {
+        SimdLevel::Off => "scalar",
+        SimdLevel::Neon => "neon",
+        SimdLevel::Rvv => "rvv",
+        SimdLevel::Sse41 => "sse4.1",
+        SimdLevel::Avx2 => "avx2",
+        SimdLevel::Avx512 => "avx512",
+    }
+}
+
+/// Describes a decoded frame for the text output, as `pts 1001 P` with `key`
+/// after key frames.
+fn decoded_text(frame: &DecodedFrame) -> String {
+    let pts = frame.pts.map_or("none".to_owned(), |pts| pts.to_string());
+    let key = if frame.key_frame { " key" } else { "" };
+    format!("pts {} {}{}", pts, frame.pict_type, key)
+}
+
+fn json_decoded(frame: &DecodedFrame) -> String {
+    json_object(&[
+        (
+            "pts",
+            frame.pts.map_or("null".to_owned(), |pts| pts.to_string()),
+        ),
+        (
+            "pts_time",
+            frame.pts_time.map_or("null".to_owned(), json_f64),
+        ),
+        ("pict_type", json_str(&frame.pict_type.to_string())),
+        ("key_frame", frame.key_frame.to_string()),
+    ])
+}
+
+fn json_weights(ksub: KSubArgs) -> String {
+    json_object(&[
+        ("l", ksub.l.to_string()),
+        ("c", ksub.c.to_string()),
+        ("h", ksub.h.to_string()),
+    ])
+}
+
+// Converts every pixel of `frame` on to `stage` of the conversion to Lab, as
+// scored.
+fn frame_stage(info: &FrameInfo, frame: &Frame, stage: Stage, out: &mut Vec<[f32; 3]>) {
+    out.resize(info.width * info.height, [0.; 3]);
+    out.par_chunks_exact_mut(info.width)
+        .enumerate()
+        .for_each(|(i, row)| row_stage(info, &info.row(frame, i), stage, row));
+}
+
+// Scores of the left and right views of the ΔE `map` of a stereo frame.
+fn eye_scores(map: &[f32], width: usize, height: usize, stereo: Stereo) -> [f64; 2] {
+    let mut sums = [0f64; 2];
+    let mut pixels = [0usize; 2];
+    for (y, row) in map.chunks_exact(width).enumerate() {
+        for (x, &delta_e) in row.iter().enumerate() {
+            let eye = match stereo {
+                Stereo::SideBySide => x >= width / 2,
+                Stereo::TopAndBottom => y >= height / 2,
+            } as usize;
+            sums[eye] += delta_e as f64;
+            pixels[eye] += 1;
+        }
+    }
+    [0, 1].map(|eye| delta_e_to_score(sums[eye] / pixels[eye] as f64))
+}
+
+// Cells of a --table row, with scores formatted the same in every locale.
+fn table_cells(
+    frame: String,
+    time: String,
+    scores: &[f64],
+    histogram: &DeltaEHistogram,
+    bands: Option<&[(f64, String)]>,
+) -> Vec<String> {
+    let mut cells = vec![
+        frame,
+        time,
+        format!("{:.4}", scores[0]),
+        format!("{:.4}", histogram.percentile(0.95)),
+        format!("{:.4}", histogram.max()),
+    ];
+    cells.extend(scores[1..].iter().map(|score| format!("{:.4}", score)));
+    if let Some(bands) = bands {
+        cells.push(rating(bands, scores[0]).unwrap_or("-").to_owned());
+    }
+    cells
+}
+
+fn table_row<S: AsRef<str>>(cells: &[S], widths: &[usize]) -> String {
+    let cells: Vec<String> = cells
+        .iter()
+        .zip(widths)
+        .map(|(cell, &width)| format!("{:>width$}", cell.as_ref(), width = width))
+        .collect();
+    cells.join("  ")
+}
+
+// Formats a line of scores, followed by the rating of the first if `bands`
+// are given.
+fn format_scores(scores: &[f64], bands: Option<&[(f64, String)]>) -> String {
+    let mut line: Vec<String> = scores.iter().map(|s| format!("{:2.4}", s)).collect();
+    if let Some(bands) = bands {
+        line.push(rating(bands, scores[0]).unwrap_or("-").to_owned());
+    }
+    line.join(" ")
+}
+
+fn main() {
+    let mut cli = parse_cli();
+    if let Some(threads) = cli.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+            .unwrap();
+    }
+    if let Some(options) = &cli.image {
+        compare_images(options);
+        return;
+    }
+    if let Some(options) = &cli.map_stats {
+        map_stats(options);
+        return;
+    }
+    if let Some(path) = &cli.replay_trace {
+        let report = File::open(path)
+            .and_then(|file| replay_trace(BufReader::new(file), cli.simd, cli.trace_tolerance));
+        match report {
+            Ok(report) => {
+                println!(
+                    "Rows: {}, mismatched: {}, max difference: {}",
+                    report.rows, report.mismatched_rows, report.max_difference
+                );
+                if report.mismatched_rows > 0 {
+                    exit(1);
+                }
+            }
+            Err(e) => {
+                eprintln!("Failed to replay {}: {}", path.display(), e);
+                exit(1);
+            }
+        }
+        return;
+    }
+    if let Some(path) = cli.probe_raw {
+        let candidates = match probe::probe(&path) {
+            Ok(candidates) => candidates,
+            Err(e) => {
+                eprintln!("Failed to probe {}: {}", path.display(), e);
+                exit(1);
+            }
+        };
+        if candidates.is_empty() {
+            eprintln!("No common format matches the size of {}", path.display());
+            exit(1);
+        }
+        for c in candidates.iter().take(5) {
+            println!(
+                "{}x{} {}-bit {}, {} frames: {:.2}",
+                c.info.width,
+                c.info.height,
+                c.info.bit_depth,
+                subsampling_name(c.info.xdec, c.info.ydec),
+                c.frames,
+                c.cost
+            );
+        }
+        let best = &candidates[0].info;
+        println!(
+            "Suggested: --raw {}x{} --bit-depth {} --subsampling {}",
+            best.width,
+            best.height,
+            best.bit_depth,
+            subsampling_name(best.xdec, best.ydec)
+        );
+        return;
+    }
+    if cli.stdio_server {
+        let stdin = io::stdin();
+        let stdout = io::stdout();
+        if let Err(e) = server::serve(stdin.lock(), stdout.lock(), cli.simd) {
+            eprintln!("Server error: {}", e);
+            exit(1);
+        }
+        return;
+    }
+    if let Some(path) = cli.shm {
+        let stdout = io::stdout();
+        if let Err(e) = shm::consume(&path, stdout.lock(), cli.simd) {
+            eprintln!("Shared memory error: {}: {}", path.display(), e);
+            exit(1);
+        }
+        return;
+    }
+    if let Some(path) = cli.unix_socket {
+        #[cfg(unix)]
+        {
+            if let Err(e) = server::listen_unix(&path, cli.simd) {
+                eprintln!("Server error: {}: {}", path.display(), e);
+                exit(1);
+            }
+            return;
+        }
+        #[cfg(not(unix))]
+        {
+            eprintln!(
+                "Cannot listen on {}: Unix domain sockets are not supported on this platform",
+                path.display()
+            );
+            exit(1);
+        }
+    }
+    let mut input1 = BufReader::new(cli.input1.take().unwrap());
+    let mut input2 = BufReader::new(cli.input2.take().unwrap());
+    let mut frame_logs = std::mem::take(&mut cli.frame_logs);
+    let (mut source1, deinterlaced1) = deinterlace(
+        &cli,
+        open_source(&mut input1, cli.raw, cli.resync, "video1"),
+    );
+    let (mut source2, deinterlaced2) = deinterlace(
+        &cli,
+        open_source(&mut input2, cli.raw, cli.resync, "video2"),
+    );
+    let mut reference_inputs: Vec<_> = cli.references.drain(..).map(BufReader::new).collect();
+    let mut references: Vec<_> = reference_inputs
+        .iter_mut()
+        .enumerate()
+        .map(|(n, input)| {
+            let name = format!("reference{}", n + 1);
+            let (source, _) = deinterlace(&cli, open_source(input, cli.raw, cli.resync, &name));
+            (source, name)
+        })
+        .collect();
+    // Source and converted formats of an input whose chroma is conformed
+    let mut conform1 = None;
+    let mut conform2 = None;
+    let mut conform_note = None;
+    // Source and scaled formats of video2 with --rescale
+    let mut rescale2 = None;
+    let mut rescale_note = None;
+    // Formats read from inputs that are rotated or flipped, or have a LUT
+    // applied
+    let mut transform1 = None;
+    let mut transform2 = None;
+    // Formats of the inputs before --crop
+    let mut crop1 = None;
+    let mut crop2 = None;
+    let lut1;
+    let lut2;
+    let (info1, info2) = {
+        let mut info1 = source1.info();
+        let mut info2 = source2.info();
+        apply_assumptions(&cli, &mut info1, cli.range1, "video1");
+        apply_assumptions(&cli, &mut info2, cli.range2, "video2");
+        info1.vision = cli.vision;
+        info2.vision = cli.vision;
+        lut1 = cli.lut1.as_ref().map(|(_, lut)| (info1, lut));
+        lut2 = cli.lut2.as_ref().map(|(_, lut)| (info2, lut));
+        for (info, transform, applied, name) in [
+            (&mut info1, cli.transform1, &mut transform1, "video1"),
+            (&mut info2, cli.transform2, &mut transform2, "video2"),
+        ] {
+            if let Some(transform) = transform {
+                *applied = Some((*info, transform));
+                *info = transform.apply_to(info).unwrap_or_else(|e| {
+                    eprintln!("{}: {}", name, e);
+                    exit(1);
+                });
+            }
+        }
+        if (info1.width, info1.height) != (info2.width, info2.height) {
+            let filter = match cli.rescale {
+                Some(filter) => filter,
+                None => {
+                    eprintln!(
+                        "Video dimensions do not match: {}x{} != {}x{}. Use --rescale to scale video2.",
+                        info1.width, info1.height, info2.width, info2.height
+                    );
+                    exit(1);
+                }
+            };
+            let scaled = scaled_info(&info2, &info1).unwrap_or_else(|e| {
+                eprintln!("video2: {}", e);
+                exit(1);
+            });
+            let light = match cli.scale_colorspace {
+                ScaleLight::Linear => "linear light",
+                ScaleLight::Gamma => "gamma",
+            };
+            let note = format!(
+                "video2 {}x{} to {}x{}, {} in {}",
+                info2.width,
+                info2.height,
+                info1.width,
+                info1.height,
+                scale_filter_name(filter),
+                light
+            );
+            if cli.text {
+                println!("Rescale: {}", note);
+            }
+            rescale_note = Some(note);
+            rescale2 = Some((info2, scaled, filter, cli.scale_colorspace));
+            info2 = scaled;
+        }
+        if info1.bit_depth != info2.bit_depth {
+            eprintln!(
+                "Bit depths do not match: {} != {}",
+                info1.bit_depth, info2.bit_depth
+            );
+            exit(1);
+        }
+        if (info1.xdec, info1.ydec) != (info2.xdec, info2.ydec) {
+            let to_reference = match cli.conform_to {
+                Some(ConformTo::Reference) => true,
+                Some(ConformTo::Distorted) => false,
+                Some(ConformTo::Best) => info1.plane_lens().1 >= info2.plane_lens().1,
+                None => {
+                    eprintln!(
+                        "Sub sampling does not match: {} != {}. Use --conform-to to convert one input.",
+                        subsampling_name(info1.xdec, info1.ydec),
+                        subsampling_name(info2.xdec, info2.ydec)
+                    );
+                    exit(1);
+                }
+            };
+            let (from, to, conform, name) = if to_reference {
+                (&mut info2, info1, &mut conform2, "video2")
+            } else {
+                (&mut info1, info2, &mut conform1, "video1")
+            };
+            let conformed = FrameInfo {
+                xdec: to.xdec,
+                ydec: to.ydec,
+                interlaced: to.interlaced,
+                ..*from
+            };
+            let mut note = format!(
+                "{} {} to {}",
+                name,
+                subsampling_name(from.xdec, from.ydec),
+                subsampling_name(to.xdec, to.ydec)
+            );
+            if cli.conform_filter == ChromaFilter::Bilinear {
+                note.push_str(", bilinear");
+            }
+            if cli.text {
+                println!("Conform: {}", note);
+            }
+            conform_note = Some(note);
+            *conform = Some((*from, conformed));
+            *from = conformed;
+        }
+        if let Some(crop) = cli.crop {
+            for (info, cropped) in [(&mut info1, &mut crop1), (&mut info2, &mut crop2)] {
+                *cropped = Some((*info, crop));
+                *info = crop.apply_to(info).unwrap_or_else(|e| {
+                    eprintln!("Crop: {}", e);
+                    exit(1);
+                });
+            }
+        }
+        (info1, info2)
+    };
+    let info = info1;
+    // Against a grayscale input, the chroma of the other is ignored too
+    let mut grayscale_note = None;
+    let neutral_chroma = match (source1.grayscale(), source2.grayscale()) {
+        (true, false) | (false, true) => {
+            let note = if source1.grayscale() {
+                "video1, chroma of video2 ignored"
+            } else {
+                "video2, chroma of video1 ignored"
+            };
+            if cli.text {
+                println!("Grayscale: {}", note);
+            }
+            grayscale_note = Some(note);
+            Some(info1.neutral_chroma())
+        }
+        _ => None,
+    };
+    let neutral_chroma = neutral_chroma.as_deref();
+    // Further references are converted like video1, so must share its format
+    let source_info1 = transform1.map_or(
+        conform1.map_or(crop1.map_or(info1, |(from, _)| from), |(from, _)| from),
+        |(from, _)| from,
+    );
+    for (source, name) in &references {
+        let mut info = source.info();
+        apply_assumptions(&cli, &mut info, cli.range1, name);
+        info.vision = cli.vision;
+        if info != source_info1 {
+            eprintln!("{}: Format does not match video1", name);
+            exit(1);
+        }
+    }
+    if let (Some(framerate1), Some(framerate2)) = (source1.framerate(), source2.framerate()) {
+        if framerate1.0 * framerate2.1 != framerate2.0 * framerate1.1 {
+            eprintln!(
+                "Warning - Framerates do not match: {}/{} != {}/{}",
+                framerate1.0, framerate1.1, framerate2.0, framerate2.1
+            );
+        }
+    }
 
-                        #[target_feature(enable = "avx2")]
-                        unsafe fn load_chroma(chunk: &[u8]) -> __m256 {
-                            let tmp = _mm_cvtsi32_si128(*(chunk.as_ptr() as *const i32));
-                            _mm256_cvtepi32_ps(_mm256_cvtepu8_epi32(_mm_unpacklo_epi8(tmp, tmp)))
+    let (width, height) = (info.width, info.height);
+    let split = match cli.stereo {
+        Some(Stereo::SideBySide) => width,
+        Some(Stereo::TopAndBottom) => height,
+        None => 2,
+    };
+    if split < 2 {
+        eprintln!(
+            "Frames of {}x{} are too small to split into two views",
+            width, height
+        );
+        exit(1);
+    }
+    // Formats and weights of the second column of --compare-configs
+    let compare = cli.compare.map(|config| {
+        let mut compare_info1 = info1;
+        let mut compare_info2 = info2;
+        for info in [&mut compare_info1, &mut compare_info2] {
+            info.matrix = config.matrix.unwrap_or(info.matrix);
+            info.range = config.range.unwrap_or(info.range);
+        }
+        (
+            compare_info1,
+            compare_info2,
+            config.weights.unwrap_or(cli.ksub),
+        )
+    });
+    if cli.fast_int
+        && (get_delta_e_row_fn_fixed(info.bit_depth, info.xdec, false).is_none()
+            || [info1, info2]
+                .iter()
+                .chain(compare.iter().flat_map(|(info1, info2, _)| [info1, info2]))
+                .any(|info| (info.matrix, info.range) != (Matrix::Bt709, Range::Limited)))
+    {
+        eprintln!("--fast-int only supports 8-bit 4:2:0 and 4:2:2 in limited range BT.709");
+        exit(1);
+    }
+    if cli.precision_f64 && (cli.metric != Metric::De2000 || cli.approx_trig || cli.fast_int) {
+        eprintln!("--precision f64 only supports --metric de2000 with exact trigonometry");
+        exit(1);
+    }
+    if cli.metric != Metric::De2000
+        && (cli.approx_trig || cli.fast_int || cli.breakdown || cli.chroma_grid)
+    {
+        eprintln!(
+            "--metric {} does not support --trig approx, --fast-int, --breakdown or --chroma-grid",
+            metric_name(cli.metric)
+        );
+        exit(1);
+    }
+    let mut assumed = Vec::new();
+    if let Some(range) = cli.assume_range {
+        assumed.push(format!("range {}", range_name(range)));
+    }
+    for (range, name) in [(cli.range1, "video1"), (cli.range2, "video2")] {
+        if let Some(range) = range {
+            assumed.push(format!("{} range {}", name, range_name(range)));
+        }
+    }
+    if let Some(matrix) = cli.assume_matrix {
+        assumed.push(format!("matrix {}", matrix_name(matrix)));
+    }
+    if let Some(transfer) = cli.transfer {
+        assumed.push(format!("transfer {}", transfer_name(transfer)));
+    }
+    if let Some(bit_depth) = cli.assume_bit_depth {
+        assumed.push(format!("bit depth {}", bit_depth));
+    }
+    if !assumed.is_empty() && cli.text {
+        println!("Assumed: {}", assumed.join(", "));
+    }
+    let transforms: Vec<String> = [(cli.transform1, "video1"), (cli.transform2, "video2")]
+        .iter()
+        .filter_map(|&(transform, name)| {
+            transform.map(|transform| format!("{} {}", name, transform_name(transform)))
+        })
+        .collect();
+    if !transforms.is_empty() && cli.text {
+        println!("Transform: {}", transforms.join(", "));
+    }
+    let luts: Vec<String> = [(&cli.lut1, "video1"), (&cli.lut2, "video2")]
+        .iter()
+        .filter_map(|&(lut, name)| {
+            lut.as_ref().map(|(path, lut)| {
+                let kind = if lut.three_d { "3D" } else { "1D" };
+                format!("{} {} ({} {})", name, path.display(), kind, lut.size)
+            })
+        })
+        .collect();
+    if !luts.is_empty() && cli.text {
+        println!("LUT: {}", luts.join(", "));
+    }
+    let crop = cli
+        .crop
+        .map(|crop| format!("{}:{}:{}:{}", crop.x, crop.y, crop.width, crop.height));
+    if let Some(crop) = &crop {
+        if cli.text {
+            println!("Crop: {}", crop);
+        }
+    }
+    let deinterlaced: Vec<String> = [(deinterlaced1, "video1"), (deinterlaced2, "video2")]
+        .iter()
+        .filter(|&&(deinterlaced, _)| deinterlaced)
+        .map(|&(_, name)| {
+            let mode = if cli.deinterlace == Deinterlace::Bob {
+                "bob"
+            } else {
+                "weave"
+            };
+            format!("{} {}", name, mode)
+        })
+        .collect();
+    if !deinterlaced.is_empty() && cli.text {
+        println!("Deinterlace: {}", deinterlaced.join(", "));
+    }
+    if cli.vision != Vision::Normal && cli.text {
+        println!("Simulated: {}", format!("{:?}", cli.vision).to_lowercase());
+    }
+    if cli.metric == Metric::Cmc && cli.text {
+        println!("Metric: cmc {}:{}", cli.ksub.l, cli.ksub.c);
+    } else if cli.metric != Metric::De2000 && cli.text {
+        println!("Metric: {}", metric_name(cli.metric));
+    }
+    if let Some(note) = cli.note.as_ref().filter(|_| cli.text) {
+        println!("Note: {}", note);
+    }
+    if let Some((compare_info, _, ksub)) = compare.filter(|_| cli.text) {
+        println!(
+            "Compare: matrix {}, range {}, weights {}",
+            matrix_name(compare_info.matrix),
+            range_name(compare_info.range),
+            weights_name(ksub)
+        );
+    }
+    let select_row_fn = |simd| {
+        if cli.precision_f64 {
+            get_delta_e_row_fn_f64(info.bit_depth, info.xdec)
+        } else if cli.metric != Metric::De2000 {
+            get_delta_e_row_fn_metric(cli.metric, info.bit_depth, info.xdec, simd)
+        } else if cli.fast_int {
+            get_delta_e_row_fn_fixed(info.bit_depth, info.xdec, cli.approx_trig).unwrap()
+        } else if cli.approx_trig {
+            get_delta_e_row_fn_approx(info.bit_depth, info.xdec, simd)
+        } else {
+            get_delta_e_row_fn(info.bit_depth, info.xdec, simd)
+        }
+    };
+    let mut simd = cli.simd;
+    let mut delta_e_row_fn = select_row_fn(simd);
+    // Backend picked by --autotune on the first frame, and the pool of the
+    // threads it picked
+    let mut tuning = None;
+    let mut tuned_pool: Option<rayon::ThreadPool> = None;
+    let mut num_frames: usize = 0;
+    // The main score, then the --compare-configs, --chroma-grid, --breakdown,
+    // --banding, --blockiness, --stereo and --roi-boxes columns, named as in
+    // --json
+    let mut columns = vec!["score"];
+    if compare.is_some() {
+        columns.push("compare_score");
+    }
+    if cli.chroma_grid {
+        columns.push("chroma_grid_score");
+    }
+    if cli.breakdown {
+        columns.extend(["lightness_share", "chroma_share", "hue_share"]);
+    }
+    if cli.banding {
+        columns.push("banding_delta_e");
+    }
+    if cli.blockiness.is_some() {
+        columns.push("blockiness");
+    }
+    if cli.stereo.is_some() {
+        columns.extend(["left_score", "right_score"]);
+    }
+    if cli.roi_boxes.is_some() {
+        columns.extend(["roi_delta_e", "background_delta_e"]);
+    }
+    let mut totals = vec![0f64; columns.len()];
+    let bands = cli.rating.as_deref();
+    let mut csv = cli.csv.as_ref().map(|path| {
+        let mut csv = match File::create(path) {
+            Ok(file) => io::BufWriter::new(file),
+            Err(e) => {
+                eprintln!("Failed to create {}: {}", path.display(), e);
+                exit(1);
+            }
+        };
+        if let Err(e) = writeln!(csv, "frame,mean_delta_e,{}", columns.join(",")) {
+            eprintln!("Failed to write {}: {}", path.display(), e);
+            exit(1);
+        }
+        (csv, path)
+    });
+    let mut arrow = cli
+        .arrow
+        .as_ref()
+        .map(|path| match ArrowWriter::new(path, &columns) {
+            Ok(writer) => (writer, path),
+            Err(e) => {
+                eprintln!("Failed to create {}: {}", path.display(), e);
+                exit(1);
+            }
+        });
+    let mut features = cli
+        .features
+        .as_ref()
+        .map(|path| match FeatureWriter::new(path) {
+            Ok(writer) => (writer, path),
+            Err(e) => {
+                eprintln!("Failed to create {}: {}", path.display(), e);
+                exit(1);
+            }
+        });
+    // Scores of every frame, for --json, --baseline and --update-baseline
+    let mut frame_scores = Vec::new();
+    // Totals and frame counts of the --scene-summary buckets
+    let mut scenes = vec![(vec![0f64; totals.len()], 0usize); SCENES.len()];
+    let mut row_sums = vec![RowSums::default(); height];
+    // Enough bands to keep every thread busy if some rows are slower
+    let mut band_height = height.div_ceil(4 * rayon::current_num_threads());
+    let (mut tile_width, skin_weight, banding, ksub) = (
+        cli.tile_width.min(width),
+        cli.skin_weight,
+        cli.banding,
+        cli.ksub,
+    );
+    // Rows of an equirectangular frame cover an area proportional to the
+    // cosine of their latitude, as in WS-PSNR
+    let latitude_weights: Option<Vec<f64>> = cli.equirect.then(|| {
+        (0..height)
+            .map(|i| ((i as f64 + 0.5 - height as f64 / 2.) * PI / height as f64).cos())
+            .collect()
+    });
+    let table = cli.table && cli.text;
+    // Per-pixel ΔE of the frame, for --dump-heatmaps, --map-output,
+    // --map-archive, --stereo and --roi-boxes
+    let keep_map = cli.heatmaps.is_some()
+        || cli.map_output.is_some()
+        || cli.map_archive.is_some()
+        || !references.is_empty()
+        || cli.stereo.is_some()
+        || cli.roi_boxes.is_some();
+    let mut map: Vec<f32> = Vec::new();
+    for dir in [cli.heatmaps.as_ref(), cli.dump_lab.as_ref()]
+        .iter()
+        .flatten()
+    {
+        if let Err(e) = fs::create_dir_all(dir) {
+            eprintln!("Failed to create {}: {}", dir.display(), e);
+            exit(1);
+        }
+    }
+    let mut lab = Vec::new();
+    let mut trace = cli.record_trace.as_ref().map(|path| {
+        match File::create(path).and_then(|file| TraceRecorder::new(io::BufWriter::new(file))) {
+            Ok(recorder) => (recorder, path),
+            Err(e) => {
+                eprintln!("Failed to create {}: {}", path.display(), e);
+                exit(1);
+            }
+        }
+    });
+    let trace_kernel = match cli.metric {
+        Metric::De2000 if cli.precision_f64 => TraceKernel::Reference,
+        Metric::De2000 if cli.fast_int => TraceKernel::Fixed {
+            approx_trig: cli.approx_trig,
+        },
+        Metric::De2000 => TraceKernel::De2000 {
+            approx_trig: cli.approx_trig,
+        },
+        metric => TraceKernel::Metric(metric),
+    };
+    let mut trace_row = Vec::new();
+    // Histogram of every frame, for the total of --table
+    let mut total_histogram = DeltaEHistogram::new();
+    let framerate = source1.framerate();
+    // Raw input has no framerate, so takes the y4m default
+    let mut map_output = cli.map_output.as_ref().map(|path| {
+        let writer = heatmap::Y4mMapWriter::new(
+            path,
+            width,
+            height,
+            framerate.unwrap_or((25, 1)),
+            cli.map_bit_depth,
+            cli.map_scale,
+        );
+        match writer {
+            Ok(writer) => (writer, path),
+            Err(e) => {
+                eprintln!("Failed to create {}: {}", path.display(), e);
+                exit(1);
+            }
+        }
+    });
+    let mut map_archive =
+        cli.map_archive
+            .as_ref()
+            .map(|path| match MapArchiveWriter::new(path, width, height) {
+                Ok(writer) => (writer, path),
+                Err(e) => {
+                    eprintln!("Failed to create {}: {}", path.display(), e);
+                    exit(1);
+                }
+            });
+    // Columns of --table: the frame, its time, the score with the 95th
+    // percentile and maximum ΔE, then any other scores
+    let table_header: Vec<&str> = ["frame", "time", "score", "p95", "max"]
+        .iter()
+        .chain(&columns[1..])
+        .chain(bands.map(|_| &"rating"))
+        .cloned()
+        .collect();
+    let table_widths: Vec<usize> = table_header
+        .iter()
+        .enumerate()
+        .map(|(i, name)| name.len().max(if i == 0 { 8 } else { 9 }))
+        .collect();
+    if table {
+        println!("{}", table_row(&table_header, &table_widths));
+    }
+    for _ in 0..cli.skip {
+        next_frame(&mut *source1, "video1");
+        next_frame(&mut *source2, "video2");
+        for (source, name) in &mut references {
+            next_frame(&mut **source, name);
+        }
+    }
+    let mut progress = cli.progress.then(|| {
+        // Repeats of video2 leave frames of video1 to spare with --dup-aware
+        let total = [&*source1, &*source2]
+            .iter()
+            .zip(cli.input_lens)
+            .skip(cli.dup_aware as usize)
+            .filter_map(|(source, len)| source.frames_left(len?))
+            .chain(cli.limit)
+            .min();
+        Progress::new(total)
+    });
+    // Frames are copied to be read ahead with --tolerate-drops, or put back
+    // and kept for the next with --dup-aware, and hashed for --update-baseline
+    let tolerate = cli.tolerate_drops > 0;
+    let hash = cli.update_baseline.is_some();
+    let buffered = tolerate || cli.dup_aware;
+    let mut queue1 = FrameQueue::new(&mut *source1, "video1", buffered, hash);
+    let mut queue2 = FrameQueue::new(&mut *source2, "video2", buffered, hash);
+    // Frames of video1 taken as dropped from video2, and the last score
+    let mut drops = Vec::new();
+    let mut last_score = None;
+    // The last frames of video1 and video2 with --dup-aware, as they were
+    // scored, and the frames of video2 scored against a frame of video1 again
+    let mut dup_last1 = Vec::new();
+    let mut dup_last2 = Vec::new();
+    let mut dups = Vec::new();
+    // Frames the decoders reported with --frame-info for each frame scored,
+    // for --json
+    let mut frames_decoded = Vec::new();
+    let ingest1 = Ingest {
+        lut: lut1,
+        transform: transform1,
+        conform: conform1,
+        rescale: None,
+        crop: crop1,
+        filter: cli.conform_filter,
+    };
+    let ingest2 = Ingest {
+        lut: lut2,
+        transform: transform2,
+        conform: conform2,
+        rescale: rescale2,
+        crop: crop2,
+        filter: cli.conform_filter,
+    };
+    // Frames before the offset found by --auto-align are skipped, from video1
+    // when it is positive and video2 when negative
+    let offset = cli.auto_align.map(|window| {
+        let len = 3 * window;
+        let signatures1 = luma_signatures(&mut queue1, &ingest1, &info1, neutral_chroma, len);
+        let signatures2 = luma_signatures(&mut queue2, &ingest2, &info2, neutral_chroma, len);
+        let offset = best_offset(&signatures1, &signatures2, window);
+        if cli.text {
+            match offset {
+                Some(offset) if offset >= 0 => {
+                    println!("Offset: video2 starts at video1 frame {}", offset)
+                }
+                Some(offset) => println!("Offset: video1 starts at video2 frame {}", -offset),
+                None => println!("Offset: none found"),
+            }
+        }
+        match offset {
+            Some(offset) if offset > 0 => queue1.discard(offset as usize),
+            Some(offset) if offset < 0 => queue2.discard(-offset as usize),
+            _ => {}
+        }
+        offset
+    });
+    // Reading ahead would delay a live feed, and repeated frames of video2
+    // throw its order off on purpose
+    if cli.order_check && cli.realtime.is_none() && !cli.dup_aware {
+        if let Some(diagnostic) = check_order(&mut queue1, &mut queue2) {
+            eprintln!("{}. Pass --no-order-check to score them anyway", diagnostic);
+            exit(1);
+        }
+    }
+    let mut drop_bufs = Default::default();
+    let mut bufs1 = Default::default();
+    let mut bufs2 = Default::default();
+    let mut reference_bufs = vec![Default::default(); references.len()];
+    let num_references = references.len();
+    let reference_width = if num_references == 0 { 0 } else { width };
+    // With --realtime, frame n is due `frame_time` after frame n - 1, and
+    // scoring every row of a frame is expected to take `full_time`
+    let frame_time = cli.realtime.map(|fps| Duration::from_secs_f64(1. / fps));
+    let start = Instant::now();
+    let mut full_time = Duration::ZERO;
+    // Row stride of each frame, for --json, and the number of frames sampled
+    let mut frame_strides = Vec::new();
+    let mut sampled_frames = 0;
+    let mut rolling = cli.realtime.filter(|_| cli.rolling).map(RollingScores::new);
+    let mut drop_alert = cli
+        .on_drop
+        .clone()
+        .map(|(command, threshold, frames)| DropAlert::new(command, threshold, frames));
+    loop {
+        let mut positions = [queue1.position(), queue2.position()];
+        let (frame1, frame2) = match (queue1.next(), queue2.next()) {
+            (Some(frame1), Some(frame2)) => (frame1, frame2),
+            _ => break,
+        };
+        // Frame numbers count from the first frame of the inputs with --skip
+        let frame_number = cli.skip + num_frames;
+        let frame1 = ingest1.frame(frame1, neutral_chroma, &mut bufs1);
+        let frame2 = ingest2.frame(frame2, neutral_chroma, &mut bufs2);
+        // A frame of video2 that repeats the one before is scored against the
+        // last frame of video1 again if that is the closer one, and the frame
+        // just read from video1 is put back for the next frame of video2
+        let last1 = info1
+            .split_planes(&dup_last1)
+            .map(|last| neutralize(last, neutral_chroma));
+        let last2 = info2
+            .split_planes(&dup_last2)
+            .map(|last| neutralize(last, neutral_chroma));
+        // Repeats are told apart after a mean ΔE of DUP_DELTA_E, so frames
+        // that differ stop being scored early
+        let dup_budget = Budget::Sum(DUP_DELTA_E * (width * height) as f64);
+        let dup = match (&last1, &last2) {
+            (Some(last1), Some(last2)) => {
+                delta_e_frame_bounded(&info2, last2, &frame2, dup_budget, simd).is_some()
+                    && delta_e_frame_bounded(
+                        &info,
+                        &frame1,
+                        &frame2,
+                        Budget::Sum(delta_e_frame(&info, last1, &frame2, simd)),
+                        simd,
+                    )
+                    .is_none()
+            }
+            _ => false,
+        };
+        let frame1 = if dup {
+            if cli.text {
+                println!(
+                    "Duplicate: frame {:08} repeats video1 frame {:08}",
+                    frame_number,
+                    frame_number - dups.len() - 1
+                );
+            }
+            dups.push(frame_number);
+            queue1.unread();
+            positions[0] -= 1;
+            last1.unwrap()
+        } else {
+            frame1
+        };
+        let mut reference_frames = Vec::with_capacity(num_references);
+        for ((source, name), bufs) in references.iter_mut().zip(&mut reference_bufs) {
+            match next_frame(&mut **source, name) {
+                Some(frame) => reference_frames.push(ingest1.frame(frame, neutral_chroma, bufs)),
+                None => break,
+            }
+        }
+        if reference_frames.len() < num_references {
+            break;
+        }
+        if cli.autotune && tuning.is_none() {
+            let max_threads = rayon::current_num_threads();
+            let tuned = autotune(&info, &frame1, &frame2, select_row_fn, max_threads);
+            if cli.text {
+                println!(
+                    "Backend: simd {}, threads {}, tile width {}",
+                    level_name(tuned.simd),
+                    tuned.threads,
+                    tuned.tile_width
+                );
+            }
+            simd = tuned.simd;
+            delta_e_row_fn = select_row_fn(simd);
+            tile_width = tuned.tile_width.min(width);
+            band_height = height.div_ceil(4 * tuned.threads);
+            // Fewer threads than the global pool has score in a pool of their
+            // own
+            if tuned.threads < max_threads {
+                tuned_pool = Some(
+                    rayon::ThreadPoolBuilder::new()
+                        .num_threads(tuned.threads)
+                        .build()
+                        .unwrap(),
+                );
+            }
+            tuning = Some(tuned);
+        }
+        let row_fn = delta_e_row_fn;
+        // Only every `stride`th row is scored when scoring them all would
+        // miss the deadline
+        let deadline = frame_time.map(|frame_time| start + frame_time * (num_frames as u32 + 1));
+        let stride = deadline.map_or(1, |deadline| {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if full_time <= left {
+                1
+            } else {
+                let stride = full_time.as_secs_f64() / left.as_secs_f64().max(1e-6);
+                (stride.ceil() as usize).min(height)
+            }
+        });
+        let scoring = Instant::now();
+        // Scores row `i`, row `j` of its band
+        let score_row = |i: usize, j: usize, scratch: &mut RowScratch| {
+            let RowScratch {
+                tile,
+                weights,
+                mask,
+                histogram,
+                map,
+                reference,
+            } = scratch;
+            let mut sums = RowSums::default();
+            if let Some(weight) = skin_weight {
+                skin_weights(&info1, &info1.row(&frame1, i), weight, weights);
+                sums.weight = weights.iter().map(|&w| w as f64).sum::<f64>();
+            } else {
+                sums.weight = width as f64;
+            }
+            let pool = |row1, row2, ksub, tile: &mut [f32], weights: &[f32]| unsafe {
+                match skin_weight {
+                    Some(_) => {
+                        delta_e_row_pool_weighted(row_fn, &info, row1, row2, ksub, tile, weights)
+                    }
+                    None => delta_e_row_pool(row_fn, &info, row1, row2, ksub, tile),
+                }
+                .sum
+            };
+            let (row1, row2) = (info1.row(&frame1, i), info2.row(&frame2, i));
+            sums.sum = match (map, histogram) {
+                // The whole row is kept for the map, in place of the tile
+                (Some(map), histogram) => {
+                    let map_row = &mut map[j * width..][..width];
+                    unsafe { row_fn(row1, row2, ksub, map_row) };
+                    for frame in &reference_frames {
+                        let (row1, row2) = (info1.row(frame, i), info2.row(&frame2, i));
+                        unsafe { row_fn(row1, row2, ksub, reference) };
+                        for (delta_e, &other) in map_row.iter_mut().zip(reference.iter()) {
+                            *delta_e = delta_e.min(other);
+                        }
+                    }
+                    let mut sum = 0f64;
+                    for (x, &delta_e) in map_row.iter().enumerate() {
+                        sum += (delta_e * skin_weight.map_or(1., |_| weights[x])) as f64;
+                        if let Some(histogram) = histogram {
+                            histogram.add(delta_e);
+                        }
+                    }
+                    sum
+                }
+                (None, Some(histogram)) => unsafe {
+                    delta_e_row_pool_histogram(
+                        row_fn,
+                        &info,
+                        row1,
+                        row2,
+                        ksub,
+                        tile,
+                        skin_weight.map(|_| &weights[..]),
+                        histogram,
+                    )
+                    .sum
+                },
+                (None, None) => pool(row1, row2, ksub, tile, weights),
+            };
+            if let Some((compare_info1, compare_info2, ksub)) = compare {
+                sums.compare = pool(
+                    compare_info1.row(&frame1, i),
+                    compare_info2.row(&frame2, i),
+                    ksub,
+                    tile,
+                    weights,
+                );
+            }
+            if banding {
+                smooth_mask(&info1, &frame1, i, mask);
+                sums.smooth_pixels = mask.iter().map(|&m| m as f64).sum::<f64>();
+                sums.smooth = unsafe {
+                    delta_e_row_pool_weighted(
+                        row_fn,
+                        &info,
+                        info1.row(&frame1, i),
+                        info2.row(&frame2, i),
+                        ksub,
+                        tile,
+                        mask,
+                    )
+                    .sum
+                };
+            }
+            if let Some(latitude_weights) = &latitude_weights {
+                let weight = latitude_weights[i];
+                sums.sum *= weight;
+                sums.compare *= weight;
+                sums.weight *= weight;
+                sums.smooth *= weight;
+                sums.smooth_pixels *= weight;
+            }
+            sums
+        };
+        // Each band of rows is scored into its own part of `row_sums`, and
+        // into its own histogram for --table and --features and ΔE map for
+        // --dump-heatmaps
+        let mut score_bands = || {
+            row_sums
+                .par_chunks_mut(band_height)
+                .enumerate()
+                .map(|(band, sums)| {
+                    let mut scratch = RowScratch {
+                        tile: vec![0.; tile_width],
+                        weights: vec![1.; width],
+                        mask: vec![0.; width],
+                        histogram: if table || features.is_some() {
+                            Some(DeltaEHistogram::new())
+                        } else {
+                            None
+                        },
+                        map: if keep_map {
+                            Some(vec![0.; sums.len() * width])
+                        } else {
+                            None
+                        },
+                        reference: vec![0.; reference_width],
+                    };
+                    for (j, sums) in sums.iter_mut().enumerate() {
+                        let i = band * band_height + j;
+                        *sums = if i % stride == 0 {
+                            score_row(i, j, &mut scratch)
+                        } else {
+                            RowSums::default()
                         };
-
-                        Self::delta_e_avx2(
-                            (
-                                load_luma(chunk1_y),
-                                load_chroma(chunk1_u),
-                                load_chroma(chunk1_v),
-                            ),
-                            (
-                                load_luma(chunk2_y),
-                                load_chroma(chunk2_u),
-                                load_chroma(chunk2_v),
-                            ),
-                            res_chunk,
-                        );
-                    } else {
-                        Self::delta_e_row_scalar(
-                            FrameRow {
-                                y: chunk1_y,
-                                u: chunk1_u,
-                                v: chunk1_v,
-                            },
-                            FrameRow {
-                                y: chunk2_y,
-                                u: chunk2_u,
-                                v: chunk2_v,
-                            },
-                            res_chunk,
-                        );
                     }
+                    (scratch.histogram, scratch.map)
+                })
+                .collect::<Vec<_>>()
+        };
+        let bands_scored = match &tuned_pool {
+            Some(pool) => pool.install(score_bands),
+            None => score_bands(),
+        };
+        let mut histogram: Option<DeltaEHistogram> = None;
+        map.clear();
+        for (band_histogram, band_map) in bands_scored {
+            if let Some(band_histogram) = band_histogram {
+                match &mut histogram {
+                    Some(histogram) => histogram.merge(&band_histogram),
+                    None => histogram = Some(band_histogram),
+                }
+            }
+            if let Some(band_map) = band_map {
+                map.extend_from_slice(&band_map);
+            }
+        }
+        full_time = scoring.elapsed() * height as u32 / height.div_ceil(stride) as u32;
+        // Summed in row order, so the result does not depend on the threads
+        let mut sum = 0f64;
+        let mut compare_sum = 0f64;
+        let mut weight_sum = 0f64;
+        let mut smooth_sum = 0f64;
+        let mut smooth_pixels = 0f64;
+        for sums in &row_sums {
+            sum += sums.sum;
+            compare_sum += sums.compare;
+            weight_sum += sums.weight;
+            smooth_sum += sums.smooth;
+            smooth_pixels += sums.smooth_pixels;
+        }
+        // Skin tones count as more than one pixel with --skin-weight, and
+        // pixels near the poles as less than one with --projection equirect
+        let pixels = if cli.skin_weight.is_some() || cli.equirect {
+            weight_sum
+        } else {
+            (width * height.div_ceil(stride)) as f64
+        };
+        let mean_delta_e = sum / pixels;
+        let mut scores = vec![delta_e_to_score(mean_delta_e)];
+        if compare.is_some() {
+            scores.push(delta_e_to_score(compare_sum / pixels));
+        }
+        if cli.chroma_grid {
+            scores.push(delta_e_to_score(delta_e_chroma_grid(
+                &info1, &frame1, &frame2, ksub,
+            )));
+        }
+        let breakdown = (cli.breakdown || features.is_some()).then(|| {
+            if cli.metric == Metric::De2000 {
+                delta_e_breakdown(&info1, &frame1, &frame2, ksub)
+            } else {
+                Breakdown {
+                    lightness: f64::NAN,
+                    chroma: f64::NAN,
+                    hue: f64::NAN,
                 }
+            }
+        });
+        if let Some(breakdown) = breakdown.filter(|_| cli.breakdown) {
+            scores.extend([breakdown.lightness, breakdown.chroma, breakdown.hue]);
+        }
+        if cli.banding {
+            scores.push(if smooth_pixels > 0. {
+                smooth_sum / smooth_pixels
             } else {
-                for (chunk1_y, chunk1_u, chunk1_v, chunk2_y, chunk2_u, chunk2_v, res_chunk) in izip!(
-                    row1.y.chunks(16),
-                    row1.u.chunks(8),
-                    row1.v.chunks(8),
-                    row2.y.chunks(16),
-                    row2.u.chunks(8),
-                    row2.v.chunks(8),
-                    res_row.chunks_mut(8)
+                0.
+            });
+        }
+        if let Some(block_size) = cli.blockiness {
+            scores.push(blockiness(&info2, &frame2, block_size));
+        }
+        if let Some(stereo) = cli.stereo {
+            scores.extend(eye_scores(&map, width, height, stereo));
+        }
+        if let Some(roi_boxes) = &cli.roi_boxes {
+            scores.extend(roi_boxes.pool(frame_number, &map, width, height));
+        }
+        let luma = cli.scene_summary.then(|| mean_luma(&info1, &frame1));
+        let luma_stats = features.is_some().then(|| {
+            let stats = |info, frame| (mean_luma(info, frame), luma_deviation(info, frame));
+            [stats(&info1, &frame1), stats(&info2, &frame2)]
+        });
+        // Written before checking for drops, which score the frame of video2
+        // again under the same number if it realigns
+        let dump_lab = cli
+            .dump_lab
+            .as_ref()
+            .filter(|_| in_frame_list(&cli.dump_lab_frames, frame_number));
+        if let Some(dir) = dump_lab {
+            for (info, frame, name) in [(&info1, &frame1, "video1"), (&info2, &frame2, "video2")] {
+                frame_stage(info, frame, cli.dump_stage, &mut lab);
+                let path = dir.join(format!("{:08}_{}.tif", frame_number, name));
+                if let Err(e) =
+                    tiff::write_lab_tiff(&path, width, height, &lab, cli.dump_lab_format)
+                {
+                    eprintln!("Failed to write {}: {}", path.display(), e);
+                    exit(1);
+                }
+            }
+        }
+        if let Some((recorder, path)) = trace
+            .as_mut()
+            .filter(|_| in_frame_list(&cli.record_trace_frames, frame_number))
+        {
+            trace_row.resize(width, 0.);
+            for i in 0..height {
+                unsafe {
+                    row_fn(
+                        info1.row(&frame1, i),
+                        info2.row(&frame2, i),
+                        ksub,
+                        &mut trace_row,
+                    )
+                };
+                if let Err(e) = recorder.record(
+                    trace_kernel,
+                    ksub,
+                    &info1,
+                    &info1.row(&frame1, i),
+                    &info2,
+                    &info2.row(&frame2, i),
+                    &trace_row,
                 ) {
-                    if chunk1_y.len() == 16 {
-                        #[target_feature(enable = "avx2")]
-                        unsafe fn load_luma(chunk: &[u8]) -> __m256 {
-                            let tmp = _mm_loadu_si128(chunk.as_ptr() as *const _);
-                            _mm256_cvtepi32_ps(_mm256_cvtepu16_epi32(tmp))
-                        };
-
-                        #[target_feature(enable = "avx2")]
-                        unsafe fn load_chroma(chunk: &[u8]) -> __m256 {
-                            let tmp = _mm_loadl_epi64(chunk.as_ptr() as *const _);
-                            _mm256_cvtepi32_ps(_mm256_cvtepu16_epi32(_mm_unpacklo_epi16(tmp, tmp)))
-                        };
-
-                        Self::delta_e_avx2(
-                            (
-                                load_luma(chunk1_y),
-                                load_chroma(chunk1_u),
-                                load_chroma(chunk1_v),
-                            ),
-                            (
-                                load_luma(chunk2_y),
-                                load_chroma(chunk2_u),
-                                load_chroma(chunk2_v),
-                            ),
-                            res_chunk,
-                        );
-                    } else {
-                        Self::delta_e_row_scalar(
-                            FrameRow {
-                                y: chunk1_y,
-                                u: chunk1_u,
-                                v: chunk1_v,
-                            },
-                            FrameRow {
-                                y: chunk2_y,
-                                u: chunk2_u,
-                                v: chunk2_v,
-                            },
-                            res_chunk,
+                    eprintln!("Failed to write {}: {}", path.display(), e);
+                    exit(1);
+                }
+            }
+        }
+        // A frame scoring DROP_STEP below the last is checked against the next
+        // frames of video1. If one scores DROP_STEP above it, video1 skips to
+        // it and the frames skipped are taken as dropped from video2.
+        let tolerated = cli.tolerate_drops - drops.len();
+        let last = last_score.filter(|_| tolerated > 0);
+        if last.is_some_and(|last| scores[0] < last - DROP_STEP) {
+            let mut budget =
+                delta_e_frame(&info, &frame1, &frame2, simd) * 10f64.powf(-DROP_STEP / 20.);
+            let mut realign = None;
+            for n in 1..=DROP_WINDOW.min(tolerated) {
+                let frame = match queue1.peek(n) {
+                    Some(frame) => frame,
+                    None => break,
+                };
+                let frame = ingest1.frame(frame, neutral_chroma, &mut drop_bufs);
+                let bounded =
+                    delta_e_frame_bounded(&info, &frame, &frame2, Budget::Sum(budget), simd);
+                if let Some(sum) = bounded {
+                    budget = sum;
+                    realign = Some(n);
+                }
+            }
+            if let Some(n) = realign {
+                for _ in 0..n {
+                    let dropped = frame_number + drops.len();
+                    if cli.text {
+                        println!(
+                            "Dropped: video1 frame {:08} is missing from video2",
+                            dropped
                         );
                     }
+                    drops.push(dropped);
+                }
+                queue1.discard(n - 1);
+                queue2.unread();
+                continue;
+            }
+        }
+        last_score = Some(scores[0]);
+        if let Some(dir) = &cli.heatmaps {
+            let path = dir.join(format!("{:08}.png", frame_number));
+            if let Err(e) = heatmap::write_heatmap(&path, width, height, &map, cli.heatmap_scale) {
+                eprintln!("Failed to write {}: {}", path.display(), e);
+                exit(1);
+            }
+        }
+        if let Some((writer, path)) = &mut map_output {
+            if let Err(e) = writer.write_frame(&map) {
+                eprintln!("Failed to write {}: {}", path.display(), e);
+                exit(1);
+            }
+        }
+        if let Some((writer, path)) = &mut map_archive {
+            if let Err(e) = writer.write_frame(&map) {
+                eprintln!("Failed to write {}: {}", path.display(), e);
+                exit(1);
+            }
+        }
+        for (total, score) in totals.iter_mut().zip(&scores) {
+            *total += score;
+        }
+        if let Some(luma) = luma {
+            let scene = SCENES.iter().position(|&(_, max)| luma < max).unwrap();
+            let (scene_totals, frames) = &mut scenes[scene];
+            for (total, score) in scene_totals.iter_mut().zip(&scores) {
+                *total += score;
+            }
+            *frames += 1;
+        }
+        if let Some((csv, path)) = &mut csv {
+            let fields: Vec<String> = scores.iter().map(|score| score.to_string()).collect();
+            if let Err(e) = writeln!(
+                csv,
+                "{},{},{}",
+                frame_number,
+                mean_delta_e,
+                fields.join(",")
+            ) {
+                eprintln!("Failed to write {}: {}", path.display(), e);
+                exit(1);
+            }
+        }
+        if let Some((writer, path)) = &mut arrow {
+            if let Err(e) = writer.push(frame_number, mean_delta_e, &scores) {
+                eprintln!("Failed to write {}: {}", path.display(), e);
+                exit(1);
+            }
+        }
+        if let (Some((writer, path)), Some(histogram), Some(breakdown), Some(luma)) =
+            (&mut features, &histogram, breakdown, luma_stats)
+        {
+            let frame_features = FrameFeatures {
+                score: scores[0],
+                mean_delta_e,
+                histogram,
+                breakdown,
+                luma,
+            };
+            if let Err(e) = writer.push(frame_number, &frame_features) {
+                eprintln!("Failed to write {}: {}", path.display(), e);
+                exit(1);
+            }
+        }
+        if let Some(histogram) = histogram.filter(|_| table) {
+            if !cli.summary {
+                let time = framerate.map_or("-".to_owned(), |(num, den)| {
+                    format!("{:.3}", frame_number as f64 * den as f64 / num as f64)
+                });
+                let cells = table_cells(
+                    format!("{:08}", frame_number),
+                    time,
+                    &scores,
+                    &histogram,
+                    bands,
+                );
+                println!("{}", table_row(&cells, &table_widths));
+            }
+            total_histogram.merge(&histogram);
+        }
+        // Frames of the decoders count from the start of the inputs
+        let decoded: Vec<Option<DecodedFrame>> = frame_logs
+            .iter_mut()
+            .zip(&positions)
+            .map(|(log, position)| log.as_mut()?.get(cli.skip + position).cloned())
+            .collect();
+        if cli.text && !cli.summary && !table {
+            let sampled = if stride > 1 {
+                format!(" sampled 1/{}", stride)
+            } else {
+                String::new()
+            };
+            let decoded: String = ["video1", "video2"]
+                .iter()
+                .zip(&decoded)
+                .filter_map(|(name, frame)| {
+                    frame
+                        .as_ref()
+                        .map(|frame| format!(" {} {}", name, decoded_text(frame)))
+                })
+                .collect();
+            println!(
+                "{:08}: {}{}{}",
+                frame_number,
+                format_scores(&scores, bands),
+                sampled,
+                decoded
+            );
+        }
+        if let Some(alert) = &mut drop_alert {
+            alert.push(frame_number, scores[0]);
+        }
+        if let Some(means) = rolling.as_mut().and_then(|rolling| rolling.push(scores[0])) {
+            let means: Vec<String> = WINDOWS
+                .iter()
+                .zip(means)
+                .map(|((_, name), mean)| format!("{} {:2.4}", name, mean))
+                .collect();
+            if cli.text {
+                println!("Rolling: {}", means.join(", "));
+            }
+        }
+        if cli.json || cli.baseline.is_some() || cli.update_baseline.is_some() {
+            frame_scores.push(scores);
+            frame_strides.push(stride);
+            frames_decoded.push(decoded);
+        }
+        if stride > 1 {
+            sampled_frames += 1;
+        }
+        // The frames scored are kept for the duplicate check of the next by
+        // swapping out the buffers holding them. A repeated frame of video1
+        // stays the last one.
+        if cli.dup_aware {
+            if !dup {
+                match ingest1.output(&mut bufs1) {
+                    Some(buf) => std::mem::swap(buf, &mut dup_last1),
+                    None => queue1.swap_current(&mut dup_last1),
                 }
             }
+            match ingest2.output(&mut bufs2) {
+                Some(buf) => std::mem::swap(buf, &mut dup_last2),
+                None => queue2.swap_current(&mut dup_last2),
+            }
+        }
+        if let Some(deadline) = deadline {
+            thread::sleep(deadline.saturating_duration_since(Instant::now()));
+        }
+        num_frames += 1;
+        if let Some(progress) = &mut progress {
+            progress.update(num_frames);
+        }
+        if let Some(limit) = cli.limit {
+            if num_frames >= limit {
+                break;
+            }
         }
     }
-
-    impl DeltaEAVX2 for BD8 {}
-    impl DeltaEAVX2 for BD10 {}
-    impl DeltaEAVX2 for BD12 {}
+    if let Some(alert) = drop_alert {
+        alert.finish();
+    }
+    if let Some(progress) = progress {
+        progress.finish(num_frames);
+    }
+    let input_hashes = [queue1.hash, queue2.hash];
+    if let Some((writer, path)) = map_output {
+        if let Err(e) = writer.finish() {
+            eprintln!("Failed to write {}: {}", path.display(), e);
+            exit(1);
+        }
+    }
+    if let Some((writer, path)) = map_archive {
+        if let Err(e) = writer.finish() {
+            eprintln!("Failed to write {}: {}", path.display(), e);
+            exit(1);
+        }
+    }
+    if let Some((recorder, path)) = trace {
+        if let Err(e) = recorder.finish() {
+            eprintln!("Failed to write {}: {}", path.display(), e);
+            exit(1);
+        }
+    }
+    if let Some((mut csv, path)) = csv {
+        if let Err(e) = csv.flush() {
+            eprintln!("Failed to write {}: {}", path.display(), e);
+            exit(1);
+        }
+    }
+    if let Some((writer, path)) = arrow {
+        if let Err(e) = writer.finish() {
+            eprintln!("Failed to write {}: {}", path.display(), e);
+            exit(1);
+        }
+    }
+    if let Some((writer, path)) = features {
+        if let Err(e) = writer.finish() {
+            eprintln!("Failed to write {}: {}", path.display(), e);
+            exit(1);
+        }
+    }
+    for (source, name) in [(&source1, "video1"), (&source2, "video2")] {
+        if source.skipped_bytes() > 0 {
+            eprintln!(
+                "{}: Skipped {} bytes of corrupted data",
+                name,
+                source.skipped_bytes()
+            );
+        }
+    }
+    for (name, retries) in &cli.read_retries {
+        if retries.get() > 0 {
+            eprintln!("{}: Retried {} failed reads", name, retries.get());
+        }
+    }
+    if sampled_frames > 0 {
+        eprintln!(
+            "Realtime: Sampled rows of {} frames to keep up",
+            sampled_frames
+        );
+    }
+    for (name, staged) in &cli.staged {
+        if let Some(copy) = &*staged.borrow() {
+            eprintln!(
+                "{}: Staged to {}, hash {}",
+                name,
+                copy.path.display(),
+                copy.hash
+            );
+        }
+    }
+    let means: Vec<f64> = totals
+        .iter()
+        .map(|total| total / (num_frames as f64))
+        .collect();
+    let mut settings = vec![
+        ("weights", json_weights(cli.ksub)),
+        ("matrix", json_str(matrix_name(info1.matrix))),
+        ("range", json_str(range_name(info1.range))),
+        ("bit_depth", info1.bit_depth.to_string()),
+        (
+            "subsampling",
+            json_str(subsampling_name(info1.xdec, info1.ydec)),
+        ),
+        (
+            "vision",
+            json_str(&format!("{:?}", cli.vision).to_lowercase()),
+        ),
+        (
+            "simd",
+            json_str(if cli.fast_int || cli.precision_f64 {
+                "scalar"
+            } else {
+                level_name(simd.min(SimdLevel::detect(info.xdec)))
+            }),
+        ),
+        (
+            "trig",
+            json_str(if cli.approx_trig { "approx" } else { "exact" }),
+        ),
+        ("fast_int", cli.fast_int.to_string()),
+    ];
+    if let Some(tuning) = tuning {
+        settings.push((
+            "autotune",
+            json_object(&[
+                ("simd", json_str(level_name(tuning.simd))),
+                ("threads", tuning.threads.to_string()),
+                ("tile_width", tuning.tile_width.to_string()),
+            ]),
+        ));
+    }
+    if let Some(fps) = cli.realtime {
+        settings.push(("realtime", json_f64(fps)));
+    }
+    if !assumed.is_empty() {
+        let assumed: Vec<String> = assumed.iter().map(|a| json_str(a)).collect();
+        settings.push(("assumed", json_array(&assumed)));
+    }
+    if cli.skip > 0 {
+        settings.push(("skip", cli.skip.to_string()));
+    }
+    if let Some(offset) = offset {
+        let offset = offset.map_or("null".to_owned(), |offset| offset.to_string());
+        settings.push(("offset", offset));
+    }
+    if cli.precision_f64 {
+        settings.push(("precision", json_str("f64")));
+    }
+    if info1.transfer != Transfer::Sdr {
+        settings.push(("transfer", json_str(transfer_name(info1.transfer))));
+    }
+    if cli.metric != Metric::De2000 {
+        settings.push(("metric", json_str(metric_name(cli.metric))));
+    }
+    if info2.range != info1.range {
+        settings.push(("range2", json_str(range_name(info2.range))));
+    }
+    if !transforms.is_empty() {
+        let transforms: Vec<String> = transforms.iter().map(|t| json_str(t)).collect();
+        settings.push(("transform", json_array(&transforms)));
+    }
+    if !luts.is_empty() {
+        let luts: Vec<String> = luts.iter().map(|lut| json_str(lut)).collect();
+        settings.push(("lut", json_array(&luts)));
+    }
+    if let Some(crop) = &crop {
+        settings.push(("crop", json_str(crop)));
+    }
+    if cli.equirect {
+        settings.push(("projection", json_str("equirect")));
+    }
+    if let Some(stereo) = cli.stereo {
+        let packing = match stereo {
+            Stereo::SideBySide => "sbs",
+            Stereo::TopAndBottom => "tab",
+        };
+        settings.push(("stereo", json_str(packing)));
+    }
+    if !deinterlaced.is_empty() {
+        let deinterlaced: Vec<String> = deinterlaced.iter().map(|d| json_str(d)).collect();
+        settings.push(("deinterlace", json_array(&deinterlaced)));
+    }
+    if let Some(note) = &conform_note {
+        settings.push(("conform", json_str(note)));
+    }
+    if let Some(note) = &rescale_note {
+        settings.push(("rescale", json_str(note)));
+    }
+    if let Some(note) = grayscale_note {
+        settings.push(("grayscale", json_str(note)));
+    }
+    let read_retries: Vec<(&str, String)> = cli
+        .read_retries
+        .iter()
+        .filter(|(_, retries)| retries.get() > 0)
+        .map(|(name, retries)| (name.as_str(), retries.get().to_string()))
+        .collect();
+    if !read_retries.is_empty() {
+        settings.push(("read_retries", json_object(&read_retries)));
+    }
+    if !references.is_empty() {
+        settings.push(("references", (1 + references.len()).to_string()));
+    }
+    if let Some((compare_info, _, ksub)) = compare {
+        settings.push((
+            "compare",
+            json_object(&[
+                ("matrix", json_str(matrix_name(compare_info.matrix))),
+                ("range", json_str(range_name(compare_info.range))),
+                ("weights", json_weights(ksub)),
+            ]),
+        ));
+    }
+    let passed = means[0] >= cli.verdict_threshold.unwrap_or(f64::NEG_INFINITY);
+    let verdict = if passed { "pass" } else { "fail" };
+    if cli.json || cli.update_baseline.is_some() {
+        // Objects naming each score by its column, after the fields in `head`
+        // and followed by the rating
+        let scores_object = |head: &[(&'static str, String)], scores: &[f64]| {
+            let mut fields: Vec<(&str, String)> = head
+                .iter()
+                .cloned()
+                .chain(
+                    columns
+                        .iter()
+                        .zip(scores)
+                        .map(|(&name, &score)| (name, json_f64(score))),
+                )
+                .collect();
+            if let Some(bands) = bands {
+                let label = rating(bands, scores[0]).map_or("null".to_owned(), json_str);
+                fields.push(("rating", label));
+            }
+            json_object(&fields)
+        };
+        let frames: Vec<String> = frame_scores
+            .iter()
+            .enumerate()
+            .map(|(i, scores)| {
+                let mut head = vec![("frame", (cli.skip + i).to_string())];
+                if cli.realtime.is_some() {
+                    head.push(("row_stride", frame_strides[i].to_string()));
+                }
+                for (name, frame) in ["video1", "video2"].iter().zip(&frames_decoded[i]) {
+                    if let Some(frame) = frame {
+                        head.push((name, json_decoded(frame)));
+                    }
+                }
+                scores_object(&head, scores)
+            })
+            .collect();
+        let mut document = vec![
+            ("settings", json_object(&settings)),
+            ("frames", json_array(&frames)),
+            (
+                "total",
+                scores_object(&[("frames", num_frames.to_string())], &means),
+            ),
+        ];
+        if cli.dup_aware {
+            let dups: Vec<String> = dups.iter().map(|dup| dup.to_string()).collect();
+            document.push(("duplicates", json_array(&dups)));
+        }
+        if tolerate {
+            let drops: Vec<String> = drops.iter().map(|drop| drop.to_string()).collect();
+            document.push(("drops", json_array(&drops)));
+        }
+        if cli.scene_summary {
+            let scenes: Vec<String> = SCENES
+                .iter()
+                .zip(&scenes)
+                .map(|(&(name, _), (scene_totals, frames))| {
+                    let means: Vec<f64> = scene_totals
+                        .iter()
+                        .map(|total| total / (*frames as f64))
+                        .collect();
+                    scores_object(
+                        &[
+                            ("scene", json_str(&name.to_lowercase())),
+                            ("frames", frames.to_string()),
+                        ],
+                        &means,
+                    )
+                })
+                .collect();
+            document.push(("scenes", json_array(&scenes)));
+        }
+        if let Some(note) = &cli.note {
+            document.insert(1, ("note", json_str(note)));
+        }
+        if let Some(threshold) = cli.verdict_threshold {
+            document.push((
+                "verdict",
+                json_object(&[
+                    ("result", json_str(verdict)),
+                    ("threshold", json_f64(threshold)),
+                ]),
+            ));
+        }
+        if let Some((key, _)) = &cli.sign {
+            let inputs: Vec<(&str, String)> = cli
+                .input_digests
+                .iter()
+                .map(|(name, digest)| (name.as_str(), json_str(&digest_hex(digest))))
+                .collect();
+            document.push((
+                "signed",
+                json_object(&[
+                    ("inputs", json_object(&inputs)),
+                    ("config", json_str(&config_hash(&settings))),
+                    ("public_key", json_str(&public_key_hex(key))),
+                ]),
+            ));
+        }
+        if cli.json {
+            // The signature covers the report exactly as printed
+            let report = json_object(&document) + "\n";
+            print!("{}", report);
+            if let Some((key, path)) = &cli.sign {
+                if let Err(e) = fs::write(path, sign::sign(key, report.as_bytes())) {
+                    eprintln!("Failed to write {}: {}", path.display(), e);
+                    exit(1);
+                }
+            }
+        }
+        if let Some(path) = &cli.update_baseline {
+            let inputs: Vec<String> = input_hashes
+                .iter()
+                .flatten()
+                .map(|h| json_str(&h.hex()))
+                .collect();
+            document.push((
+                "baseline",
+                json_object(&[
+                    ("config", json_str(&config_hash(&settings))),
+                    ("inputs", json_array(&inputs)),
+                ]),
+            ));
+            if let Err(e) = fs::write(path, json_object(&document) + "\n") {
+                eprintln!("Failed to write {}: {}", path.display(), e);
+                exit(1);
+            }
+        }
+    }
+    if table {
+        let cells = table_cells(
+            "total".to_owned(),
+            "-".to_owned(),
+            &means,
+            &total_histogram,
+            bands,
+        );
+        println!("{}", table_row(&cells, &table_widths));
+    } else if cli.text {
+        println!("Total: {}", format_scores(&means, bands));
+        if cli.scene_summary {
+            for (&(name, _), (scene_totals, frames)) in SCENES.iter().zip(&scenes) {
+                if *frames == 0 {
+                    println!("{}: no frames", name);
+                    continue;
+                }
+                let means: Vec<f64> = scene_totals
+                    .iter()
+                    .map(|total| total / (*frames as f64))
+                    .collect();
+                println!(
+                    "{}: {} ({} frames)",
+                    name,
+                    format_scores(&means, bands),
+                    frames
+                );
+            }
+        }
+    }
+    if let Some(threshold) = cli.verdict_threshold.filter(|_| cli.text) {
+        println!("Verdict: {}, threshold {}", verdict, threshold);
+    }
+    if let Some(baseline) = &cli.baseline {
+        if baseline
+            .config
+            .as_deref()
+            .is_some_and(|config| config != config_hash(&settings))
+        {
+            eprintln!("Warning - Baseline was made with other settings");
+        }
+        let scores: Vec<f64> = frame_scores.iter().map(|scores| scores[0]).collect();
+        let report = baseline.regressions(&scores, means[0], cli.max_regression);
+        if !report.is_empty() {
+            for line in report {
+                eprintln!("Regressed: {}", line);
+            }
+            exit(1);
+        }
+    }
+    // The scores stop where a decoder failed, so they do not cover the inputs
+    if !passed || cli.decode_failed.iter().any(|failed| failed.get()) {
+        exit(1);
+    }
 }
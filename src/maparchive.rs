@@ -0,0 +1,255 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2019, the dump_ciede2000 contributors
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//  list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//  this list of conditions and the following disclaimer in the documentation
+//  and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Archives of the per-pixel ΔE of every frame, split into zstd-compressed
+//! tiles so that a region of a frame can be read back without the rest.
+//!
+//! An archive starts with [`MAP_MAGIC`], the width, height and tile size as
+//! `u32`, followed by the compressed tiles of each frame, row by row. Each
+//! tile holds the ΔE of its pixels as `u16` in steps of 1/256, the high
+//! bytes of the tile first and then the low bytes, which compresses better.
+//! An index follows the tiles, with for each tile its offset as a `u64`, its
+//! length as a `u32`, the sum of its ΔE as an `f64` and its maximum as an
+//! `f32`. The archive ends with the offset of the index as a `u64` and the
+//! number of frames as a `u32`.
+//!
+//! Numbers are little-endian.
+
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{self, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Bytes an archive starts with, which change with the format.
+pub const MAP_MAGIC: &[u8; 8] = b"DE2KMAP1";
+
+/// Width and height of the tiles
+const TILE_SIZE: usize = 64;
+
+/// Steps of ΔE in a stored value
+const STEPS: f32 = 256.;
+
+/// Bytes of an index entry
+const ENTRY_LEN: usize = 8 + 4 + 8 + 4;
+
+/// Position and totals of a compressed tile.
+#[derive(Clone, Copy)]
+struct TileEntry {
+    offset: u64,
+    len: u32,
+    sum: f64,
+    max: f32,
+}
+
+/// Tiles covering a `width` by `height` frame, as (x, y, width, height).
+fn tiles(width: usize, height: usize, tile_size: usize) -> Vec<(usize, usize, usize, usize)> {
+    let mut tiles = Vec::new();
+    for y in (0..height).step_by(tile_size) {
+        for x in (0..width).step_by(tile_size) {
+            tiles.push((x, y, tile_size.min(width - x), tile_size.min(height - y)));
+        }
+    }
+    tiles
+}
+
+fn quantize(delta_e: f32) -> u16 {
+    (delta_e * STEPS).round().clamp(0., u16::MAX as f32) as u16
+}
+
+pub struct MapArchiveWriter {
+    writer: BufWriter<File>,
+    width: usize,
+    height: usize,
+    offset: u64,
+    index: Vec<TileEntry>,
+    frames: u32,
+    buf: Vec<u8>,
+}
+
+impl MapArchiveWriter {
+    pub fn new(path: &Path, width: usize, height: usize) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(MAP_MAGIC)?;
+        for n in [width, height, TILE_SIZE] {
+            writer.write_all(&(n as u32).to_le_bytes())?;
+        }
+        Ok(MapArchiveWriter {
+            writer,
+            width,
+            height,
+            offset: (MAP_MAGIC.len() + 12) as u64,
+            index: Vec::new(),
+            frames: 0,
+            buf: Vec::new(),
+        })
+    }
+
+    pub fn write_frame(&mut self, delta_e: &[f32]) -> io::Result<()> {
+        for (x, y, w, h) in tiles(self.width, self.height, TILE_SIZE) {
+            let values: Vec<u16> = (y..y + h)
+                .flat_map(|i| &delta_e[i * self.width + x..i * self.width + x + w])
+                .map(|&delta_e| quantize(delta_e))
+                .collect();
+            self.buf.clear();
+            self.buf.extend(values.iter().map(|v| (v >> 8) as u8));
+            self.buf.extend(values.iter().map(|&v| v as u8));
+            let compressed = zstd::bulk::compress(&self.buf, 0)?;
+            self.writer.write_all(&compressed)?;
+            self.index.push(TileEntry {
+                offset: self.offset,
+                len: compressed.len() as u32,
+                sum: values.iter().map(|&v| v as f64 / STEPS as f64).sum(),
+                max: values.iter().max().map_or(0., |&v| v as f32 / STEPS),
+            });
+            self.offset += compressed.len() as u64;
+        }
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Writes the index, without which the tiles can not be found.
+    pub fn finish(mut self) -> io::Result<()> {
+        for entry in &self.index {
+            self.writer.write_all(&entry.offset.to_le_bytes())?;
+            self.writer.write_all(&entry.len.to_le_bytes())?;
+            self.writer.write_all(&entry.sum.to_le_bytes())?;
+            self.writer.write_all(&entry.max.to_le_bytes())?;
+        }
+        self.writer.write_all(&self.offset.to_le_bytes())?;
+        self.writer.write_all(&self.frames.to_le_bytes())?;
+        self.writer.flush()
+    }
+}
+
+/// Rectangle of a frame, as x, y, width and height.
+pub type Region = (usize, usize, usize, usize);
+
+/// Mean and maximum ΔE over a region of a frame.
+pub struct RegionStats {
+    pub mean: f64,
+    pub max: f32,
+}
+
+pub struct MapArchiveReader {
+    file: File,
+    pub width: usize,
+    pub height: usize,
+    pub frames: usize,
+    tile_size: usize,
+    index: Vec<TileEntry>,
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+impl MapArchiveReader {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        let mut header = [0u8; 20];
+        file.read_exact(&mut header)?;
+        if &header[..8] != MAP_MAGIC {
+            return Err(invalid("not a ΔE map archive, or of another version"));
+        }
+        let field = |i: usize| u32::from_le_bytes(header[i..i + 4].try_into().unwrap()) as usize;
+        let (width, height, tile_size) = (field(8), field(12), field(16));
+        if width == 0 || height == 0 || tile_size == 0 {
+            return Err(invalid("empty frames or tiles"));
+        }
+        let mut footer = [0u8; 12];
+        file.seek(SeekFrom::End(-12))?;
+        file.read_exact(&mut footer)?;
+        let index_offset = u64::from_le_bytes(footer[..8].try_into().unwrap());
+        let frames = u32::from_le_bytes(footer[8..].try_into().unwrap()) as usize;
+        let index_len = (frames * tiles(width, height, tile_size).len() * ENTRY_LEN) as u64;
+        if index_offset.checked_add(index_len) != Some(file.metadata()?.len() - 12) {
+            return Err(invalid("index does not end where the archive does"));
+        }
+        let mut index = vec![0u8; index_len as usize];
+        file.seek(SeekFrom::Start(index_offset))?;
+        file.read_exact(&mut index)?;
+        let index = index
+            .chunks_exact(ENTRY_LEN)
+            .map(|entry| TileEntry {
+                offset: u64::from_le_bytes(entry[..8].try_into().unwrap()),
+                len: u32::from_le_bytes(entry[8..12].try_into().unwrap()),
+                sum: f64::from_le_bytes(entry[12..20].try_into().unwrap()),
+                max: f32::from_le_bytes(entry[20..].try_into().unwrap()),
+            })
+            .collect();
+        Ok(MapArchiveReader {
+            file,
+            width,
+            height,
+            frames,
+            tile_size,
+            index,
+        })
+    }
+
+    /// Returns the mean and maximum ΔE over `region` of `frame`. Tiles
+    /// wholly inside the region are taken from the index, and only those it
+    /// cuts through are decompressed.
+    pub fn region_stats(&mut self, frame: usize, region: Region) -> io::Result<RegionStats> {
+        let (rx, ry, rw, rh) = region;
+        let tiles = tiles(self.width, self.height, self.tile_size);
+        let entries = &self.index[frame * tiles.len()..(frame + 1) * tiles.len()];
+        let (mut sum, mut max) = (0f64, 0f32);
+        let mut buf = Vec::new();
+        for (&(x, y, w, h), entry) in tiles.iter().zip(entries) {
+            let (x0, y0) = (x.max(rx), y.max(ry));
+            let (x1, y1) = ((x + w).min(rx + rw), (y + h).min(ry + rh));
+            if x0 >= x1 || y0 >= y1 {
+                continue;
+            }
+            if (x0, y0, x1, y1) == (x, y, x + w, y + h) {
+                sum += entry.sum;
+                max = max.max(entry.max);
+                continue;
+            }
+            buf.resize(entry.len as usize, 0);
+            self.file.seek(SeekFrom::Start(entry.offset))?;
+            self.file.read_exact(&mut buf)?;
+            let bytes = zstd::bulk::decompress(&buf, 2 * w * h)?;
+            if bytes.len() != 2 * w * h {
+                return Err(invalid("tile of the wrong size"));
+            }
+            let (high, low) = bytes.split_at(w * h);
+            for i in y0 - y..y1 - y {
+                for j in x0 - x..x1 - x {
+                    let k = i * w + j;
+                    let delta_e = u16::from_be_bytes([high[k], low[k]]) as f32 / STEPS;
+                    sum += delta_e as f64;
+                    max = max.max(delta_e);
+                }
+            }
+        }
+        Ok(RegionStats {
+            mean: sum / (rw * rh) as f64,
+            max,
+        })
+    }
+}
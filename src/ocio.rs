@@ -0,0 +1,77 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2019, the dump_ciede2000 contributors
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//  list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//  this list of conditions and the following disclaimer in the documentation
+//  and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Conversions between the color spaces of an OpenColorIO config, baked into
+//! a LUT by the `ociobakelut` tool that comes with OpenColorIO.
+
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+
+use dump_ciede2000::Lut;
+
+/// Entries along each axis of the baked LUT
+const CUBE_SIZE: &str = "65";
+
+// Creates an empty file for the LUT in the temp dir. It is created
+// exclusively, so that a file or link already at the name is never written
+// through.
+fn create_temp() -> Result<PathBuf, String> {
+    let dir = std::env::temp_dir();
+    let mut attempt = 0;
+    loop {
+        let name = format!("dump_ciede2000-{}-{}.cube", process::id(), attempt);
+        let path = dir.join(name);
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(_) => return Ok(path),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists && attempt < 100 => attempt += 1,
+            Err(e) => return Err(format!("Could not create {}: {}", path.display(), e)),
+        }
+    }
+}
+
+/// Bakes the conversion from the color space `input` to `output` of the
+/// config at `config` into a 3D LUT. Whatever `ociobakelut` prints about a
+/// failure goes to stderr.
+pub fn bake_lut(config: &Path, input: &str, output: &str) -> Result<Lut, String> {
+    let path = create_temp()?;
+    let status = Command::new("ociobakelut")
+        .arg("--iconfig")
+        .arg(config)
+        .args(["--inputspace", input, "--outputspace", output])
+        .args(["--format", "resolve_cube", "--cubesize", CUBE_SIZE])
+        .arg(&path)
+        .stdout(Stdio::null())
+        .status();
+    let text = fs::read_to_string(&path);
+    let _ = fs::remove_file(&path);
+    let status = status.map_err(|e| format!("Could not run ociobakelut: {}", e))?;
+    if !status.success() {
+        return Err(format!("ociobakelut failed with {}", status));
+    }
+    Lut::parse(&text.map_err(|e| e.to_string())?)
+}
@@ -0,0 +1,199 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2019, the dump_ciede2000 contributors
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//  list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//  this list of conditions and the following disclaimer in the documentation
+//  and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Pooling of the per-pixel ΔE of a frame into values such as a mean or a
+//! percentile, through a trait so callers can plug in their own.
+
+use std::mem;
+
+use crate::{get_delta_e_row_fn, DeltaEHistogram, Frame, FrameInfo, Pool, SimdLevel, K_SUB};
+
+/// Pools the per-pixel ΔE of a frame, given a run of pixels at a time.
+///
+/// ```
+/// use dump_ciede2000::FramePooler;
+///
+/// /// Share of pixels with a visible difference
+/// #[derive(Default)]
+/// struct Visible {
+///     visible: u64,
+///     count: u64,
+/// }
+///
+/// impl FramePooler for Visible {
+///     type Output = f64;
+///
+///     fn accumulate(&mut self, delta_e: &[f32]) {
+///         self.visible += delta_e.iter().filter(|&&d| d > 2.3).count() as u64;
+///         self.count += delta_e.len() as u64;
+///     }
+///
+///     fn finalize(&mut self) -> f64 {
+///         let share = self.visible as f64 / self.count.max(1) as f64;
+///         *self = Visible::default();
+///         share
+///     }
+/// }
+/// ```
+pub trait FramePooler {
+    type Output;
+
+    /// Adds the ΔE of the next pixels of the frame.
+    fn accumulate(&mut self, delta_e: &[f32]);
+
+    /// Returns the pooled value of the pixels added since the last call, and
+    /// starts over for the next frame.
+    fn finalize(&mut self) -> Self::Output;
+}
+
+/// Mean ΔE of a frame, as passed to [`delta_e_to_score`](crate::delta_e_to_score).
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MeanPooler {
+    sum: f64,
+    count: u64,
+}
+
+impl FramePooler for MeanPooler {
+    type Output = f64;
+
+    fn accumulate(&mut self, delta_e: &[f32]) {
+        for &delta_e in delta_e {
+            self.sum += delta_e as f64;
+        }
+        self.count += delta_e.len() as u64;
+    }
+
+    /// Returns 0 for a frame without pixels.
+    fn finalize(&mut self) -> f64 {
+        let mean = if self.count > 0 {
+            self.sum / self.count as f64
+        } else {
+            0.
+        };
+        *self = MeanPooler::default();
+        mean
+    }
+}
+
+/// The ΔE that fraction `p` of the pixels are at or below, as given by
+/// [`DeltaEHistogram::percentile`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct PercentilePooler {
+    p: f64,
+    histogram: DeltaEHistogram,
+}
+
+impl PercentilePooler {
+    pub fn new(p: f64) -> Self {
+        PercentilePooler {
+            p,
+            histogram: DeltaEHistogram::new(),
+        }
+    }
+}
+
+impl FramePooler for PercentilePooler {
+    type Output = f32;
+
+    fn accumulate(&mut self, delta_e: &[f32]) {
+        self.histogram.accumulate(delta_e);
+    }
+
+    fn finalize(&mut self) -> f32 {
+        self.histogram.finalize().percentile(self.p)
+    }
+}
+
+/// Pools into the whole histogram of the frame.
+impl FramePooler for DeltaEHistogram {
+    type Output = DeltaEHistogram;
+
+    fn accumulate(&mut self, delta_e: &[f32]) {
+        for &delta_e in delta_e {
+            self.add(delta_e);
+        }
+    }
+
+    fn finalize(&mut self) -> DeltaEHistogram {
+        mem::take(self)
+    }
+}
+
+/// Pools into the sum and maximum, as [`delta_e_row_pool`](crate::delta_e_row_pool)
+/// does for a row.
+impl FramePooler for Pool {
+    type Output = Pool;
+
+    fn accumulate(&mut self, delta_e: &[f32]) {
+        for &delta_e in delta_e {
+            self.sum += delta_e as f64;
+            self.max = self.max.max(delta_e);
+        }
+    }
+
+    fn finalize(&mut self) -> Pool {
+        mem::replace(self, Pool { sum: 0., max: 0. })
+    }
+}
+
+/// Pools the same pixels two ways at once. Nest pairs for more.
+impl<A: FramePooler, B: FramePooler> FramePooler for (A, B) {
+    type Output = (A::Output, B::Output);
+
+    fn accumulate(&mut self, delta_e: &[f32]) {
+        self.0.accumulate(delta_e);
+        self.1.accumulate(delta_e);
+    }
+
+    fn finalize(&mut self) -> Self::Output {
+        (self.0.finalize(), self.1.finalize())
+    }
+}
+
+/// Scores two frames like [`delta_e_frame`](crate::delta_e_frame), passing
+/// the ΔE of each row to `pooler` and returning what it pools.
+pub fn delta_e_frame_pooled<P: FramePooler>(
+    info: &FrameInfo,
+    frame1: &Frame,
+    frame2: &Frame,
+    simd: impl Into<SimdLevel>,
+    pooler: &mut P,
+) -> P::Output {
+    let delta_e_row_fn = get_delta_e_row_fn(info.bit_depth, info.xdec, simd);
+    let mut delta_e_row: Vec<f32> = vec![0.0; info.width];
+    for i in 0..info.height {
+        unsafe {
+            delta_e_row_fn(
+                info.row(frame1, i),
+                info.row(frame2, i),
+                K_SUB,
+                &mut delta_e_row,
+            );
+        }
+        pooler.accumulate(&delta_e_row);
+    }
+    pooler.finalize()
+}
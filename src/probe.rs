@@ -0,0 +1,161 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2019, the dump_ciede2000 contributors
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//  list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//  this list of conditions and the following disclaimer in the documentation
+//  and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Guesses the format of headerless YUV files.
+//!
+//! Every common frame size, bit depth and subsampling that divides the file
+//! size evenly is a candidate. Candidates are ranked by how much luma changes
+//! between adjacent rows and between the first two frames, both of which are
+//! small for the right format and large when rows or frames are misaligned.
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use dump_ciede2000::{FrameInfo, Matrix, Range, Transfer, Vision};
+
+/// Frame sizes to try, most common first so that ties favour them.
+const SIZES: &[(usize, usize)] = &[
+    (1920, 1080),
+    (1280, 720),
+    (3840, 2160),
+    (720, 480),
+    (720, 576),
+    (640, 480),
+    (352, 288),
+    (176, 144),
+    (854, 480),
+    (960, 540),
+    (1024, 576),
+    (1024, 768),
+    (1280, 1024),
+    (1440, 1080),
+    (1600, 900),
+    (2048, 1080),
+    (2560, 1440),
+    (4096, 2160),
+    (7680, 4320),
+    (832, 480),
+    (416, 240),
+    (352, 240),
+    (320, 240),
+];
+
+/// Subsamplings to try as (xdec, ydec).
+const SUBSAMPLINGS: &[(usize, usize)] = &[(1, 1), (1, 0), (0, 0), (2, 0)];
+
+/// Luma rows compared in each frame.
+const ROWS: usize = 64;
+
+pub struct Candidate {
+    pub info: FrameInfo,
+    pub frames: u64,
+    /// Mean luma difference on an 8-bit scale, lower is more likely
+    pub cost: f64,
+}
+
+/// Returns the plausible formats of a raw file, most likely first.
+pub fn probe(path: &Path) -> io::Result<Vec<Candidate>> {
+    let mut file = File::open(path)?;
+    let file_len = file.metadata()?.len();
+    let mut candidates = Vec::new();
+    for &(width, height) in SIZES {
+        for bit_depth in [8, 10, 12] {
+            for &(xdec, ydec) in SUBSAMPLINGS {
+                let info = FrameInfo {
+                    width,
+                    height,
+                    bit_depth,
+                    xdec,
+                    ydec,
+                    interlaced: false,
+                    matrix: Matrix::Bt709,
+                    range: Range::Limited,
+                    transfer: Transfer::Sdr,
+                    vision: Vision::Normal,
+                };
+                let (luma_len, chroma_len) = info.plane_lens();
+                let frame_len = (luma_len + 2 * chroma_len) as u64;
+                if file_len == 0 || file_len % frame_len != 0 {
+                    continue;
+                }
+                let frames = file_len / frame_len;
+                if let Some(cost) = cost(&mut file, &info, frame_len, frames)? {
+                    candidates.push(Candidate { info, frames, cost });
+                }
+            }
+        }
+    }
+    candidates.sort_by(|a, b| a.cost.partial_cmp(&b.cost).unwrap());
+    Ok(candidates)
+}
+
+/// Reads the top luma rows of a frame. Returns `None` if a sample is out of
+/// range for the bit depth, or if 12-bit samples would all fit in 10 bits.
+fn read_luma(file: &mut File, info: &FrameInfo, offset: u64) -> io::Result<Option<Vec<u16>>> {
+    let bytewidth = if info.bit_depth > 8 { 2 } else { 1 };
+    let mut buf = vec![0u8; info.width * ROWS.min(info.height) * bytewidth];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut buf)?;
+    let samples: Vec<u16> = if bytewidth == 1 {
+        buf.iter().map(|&s| s as u16).collect()
+    } else {
+        buf.chunks(2)
+            .map(|s| u16::from_le_bytes([s[0], s[1]]))
+            .collect()
+    };
+    let max = samples.iter().copied().max().unwrap_or(0);
+    if max >> info.bit_depth != 0 || (info.bit_depth == 12 && max < 1 << 10) {
+        return Ok(None);
+    }
+    Ok(Some(samples))
+}
+
+fn mean_abs_diff(a: &[u16], b: &[u16]) -> f64 {
+    let sum: u64 = a
+        .iter()
+        .zip(b)
+        .map(|(&a, &b)| (a as i64 - b as i64).unsigned_abs())
+        .sum();
+    sum as f64 / a.len() as f64
+}
+
+fn cost(file: &mut File, info: &FrameInfo, frame_len: u64, frames: u64) -> io::Result<Option<f64>> {
+    let frame0 = match read_luma(file, info, 0)? {
+        Some(frame0) => frame0,
+        None => return Ok(None),
+    };
+    let scale = (1 << (info.bit_depth - 8)) as f64;
+    let mut cost = mean_abs_diff(&frame0, &frame0[info.width..]) / scale;
+    if frames > 1 {
+        let frame1 = match read_luma(file, info, frame_len)? {
+            Some(frame1) => frame1,
+            None => return Ok(None),
+        };
+        cost = (cost + mean_abs_diff(&frame0, &frame1) / scale) / 2.;
+    }
+    Ok(Some(cost))
+}
@@ -0,0 +1,91 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2019, the dump_ciede2000 contributors
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//  list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//  this list of conditions and the following disclaimer in the documentation
+//  and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! A progress line on stderr, for runs that print nothing until the end.
+
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+/// Time between updates of the line
+const INTERVAL: Duration = Duration::from_millis(250);
+
+pub struct Progress {
+    start: Instant,
+    last: Instant,
+    /// Frames expected in all, if known
+    total: Option<usize>,
+    /// Length of the line last printed, to blank what a shorter one leaves
+    width: usize,
+}
+
+impl Progress {
+    pub fn new(total: Option<usize>) -> Self {
+        let start = Instant::now();
+        Progress {
+            start,
+            last: start,
+            total,
+            width: 0,
+        }
+    }
+
+    /// Shows that `frames` frames are done, at most every [`INTERVAL`].
+    pub fn update(&mut self, frames: usize) {
+        let now = Instant::now();
+        if now - self.last >= INTERVAL {
+            self.last = now;
+            self.print(frames);
+        }
+    }
+
+    /// Shows the final count and moves on to the next line.
+    pub fn finish(mut self, frames: usize) {
+        self.total = None;
+        self.print(frames);
+        eprintln!();
+    }
+
+    fn print(&mut self, frames: usize) {
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let fps = if elapsed > 0. {
+            frames as f64 / elapsed
+        } else {
+            0.
+        };
+        let mut line = match self.total {
+            Some(total) => format!("{}/{} frames, {:.1} fps", frames, total.max(frames), fps),
+            None => format!("{} frames, {:.1} fps", frames, fps),
+        };
+        if let Some(total) = self.total.filter(|_| fps > 0.) {
+            let eta = (total.saturating_sub(frames) as f64 / fps).round() as u64;
+            line += &format!(", ETA {}:{:02}:{:02}", eta / 3600, eta / 60 % 60, eta % 60);
+        }
+        let blank = self.width.saturating_sub(line.chars().count());
+        self.width = line.chars().count();
+        // Progress is best effort, so a closed stderr is not an error
+        let _ = write!(io::stderr(), "\r{}{}", line, " ".repeat(blank));
+    }
+}
@@ -0,0 +1,123 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2019, the dump_ciede2000 contributors
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//  list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//  this list of conditions and the following disclaimer in the documentation
+//  and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! The conversion to Lab and DE2000 in f64 throughout, with exact powers and
+//! trigonometry, as a reference for the f32 kernels.
+
+use super::*;
+
+/// Returns a scalar kernel scoring DE2000 in f64 from the samples on, with
+/// the sRGB and PQ curves and the cube root of Lab computed exactly. The
+/// matrices are those of the f32 kernels, widened. The ΔE of each pixel is
+/// rounded to f32 as it is written out.
+///
+/// About twice as slow as the scalar f32 kernel, and without SIMD, so meant
+/// for checking other kernels and for reference numbers rather than for
+/// everyday runs.
+pub fn get_delta_e_row_fn_f64(bit_depth: usize, xdec: usize) -> DeltaERowFn {
+    match (bit_depth, xdec) {
+        (8, 1) => delta_e_row_f64::<Kernel<8, 1>>,
+        (10, 1) => delta_e_row_f64::<Kernel<10, 1>>,
+        (12, 1) => delta_e_row_f64::<Kernel<12, 1>>,
+        (8, 0) => delta_e_row_f64::<Kernel<8, 0>>,
+        (10, 0) => delta_e_row_f64::<Kernel<10, 0>>,
+        (12, 0) => delta_e_row_f64::<Kernel<12, 0>>,
+        (8, 2) => delta_e_row_f64::<Kernel<8, 2>>,
+        (10, 2) => delta_e_row_f64::<Kernel<10, 2>>,
+        (12, 2) => delta_e_row_f64::<Kernel<12, 2>>,
+        _ => unreachable!(),
+    }
+}
+
+unsafe fn delta_e_row_f64<C: DeltaEScalar>(
+    row1: FrameRow,
+    row2: FrameRow,
+    ksub: KSubArgs,
+    res_row: &mut [f32],
+) {
+    let len = row_pixels::<C>(&row1, &row2, res_row.len());
+    for (i, res) in res_row[..len].iter_mut().enumerate() {
+        let lab1 = row1.to_rgb.lab_f64(C::sample(&row1, i));
+        let lab2 = row2.to_rgb.lab_f64(C::sample(&row2, i));
+        *res = DE2000::new_f64(lab1, lab2, ksub) as f32;
+    }
+}
+
+// κ and ε of Lab, as in rgbtolab
+const KAPPA: f64 = 24389.0 / 27.0;
+const EPSILON: f64 = 216.0 / 24389.0;
+
+impl YuvToRgb {
+    fn lab_f64(&self, yuv: (u16, u16, u16)) -> [f64; 3] {
+        let y = (yuv.0 as f64 - self.y_offset as f64) * self.y_scale as f64;
+        let u = (yuv.1 as f64 - self.uv_offset as f64) * self.uv_scale as f64;
+        let v = (yuv.2 as f64 - self.uv_offset as f64) * self.uv_scale as f64;
+        let rgb = if self.gbr {
+            [v, y, u]
+        } else {
+            [
+                y + self.r_v as f64 * v,
+                y - self.g_u as f64 * u - self.g_v as f64 * v,
+                y + self.b_u as f64 * u,
+            ]
+        };
+        let linear = rgb.map(|c| match self.transfer {
+            Transfer::Sdr => srgb_to_linear_f64(c),
+            Transfer::Pq => pq_to_linear_f64(c),
+            Transfer::Linear => c,
+        });
+        let m = self.rgb_to_xyz.map(|row| row.map(|m| m as f64));
+        let [x, y, z] = m.map(|row| row[0] * linear[0] + row[1] * linear[1] + row[2] * linear[2]);
+        let map = |c: f64| {
+            if c > EPSILON {
+                c.cbrt()
+            } else {
+                (KAPPA * c + 16.0) / 116.0
+            }
+        };
+        let (x, y, z) = (map(x / 0.95047), map(y), map(z / 1.08883));
+        [116.0 * y - 16.0, 500.0 * (x - y), 200.0 * (y - z)]
+    }
+}
+
+fn srgb_to_linear_f64(c: f64) -> f64 {
+    if c > 0.04045 {
+        ((c + 0.055) / 1.055).powf(2.4)
+    } else {
+        c / 12.92
+    }
+}
+
+// The PQ EOTF, relative to the reference white, clamped as in rgbtolab
+fn pq_to_linear_f64(c: f64) -> f64 {
+    const M1: f64 = 2610.0 / 16384.0;
+    const M2: f64 = 2523.0 / 4096.0 * 128.0;
+    const C1: f64 = 3424.0 / 4096.0;
+    const C2: f64 = 2413.0 / 4096.0 * 32.0;
+    const C3: f64 = 2392.0 / 4096.0 * 32.0;
+    let p = c.clamp(0.0, 1.0).powf(1.0 / M2);
+    ((p - C1).max(0.0) / (C2 - C3 * p)).powf(1.0 / M1) * (10000.0 / PQ_REFERENCE_WHITE as f64)
+}
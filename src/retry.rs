@@ -0,0 +1,107 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2019, the dump_ciede2000 contributors
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//  list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//  this list of conditions and the following disclaimer in the documentation
+//  and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Retrying failed reads of inputs on network filesystems, such as NFS or
+//! HTTP mounts, where errors are often transient.
+
+use std::cell::Cell;
+use std::fs::File;
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
+
+/// Wait before the first retry of a read, growing by as much for each
+/// further one
+const RETRY_DELAY: Duration = Duration::from_millis(250);
+
+/// Reads a file, retrying a failed read up to `max_retries` times in a row.
+/// Each retry reopens the file and seeks back to where reading stopped, which
+/// also recovers from stale NFS handles. Anything but a regular file is read
+/// without retries, since it cannot be resumed.
+pub struct RetryReader {
+    file: File,
+    path: PathBuf,
+    /// Bytes read so far, where a retry resumes
+    offset: u64,
+    max_retries: usize,
+    retries: Rc<Cell<u64>>,
+}
+
+impl RetryReader {
+    pub fn new(file: File, path: PathBuf, max_retries: usize) -> Self {
+        let seekable = file.metadata().is_ok_and(|metadata| metadata.is_file());
+        RetryReader {
+            file,
+            path,
+            offset: 0,
+            max_retries: if seekable { max_retries } else { 0 },
+            retries: Rc::new(Cell::new(0)),
+        }
+    }
+
+    /// Number of retries made so far, updated as reading goes on.
+    pub fn retries(&self) -> Rc<Cell<u64>> {
+        self.retries.clone()
+    }
+
+    fn reopen(&mut self) -> io::Result<()> {
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(self.offset))?;
+        self.file = file;
+        Ok(())
+    }
+}
+
+impl Read for RetryReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut retries = 0;
+        loop {
+            match self.file.read(buf) {
+                Ok(len) => {
+                    self.offset += len as u64;
+                    return Ok(len);
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted || retries == self.max_retries => {
+                    return Err(e)
+                }
+                Err(_) => {}
+            }
+            // A failure to reopen or seek uses up a retry too
+            loop {
+                retries += 1;
+                self.retries.set(self.retries.get() + 1);
+                thread::sleep(RETRY_DELAY * retries as u32);
+                match self.reopen() {
+                    Ok(()) => break,
+                    Err(e) if retries == self.max_retries => return Err(e),
+                    Err(_) => {}
+                }
+            }
+        }
+    }
+}
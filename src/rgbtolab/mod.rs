@@ -1,5 +1,7 @@
 // Modified version of https://github.com/TooManyBees/lab
 
+#![allow(clippy::excessive_precision)]
+
 use lab::Lab;
 
 // κ and ε parameters used in conversion between XYZ and La*b*.  See
@@ -8,24 +10,85 @@ use lab::Lab;
 const KAPPA: f32 = 24389.0 / 27.0;
 const EPSILON: f32 = 216.0 / 24389.0;
 
+/// Linear sRGB to XYZ (D65)
+pub const SRGB_TO_XYZ: [[f32; 3]; 3] = [
+    [0.4124564390896921, 0.357576077643909, 0.18043748326639894],
+    [0.21267285140562248, 0.715152155287818, 0.07217499330655958],
+    [0.019333895582329317, 0.119192025881303, 0.9503040785363677],
+];
+
+/// XYZ (D65) to linear sRGB
+pub const XYZ_TO_SRGB: [[f32; 3]; 3] = [
+    [3.2404542, -1.5371385, -0.4985314],
+    [-0.9692660, 1.8760108, 0.0415560],
+    [0.0556434, -0.2040259, 1.0572252],
+];
+
+/// Linear BT.2020 RGB to XYZ (D65)
+pub const BT2020_TO_XYZ: [[f32; 3]; 3] = [
+    [0.6369580483012914, 0.14461690358620832, 0.1688809751641721],
+    [0.2627002120112671, 0.6779980715188708, 0.05930171646986196],
+    [0.0, 0.028072693049087428, 1.060985057710791],
+];
+
+/// XYZ (D65) to linear BT.2020 RGB
+pub const XYZ_TO_BT2020: [[f32; 3]; 3] = [
+    [1.7166511880, -0.3556707838, -0.2533662814],
+    [-0.6666843518, 1.6164812366, 0.0157685458],
+    [0.0176398574, -0.0427706133, 0.9421031212],
+];
+
+/// Luminance in cd/m² that PQ encoded light is taken relative to, the
+/// reference white of BT.2408
+pub(crate) const PQ_REFERENCE_WHITE: f32 = 203.0;
+
+// Constants of the PQ curve of SMPTE ST 2084
+const PQ_M1: f32 = 2610.0 / 16384.0;
+const PQ_M2: f32 = 2523.0 / 4096.0 * 128.0;
+const PQ_C1: f32 = 3424.0 / 4096.0;
+const PQ_C2: f32 = 2413.0 / 4096.0 * 32.0;
+const PQ_C3: f32 = 2392.0 / 4096.0 * 32.0;
+
 pub fn rgb_to_lab(rgb: &[f32; 3]) -> Lab {
-    xyz_to_lab(rgb_to_xyz(rgb))
+    rgb_to_lab_with(rgb, &SRGB_TO_XYZ)
+}
+
+/// Like `rgb_to_lab`, with `to_xyz` in place of `SRGB_TO_XYZ`.
+pub fn rgb_to_lab_with(rgb: &[f32; 3], to_xyz: &[[f32; 3]; 3]) -> Lab {
+    xyz_to_lab(rgb_to_xyz(rgb, to_xyz))
+}
+
+/// Like `rgb_to_lab_with`, for RGB encoded with the PQ transfer function of
+/// SMPTE ST 2084. Light brighter than the reference white has an L* above 100.
+pub fn pq_rgb_to_lab_with(rgb: &[f32; 3], to_xyz: &[[f32; 3]; 3]) -> Lab {
+    let linear = [pq_to_linear(rgb[0]), pq_to_linear(rgb[1]), pq_to_linear(rgb[2])];
+    xyz_to_lab(linear_to_xyz(&linear, to_xyz))
+}
+
+/// Like `rgb_to_lab_with`, for linear RGB.
+pub fn linear_rgb_to_lab_with(rgb: &[f32; 3], to_xyz: &[[f32; 3]; 3]) -> Lab {
+    xyz_to_lab(linear_to_xyz(rgb, to_xyz))
 }
 
-fn rgb_to_xyz(rgb: &[f32; 3]) -> [f32; 3] {
-    let r = rgb_to_xyz_map(rgb[0]);
-    let g = rgb_to_xyz_map(rgb[1]);
-    let b = rgb_to_xyz_map(rgb[2]);
+#[inline]
+fn rgb_to_xyz(rgb: &[f32; 3], m: &[[f32; 3]; 3]) -> [f32; 3] {
+    let linear = [srgb_to_linear(rgb[0]), srgb_to_linear(rgb[1]), srgb_to_linear(rgb[2])];
+    linear_to_xyz(&linear, m)
+}
+
+#[inline]
+pub(crate) fn linear_to_xyz(rgb: &[f32; 3], m: &[[f32; 3]; 3]) -> [f32; 3] {
+    let [r, g, b] = *rgb;
 
     [
-        r * 0.4124564390896921 + g * 0.357576077643909 + b * 0.18043748326639894,
-        r * 0.21267285140562248 + g * 0.715152155287818 + b * 0.07217499330655958,
-        r * 0.019333895582329317 + g * 0.119192025881303 + b * 0.9503040785363677,
+        r * m[0][0] + g * m[0][1] + b * m[0][2],
+        r * m[1][0] + g * m[1][1] + b * m[1][2],
+        r * m[2][0] + g * m[2][1] + b * m[2][2],
     ]
 }
 
 #[inline]
-fn rgb_to_xyz_map(c: f32) -> f32 {
+pub(crate) fn srgb_to_linear(c: f32) -> f32 {
     if c > 10. / 255. {
         const A: f32 = 0.055;
         const D: f32 = 1.0 / 1.055;
@@ -36,6 +99,28 @@ fn rgb_to_xyz_map(c: f32) -> f32 {
     }
 }
 
+// The inverse of `srgb_to_linear`.
+pub(crate) fn linear_to_srgb(y: f32) -> f32 {
+    if y > 10. / 255. / 12.92 {
+        1.055 * y.powf(1.0 / 2.4) - 0.055
+    } else {
+        y * 12.92
+    }
+}
+
+// The PQ EOTF, relative to PQ_REFERENCE_WHITE. Values outside of [0, 1] are
+// clamped, since the curve is undefined below 0.
+pub(crate) fn pq_to_linear(c: f32) -> f32 {
+    let p = c.clamp(0.0, 1.0).powf(1.0 / PQ_M2);
+    ((p - PQ_C1).max(0.0) / (PQ_C2 - PQ_C3 * p)).powf(1.0 / PQ_M1) * (10000.0 / PQ_REFERENCE_WHITE)
+}
+
+// The inverse of the PQ EOTF, for light relative to 10000 cd/m².
+pub(crate) fn linear_to_pq(y: f32) -> f32 {
+    let p = y.max(0.0).powf(PQ_M1);
+    ((PQ_C1 + PQ_C2 * p) / (1.0 + PQ_C3 * p)).powf(PQ_M2)
+}
+
 fn xyz_to_lab(xyz: [f32; 3]) -> Lab {
     let x = xyz_to_lab_map(xyz[0] * (1.0 / 0.95047));
     let y = xyz_to_lab_map(xyz[1]);
@@ -248,43 +333,77 @@ mod avx2 {
             }
         };
         ( $(($vec:expr, $mul:expr)),* ) => {
-            sum_mult_avx!((0.0), $(($vec, $mul)),*);
+            sum_mult_avx!((0.0), $(($vec, $mul)),*)
         };
     }
 
+    /// # Safety
+    ///
+    /// The CPU must support AVX2.
     #[target_feature(enable = "avx2")]
     pub unsafe fn rgb_to_lab_avx2(rgb: &[__m256; 3]) -> [Lab; 8] {
-        xyz_to_lab_avx2(rgb_to_xyz_avx2(rgb))
+        rgb_to_lab_with_avx2(rgb, &SRGB_TO_XYZ)
     }
 
+    /// # Safety
+    ///
+    /// The CPU must support AVX2.
     #[target_feature(enable = "avx2")]
-    unsafe fn rgb_to_xyz_avx2(rgb: &[__m256; 3]) -> [__m256; 3] {
-        let r = rgb_to_xyz_map_avx2(rgb[0]);
-        let g = rgb_to_xyz_map_avx2(rgb[1]);
-        let b = rgb_to_xyz_map_avx2(rgb[2]);
-
-        let x = sum_mult_avx!(
-            (r, 0.4124564390896921),
-            (g, 0.357576077643909),
-            (b, 0.18043748326639894)
-        );
-        let y = sum_mult_avx!(
-            (r, 0.21267285140562248),
-            (g, 0.715152155287818),
-            (b, 0.07217499330655958)
-        );
-        let z = sum_mult_avx!(
-            (r, 0.019333895582329317),
-            (g, 0.119192025881303),
-            (b, 0.9503040785363677)
-        );
+    pub unsafe fn rgb_to_lab_with_avx2(rgb: &[__m256; 3], to_xyz: &[[f32; 3]; 3]) -> [Lab; 8] {
+        xyz_to_lab_avx2(rgb_to_xyz_avx2(rgb, to_xyz))
+    }
+
+    /// # Safety
+    ///
+    /// The CPU must support AVX2.
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn linear_rgb_to_lab_with_avx2(
+        rgb: &[__m256; 3],
+        to_xyz: &[[f32; 3]; 3],
+    ) -> [Lab; 8] {
+        xyz_to_lab_avx2(linear_to_xyz_avx2(rgb, to_xyz))
+    }
+
+    /// Like `pq_rgb_to_lab_with`, converting each lane in turn.
+    ///
+    /// # Safety
+    ///
+    /// The CPU must support AVX2.
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn pq_rgb_to_lab_with_avx2(rgb: &[__m256; 3], to_xyz: &[[f32; 3]; 3]) -> [Lab; 8] {
+        let mut lanes = [[0f32; 8]; 3];
+        for (lane, &c) in lanes.iter_mut().zip(rgb) {
+            _mm256_storeu_ps(lane.as_mut_ptr(), c);
+        }
+        std::array::from_fn(|i| pq_rgb_to_lab_with(&[lanes[0][i], lanes[1][i], lanes[2][i]], to_xyz))
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn rgb_to_xyz_avx2(rgb: &[__m256; 3], m: &[[f32; 3]; 3]) -> [__m256; 3] {
+        let linear = [
+            srgb_to_linear_avx2(rgb[0]),
+            srgb_to_linear_avx2(rgb[1]),
+            srgb_to_linear_avx2(rgb[2]),
+        ];
+        linear_to_xyz_avx2(&linear, m)
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn linear_to_xyz_avx2(rgb: &[__m256; 3], m: &[[f32; 3]; 3]) -> [__m256; 3] {
+        let [r, g, b] = *rgb;
+
+        let x = sum_mult_avx!((r, m[0][0]), (g, m[0][1]), (b, m[0][2]));
+        let y = sum_mult_avx!((r, m[1][0]), (g, m[1][1]), (b, m[1][2]));
+        let z = sum_mult_avx!((r, m[2][0]), (g, m[2][1]), (b, m[2][2]));
 
         [x, y, z]
     }
 
     #[inline]
     #[target_feature(enable = "avx2")]
-    unsafe fn rgb_to_xyz_map_avx2(c: __m256) -> __m256 {
+    unsafe fn srgb_to_linear_avx2(c: __m256) -> __m256 {
         let low = _mm256_mul_ps(c, _mm256_set1_ps(1.0 / 12.92));
         let hi = pow_2_4_avx2(_mm256_mul_ps(
             _mm256_add_ps(c, _mm256_set1_ps(0.055)),
@@ -463,3 +582,1032 @@ mod avx2 {
         _mm256_mul_ps(est, _mm256_mul_ps(truncated_cbrt, exp_cbrt))
     }
 }
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub use self::avx512::*;
+
+// Same as the avx2 versions 16 lanes at a time, with the same operations in
+// the same order so both give the same results
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod avx512 {
+    use super::*;
+
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::*;
+
+    // The 8 entries twice, so indexes wrap at 8 as with _mm256_permutevar8x32_ps
+    macro_rules! lookup_table_8_avx512 {
+        (start: $start:expr, closure: $closure:expr) => {
+            _mm512_setr_ps(
+                $closure($start + 0),
+                $closure($start + 1),
+                $closure($start + 2),
+                $closure($start + 3),
+                $closure($start + 4),
+                $closure($start + 5),
+                $closure($start + 6),
+                $closure($start + 7),
+                $closure($start + 0),
+                $closure($start + 1),
+                $closure($start + 2),
+                $closure($start + 3),
+                $closure($start + 4),
+                $closure($start + 5),
+                $closure($start + 6),
+                $closure($start + 7),
+            )
+        };
+    }
+
+    macro_rules! lookup_table_16_avx512 {
+        (start: $start:expr, closure: $closure:expr) => {
+            _mm512_setr_ps(
+                $closure($start + 0),
+                $closure($start + 1),
+                $closure($start + 2),
+                $closure($start + 3),
+                $closure($start + 4),
+                $closure($start + 5),
+                $closure($start + 6),
+                $closure($start + 7),
+                $closure($start + 8),
+                $closure($start + 9),
+                $closure($start + 10),
+                $closure($start + 11),
+                $closure($start + 12),
+                $closure($start + 13),
+                $closure($start + 14),
+                $closure($start + 15),
+            )
+        };
+    }
+
+    macro_rules! sum_mult_avx512 {
+        (($init:expr), $(($vec:expr, $mul:expr)),* ) => {
+            {
+                let mut sum = _mm512_set1_ps($init);
+                $(
+                    sum = _mm512_add_ps(sum, _mm512_mul_ps($vec, _mm512_set1_ps($mul)));
+                )*
+                sum
+            }
+        };
+        ( $(($vec:expr, $mul:expr)),* ) => {
+            sum_mult_avx512!((0.0), $(($vec, $mul)),*)
+        };
+    }
+
+    /// # Safety
+    ///
+    /// The CPU must support AVX-512F.
+    #[target_feature(enable = "avx512f")]
+    pub unsafe fn rgb_to_lab_with_avx512(rgb: &[__m512; 3], to_xyz: &[[f32; 3]; 3]) -> [Lab; 16] {
+        xyz_to_lab_avx512(rgb_to_xyz_avx512(rgb, to_xyz))
+    }
+
+    /// # Safety
+    ///
+    /// The CPU must support AVX-512F.
+    #[target_feature(enable = "avx512f")]
+    pub unsafe fn linear_rgb_to_lab_with_avx512(
+        rgb: &[__m512; 3],
+        to_xyz: &[[f32; 3]; 3],
+    ) -> [Lab; 16] {
+        xyz_to_lab_avx512(linear_to_xyz_avx512(rgb, to_xyz))
+    }
+
+    /// Like `pq_rgb_to_lab_with`, converting each lane in turn.
+    ///
+    /// # Safety
+    ///
+    /// The CPU must support AVX-512F.
+    #[target_feature(enable = "avx512f")]
+    pub unsafe fn pq_rgb_to_lab_with_avx512(
+        rgb: &[__m512; 3],
+        to_xyz: &[[f32; 3]; 3],
+    ) -> [Lab; 16] {
+        let mut lanes = [[0f32; 16]; 3];
+        for (lane, &c) in lanes.iter_mut().zip(rgb) {
+            _mm512_storeu_ps(lane.as_mut_ptr(), c);
+        }
+        std::array::from_fn(|i| pq_rgb_to_lab_with(&[lanes[0][i], lanes[1][i], lanes[2][i]], to_xyz))
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f")]
+    unsafe fn rgb_to_xyz_avx512(rgb: &[__m512; 3], m: &[[f32; 3]; 3]) -> [__m512; 3] {
+        let linear = [
+            srgb_to_linear_avx512(rgb[0]),
+            srgb_to_linear_avx512(rgb[1]),
+            srgb_to_linear_avx512(rgb[2]),
+        ];
+        linear_to_xyz_avx512(&linear, m)
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f")]
+    unsafe fn linear_to_xyz_avx512(rgb: &[__m512; 3], m: &[[f32; 3]; 3]) -> [__m512; 3] {
+        let [r, g, b] = *rgb;
+
+        let x = sum_mult_avx512!((r, m[0][0]), (g, m[0][1]), (b, m[0][2]));
+        let y = sum_mult_avx512!((r, m[1][0]), (g, m[1][1]), (b, m[1][2]));
+        let z = sum_mult_avx512!((r, m[2][0]), (g, m[2][1]), (b, m[2][2]));
+
+        [x, y, z]
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f")]
+    unsafe fn srgb_to_linear_avx512(c: __m512) -> __m512 {
+        let low = _mm512_mul_ps(c, _mm512_set1_ps(1.0 / 12.92));
+        let hi = pow_2_4_avx512(_mm512_mul_ps(
+            _mm512_add_ps(c, _mm512_set1_ps(0.055)),
+            _mm512_set1_ps(1.0 / 1.055),
+        ));
+        let select = _mm512_cmp_ps_mask(c, _mm512_set1_ps(10. / 255.), _CMP_GT_OS);
+        _mm512_mask_blend_ps(select, low, hi)
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f")]
+    unsafe fn xyz_to_lab_avx512(xyz: [__m512; 3]) -> [Lab; 16] {
+        let x = xyz_to_lab_map_avx512(_mm512_mul_ps(xyz[0], _mm512_set1_ps(1.0 / 0.95047)));
+        let y = xyz_to_lab_map_avx512(xyz[1]);
+        let z = xyz_to_lab_map_avx512(_mm512_mul_ps(xyz[2], _mm512_set1_ps(1.0 / 1.08883)));
+
+        let l = _mm512_sub_ps(
+            _mm512_mul_ps(_mm512_set1_ps(116.0), y),
+            _mm512_set1_ps(16.0),
+        );
+        let a = _mm512_mul_ps(_mm512_sub_ps(x, y), _mm512_set1_ps(500.0));
+        let b = _mm512_mul_ps(_mm512_sub_ps(y, z), _mm512_set1_ps(200.0));
+
+        let l: [f32; 16] = std::mem::transmute(l);
+        let a: [f32; 16] = std::mem::transmute(a);
+        let b: [f32; 16] = std::mem::transmute(b);
+        std::array::from_fn(|i| Lab {
+            l: l[i],
+            a: a[i],
+            b: b[i],
+        })
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f")]
+    unsafe fn xyz_to_lab_map_avx512(c: __m512) -> __m512 {
+        let low = _mm512_mul_ps(
+            _mm512_add_ps(
+                _mm512_mul_ps(c, _mm512_set1_ps(KAPPA)),
+                _mm512_set1_ps(16.0),
+            ),
+            _mm512_set1_ps(1.0 / 116.0),
+        );
+        let hi = cbrt_approx_avx512(c);
+        let select = _mm512_cmp_ps_mask(c, _mm512_set1_ps(EPSILON), _CMP_GT_OS);
+        _mm512_mask_blend_ps(select, low, hi)
+    }
+
+    // Zeroes the exponent of x, or divides it by 2^log2
+    #[inline]
+    #[target_feature(enable = "avx512f")]
+    unsafe fn mantissa_avx512(bits: __m512i) -> __m512 {
+        _mm512_castsi512_ps(_mm512_or_si512(
+            _mm512_and_si512(bits, _mm512_set1_epi32(0x807fffffu32 as i32)),
+            _mm512_set1_epi32(0x3f800000),
+        ))
+    }
+
+    #[target_feature(enable = "avx512f")]
+    unsafe fn pow_2_4_avx512(x: __m512) -> __m512 {
+        // See non-avx2 version
+
+        const FRAC_BITS: u32 = 3;
+
+        let bits = _mm512_castps_si512(x);
+
+        let log2_index =
+            _mm512_add_epi32(_mm512_srli_epi32(bits, 23), _mm512_set1_epi32(-0x7f + 4));
+
+        let lookup_entry_exp_pow_2_4 =
+            |log2: i32| (f32::from_bits(((log2 + 0x7f) << 23) as u32) as f64).powf(2.4) as f32;
+        let lookup_table_exp_pow_2_4 =
+            lookup_table_8_avx512!(start: -4, closure: lookup_entry_exp_pow_2_4);
+
+        let exp_pow_2_4 = _mm512_permutexvar_ps(log2_index, lookup_table_exp_pow_2_4);
+
+        let x = mantissa_avx512(bits);
+
+        let lookup_entry_inv_truncated = |fraction: i32| {
+            let truncated = 1.0 + (fraction as f64 + 0.5) / ((1 << FRAC_BITS) as f64);
+            (1.0 / truncated) as f32
+        };
+        let lookup_table_inv_truncated =
+            lookup_table_8_avx512!(start: 0, closure: lookup_entry_inv_truncated);
+        let lookup_entry_truncated_pow_2_4 =
+            |fraction: i32| (lookup_entry_inv_truncated(fraction) as f64).powf(-2.4) as f32;
+        let lookup_table_truncated_pow_2_4 =
+            lookup_table_8_avx512!(start: 0, closure: lookup_entry_truncated_pow_2_4);
+
+        // No reason to mask the higher bits
+        let fraction = _mm512_srli_epi32(bits, 23 - FRAC_BITS);
+        let truncated_pow_2_4 = _mm512_permutexvar_ps(fraction, lookup_table_truncated_pow_2_4);
+        let x = _mm512_mul_ps(
+            x,
+            _mm512_permutexvar_ps(fraction, lookup_table_inv_truncated),
+        );
+
+        let x2 = _mm512_mul_ps(x, x);
+        let x3 = _mm512_mul_ps(x2, x);
+        let est = sum_mult_avx512!(
+            (7.0 / 125.0),
+            (x, -36. / 125.),
+            (x2, 126. / 125.),
+            (x3, 28. / 125.)
+        );
+
+        _mm512_mul_ps(est, _mm512_mul_ps(truncated_pow_2_4, exp_pow_2_4))
+    }
+
+    #[target_feature(enable = "avx512f")]
+    unsafe fn cbrt_approx_avx512(x: __m512) -> __m512 {
+        // See non-avx2 version
+
+        const FRAC_BITS: u32 = 3;
+
+        let bits = _mm512_castps_si512(x);
+
+        let log2_index =
+            _mm512_add_epi32(_mm512_srli_epi32(bits, 23), _mm512_set1_epi32(-0x7f + 7));
+
+        let lookup_entry_exp_cbrt =
+            |log2: i32| (f32::from_bits(((log2 + 0x7f) << 23) as u32) as f64).powf(1. / 3.) as f32;
+        let lookup_table_exp_cbrt =
+            lookup_table_16_avx512!(start: -7, closure: lookup_entry_exp_cbrt);
+
+        let exp_cbrt = _mm512_permutexvar_ps(log2_index, lookup_table_exp_cbrt);
+
+        let x = mantissa_avx512(bits);
+
+        let lookup_entry_inv_truncated = |fraction: i32| {
+            let truncated = 1.0 + (fraction as f64 + 0.5) / ((1 << FRAC_BITS) as f64);
+            (1.0 / truncated) as f32
+        };
+        let lookup_table_inv_truncated =
+            lookup_table_8_avx512!(start: 0, closure: lookup_entry_inv_truncated);
+        let lookup_entry_truncated_cbrt =
+            |fraction: i32| (lookup_entry_inv_truncated(fraction) as f64).powf(-1. / 3.) as f32;
+        let lookup_table_truncated_cbrt =
+            lookup_table_8_avx512!(start: 0, closure: lookup_entry_truncated_cbrt);
+
+        // No reason to mask the higher bits
+        let fraction = _mm512_srli_epi32(bits, 23 - FRAC_BITS);
+        let truncated_cbrt = _mm512_permutexvar_ps(fraction, lookup_table_truncated_cbrt);
+        let x = _mm512_mul_ps(
+            x,
+            _mm512_permutexvar_ps(fraction, lookup_table_inv_truncated),
+        );
+
+        let x2 = _mm512_mul_ps(x, x);
+        let x3 = _mm512_mul_ps(x2, x);
+        let est = sum_mult_avx512!(
+            (40. / 81.0),
+            (x, 60. / 81.),
+            (x2, -24. / 81.),
+            (x3, 5. / 81.)
+        );
+
+        _mm512_mul_ps(est, _mm512_mul_ps(truncated_cbrt, exp_cbrt))
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+pub use self::neon::*;
+
+// Same as the avx2 versions 4 lanes at a time, with the same operations in the
+// same order so all give the same results
+#[cfg(target_arch = "aarch64")]
+mod neon {
+    use super::*;
+
+    use std::arch::aarch64::*;
+
+    macro_rules! sum_mult_neon {
+        (($init:expr), $(($vec:expr, $mul:expr)),* ) => {
+            {
+                let mut sum = vdupq_n_f32($init);
+                $(
+                    sum = vaddq_f32(sum, vmulq_f32($vec, vdupq_n_f32($mul)));
+                )*
+                sum
+            }
+        };
+        ( $(($vec:expr, $mul:expr)),* ) => {
+            sum_mult_neon!((0.0), $(($vec, $mul)),*)
+        };
+    }
+
+    // Looks up each lane of `index`, wrapping at the length of `table` like
+    // the avx2 permutes
+    #[inline]
+    #[target_feature(enable = "neon")]
+    unsafe fn lookup_neon<const N: usize>(table: &[f32; N], index: uint32x4_t) -> float32x4_t {
+        let mut lanes = [0u32; 4];
+        vst1q_u32(lanes.as_mut_ptr(), index);
+        let values = lanes.map(|i| table[i as usize % N]);
+        vld1q_f32(values.as_ptr())
+    }
+
+    /// # Safety
+    ///
+    /// The CPU must support NEON.
+    #[target_feature(enable = "neon")]
+    pub unsafe fn rgb_to_lab_with_neon(rgb: &[float32x4_t; 3], to_xyz: &[[f32; 3]; 3]) -> [Lab; 4] {
+        xyz_to_lab_neon(rgb_to_xyz_neon(rgb, to_xyz))
+    }
+
+    /// # Safety
+    ///
+    /// The CPU must support NEON.
+    #[target_feature(enable = "neon")]
+    pub unsafe fn linear_rgb_to_lab_with_neon(
+        rgb: &[float32x4_t; 3],
+        to_xyz: &[[f32; 3]; 3],
+    ) -> [Lab; 4] {
+        xyz_to_lab_neon(linear_to_xyz_neon(rgb, to_xyz))
+    }
+
+    /// Like `pq_rgb_to_lab_with`, converting each lane in turn.
+    ///
+    /// # Safety
+    ///
+    /// The CPU must support NEON.
+    #[target_feature(enable = "neon")]
+    pub unsafe fn pq_rgb_to_lab_with_neon(
+        rgb: &[float32x4_t; 3],
+        to_xyz: &[[f32; 3]; 3],
+    ) -> [Lab; 4] {
+        let mut lanes = [[0f32; 4]; 3];
+        for (lane, &c) in lanes.iter_mut().zip(rgb) {
+            vst1q_f32(lane.as_mut_ptr(), c);
+        }
+        std::array::from_fn(|i| {
+            pq_rgb_to_lab_with(&[lanes[0][i], lanes[1][i], lanes[2][i]], to_xyz)
+        })
+    }
+
+    #[inline]
+    #[target_feature(enable = "neon")]
+    unsafe fn rgb_to_xyz_neon(rgb: &[float32x4_t; 3], m: &[[f32; 3]; 3]) -> [float32x4_t; 3] {
+        let linear = [
+            srgb_to_linear_neon(rgb[0]),
+            srgb_to_linear_neon(rgb[1]),
+            srgb_to_linear_neon(rgb[2]),
+        ];
+        linear_to_xyz_neon(&linear, m)
+    }
+
+    #[inline]
+    #[target_feature(enable = "neon")]
+    unsafe fn linear_to_xyz_neon(rgb: &[float32x4_t; 3], m: &[[f32; 3]; 3]) -> [float32x4_t; 3] {
+        let [r, g, b] = *rgb;
+
+        let x = sum_mult_neon!((r, m[0][0]), (g, m[0][1]), (b, m[0][2]));
+        let y = sum_mult_neon!((r, m[1][0]), (g, m[1][1]), (b, m[1][2]));
+        let z = sum_mult_neon!((r, m[2][0]), (g, m[2][1]), (b, m[2][2]));
+
+        [x, y, z]
+    }
+
+    #[inline]
+    #[target_feature(enable = "neon")]
+    unsafe fn srgb_to_linear_neon(c: float32x4_t) -> float32x4_t {
+        let low = vmulq_f32(c, vdupq_n_f32(1.0 / 12.92));
+        let hi = pow_2_4_neon(vmulq_f32(
+            vaddq_f32(c, vdupq_n_f32(0.055)),
+            vdupq_n_f32(1.0 / 1.055),
+        ));
+        let select = vcgtq_f32(c, vdupq_n_f32(10. / 255.));
+        vbslq_f32(select, hi, low)
+    }
+
+    #[inline]
+    #[target_feature(enable = "neon")]
+    unsafe fn xyz_to_lab_neon(xyz: [float32x4_t; 3]) -> [Lab; 4] {
+        let x = xyz_to_lab_map_neon(vmulq_f32(xyz[0], vdupq_n_f32(1.0 / 0.95047)));
+        let y = xyz_to_lab_map_neon(xyz[1]);
+        let z = xyz_to_lab_map_neon(vmulq_f32(xyz[2], vdupq_n_f32(1.0 / 1.08883)));
+
+        let l = vsubq_f32(vmulq_f32(vdupq_n_f32(116.0), y), vdupq_n_f32(16.0));
+        let a = vmulq_f32(vsubq_f32(x, y), vdupq_n_f32(500.0));
+        let b = vmulq_f32(vsubq_f32(y, z), vdupq_n_f32(200.0));
+
+        let mut lanes = [[0f32; 4]; 3];
+        for (lane, &c) in lanes.iter_mut().zip(&[l, a, b]) {
+            vst1q_f32(lane.as_mut_ptr(), c);
+        }
+        std::array::from_fn(|i| Lab {
+            l: lanes[0][i],
+            a: lanes[1][i],
+            b: lanes[2][i],
+        })
+    }
+
+    #[inline]
+    #[target_feature(enable = "neon")]
+    unsafe fn xyz_to_lab_map_neon(c: float32x4_t) -> float32x4_t {
+        let low = vmulq_f32(
+            vaddq_f32(vmulq_f32(c, vdupq_n_f32(KAPPA)), vdupq_n_f32(16.0)),
+            vdupq_n_f32(1.0 / 116.0),
+        );
+        let hi = cbrt_approx_neon(c);
+        let select = vcgtq_f32(c, vdupq_n_f32(EPSILON));
+        vbslq_f32(select, hi, low)
+    }
+
+    // Zeroes the exponent of x, or divides it by 2^log2
+    #[inline]
+    #[target_feature(enable = "neon")]
+    unsafe fn mantissa_neon(bits: uint32x4_t) -> float32x4_t {
+        vreinterpretq_f32_u32(vorrq_u32(
+            vandq_u32(bits, vdupq_n_u32(0x807fffff)),
+            vdupq_n_u32(0x3f800000),
+        ))
+    }
+
+    // 23 - FRAC_BITS of pow_2_4 and cbrt_approx, where the fraction starts
+    const FRACTION_SHIFT: i32 = 20;
+
+    #[target_feature(enable = "neon")]
+    unsafe fn pow_2_4_neon(x: float32x4_t) -> float32x4_t {
+        // See non-avx2 version
+
+        const FRAC_BITS: u32 = 3;
+
+        let bits = vreinterpretq_u32_f32(x);
+
+        let log2_index = vaddq_u32(vshrq_n_u32::<23>(bits), vdupq_n_u32((-0x7f + 4) as u32));
+
+        let lookup_entry_exp_pow_2_4 =
+            |log2: i32| (f32::from_bits(((log2 + 0x7f) << 23) as u32) as f64).powf(2.4) as f32;
+        let lookup_table_exp_pow_2_4 =
+            lookup_table_8!(start: -4, closure: lookup_entry_exp_pow_2_4);
+
+        let exp_pow_2_4 = lookup_neon(&lookup_table_exp_pow_2_4, log2_index);
+
+        let x = mantissa_neon(bits);
+
+        let lookup_entry_inv_truncated = |fraction: i32| {
+            let truncated = 1.0 + (fraction as f64 + 0.5) / ((1 << FRAC_BITS) as f64);
+            (1.0 / truncated) as f32
+        };
+        let lookup_table_inv_truncated =
+            lookup_table_8!(start: 0, closure: lookup_entry_inv_truncated);
+        let lookup_entry_truncated_pow_2_4 =
+            |fraction: i32| (lookup_entry_inv_truncated(fraction) as f64).powf(-2.4) as f32;
+        let lookup_table_truncated_pow_2_4 =
+            lookup_table_8!(start: 0, closure: lookup_entry_truncated_pow_2_4);
+
+        // No reason to mask the higher bits
+        let fraction = vshrq_n_u32::<FRACTION_SHIFT>(bits);
+        let truncated_pow_2_4 = lookup_neon(&lookup_table_truncated_pow_2_4, fraction);
+        let x = vmulq_f32(x, lookup_neon(&lookup_table_inv_truncated, fraction));
+
+        let x2 = vmulq_f32(x, x);
+        let x3 = vmulq_f32(x2, x);
+        let est = sum_mult_neon!(
+            (7.0 / 125.0),
+            (x, -36. / 125.),
+            (x2, 126. / 125.),
+            (x3, 28. / 125.)
+        );
+
+        vmulq_f32(est, vmulq_f32(truncated_pow_2_4, exp_pow_2_4))
+    }
+
+    #[target_feature(enable = "neon")]
+    unsafe fn cbrt_approx_neon(x: float32x4_t) -> float32x4_t {
+        // See non-avx2 version
+
+        const FRAC_BITS: u32 = 3;
+
+        let bits = vreinterpretq_u32_f32(x);
+
+        let log2_index = vaddq_u32(vshrq_n_u32::<23>(bits), vdupq_n_u32((-0x7f + 7) as u32));
+
+        let lookup_entry_exp_cbrt =
+            |log2: i32| (f32::from_bits(((log2 + 0x7f) << 23) as u32) as f64).powf(1. / 3.) as f32;
+        let lookup_table_exp_cbrt = lookup_table_16!(start: -7, closure: lookup_entry_exp_cbrt);
+
+        let exp_cbrt = lookup_neon(&lookup_table_exp_cbrt, log2_index);
+
+        let x = mantissa_neon(bits);
+
+        let lookup_entry_inv_truncated = |fraction: i32| {
+            let truncated = 1.0 + (fraction as f64 + 0.5) / ((1 << FRAC_BITS) as f64);
+            (1.0 / truncated) as f32
+        };
+        let lookup_table_inv_truncated =
+            lookup_table_8!(start: 0, closure: lookup_entry_inv_truncated);
+        let lookup_entry_truncated_cbrt =
+            |fraction: i32| (lookup_entry_inv_truncated(fraction) as f64).powf(-1. / 3.) as f32;
+        let lookup_table_truncated_cbrt =
+            lookup_table_8!(start: 0, closure: lookup_entry_truncated_cbrt);
+
+        // No reason to mask the higher bits
+        let fraction = vshrq_n_u32::<FRACTION_SHIFT>(bits);
+        let truncated_cbrt = lookup_neon(&lookup_table_truncated_cbrt, fraction);
+        let x = vmulq_f32(x, lookup_neon(&lookup_table_inv_truncated, fraction));
+
+        let x2 = vmulq_f32(x, x);
+        let x3 = vmulq_f32(x2, x);
+        let est = sum_mult_neon!(
+            (40. / 81.0),
+            (x, 60. / 81.),
+            (x2, -24. / 81.),
+            (x3, 5. / 81.)
+        );
+
+        vmulq_f32(est, vmulq_f32(truncated_cbrt, exp_cbrt))
+    }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub use self::sse41::*;
+
+// Same as the avx2 versions 4 lanes at a time, with the same operations in the
+// same order so both give the same results
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod sse41 {
+    use super::*;
+
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::*;
+
+    macro_rules! sum_mult_sse {
+        (($init:expr), $(($vec:expr, $mul:expr)),* ) => {
+            {
+                let mut sum = _mm_set1_ps($init);
+                $(
+                    sum = _mm_add_ps(sum, _mm_mul_ps($vec, _mm_set1_ps($mul)));
+                )*
+                sum
+            }
+        };
+        ( $(($vec:expr, $mul:expr)),* ) => {
+            sum_mult_sse!((0.0), $(($vec, $mul)),*)
+        };
+    }
+
+    // Looks up each lane of `index`, wrapping at the length of `table` like
+    // the avx2 permutes
+    #[inline]
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn lookup_sse41<const N: usize>(table: &[f32; N], index: __m128i) -> __m128 {
+        let mut lanes = [0u32; 4];
+        _mm_storeu_si128(lanes.as_mut_ptr() as *mut _, index);
+        let values = lanes.map(|i| table[i as usize % N]);
+        _mm_loadu_ps(values.as_ptr())
+    }
+
+    /// # Safety
+    ///
+    /// The CPU must support SSE4.1.
+    #[target_feature(enable = "sse4.1")]
+    pub unsafe fn rgb_to_lab_with_sse41(rgb: &[__m128; 3], to_xyz: &[[f32; 3]; 3]) -> [Lab; 4] {
+        xyz_to_lab_sse41(rgb_to_xyz_sse41(rgb, to_xyz))
+    }
+
+    /// # Safety
+    ///
+    /// The CPU must support SSE4.1.
+    #[target_feature(enable = "sse4.1")]
+    pub unsafe fn linear_rgb_to_lab_with_sse41(
+        rgb: &[__m128; 3],
+        to_xyz: &[[f32; 3]; 3],
+    ) -> [Lab; 4] {
+        xyz_to_lab_sse41(linear_to_xyz_sse41(rgb, to_xyz))
+    }
+
+    /// Like `pq_rgb_to_lab_with`, converting each lane in turn.
+    ///
+    /// # Safety
+    ///
+    /// The CPU must support SSE4.1.
+    #[target_feature(enable = "sse4.1")]
+    pub unsafe fn pq_rgb_to_lab_with_sse41(rgb: &[__m128; 3], to_xyz: &[[f32; 3]; 3]) -> [Lab; 4] {
+        let mut lanes = [[0f32; 4]; 3];
+        for (lane, &c) in lanes.iter_mut().zip(rgb) {
+            _mm_storeu_ps(lane.as_mut_ptr(), c);
+        }
+        std::array::from_fn(|i| {
+            pq_rgb_to_lab_with(&[lanes[0][i], lanes[1][i], lanes[2][i]], to_xyz)
+        })
+    }
+
+    #[inline]
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn rgb_to_xyz_sse41(rgb: &[__m128; 3], m: &[[f32; 3]; 3]) -> [__m128; 3] {
+        let linear = [
+            srgb_to_linear_sse41(rgb[0]),
+            srgb_to_linear_sse41(rgb[1]),
+            srgb_to_linear_sse41(rgb[2]),
+        ];
+        linear_to_xyz_sse41(&linear, m)
+    }
+
+    #[inline]
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn linear_to_xyz_sse41(rgb: &[__m128; 3], m: &[[f32; 3]; 3]) -> [__m128; 3] {
+        let [r, g, b] = *rgb;
+
+        let x = sum_mult_sse!((r, m[0][0]), (g, m[0][1]), (b, m[0][2]));
+        let y = sum_mult_sse!((r, m[1][0]), (g, m[1][1]), (b, m[1][2]));
+        let z = sum_mult_sse!((r, m[2][0]), (g, m[2][1]), (b, m[2][2]));
+
+        [x, y, z]
+    }
+
+    #[inline]
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn srgb_to_linear_sse41(c: __m128) -> __m128 {
+        let low = _mm_mul_ps(c, _mm_set1_ps(1.0 / 12.92));
+        let hi = pow_2_4_sse41(_mm_mul_ps(
+            _mm_add_ps(c, _mm_set1_ps(0.055)),
+            _mm_set1_ps(1.0 / 1.055),
+        ));
+        let select = _mm_cmpgt_ps(c, _mm_set1_ps(10. / 255.));
+        _mm_blendv_ps(low, hi, select)
+    }
+
+    #[inline]
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn xyz_to_lab_sse41(xyz: [__m128; 3]) -> [Lab; 4] {
+        let x = xyz_to_lab_map_sse41(_mm_mul_ps(xyz[0], _mm_set1_ps(1.0 / 0.95047)));
+        let y = xyz_to_lab_map_sse41(xyz[1]);
+        let z = xyz_to_lab_map_sse41(_mm_mul_ps(xyz[2], _mm_set1_ps(1.0 / 1.08883)));
+
+        let l = _mm_sub_ps(_mm_mul_ps(_mm_set1_ps(116.0), y), _mm_set1_ps(16.0));
+        let a = _mm_mul_ps(_mm_sub_ps(x, y), _mm_set1_ps(500.0));
+        let b = _mm_mul_ps(_mm_sub_ps(y, z), _mm_set1_ps(200.0));
+
+        let l: [f32; 4] = std::mem::transmute(l);
+        let a: [f32; 4] = std::mem::transmute(a);
+        let b: [f32; 4] = std::mem::transmute(b);
+        std::array::from_fn(|i| Lab {
+            l: l[i],
+            a: a[i],
+            b: b[i],
+        })
+    }
+
+    #[inline]
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn xyz_to_lab_map_sse41(c: __m128) -> __m128 {
+        let low = _mm_mul_ps(
+            _mm_add_ps(_mm_mul_ps(c, _mm_set1_ps(KAPPA)), _mm_set1_ps(16.0)),
+            _mm_set1_ps(1.0 / 116.0),
+        );
+        let hi = cbrt_approx_sse41(c);
+        let select = _mm_cmpgt_ps(c, _mm_set1_ps(EPSILON));
+        _mm_blendv_ps(low, hi, select)
+    }
+
+    // Zeroes the exponent of x, or divides it by 2^log2
+    #[inline]
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn mantissa_sse41(bits: __m128i) -> __m128 {
+        _mm_castsi128_ps(_mm_or_si128(
+            _mm_and_si128(bits, _mm_set1_epi32(0x807fffffu32 as i32)),
+            _mm_set1_epi32(0x3f800000),
+        ))
+    }
+
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn pow_2_4_sse41(x: __m128) -> __m128 {
+        // See non-avx2 version
+
+        const FRAC_BITS: u32 = 3;
+
+        let bits = _mm_castps_si128(x);
+
+        let log2_index = _mm_add_epi32(_mm_srli_epi32(bits, 23), _mm_set1_epi32(-0x7f + 4));
+
+        let lookup_entry_exp_pow_2_4 =
+            |log2: i32| (f32::from_bits(((log2 + 0x7f) << 23) as u32) as f64).powf(2.4) as f32;
+        let lookup_table_exp_pow_2_4 =
+            lookup_table_8!(start: -4, closure: lookup_entry_exp_pow_2_4);
+
+        let exp_pow_2_4 = lookup_sse41(&lookup_table_exp_pow_2_4, log2_index);
+
+        let x = mantissa_sse41(bits);
+
+        let lookup_entry_inv_truncated = |fraction: i32| {
+            let truncated = 1.0 + (fraction as f64 + 0.5) / ((1 << FRAC_BITS) as f64);
+            (1.0 / truncated) as f32
+        };
+        let lookup_table_inv_truncated =
+            lookup_table_8!(start: 0, closure: lookup_entry_inv_truncated);
+        let lookup_entry_truncated_pow_2_4 =
+            |fraction: i32| (lookup_entry_inv_truncated(fraction) as f64).powf(-2.4) as f32;
+        let lookup_table_truncated_pow_2_4 =
+            lookup_table_8!(start: 0, closure: lookup_entry_truncated_pow_2_4);
+
+        // No reason to mask the higher bits
+        let fraction = _mm_srli_epi32(bits, 23 - FRAC_BITS as i32);
+        let truncated_pow_2_4 = lookup_sse41(&lookup_table_truncated_pow_2_4, fraction);
+        let x = _mm_mul_ps(x, lookup_sse41(&lookup_table_inv_truncated, fraction));
+
+        let x2 = _mm_mul_ps(x, x);
+        let x3 = _mm_mul_ps(x2, x);
+        let est = sum_mult_sse!(
+            (7.0 / 125.0),
+            (x, -36. / 125.),
+            (x2, 126. / 125.),
+            (x3, 28. / 125.)
+        );
+
+        _mm_mul_ps(est, _mm_mul_ps(truncated_pow_2_4, exp_pow_2_4))
+    }
+
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn cbrt_approx_sse41(x: __m128) -> __m128 {
+        // See non-avx2 version
+
+        const FRAC_BITS: u32 = 3;
+
+        let bits = _mm_castps_si128(x);
+
+        let log2_index = _mm_add_epi32(_mm_srli_epi32(bits, 23), _mm_set1_epi32(-0x7f + 7));
+
+        let lookup_entry_exp_cbrt =
+            |log2: i32| (f32::from_bits(((log2 + 0x7f) << 23) as u32) as f64).powf(1. / 3.) as f32;
+        let lookup_table_exp_cbrt = lookup_table_16!(start: -7, closure: lookup_entry_exp_cbrt);
+
+        let exp_cbrt = lookup_sse41(&lookup_table_exp_cbrt, log2_index);
+
+        let x = mantissa_sse41(bits);
+
+        let lookup_entry_inv_truncated = |fraction: i32| {
+            let truncated = 1.0 + (fraction as f64 + 0.5) / ((1 << FRAC_BITS) as f64);
+            (1.0 / truncated) as f32
+        };
+        let lookup_table_inv_truncated =
+            lookup_table_8!(start: 0, closure: lookup_entry_inv_truncated);
+        let lookup_entry_truncated_cbrt =
+            |fraction: i32| (lookup_entry_inv_truncated(fraction) as f64).powf(-1. / 3.) as f32;
+        let lookup_table_truncated_cbrt =
+            lookup_table_8!(start: 0, closure: lookup_entry_truncated_cbrt);
+
+        // No reason to mask the higher bits
+        let fraction = _mm_srli_epi32(bits, 23 - FRAC_BITS as i32);
+        let truncated_cbrt = lookup_sse41(&lookup_table_truncated_cbrt, fraction);
+        let x = _mm_mul_ps(x, lookup_sse41(&lookup_table_inv_truncated, fraction));
+
+        let x2 = _mm_mul_ps(x, x);
+        let x3 = _mm_mul_ps(x2, x);
+        let est = sum_mult_sse!(
+            (40. / 81.0),
+            (x, 60. / 81.),
+            (x2, -24. / 81.),
+            (x3, 5. / 81.)
+        );
+
+        _mm_mul_ps(est, _mm_mul_ps(truncated_cbrt, exp_cbrt))
+    }
+}
+
+#[cfg(all(feature = "rvv", target_arch = "riscv64"))]
+pub use self::rvv::*;
+
+// Same as the avx2 versions 16 lanes at a time, with the same operations in
+// the same order so all give the same results. There are no stable RVV
+// intrinsics, so these work on arrays and are always inlined into kernels
+// that enable the V extension, where the compiler vectorizes them.
+#[cfg(all(feature = "rvv", target_arch = "riscv64"))]
+mod rvv {
+    use super::*;
+
+    /// 16 lanes of f32.
+    pub type Lanes = [f32; 16];
+
+    // A loop rather than array::from_fn, which is not always inlined
+    #[inline(always)]
+    fn lanes(mut f: impl FnMut(usize) -> f32) -> Lanes {
+        let mut out = [0.; 16];
+        for (i, out) in out.iter_mut().enumerate() {
+            *out = f(i);
+        }
+        out
+    }
+
+    macro_rules! sum_mult_rvv {
+        (($init:expr), $(($vec:expr, $mul:expr)),* ) => {
+            lanes(|i| {
+                let mut sum: f32 = $init;
+                $(
+                    sum += $vec[i] * $mul;
+                )*
+                sum
+            })
+        };
+        ( $(($vec:expr, $mul:expr)),* ) => {
+            sum_mult_rvv!((0.0), $(($vec, $mul)),*)
+        };
+    }
+
+    // Looks up each lane of `index`, wrapping at the length of `table` like
+    // the avx2 permutes
+    #[inline(always)]
+    fn lookup_rvv<const N: usize>(table: &[f32; N], index: &[u32; 16]) -> Lanes {
+        lanes(|i| table[index[i] as usize % N])
+    }
+
+    #[inline(always)]
+    pub fn rgb_to_lab_with_rvv(rgb: &[Lanes; 3], to_xyz: &[[f32; 3]; 3]) -> [Lab; 16] {
+        xyz_to_lab_rvv(rgb_to_xyz_rvv(rgb, to_xyz))
+    }
+
+    #[inline(always)]
+    pub fn linear_rgb_to_lab_with_rvv(rgb: &[Lanes; 3], to_xyz: &[[f32; 3]; 3]) -> [Lab; 16] {
+        xyz_to_lab_rvv(linear_to_xyz_rvv(rgb, to_xyz))
+    }
+
+    /// Like `pq_rgb_to_lab_with`, converting each lane in turn.
+    #[inline(always)]
+    pub fn pq_rgb_to_lab_with_rvv(rgb: &[Lanes; 3], to_xyz: &[[f32; 3]; 3]) -> [Lab; 16] {
+        std::array::from_fn(|i| pq_rgb_to_lab_with(&[rgb[0][i], rgb[1][i], rgb[2][i]], to_xyz))
+    }
+
+    #[inline(always)]
+    fn rgb_to_xyz_rvv(rgb: &[Lanes; 3], m: &[[f32; 3]; 3]) -> [Lanes; 3] {
+        let linear = [
+            srgb_to_linear_rvv(&rgb[0]),
+            srgb_to_linear_rvv(&rgb[1]),
+            srgb_to_linear_rvv(&rgb[2]),
+        ];
+        linear_to_xyz_rvv(&linear, m)
+    }
+
+    #[inline(always)]
+    fn linear_to_xyz_rvv(rgb: &[Lanes; 3], m: &[[f32; 3]; 3]) -> [Lanes; 3] {
+        let [r, g, b] = rgb;
+
+        let x = sum_mult_rvv!((r, m[0][0]), (g, m[0][1]), (b, m[0][2]));
+        let y = sum_mult_rvv!((r, m[1][0]), (g, m[1][1]), (b, m[1][2]));
+        let z = sum_mult_rvv!((r, m[2][0]), (g, m[2][1]), (b, m[2][2]));
+
+        [x, y, z]
+    }
+
+    #[inline(always)]
+    fn srgb_to_linear_rvv(c: &Lanes) -> Lanes {
+        let hi = pow_2_4_rvv(&lanes(|i| (c[i] + 0.055) * (1.0 / 1.055)));
+        lanes(|i| {
+            if c[i] > 10. / 255. {
+                hi[i]
+            } else {
+                c[i] * (1.0 / 12.92)
+            }
+        })
+    }
+
+    #[inline(always)]
+    fn xyz_to_lab_rvv(xyz: [Lanes; 3]) -> [Lab; 16] {
+        let x = xyz_to_lab_map_rvv(&lanes(|i| xyz[0][i] * (1.0 / 0.95047)));
+        let y = xyz_to_lab_map_rvv(&xyz[1]);
+        let z = xyz_to_lab_map_rvv(&lanes(|i| xyz[2][i] * (1.0 / 1.08883)));
+
+        let l = lanes(|i| 116.0 * y[i] - 16.0);
+        let a = lanes(|i| (x[i] - y[i]) * 500.0);
+        let b = lanes(|i| (y[i] - z[i]) * 200.0);
+        let mut lab = [Lab::default(); 16];
+        for (i, lab) in lab.iter_mut().enumerate() {
+            *lab = Lab {
+                l: l[i],
+                a: a[i],
+                b: b[i],
+            };
+        }
+        lab
+    }
+
+    #[inline(always)]
+    fn xyz_to_lab_map_rvv(c: &Lanes) -> Lanes {
+        let hi = cbrt_approx_rvv(c);
+        lanes(|i| {
+            if c[i] > EPSILON {
+                hi[i]
+            } else {
+                (c[i] * KAPPA + 16.0) * (1.0 / 116.0)
+            }
+        })
+    }
+
+    // Zeroes the exponent of x, or divides it by 2^log2
+    #[inline(always)]
+    fn mantissa_rvv(bits: &[u32; 16]) -> Lanes {
+        lanes(|i| f32::from_bits((bits[i] & 0x807fffff) | 0x3f800000))
+    }
+
+    // 23 - FRAC_BITS of pow_2_4 and cbrt_approx, where the fraction starts
+    const FRACTION_SHIFT: u32 = 20;
+
+    #[inline(always)]
+    fn pow_2_4_rvv(x: &Lanes) -> Lanes {
+        // See non-avx2 version
+
+        const FRAC_BITS: u32 = 3;
+
+        let bits = x.map(f32::to_bits);
+
+        let log2_index = bits.map(|bits| (bits >> 23).wrapping_add((-0x7f + 4) as u32));
+
+        let lookup_entry_exp_pow_2_4 =
+            |log2: i32| (f32::from_bits(((log2 + 0x7f) << 23) as u32) as f64).powf(2.4) as f32;
+        let lookup_table_exp_pow_2_4 =
+            lookup_table_8!(start: -4, closure: lookup_entry_exp_pow_2_4);
+
+        let exp_pow_2_4 = lookup_rvv(&lookup_table_exp_pow_2_4, &log2_index);
+
+        let x = mantissa_rvv(&bits);
+
+        let lookup_entry_inv_truncated = |fraction: i32| {
+            let truncated = 1.0 + (fraction as f64 + 0.5) / ((1 << FRAC_BITS) as f64);
+            (1.0 / truncated) as f32
+        };
+        let lookup_table_inv_truncated =
+            lookup_table_8!(start: 0, closure: lookup_entry_inv_truncated);
+        let lookup_entry_truncated_pow_2_4 =
+            |fraction: i32| (lookup_entry_inv_truncated(fraction) as f64).powf(-2.4) as f32;
+        let lookup_table_truncated_pow_2_4 =
+            lookup_table_8!(start: 0, closure: lookup_entry_truncated_pow_2_4);
+
+        // No reason to mask the higher bits
+        let fraction = bits.map(|bits| bits >> FRACTION_SHIFT);
+        let truncated_pow_2_4 = lookup_rvv(&lookup_table_truncated_pow_2_4, &fraction);
+        let inv_truncated = lookup_rvv(&lookup_table_inv_truncated, &fraction);
+        let x = lanes(|i| x[i] * inv_truncated[i]);
+
+        let x2 = lanes(|i| x[i] * x[i]);
+        let x3 = lanes(|i| x2[i] * x[i]);
+        let est = sum_mult_rvv!(
+            (7.0 / 125.0),
+            (x, -36. / 125.),
+            (x2, 126. / 125.),
+            (x3, 28. / 125.)
+        );
+
+        lanes(|i| est[i] * (truncated_pow_2_4[i] * exp_pow_2_4[i]))
+    }
+
+    #[inline(always)]
+    fn cbrt_approx_rvv(x: &Lanes) -> Lanes {
+        // See non-avx2 version
+
+        const FRAC_BITS: u32 = 3;
+
+        let bits = x.map(f32::to_bits);
+
+        let log2_index = bits.map(|bits| (bits >> 23).wrapping_add((-0x7f + 7) as u32));
+
+        let lookup_entry_exp_cbrt =
+            |log2: i32| (f32::from_bits(((log2 + 0x7f) << 23) as u32) as f64).powf(1. / 3.) as f32;
+        let lookup_table_exp_cbrt = lookup_table_16!(start: -7, closure: lookup_entry_exp_cbrt);
+
+        let exp_cbrt = lookup_rvv(&lookup_table_exp_cbrt, &log2_index);
+
+        let x = mantissa_rvv(&bits);
+
+        let lookup_entry_inv_truncated = |fraction: i32| {
+            let truncated = 1.0 + (fraction as f64 + 0.5) / ((1 << FRAC_BITS) as f64);
+            (1.0 / truncated) as f32
+        };
+        let lookup_table_inv_truncated =
+            lookup_table_8!(start: 0, closure: lookup_entry_inv_truncated);
+        let lookup_entry_truncated_cbrt =
+            |fraction: i32| (lookup_entry_inv_truncated(fraction) as f64).powf(-1. / 3.) as f32;
+        let lookup_table_truncated_cbrt =
+            lookup_table_8!(start: 0, closure: lookup_entry_truncated_cbrt);
+
+        // No reason to mask the higher bits
+        let fraction = bits.map(|bits| bits >> FRACTION_SHIFT);
+        let truncated_cbrt = lookup_rvv(&lookup_table_truncated_cbrt, &fraction);
+        let inv_truncated = lookup_rvv(&lookup_table_inv_truncated, &fraction);
+        let x = lanes(|i| x[i] * inv_truncated[i]);
+
+        let x2 = lanes(|i| x[i] * x[i]);
+        let x3 = lanes(|i| x2[i] * x[i]);
+        let est = sum_mult_rvv!(
+            (40. / 81.0),
+            (x, 60. / 81.),
+            (x2, -24. / 81.),
+            (x3, 5. / 81.)
+        );
+
+        lanes(|i| est[i] * (truncated_cbrt[i] * exp_cbrt[i]))
+    }
+}
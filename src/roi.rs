@@ -0,0 +1,93 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2019, the dump_ciede2000 contributors
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//  list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//  this list of conditions and the following disclaimer in the documentation
+//  and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Regions of interest given per frame as boxes, such as from an object
+//! tracker.
+//!
+//! Each line of a box file holds `FRAME X Y WIDTH HEIGHT`, separated by
+//! spaces or commas, with frames numbered from 0 as in the scores. A frame
+//! may have any number of boxes, and lines starting with `#` are ignored.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+pub struct RoiBoxes {
+    /// Boxes of each frame as `[x, y, width, height]`
+    frames: HashMap<usize, Vec<[usize; 4]>>,
+}
+
+impl RoiBoxes {
+    pub fn load(path: &Path) -> Result<RoiBoxes, String> {
+        let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let mut frames: HashMap<usize, Vec<[usize; 4]>> = HashMap::new();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<usize> = line
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|field| !field.is_empty())
+                .map(str::parse)
+                .collect::<Result<_, _>>()
+                .map_err(|_| format!("line {}: fields must be whole numbers", n + 1))?;
+            match fields[..] {
+                [frame, x, y, width, height] => {
+                    frames.entry(frame).or_default().push([x, y, width, height])
+                }
+                _ => return Err(format!("line {}: expected FRAME X Y WIDTH HEIGHT", n + 1)),
+            }
+        }
+        Ok(RoiBoxes { frames })
+    }
+
+    /// Mean ΔE of the per-pixel `map` of `frame` inside its boxes and
+    /// outside of them, or 0 for a region without pixels. Boxes are clipped
+    /// to the frame and may overlap.
+    pub fn pool(&self, frame: usize, map: &[f32], width: usize, height: usize) -> [f64; 2] {
+        let mut inside = vec![false; width * height];
+        for &[x, y, w, h] in self.frames.get(&frame).into_iter().flatten() {
+            for row in y.min(height)..y.saturating_add(h).min(height) {
+                inside[row * width..][x.min(width)..x.saturating_add(w).min(width)].fill(true);
+            }
+        }
+        let mut sums = [0f64; 2];
+        let mut pixels = [0usize; 2];
+        for (&delta_e, &inside) in map.iter().zip(&inside) {
+            let region = !inside as usize;
+            sums[region] += delta_e as f64;
+            pixels[region] += 1;
+        }
+        [0, 1].map(|region| {
+            if pixels[region] > 0 {
+                sums[region] / pixels[region] as f64
+            } else {
+                0.
+            }
+        })
+    }
+}
@@ -0,0 +1,72 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2019, the dump_ciede2000 contributors
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//  list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//  this list of conditions and the following disclaimer in the documentation
+//  and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Means of the scores of the last 10 seconds, minute and 5 minutes of a live
+//! stream, like the load averages of `uptime`.
+
+use std::collections::VecDeque;
+
+/// Lengths of the windows in seconds
+pub const WINDOWS: [(f64, &str); 3] = [(10., "10s"), (60., "1min"), (300., "5min")];
+
+pub struct RollingScores {
+    /// Frames in each window
+    lengths: [usize; 3],
+    /// Frames in a second, how often the means are reported
+    period: usize,
+    /// Scores of the frames of the longest window, most recent last
+    scores: VecDeque<f64>,
+    frames: usize,
+}
+
+impl RollingScores {
+    pub fn new(fps: f64) -> Self {
+        let frames_in = |seconds: f64| ((seconds * fps).round() as usize).max(1);
+        RollingScores {
+            lengths: WINDOWS.map(|(seconds, _)| frames_in(seconds)),
+            period: frames_in(1.),
+            scores: VecDeque::new(),
+            frames: 0,
+        }
+    }
+
+    /// Adds the score of the next frame. After each second of frames, returns
+    /// the mean score of each window, over the frames so far until it fills.
+    pub fn push(&mut self, score: f64) -> Option<[f64; 3]> {
+        if self.scores.len() == self.lengths[2] {
+            self.scores.pop_front();
+        }
+        self.scores.push_back(score);
+        self.frames += 1;
+        if !self.frames.is_multiple_of(self.period) {
+            return None;
+        }
+        Some(self.lengths.map(|length| {
+            let length = length.min(self.scores.len());
+            self.scores.iter().rev().take(length).sum::<f64>() / length as f64
+        }))
+    }
+}
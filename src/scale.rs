@@ -0,0 +1,269 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2019, the dump_ciede2000 contributors
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//  list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//  this list of conditions and the following disclaimer in the documentation
+//  and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+//! Rescaling of whole frames to another resolution.
+
+use crate::conform::read;
+use crate::{pixel_rgb, Frame, FrameInfo, YuvToRgb};
+
+/// How [`scale_frame`] resamples each plane.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScaleFilter {
+    /// Linear interpolation, or a box-like average when downscaling.
+    Bilinear,
+    /// Catmull-Rom cubic interpolation.
+    Bicubic,
+    /// Lanczos with 3 lobes.
+    Lanczos,
+}
+
+impl ScaleFilter {
+    // Half width of the kernel in source samples, before stretching for
+    // downscaling.
+    fn support(self) -> f64 {
+        match self {
+            ScaleFilter::Bilinear => 1.,
+            ScaleFilter::Bicubic => 2.,
+            ScaleFilter::Lanczos => 3.,
+        }
+    }
+
+    fn weight(self, x: f64) -> f64 {
+        let x = x.abs();
+        match self {
+            ScaleFilter::Bilinear => (1. - x).max(0.),
+            ScaleFilter::Bicubic => {
+                if x < 1. {
+                    1.5 * x * x * x - 2.5 * x * x + 1.
+                } else if x < 2. {
+                    -0.5 * x * x * x + 2.5 * x * x - 4. * x + 2.
+                } else {
+                    0.
+                }
+            }
+            ScaleFilter::Lanczos => {
+                if x == 0. {
+                    1.
+                } else if x < 3. {
+                    let pi_x = std::f64::consts::PI * x;
+                    3. * pi_x.sin() * (pi_x / 3.).sin() / (pi_x * pi_x)
+                } else {
+                    0.
+                }
+            }
+        }
+    }
+}
+
+// Source samples and normalized weights making up each sample along one axis,
+// when scaling `from_len` samples to `to_len`. Samples are taken to be centred
+// on their area, and the kernel is stretched when downscaling so every source
+// sample contributes.
+fn scale_taps(from_len: usize, to_len: usize, filter: ScaleFilter) -> Vec<Vec<(usize, f32)>> {
+    let ratio = from_len as f64 / to_len as f64;
+    let stretch = ratio.max(1.);
+    let support = filter.support() * stretch;
+    (0..to_len)
+        .map(|k| {
+            let center = (k as f64 + 0.5) * ratio - 0.5;
+            let first = (center - support).floor() as i64;
+            let last = (center + support).ceil() as i64;
+            let mut taps: Vec<(usize, f64)> = Vec::new();
+            for i in first..=last {
+                let weight = filter.weight((i as f64 - center) / stretch);
+                if weight == 0. {
+                    continue;
+                }
+                // Edges are extended by repeating the outermost samples
+                let i = i.clamp(0, from_len as i64 - 1) as usize;
+                match taps.iter_mut().find(|(j, _)| *j == i) {
+                    Some((_, w)) => *w += weight,
+                    None => taps.push((i, weight)),
+                }
+            }
+            let total: f64 = taps.iter().map(|&(_, w)| w).sum();
+            taps.into_iter()
+                .map(|(i, w)| (i, (w / total) as f32))
+                .collect()
+        })
+        .collect()
+}
+
+/// Format of frames of the format `from` scaled to the dimensions of `to`,
+/// keeping the chroma subsampling and bit depth of `from`, or an error if
+/// `from` is interlaced, as its fields would be mixed.
+pub fn scaled_info(from: &FrameInfo, to: &FrameInfo) -> Result<FrameInfo, String> {
+    if from.interlaced {
+        return Err("Interlaced inputs cannot be rescaled, deinterlace them first".to_owned());
+    }
+    Ok(FrameInfo {
+        width: to.width,
+        height: to.height,
+        ..*from
+    })
+}
+
+/// Light [`scale_frame`] filters in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScaleLight {
+    /// Linear RGB: each pixel is converted to RGB with its chroma repeated
+    /// and linearized, then the filtered RGB is encoded back and its chroma
+    /// averaged, so fine detail keeps its brightness when downscaled.
+    Linear,
+    /// The Y, U and V samples as coded, like most scalers.
+    Gamma,
+}
+
+// Scales the plane of `from` samples read with `sample` to `to` samples with
+// `filter`, horizontally into `rows` and then vertically into `out`.
+fn scale_plane(
+    sample: impl Fn(usize) -> f32,
+    (from_width, from_height): (usize, usize),
+    (to_width, to_height): (usize, usize),
+    filter: ScaleFilter,
+    rows: &mut Vec<f32>,
+    out: &mut Vec<f32>,
+) {
+    let cols = scale_taps(from_width, to_width, filter);
+    rows.clear();
+    for y in 0..from_height {
+        for taps in &cols {
+            rows.push(
+                taps.iter()
+                    .map(|&(x, w)| w * sample(y * from_width + x))
+                    .sum(),
+            );
+        }
+    }
+    out.clear();
+    for taps in scale_taps(from_height, to_height, filter) {
+        for x in 0..to_width {
+            out.push(taps.iter().map(|&(y, w)| w * rows[y * to_width + x]).sum());
+        }
+    }
+}
+
+/// Scales `frame` of the format `from` to the format `to` returned by
+/// [`scaled_info`] with `filter` in `light`, writing the planes to `buf`. The
+/// frame is split back out with `to.split_planes(buf)`.
+pub fn scale_frame(
+    from: &FrameInfo,
+    frame: &Frame,
+    to: &FrameInfo,
+    filter: ScaleFilter,
+    light: ScaleLight,
+    buf: &mut Vec<u8>,
+) {
+    let bytewidth = from.bytewidth();
+    let max = ((1u32 << from.bit_depth) - 1) as f32;
+    let push = |buf: &mut Vec<u8>, sample: f32| {
+        let sample = sample.round().clamp(0., max) as u16;
+        if bytewidth == 1 {
+            buf.push(sample as u8);
+        } else {
+            buf.extend_from_slice(&sample.to_le_bytes());
+        }
+    };
+    let (luma_len, chroma_len) = to.plane_lens();
+    let (from_chroma_width, from_chroma_height) = from.chroma_dims();
+    let (to_chroma_width, to_chroma_height) = to.chroma_dims();
+    buf.clear();
+    buf.reserve(luma_len + 2 * chroma_len);
+    let mut rows = Vec::new();
+    if light == ScaleLight::Gamma {
+        let mut scaled = Vec::new();
+        let planes = [
+            (frame.y, (from.width, from.height), (to.width, to.height)),
+            (
+                frame.u,
+                (from_chroma_width, from_chroma_height),
+                (to_chroma_width, to_chroma_height),
+            ),
+            (
+                frame.v,
+                (from_chroma_width, from_chroma_height),
+                (to_chroma_width, to_chroma_height),
+            ),
+        ];
+        for (plane, from_dims, to_dims) in planes {
+            let sample = |i| read(plane, bytewidth, i) as f32;
+            scale_plane(sample, from_dims, to_dims, filter, &mut rows, &mut scaled);
+            for &sample in &scaled {
+                push(buf, sample);
+            }
+        }
+        return;
+    }
+    // Linear RGB of each pixel, by plane
+    let mut linear = [(); 3].map(|_| Vec::with_capacity(from.width * from.height));
+    for i in 0..from.height {
+        let row = from.row(frame, i);
+        for x in 0..from.width {
+            let rgb = row.to_rgb.linear(&pixel_rgb(from, &row, x));
+            for (plane, c) in linear.iter_mut().zip(rgb) {
+                plane.push(c);
+            }
+        }
+    }
+    // `to` only differs from `from` in its dimensions
+    let to_rgb = YuvToRgb::new(to.bit_depth, to.matrix, to.range, to.transfer, to.vision);
+    let scaled: Vec<Vec<f32>> = linear
+        .iter()
+        .map(|plane| {
+            let mut out = Vec::new();
+            let sample = |i| plane[i];
+            let dims = (from.width, from.height);
+            scale_plane(
+                sample,
+                dims,
+                (to.width, to.height),
+                filter,
+                &mut rows,
+                &mut out,
+            );
+            out
+        })
+        .collect();
+    // Sums of the U and V of the pixels of each chroma sample, and their count
+    let mut chroma = vec![[0f32; 3]; to_chroma_width * to_chroma_height];
+    for i in 0..to.height {
+        let chroma_row = &mut chroma[(i >> to.ydec) * to_chroma_width..][..to_chroma_width];
+        for x in 0..to.width {
+            let k = i * to.width + x;
+            let rgb = [scaled[0][k], scaled[1][k], scaled[2][k]];
+            let [y, u, v] = to_rgb.rgb_to_yuv(&to_rgb.nonlinear(&rgb));
+            push(buf, y);
+            let sums = &mut chroma_row[x >> to.xdec];
+            sums[0] += u;
+            sums[1] += v;
+            sums[2] += 1.;
+        }
+    }
+    for c in 0..2 {
+        for sums in &chroma {
+            push(buf, sums[c] / sums[2]);
+        }
+    }
+}
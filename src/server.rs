@@ -0,0 +1,230 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2019, the dump_ciede2000 contributors
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//  list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//  this list of conditions and the following disclaimer in the documentation
+//  and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Scores frame pairs streamed over a pipe or a Unix domain socket.
+//!
+//! Each request is a little-endian `u32` length followed by that many bytes:
+//!
+//! | bytes | field                                     |
+//! |-------|-------------------------------------------|
+//! | 4     | width (`u32`)                             |
+//! | 4     | height (`u32`)                            |
+//! | 1     | bit depth: 8, 10 or 12                    |
+//! | 1     | xdec: log2 of horizontal chroma scale     |
+//! | 1     | ydec: 1 if chroma is vertically halved    |
+//! | 1     | flags: bit 0 set for interlaced chroma    |
+//! | ...   | reference Y, U and V planes               |
+//! | ...   | distorted Y, U and V planes               |
+//!
+//! Planes are laid out like y4m frame data, with samples above 8 bits stored
+//! as 16-bit little-endian. A length of 0 or the end of the input ends the
+//! session. Every request gets one line of JSON in response, either
+//! `{"frame": N, "score": S, "delta_e": D}` or `{"frame": N, "error": "..."}`.
+//! A score is `null` when the frames are identical.
+
+use std::convert::TryInto;
+use std::io::{self, ErrorKind, Read, Write};
+#[cfg(unix)]
+use std::path::Path;
+
+use dump_ciede2000::*;
+
+use crate::json::{json_f64, json_str};
+
+const HEADER_LEN: usize = 12;
+
+fn parse_header(header: &[u8]) -> Result<FrameInfo, String> {
+    let width = u32::from_le_bytes(header[0..4].try_into().unwrap()) as usize;
+    let height = u32::from_le_bytes(header[4..8].try_into().unwrap()) as usize;
+    let (bit_depth, xdec, ydec) = (header[8] as usize, header[9] as usize, header[10] as usize);
+    if ![8, 10, 12].contains(&bit_depth) {
+        return Err(format!("Unsupported bit depth {}", bit_depth));
+    }
+    if xdec > 2 || ydec > 1 {
+        return Err(format!(
+            "Unsupported subsampling xdec={} ydec={}",
+            xdec, ydec
+        ));
+    }
+    let info = FrameInfo {
+        width,
+        height,
+        bit_depth,
+        xdec,
+        ydec,
+        interlaced: header[11] & 1 != 0,
+        matrix: Matrix::Bt709,
+        range: Range::Limited,
+        transfer: Transfer::Sdr,
+        vision: Vision::Normal,
+    };
+    info.check_size()?;
+    Ok(info)
+}
+
+fn score_request(request: &[u8], simd: SimdLevel) -> Result<(f64, f64), String> {
+    if request.len() < HEADER_LEN {
+        return Err(format!("Request of {} bytes is too short", request.len()));
+    }
+    let info = parse_header(&request[..HEADER_LEN])?;
+    let (luma_len, chroma_len) = info.plane_lens();
+    let frame_len = luma_len + 2 * chroma_len;
+    let planes = &request[HEADER_LEN..];
+    if planes.len() != 2 * frame_len {
+        return Err(format!(
+            "Expected {} bytes of frame data for {}x{}, got {}",
+            2 * frame_len,
+            info.width,
+            info.height,
+            planes.len()
+        ));
+    }
+    let sum = delta_e_frame(
+        &info,
+        &info.split_planes(&planes[..frame_len]).unwrap(),
+        &info.split_planes(&planes[frame_len..]).unwrap(),
+        simd,
+    );
+    let mean = sum / ((info.width * info.height) as f64);
+    Ok((delta_e_to_score(mean), mean))
+}
+
+/// Scores the request payload `request` and writes the response line.
+pub fn respond<W: Write>(
+    output: &mut W,
+    frame: usize,
+    request: &[u8],
+    simd: SimdLevel,
+) -> io::Result<()> {
+    match score_request(request, simd) {
+        Ok((score, delta_e)) => writeln!(
+            output,
+            "{{\"frame\": {}, \"score\": {}, \"delta_e\": {}}}",
+            frame,
+            json_f64(score),
+            json_f64(delta_e)
+        ),
+        Err(e) => write_error(output, frame, &e),
+    }
+}
+
+fn write_error<W: Write>(output: &mut W, frame: usize, error: &str) -> io::Result<()> {
+    writeln!(
+        output,
+        "{{\"frame\": {}, \"error\": {}}}",
+        frame,
+        json_str(error)
+    )
+}
+
+/// Handles requests from `input` until it ends, writing responses to `output`.
+/// The header of each request is checked before its frames are read, and the
+/// frames of a request with the wrong length are skipped unread.
+pub fn serve<R: Read, W: Write>(mut input: R, mut output: W, simd: SimdLevel) -> io::Result<()> {
+    let mut request = Vec::new();
+    for frame in 0.. {
+        let mut len = [0u8; 4];
+        match input.read_exact(&mut len) {
+            Err(ref e) if e.kind() == ErrorKind::UnexpectedEof => break,
+            result => result?,
+        }
+        let len = u32::from_le_bytes(len) as usize;
+        if len == 0 {
+            break;
+        }
+        let mut header = [0u8; HEADER_LEN];
+        let checked = if len < HEADER_LEN {
+            Err(format!("Request of {} bytes is too short", len))
+        } else {
+            input.read_exact(&mut header)?;
+            parse_header(&header).and_then(|info| {
+                let (luma_len, chroma_len) = info.plane_lens();
+                let frame_len = luma_len + 2 * chroma_len;
+                if len - HEADER_LEN == 2 * frame_len {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "Expected {} bytes of frame data for {}x{}, got {}",
+                        2 * frame_len,
+                        info.width,
+                        info.height,
+                        len - HEADER_LEN
+                    ))
+                }
+            })
+        };
+        match checked {
+            Ok(()) => {
+                request.clear();
+                request.extend_from_slice(&header);
+                request.resize(len, 0);
+                input.read_exact(&mut request[HEADER_LEN..])?;
+                respond(&mut output, frame, &request, simd)?;
+            }
+            Err(e) => {
+                // The header was read unless the request is too short for it
+                let unread = if len < HEADER_LEN {
+                    len
+                } else {
+                    len - HEADER_LEN
+                };
+                io::copy(&mut (&mut input).take(unread as u64), &mut io::sink())?;
+                write_error(&mut output, frame, &e)?;
+            }
+        }
+        output.flush()?;
+    }
+    Ok(())
+}
+
+/// Serves each connection to a Unix domain socket at `path` on its own thread.
+#[cfg(unix)]
+pub fn listen_unix(path: &Path, simd: SimdLevel) -> io::Result<()> {
+    use std::fs;
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::UnixListener;
+    use std::thread;
+
+    // Replace a socket left behind by a previous run, but nothing else.
+    if let Ok(metadata) = fs::symlink_metadata(path) {
+        if metadata.file_type().is_socket() {
+            fs::remove_file(path)?;
+        }
+    }
+    let listener = UnixListener::bind(path)?;
+    for stream in listener.incoming() {
+        let stream = stream?;
+        thread::spawn(move || {
+            let result = stream
+                .try_clone()
+                .and_then(|reader| serve(reader, &stream, simd));
+            if let Err(e) = result {
+                eprintln!("Connection error: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,128 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2019, the dump_ciede2000 contributors
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//  list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//  this list of conditions and the following disclaimer in the documentation
+//  and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Scores frame pairs from a ring buffer in shared memory.
+//!
+//! The producer creates the file (e.g. under `/dev/shm`, or a memfd passed as
+//! `/proc/<pid>/fd/<n>`) and lays it out as follows, all little-endian:
+//!
+//! | offset | field                                                  |
+//! |--------|--------------------------------------------------------|
+//! | 0      | magic `b"DCE2RING"`                                    |
+//! | 8      | version (`u32`), 1                                     |
+//! | 12     | number of slots (`u32`)                                |
+//! | 16     | size of each slot in bytes (`u64`)                     |
+//! | 24     | write index (`u64`): slots published by the producer   |
+//! | 32     | read index (`u64`): slots released by this tool        |
+//! | 40     | closed flag (`u32`): set by the producer when done     |
+//! | 64     | slots                                                  |
+//!
+//! Slot `n % slots` holds request `n` in the `--stdio-server` format: a `u32`
+//! length followed by the header and planes. The producer fills a slot,
+//! then increments the write index. Once the slot is scored its index is
+//! released, after which the producer may reuse it. Frames are scored in
+//! place and never copied. Responses are written to stdout as JSON lines.
+
+use std::convert::TryInto;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use memmap2::MmapMut;
+
+use dump_ciede2000::SimdLevel;
+
+use crate::server::respond;
+
+const MAGIC: &[u8; 8] = b"DCE2RING";
+const VERSION: u32 = 1;
+const HEADER_LEN: usize = 64;
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+pub fn consume<W: Write>(path: &Path, mut output: W, simd: SimdLevel) -> io::Result<()> {
+    let file = OpenOptions::new().read(true).write(true).open(path)?;
+    let map = unsafe { MmapMut::map_mut(&file)? };
+    if map.len() < HEADER_LEN || &map[..8] != MAGIC {
+        return Err(invalid("Not a frame ring".to_owned()));
+    }
+    let version = u32::from_le_bytes(map[8..12].try_into().unwrap());
+    if version != VERSION {
+        return Err(invalid(format!("Unsupported ring version {}", version)));
+    }
+    let slots = u32::from_le_bytes(map[12..16].try_into().unwrap()) as usize;
+    let slot_size = u64::from_le_bytes(map[16..24].try_into().unwrap()) as usize;
+    let needed = slots
+        .checked_mul(slot_size)
+        .and_then(|n| n.checked_add(HEADER_LEN));
+    if slots == 0 || slot_size < 4 || needed.is_none_or(|n| map.len() < n) {
+        return Err(invalid(format!(
+            "Ring of {} slots of {} bytes does not fit in {} bytes",
+            slots,
+            slot_size,
+            map.len()
+        )));
+    }
+
+    // The mapping is page aligned, so the counters are naturally aligned.
+    let base = map.as_ptr();
+    let write_index = unsafe { &*(base.add(24) as *const AtomicU64) };
+    let read_index = unsafe { &*(base.add(32) as *const AtomicU64) };
+    let closed = unsafe { &*(base.add(40) as *const AtomicU32) };
+
+    let mut next = read_index.load(Ordering::Acquire);
+    loop {
+        if next == write_index.load(Ordering::Acquire) {
+            // Check again after seeing the flag so a final slot published
+            // right before closing isn't missed.
+            if closed.load(Ordering::Acquire) != 0 && next == write_index.load(Ordering::Acquire) {
+                break;
+            }
+            thread::sleep(Duration::from_micros(100));
+            continue;
+        }
+        let offset = HEADER_LEN + (next as usize % slots) * slot_size;
+        // The producer doesn't touch a slot until it is released.
+        let slot = unsafe { std::slice::from_raw_parts(base.add(offset), slot_size) };
+        let len = u32::from_le_bytes(slot[..4].try_into().unwrap()) as usize;
+        if len > slot_size - 4 {
+            return Err(invalid(format!(
+                "Request of {} bytes overruns a {} byte slot",
+                len, slot_size
+            )));
+        }
+        respond(&mut output, next as usize, &slot[4..][..len], simd)?;
+        output.flush()?;
+        next += 1;
+        read_index.store(next, Ordering::Release);
+    }
+    Ok(())
+}
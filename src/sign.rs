@@ -0,0 +1,93 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2019, the dump_ciede2000 contributors
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//  list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//  this list of conditions and the following disclaimer in the documentation
+//  and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Detached Ed25519 signatures of `--json` reports, so that a delivered
+//! report can be checked against the public key of whoever signed it.
+
+use std::cell::RefCell;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+use std::rc::Rc;
+
+use ed25519_dalek::pkcs8::DecodePrivateKey;
+use ed25519_dalek::{Signer, SigningKey};
+use sha2::{Digest, Sha256};
+
+/// SHA-256 of everything read from an input so far.
+pub type InputDigest = Rc<RefCell<Sha256>>;
+
+/// Hashes everything read from `inner`.
+pub struct DigestReader<R: Read> {
+    inner: R,
+    digest: InputDigest,
+}
+
+impl<R: Read> DigestReader<R> {
+    pub fn new(inner: R) -> Self {
+        DigestReader {
+            inner,
+            digest: Rc::new(RefCell::new(Sha256::new())),
+        }
+    }
+
+    pub fn digest(&self) -> InputDigest {
+        self.digest.clone()
+    }
+}
+
+impl<R: Read> Read for DigestReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.digest.borrow_mut().update(&buf[..len]);
+        Ok(len)
+    }
+}
+
+pub fn digest_hex(digest: &InputDigest) -> String {
+    hex(&digest.borrow().clone().finalize())
+}
+
+/// Reads a PKCS#8 PEM private key, as written by
+/// `openssl genpkey -algorithm ed25519`.
+pub fn read_key(path: &Path) -> Result<SigningKey, String> {
+    let pem = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    SigningKey::from_pkcs8_pem(&pem).map_err(|e| e.to_string())
+}
+
+pub fn public_key_hex(key: &SigningKey) -> String {
+    hex(key.verifying_key().as_bytes())
+}
+
+/// The raw 64-byte signature of `report`, which
+/// `openssl pkeyutl -verify -rawin` checks.
+pub fn sign(key: &SigningKey, report: &[u8]) -> [u8; 64] {
+    key.sign(report).to_bytes()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
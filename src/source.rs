@@ -0,0 +1,492 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2019, the dump_ciede2000 contributors
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//  list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//  this list of conditions and the following disclaimer in the documentation
+//  and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Inputs that produce frames for scoring.
+
+use std::io::{self, BufRead, ErrorKind, Read};
+
+use crate::{Frame, FrameInfo, Matrix, Range, Transfer, Vision};
+
+/// A stream of frames of a fixed format.
+///
+/// Implement this to score frames from a demuxer or decoder that isn't
+/// supported out of the box.
+pub trait FrameSource {
+    /// Format of every frame returned by `next_frame`.
+    fn info(&self) -> FrameInfo;
+
+    /// Frame rate as a numerator and denominator, if known.
+    fn framerate(&self) -> Option<(usize, usize)> {
+        None
+    }
+
+    /// Returns the next frame, or `None` at the end of the stream.
+    fn next_frame(&mut self) -> io::Result<Option<Frame<'_>>>;
+
+    /// Number of bytes of corrupted data skipped so far to recover.
+    fn skipped_bytes(&self) -> u64 {
+        0
+    }
+
+    /// Whether the stream has no chroma, which frames fill with the neutral
+    /// value.
+    fn grayscale(&self) -> bool {
+        false
+    }
+
+    /// Whether the bottom field of interlaced frames comes first in time.
+    fn bottom_field_first(&self) -> bool {
+        false
+    }
+
+    /// Estimate of the frames left in a stream of `stream_len` bytes in all,
+    /// from the bytes read so far, if the format has frames of a fixed size.
+    fn frames_left(&self, stream_len: u64) -> Option<usize> {
+        let _ = stream_len;
+        None
+    }
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, msg)
+}
+
+/// Longest stream or frame header accepted, excluding the newline.
+const MAX_LINE_LEN: usize = 1024;
+/// Longest stream header parameter accepted.
+const MAX_PARAM_LEN: usize = 128;
+
+// Reads a line into `line` without its newline, stopping early once it holds
+// more than MAX_LINE_LEN bytes. Returns the number of bytes consumed, which is
+// 0 at the end of the stream, and whether a whole line was read.
+fn read_line<R: BufRead>(reader: &mut R, line: &mut Vec<u8>) -> io::Result<(usize, bool)> {
+    line.clear();
+    let mut consumed = 0;
+    while line.len() <= MAX_LINE_LEN {
+        let buf = match reader.fill_buf() {
+            Ok(buf) => buf,
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if buf.is_empty() {
+            return Ok((consumed, false));
+        }
+        let buf = &buf[..buf.len().min(MAX_LINE_LEN + 1 - line.len())];
+        let newline = buf.iter().position(|&b| b == b'\n');
+        let len = newline.map_or(buf.len(), |i| i + 1);
+        line.extend_from_slice(&buf[..newline.unwrap_or(len)]);
+        reader.consume(len);
+        consumed += len;
+        if newline.is_some() {
+            return Ok((consumed, true));
+        }
+    }
+    Ok((consumed, false))
+}
+
+fn incomplete_line(what: &str, offset: u64, line: &[u8]) -> io::Error {
+    if line.len() > MAX_LINE_LEN {
+        invalid_data(format!(
+            "{} at byte {} is longer than {} bytes",
+            what, offset, MAX_LINE_LEN
+        ))
+    } else {
+        invalid_data(format!(
+            "{} at byte {} is cut off by the end of the stream",
+            what, offset
+        ))
+    }
+}
+
+fn read_byte<R: BufRead>(reader: &mut R) -> io::Result<Option<u8>> {
+    loop {
+        match reader.fill_buf() {
+            Ok(buf) => {
+                let byte = buf.first().copied();
+                if byte.is_some() {
+                    reader.consume(1);
+                }
+                return Ok(byte);
+            }
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// Position of the first frame marker, FRAME followed by a space or newline
+fn find_marker(bytes: &[u8]) -> Option<usize> {
+    bytes
+        .windows(6)
+        .position(|w| w.starts_with(b"FRAME") && (w[5] == b' ' || w[5] == b'\n'))
+}
+
+/// Stream header values that are not part of [`FrameInfo`].
+struct Header {
+    framerate: (usize, usize),
+    /// Given the format of 4:2:0
+    grayscale: bool,
+    bottom_field_first: bool,
+}
+
+fn parse_header(line: &[u8]) -> io::Result<(FrameInfo, Header)> {
+    const MAGIC: &[u8] = b"YUV4MPEG2 ";
+    if !line.starts_with(MAGIC) {
+        return Err(invalid_data("Not a y4m stream".to_owned()));
+    }
+    let mut info = FrameInfo {
+        width: 0,
+        height: 0,
+        bit_depth: 8,
+        xdec: 1,
+        ydec: 1,
+        interlaced: false,
+        matrix: Matrix::Bt709,
+        range: Range::Limited,
+        transfer: Transfer::Sdr,
+        vision: Vision::Normal,
+    };
+    // The spec requires a frame rate, but ffmpeg does not
+    let mut header = Header {
+        framerate: (25, 1),
+        grayscale: false,
+        bottom_field_first: false,
+    };
+    let mut offset = MAGIC.len();
+    for param in line[MAGIC.len()..].split(|&b| b == b' ') {
+        let invalid = || {
+            invalid_data(format!(
+                "Invalid y4m header parameter {} at byte {}",
+                String::from_utf8_lossy(param),
+                offset
+            ))
+        };
+        if param.len() > MAX_PARAM_LEN {
+            return Err(invalid_data(format!(
+                "y4m header parameter at byte {} is longer than {} bytes",
+                offset, MAX_PARAM_LEN
+            )));
+        }
+        let number = |value: &[u8]| {
+            std::str::from_utf8(value)
+                .ok()
+                .and_then(|value| value.parse::<usize>().ok())
+                .ok_or_else(invalid)
+        };
+        match param.split_first() {
+            Some((b'W', value)) => info.width = number(value)?,
+            Some((b'H', value)) => info.height = number(value)?,
+            Some((b'F', value)) => {
+                let mut parts = value.splitn(2, |&b| b == b':');
+                let num = number(parts.next().unwrap())?;
+                let den = number(parts.next().ok_or_else(invalid)?)?;
+                if num == 0 || den == 0 {
+                    return Err(invalid());
+                }
+                header.framerate = (num, den);
+            }
+            // Interlacing is p, t, b or m
+            Some((b'I', value)) => {
+                info.interlaced = value == b"t" || value == b"b";
+                header.bottom_field_first = value == b"b";
+            }
+            Some((b'C', value)) => {
+                let (bit_depth, xdec, ydec) = match value {
+                    b"420" | b"420jpeg" | b"420paldv" | b"420mpeg2" => (8, 1, 1),
+                    b"420p10" => (10, 1, 1),
+                    b"420p12" => (12, 1, 1),
+                    b"422" => (8, 1, 0),
+                    b"422p10" => (10, 1, 0),
+                    b"422p12" => (12, 1, 0),
+                    b"444" => (8, 0, 0),
+                    b"444p10" => (10, 0, 0),
+                    b"444p12" => (12, 0, 0),
+                    b"411" => (8, 2, 0),
+                    b"mono" => (8, 1, 1),
+                    b"mono10" => (10, 1, 1),
+                    b"mono12" => (12, 1, 1),
+                    _ => {
+                        return Err(invalid_data(format!(
+                            "Unsupported y4m colorspace {} at byte {}",
+                            String::from_utf8_lossy(param),
+                            offset
+                        )))
+                    }
+                };
+                info.bit_depth = bit_depth;
+                info.xdec = xdec;
+                info.ydec = ydec;
+                header.grayscale = value.starts_with(b"mono");
+            }
+            // Color metadata from ffmpeg and other tools, which overrides the
+            // defaults of BT.709 limited range SDR. Unknown values are ignored.
+            Some((b'X', value)) => {
+                let mut parts = value.splitn(2, |&b| b == b'=');
+                match (parts.next().unwrap(), parts.next()) {
+                    (b"COLORRANGE", Some(b"FULL")) => info.range = Range::Full,
+                    (b"COLORRANGE", Some(b"LIMITED")) => info.range = Range::Limited,
+                    (b"COLORMATRIX", Some(b"BT601")) => info.matrix = Matrix::Bt601,
+                    (b"COLORMATRIX", Some(b"BT709")) => info.matrix = Matrix::Bt709,
+                    (b"COLORMATRIX", Some(b"BT2020")) => info.matrix = Matrix::Bt2020,
+                    (b"COLORTRC", Some(b"SMPTE2084")) => info.transfer = Transfer::Pq,
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+        offset += param.len() + 1;
+    }
+    info.check_size().map_err(invalid_data)?;
+    Ok((info, header))
+}
+
+/// Frames from a YUV4MPEG2 stream.
+pub struct Y4mSource<R: BufRead> {
+    reader: R,
+    info: FrameInfo,
+    framerate: (usize, usize),
+    line: Vec<u8>,
+    buf: Vec<u8>,
+    /// Bytes consumed from `reader`
+    offset: u64,
+    resync: bool,
+    skipped: u64,
+    grayscale: bool,
+    bottom_field_first: bool,
+}
+
+impl<R: BufRead> Y4mSource<R> {
+    /// Parses the stream header. Frames too large for
+    /// [`FrameInfo::check_size`] are rejected. Grayscale streams are read as
+    /// 4:2:0 with neutral chroma, so only lightness differs between frames.
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut line = Vec::new();
+        let (len, complete) = read_line(&mut reader, &mut line)?;
+        if !complete {
+            return Err(incomplete_line("y4m header", 0, &line));
+        }
+        let (info, header) = parse_header(&line)?;
+        let (luma_len, chroma_len) = info.plane_lens();
+        let mut buf = vec![0; luma_len + 2 * chroma_len];
+        if header.grayscale {
+            // Frames only overwrite luma
+            buf[luma_len..].copy_from_slice(&info.neutral_chroma().repeat(2));
+        }
+        Ok(Y4mSource {
+            reader,
+            info,
+            framerate: header.framerate,
+            line,
+            buf,
+            offset: len as u64,
+            resync: false,
+            skipped: 0,
+            grayscale: header.grayscale,
+            bottom_field_first: header.bottom_field_first,
+        })
+    }
+
+    /// When a frame does not start with a FRAME marker, skip ahead to the
+    /// next marker instead of failing. Data corrupted by dropped bytes loses
+    /// the frame after it, which misaligns the inputs from there on.
+    pub fn set_resync(&mut self, resync: bool) {
+        self.resync = resync;
+    }
+
+    // Bytes of each frame in the stream
+    fn frame_len(&self) -> usize {
+        if self.grayscale {
+            self.info.plane_lens().0
+        } else {
+            self.buf.len()
+        }
+    }
+
+    // Skips from the start of the bad frame header in `self.line` to the end
+    // of the next frame header. Returns false at the end of the stream.
+    fn skip_to_marker(&mut self, start: u64, complete: bool) -> io::Result<bool> {
+        let mut window = std::mem::take(&mut self.line);
+        if complete {
+            window.push(b'\n');
+        }
+        if let Some(i) = find_marker(&window) {
+            // The rest of the frame header was read with the line
+            self.skipped += i as u64;
+            return Ok(true);
+        }
+        window.drain(..window.len().saturating_sub(5));
+        loop {
+            let byte = match read_byte(&mut self.reader)? {
+                Some(byte) => byte,
+                None => {
+                    self.skipped += self.offset - start;
+                    return Ok(false);
+                }
+            };
+            self.offset += 1;
+            window.push(byte);
+            if window.len() > 6 {
+                window.remove(0);
+            }
+            if find_marker(&window).is_some() {
+                break;
+            }
+        }
+        self.skipped += self.offset - 6 - start;
+        if window[5] == b' ' {
+            let (len, complete) = read_line(&mut self.reader, &mut self.line)?;
+            self.offset += len as u64;
+            if !complete {
+                let start = self.offset - len as u64 - 6;
+                return Err(incomplete_line("Frame header", start, &self.line));
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl<R: BufRead> FrameSource for Y4mSource<R> {
+    fn info(&self) -> FrameInfo {
+        self.info
+    }
+
+    fn framerate(&self) -> Option<(usize, usize)> {
+        Some(self.framerate)
+    }
+
+    fn next_frame(&mut self) -> io::Result<Option<Frame<'_>>> {
+        let start = self.offset;
+        let (len, complete) = read_line(&mut self.reader, &mut self.line)?;
+        if len == 0 {
+            return Ok(None);
+        }
+        self.offset += len as u64;
+        let is_marker = complete && (self.line == b"FRAME" || self.line.starts_with(b"FRAME "));
+        if !is_marker {
+            if !self.resync {
+                if !complete {
+                    return Err(incomplete_line("Frame header", start, &self.line));
+                }
+                return Err(invalid_data(format!(
+                    "Expected a FRAME marker at byte {}",
+                    start
+                )));
+            }
+            if !self.skip_to_marker(start, complete)? {
+                return Ok(None);
+            }
+        }
+        let frame_len = self.frame_len();
+        match self.reader.read_exact(&mut self.buf[..frame_len]) {
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                return Err(invalid_data(format!(
+                    "Truncated frame at byte {}: expected {} bytes",
+                    self.offset, frame_len
+                )))
+            }
+            result => result?,
+        }
+        self.offset += frame_len as u64;
+        Ok(self.info.split_planes(&self.buf))
+    }
+
+    fn skipped_bytes(&self) -> u64 {
+        self.skipped
+    }
+
+    fn grayscale(&self) -> bool {
+        self.grayscale
+    }
+
+    fn bottom_field_first(&self) -> bool {
+        self.bottom_field_first
+    }
+
+    // Frame headers are taken to be a bare FRAME marker
+    fn frames_left(&self, stream_len: u64) -> Option<usize> {
+        let frame_len = (b"FRAME\n".len() + self.frame_len()) as u64;
+        Some((stream_len.saturating_sub(self.offset) / frame_len) as usize)
+    }
+}
+
+/// Headerless frames of a known format, stored back to back with planes laid
+/// out as in y4m.
+pub struct RawSource<R: Read> {
+    reader: R,
+    info: FrameInfo,
+    buf: Vec<u8>,
+    /// Bytes consumed from `reader`
+    offset: u64,
+}
+
+impl<R: Read> RawSource<R> {
+    /// Fails if `info` does not pass [`FrameInfo::check_size`].
+    pub fn new(reader: R, info: FrameInfo) -> io::Result<Self> {
+        info.check_size().map_err(invalid_data)?;
+        let (luma_len, chroma_len) = info.plane_lens();
+        Ok(RawSource {
+            reader,
+            info,
+            buf: vec![0; luma_len + 2 * chroma_len],
+            offset: 0,
+        })
+    }
+}
+
+impl<R: Read> FrameSource for RawSource<R> {
+    fn info(&self) -> FrameInfo {
+        self.info
+    }
+
+    fn next_frame(&mut self) -> io::Result<Option<Frame<'_>>> {
+        let mut filled = 0;
+        while filled < self.buf.len() {
+            match self.reader.read(&mut self.buf[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        if filled == 0 {
+            return Ok(None);
+        }
+        if filled < self.buf.len() {
+            return Err(invalid_data(format!(
+                "Truncated frame at byte {}: {} of {} bytes",
+                self.offset,
+                filled,
+                self.buf.len()
+            )));
+        }
+        self.offset += filled as u64;
+        Ok(self.info.split_planes(&self.buf))
+    }
+
+    fn frames_left(&self, stream_len: u64) -> Option<usize> {
+        Some((stream_len.saturating_sub(self.offset) / self.buf.len() as u64) as usize)
+    }
+}
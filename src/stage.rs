@@ -0,0 +1,121 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2019, the dump_ciede2000 contributors
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//  list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//  this list of conditions and the following disclaimer in the documentation
+//  and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Local copies of inputs on slow or remote storage, written as they are
+//! first read so that later runs on the same inputs read them locally.
+//!
+//! A copy is named after the path, size and modification time of its input,
+//! and only takes that name once the whole input has been read.
+
+use std::cell::RefCell;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::UNIX_EPOCH;
+
+use crate::baseline::InputHash;
+
+/// A complete copy of an input.
+pub struct StagedCopy {
+    pub path: PathBuf,
+    /// Hash of every byte of the input
+    pub hash: String,
+}
+
+/// Path of the copy of `input` in `dir`, which may not exist yet.
+pub fn staged_path(dir: &Path, input: &Path) -> io::Result<PathBuf> {
+    let metadata = fs::metadata(input)?;
+    let mut key = InputHash::new();
+    key.update(fs::canonicalize(input)?.to_string_lossy().as_bytes());
+    key.update(&metadata.len().to_le_bytes());
+    if let Some(modified) = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+    {
+        key.update(&modified.as_nanos().to_le_bytes());
+    }
+    let name = input.file_name().unwrap_or_default().to_string_lossy();
+    Ok(dir.join(format!("{}-{}", key.hex(), name)))
+}
+
+/// Copies everything read from `inner` to a partial file while hashing it,
+/// and moves the copy into place at the end of the input.
+pub struct StageReader<R: Read> {
+    inner: R,
+    /// The partial copy and the path it takes once complete
+    copy: Option<(BufWriter<File>, PathBuf, PathBuf)>,
+    hash: InputHash,
+    staged: Rc<RefCell<Option<StagedCopy>>>,
+}
+
+impl<R: Read> StageReader<R> {
+    pub fn new(inner: R, path: PathBuf) -> io::Result<Self> {
+        let mut partial = path.clone().into_os_string();
+        partial.push(".partial");
+        let partial = PathBuf::from(partial);
+        let out = BufWriter::new(File::create(&partial)?);
+        Ok(StageReader {
+            inner,
+            copy: Some((out, partial, path)),
+            hash: InputHash::new(),
+            staged: Rc::new(RefCell::new(None)),
+        })
+    }
+
+    /// The copy, once the input has been read to the end.
+    pub fn staged(&self) -> Rc<RefCell<Option<StagedCopy>>> {
+        self.staged.clone()
+    }
+}
+
+impl<R: Read> Read for StageReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        if let Some((out, partial, path)) = &mut self.copy {
+            let result = if len == 0 {
+                out.flush().and_then(|()| fs::rename(&*partial, &*path))
+            } else {
+                self.hash.update(&buf[..len]);
+                out.write_all(&buf[..len])
+            };
+            // The copy only saves time later, so the run goes on without it
+            if let Err(e) = result {
+                eprintln!("Failed to stage {}: {}", path.display(), e);
+                let _ = fs::remove_file(&*partial);
+                self.copy = None;
+            } else if len == 0 {
+                *self.staged.borrow_mut() = Some(StagedCopy {
+                    path: path.clone(),
+                    hash: self.hash.hex(),
+                });
+                self.copy = None;
+            }
+        }
+        Ok(len)
+    }
+}
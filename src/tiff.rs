@@ -0,0 +1,128 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2019, the dump_ciede2000 contributors
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//  list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//  this list of conditions and the following disclaimer in the documentation
+//  and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Uncompressed TIFF images of Lab planes, or of an earlier stage of the
+//! conversion, for `--dump-lab`.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// How L*, a* and b* are stored.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LabFormat {
+    /// As is, in 32-bit floating point
+    F32,
+    /// In 16 bits, with L* 0 to 100 and a* and b* -128 to 128 scaled to
+    /// the whole range
+    U16,
+}
+
+/// Writes `lab` as a `width` by `height` TIFF with the three samples of each
+/// pixel interleaved.
+pub fn write_lab_tiff(
+    path: &Path,
+    width: usize,
+    height: usize,
+    lab: &[[f32; 3]],
+    format: LabFormat,
+) -> io::Result<()> {
+    let (bits, sample_format) = match format {
+        LabFormat::F32 => (32, 3),
+        LabFormat::U16 => (16, 1),
+    };
+    let data_len = (width * height * 3 * bits / 8) as u32;
+    // The header, the IFD of 12 entries, then the BitsPerSample and
+    // SampleFormat values, which do not fit in their entries
+    const IFD_OFFSET: u32 = 8;
+    const VALUES_OFFSET: u32 = IFD_OFFSET + 2 + 12 * 12 + 4;
+    const DATA_OFFSET: u32 = VALUES_OFFSET + 12;
+    let mut out = BufWriter::new(File::create(path)?);
+    out.write_all(b"II*\0")?;
+    out.write_all(&IFD_OFFSET.to_le_bytes())?;
+    // Tag, type (3 for SHORT, 4 for LONG), count and value or offset
+    let entries: [(u16, u16, u32, u32); 12] = [
+        (256, 4, 1, width as u32),
+        (257, 4, 1, height as u32),
+        (258, 3, 3, VALUES_OFFSET),
+        // No compression
+        (259, 3, 1, 1),
+        // Black is zero, with the two extra samples below
+        (262, 3, 1, 1),
+        (273, 4, 1, DATA_OFFSET),
+        (277, 3, 1, 3),
+        (278, 4, 1, height as u32),
+        (279, 4, 1, data_len),
+        // Samples interleaved
+        (284, 3, 1, 1),
+        // Two unspecified extra samples, packed in the value
+        (338, 3, 2, 0),
+        (339, 3, 3, VALUES_OFFSET + 6),
+    ];
+    out.write_all(&(entries.len() as u16).to_le_bytes())?;
+    for (tag, kind, count, value) in entries {
+        out.write_all(&tag.to_le_bytes())?;
+        out.write_all(&kind.to_le_bytes())?;
+        out.write_all(&count.to_le_bytes())?;
+        out.write_all(&value.to_le_bytes())?;
+    }
+    // No further IFDs
+    out.write_all(&0u32.to_le_bytes())?;
+    for value in [
+        bits,
+        bits,
+        bits,
+        sample_format,
+        sample_format,
+        sample_format,
+    ] {
+        out.write_all(&(value as u16).to_le_bytes())?;
+    }
+    for &[l, a, b] in &lab[..width * height] {
+        match format {
+            LabFormat::F32 => {
+                for sample in [l, a, b] {
+                    out.write_all(&sample.to_le_bytes())?;
+                }
+            }
+            LabFormat::U16 => {
+                let scale = |value: f32, min: f32, max: f32| {
+                    ((value - min) / (max - min) * 65535.)
+                        .round()
+                        .clamp(0., 65535.) as u16
+                };
+                for sample in [
+                    scale(l, 0., 100.),
+                    scale(a, -128., 128.),
+                    scale(b, -128., 128.),
+                ] {
+                    out.write_all(&sample.to_le_bytes())?;
+                }
+            }
+        }
+    }
+    out.flush()
+}
@@ -0,0 +1,335 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2019, the dump_ciede2000 contributors
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//  list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//  this list of conditions and the following disclaimer in the documentation
+//  and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Traces of rows scored by a kernel, for checking later versions of the
+//! kernels against real content.
+//!
+//! A trace starts with [`TRACE_MAGIC`], followed by one record per row:
+//!
+//! - the kernel, as a byte, and the kL, kC and kH it was given, as `f32`
+//! - for each of the two rows, the bit depth, horizontal chroma subsampling,
+//!   matrix, range, transfer and vision as one byte each, then the Y, U and V
+//!   samples, each preceded by their length in bytes as a `u32`
+//! - the width as a `u32`, followed by the ΔE of each pixel as `f32`
+//!
+//! Numbers are little-endian.
+
+use std::io::{self, ErrorKind, Read, Write};
+
+use crate::{
+    get_delta_e_row_fn, get_delta_e_row_fn_approx, get_delta_e_row_fn_f64,
+    get_delta_e_row_fn_fixed, get_delta_e_row_fn_metric, DeltaERowFn, FrameInfo, FrameRow,
+    KSubArgs, Matrix, Metric, Range, SimdLevel, Transfer, Vision, YuvToRgb,
+};
+
+/// Bytes a trace starts with, which change with the format.
+pub const TRACE_MAGIC: &[u8; 8] = b"DE2KTRC1";
+
+/// Kernel a trace was recorded with.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TraceKernel {
+    /// From [`get_delta_e_row_fn`], or [`get_delta_e_row_fn_approx`] with
+    /// `approx_trig`
+    De2000 { approx_trig: bool },
+    /// From [`get_delta_e_row_fn_fixed`]
+    Fixed { approx_trig: bool },
+    /// From [`get_delta_e_row_fn_metric`], for metrics other than DE2000
+    Metric(Metric),
+    /// From [`get_delta_e_row_fn_f64`]
+    Reference,
+}
+
+impl TraceKernel {
+    /// The current version of the kernel, or `None` if there is none for
+    /// the format.
+    pub fn row_fn(
+        self,
+        bit_depth: usize,
+        xdec: usize,
+        simd: impl Into<SimdLevel>,
+    ) -> Option<DeltaERowFn> {
+        match self {
+            TraceKernel::De2000 { approx_trig: false } => {
+                Some(get_delta_e_row_fn(bit_depth, xdec, simd))
+            }
+            TraceKernel::De2000 { approx_trig: true } => {
+                Some(get_delta_e_row_fn_approx(bit_depth, xdec, simd))
+            }
+            TraceKernel::Fixed { approx_trig } => {
+                get_delta_e_row_fn_fixed(bit_depth, xdec, approx_trig)
+            }
+            TraceKernel::Metric(metric) => {
+                Some(get_delta_e_row_fn_metric(metric, bit_depth, xdec, simd))
+            }
+            TraceKernel::Reference => Some(get_delta_e_row_fn_f64(bit_depth, xdec)),
+        }
+    }
+
+    fn code(self) -> u8 {
+        match self {
+            TraceKernel::De2000 { approx_trig } => approx_trig as u8,
+            TraceKernel::Fixed { approx_trig } => 2 + approx_trig as u8,
+            TraceKernel::Metric(Metric::De2000) => 0,
+            TraceKernel::Metric(Metric::Itp) => 4,
+            TraceKernel::Metric(Metric::De76) => 5,
+            TraceKernel::Metric(Metric::De94) => 6,
+            TraceKernel::Metric(Metric::Cmc) => 7,
+            TraceKernel::Reference => 8,
+        }
+    }
+
+    fn from_code(code: u8) -> Option<Self> {
+        Some(match code {
+            0 | 1 => TraceKernel::De2000 {
+                approx_trig: code == 1,
+            },
+            2 | 3 => TraceKernel::Fixed {
+                approx_trig: code == 3,
+            },
+            4 => TraceKernel::Metric(Metric::Itp),
+            5 => TraceKernel::Metric(Metric::De76),
+            6 => TraceKernel::Metric(Metric::De94),
+            7 => TraceKernel::Metric(Metric::Cmc),
+            8 => TraceKernel::Reference,
+            _ => return None,
+        })
+    }
+}
+
+/// Writes rows with the ΔE a kernel gave them, to be checked with
+/// [`replay_trace`].
+pub struct TraceRecorder<W: Write> {
+    out: W,
+}
+
+impl<W: Write> TraceRecorder<W> {
+    pub fn new(mut out: W) -> io::Result<Self> {
+        out.write_all(TRACE_MAGIC)?;
+        Ok(TraceRecorder { out })
+    }
+
+    /// Records that `kernel` scored rows `row1` and `row2`, returned by
+    /// [`FrameInfo::row`] for `info1` and `info2`, as `delta_e`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn record(
+        &mut self,
+        kernel: TraceKernel,
+        ksub: KSubArgs,
+        info1: &FrameInfo,
+        row1: &FrameRow,
+        info2: &FrameInfo,
+        row2: &FrameRow,
+        delta_e: &[f32],
+    ) -> io::Result<()> {
+        let out = &mut self.out;
+        out.write_all(&[kernel.code()])?;
+        for k in [ksub.l, ksub.c, ksub.h] {
+            out.write_all(&k.to_le_bytes())?;
+        }
+        for (info, row) in [(info1, row1), (info2, row2)] {
+            out.write_all(&[
+                info.bit_depth as u8,
+                info.xdec as u8,
+                info.matrix as u8,
+                info.range as u8,
+                info.transfer as u8,
+                info.vision as u8,
+            ])?;
+            for plane in [row.y, row.u, row.v] {
+                out.write_all(&(plane.len() as u32).to_le_bytes())?;
+                out.write_all(plane)?;
+            }
+        }
+        out.write_all(&(delta_e.len() as u32).to_le_bytes())?;
+        for d in delta_e {
+            out.write_all(&d.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Flushes the trace and returns the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// Outcome of [`replay_trace`].
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct TraceReport {
+    pub rows: usize,
+    /// Rows with a pixel whose ΔE moved by more than the tolerance
+    pub mismatched_rows: usize,
+    /// Largest change in the ΔE of any pixel
+    pub max_difference: f32,
+}
+
+/// Scores every row of a trace again with the current kernels, comparing
+/// against the ΔE recorded. Pixels whose ΔE was NaN must still be.
+pub fn replay_trace<R: Read>(
+    mut input: R,
+    simd: impl Into<SimdLevel>,
+    tolerance: f32,
+) -> io::Result<TraceReport> {
+    let simd = simd.into();
+    let mut magic = [0u8; 8];
+    input.read_exact(&mut magic)?;
+    if &magic != TRACE_MAGIC {
+        return Err(invalid_data("not a ΔE trace".to_owned()));
+    }
+    let mut report = TraceReport::default();
+    let mut planes: [Vec<u8>; 6] = Default::default();
+    let mut expected = Vec::new();
+    let mut delta_e = Vec::new();
+    loop {
+        let mut code = [0u8];
+        match input.read_exact(&mut code) {
+            Err(ref e) if e.kind() == ErrorKind::UnexpectedEof => break,
+            result => result?,
+        }
+        let kernel = TraceKernel::from_code(code[0])
+            .ok_or_else(|| invalid_data(format!("unknown kernel {}", code[0])))?;
+        let ksub = KSubArgs {
+            l: read_f32(&mut input)?,
+            c: read_f32(&mut input)?,
+            h: read_f32(&mut input)?,
+        };
+        let mut formats = [[0u8; 6]; 2];
+        for (format, planes) in formats.iter_mut().zip(planes.chunks_mut(3)) {
+            input.read_exact(format)?;
+            for plane in planes {
+                let len = read_u32(&mut input)? as usize;
+                plane.resize(len, 0);
+                input.read_exact(plane)?;
+            }
+        }
+        let width = read_u32(&mut input)? as usize;
+        expected.clear();
+        for _ in 0..width {
+            expected.push(read_f32(&mut input)?);
+        }
+
+        let to_rgb = |format: &[u8; 6]| -> io::Result<YuvToRgb> {
+            let field =
+                |i: usize, name: &str| invalid_data(format!("invalid {} {}", name, format[i]));
+            Ok(YuvToRgb::new(
+                format[0] as usize,
+                match format[2] {
+                    0 => Matrix::Bt601,
+                    1 => Matrix::Bt709,
+                    2 => Matrix::Bt2020,
+                    3 => Matrix::Identity,
+                    _ => return Err(field(2, "matrix")),
+                },
+                match format[3] {
+                    0 => Range::Limited,
+                    1 => Range::Full,
+                    _ => return Err(field(3, "range")),
+                },
+                match format[4] {
+                    0 => Transfer::Sdr,
+                    1 => Transfer::Pq,
+                    2 => Transfer::Linear,
+                    _ => return Err(field(4, "transfer")),
+                },
+                match format[5] {
+                    0 => Vision::Normal,
+                    1 => Vision::Protanopia,
+                    2 => Vision::Deuteranopia,
+                    3 => Vision::Tritanopia,
+                    _ => return Err(field(5, "vision")),
+                },
+            ))
+        };
+        let (bit_depth, xdec) = (formats[0][0] as usize, formats[0][1] as usize);
+        if ![8, 10, 12].contains(&bit_depth) || xdec > 2 || formats[1][..2] != formats[0][..2] {
+            return Err(invalid_data(format!(
+                "unsupported format {}-bit, xdec {}",
+                bit_depth, xdec
+            )));
+        }
+        // The kernels take the rows to be of the width given
+        let bytewidth = if bit_depth > 8 { 2 } else { 1 };
+        let chroma_len = ((width + (1 << xdec) - 1) >> xdec) * bytewidth;
+        if planes.chunks(3).any(|row| {
+            row[0].len() != width * bytewidth
+                || row[1].len() != chroma_len
+                || row[2].len() != chroma_len
+        }) {
+            return Err(invalid_data(
+                "row lengths do not match the width".to_owned(),
+            ));
+        }
+        let row_fn = kernel
+            .row_fn(bit_depth, xdec, simd)
+            .ok_or_else(|| invalid_data(format!("no {:?} kernel for the format", kernel)))?;
+        let row1 = FrameRow {
+            y: &planes[0],
+            u: &planes[1],
+            v: &planes[2],
+            to_rgb: to_rgb(&formats[0])?,
+        };
+        let row2 = FrameRow {
+            y: &planes[3],
+            u: &planes[4],
+            v: &planes[5],
+            to_rgb: to_rgb(&formats[1])?,
+        };
+        delta_e.clear();
+        delta_e.resize(width, 0.);
+        unsafe { row_fn(row1, row2, ksub, &mut delta_e) };
+
+        let mut mismatched = false;
+        for (&a, &b) in delta_e.iter().zip(&expected) {
+            let difference = if a.is_nan() && b.is_nan() {
+                0.
+            } else if a.is_nan() || b.is_nan() {
+                f32::INFINITY
+            } else {
+                (a - b).abs()
+            };
+            report.max_difference = report.max_difference.max(difference);
+            mismatched |= difference > tolerance;
+        }
+        report.rows += 1;
+        report.mismatched_rows += mismatched as usize;
+    }
+    Ok(report)
+}
+
+fn read_u32<R: Read>(input: &mut R) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_f32<R: Read>(input: &mut R) -> io::Result<f32> {
+    read_u32(input).map(f32::from_bits)
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, msg)
+}
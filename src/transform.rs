@@ -0,0 +1,178 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2019, the dump_ciede2000 contributors
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//  list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//  this list of conditions and the following disclaimer in the documentation
+//  and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Rotation, flipping and cropping of whole frames.
+
+use crate::{Frame, FrameInfo};
+
+/// A rotation or flip, for inputs that had display orientation applied
+/// differently.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transform {
+    /// Rotates a quarter turn clockwise.
+    Rot90,
+    Rot180,
+    /// Mirrors left to right.
+    HFlip,
+    /// Mirrors top to bottom.
+    VFlip,
+}
+
+impl Transform {
+    /// Format of frames after the transform, or an error if chroma cannot
+    /// be rotated because it is subsampled differently in each direction or
+    /// interlaced.
+    pub fn apply_to(self, info: &FrameInfo) -> Result<FrameInfo, String> {
+        if self != Transform::Rot90 {
+            return Ok(*info);
+        }
+        if info.xdec != info.ydec || info.interlaced {
+            return Err("Only progressive 4:2:0 and 4:4:4 can be rotated by 90 degrees".to_owned());
+        }
+        Ok(FrameInfo {
+            width: info.height,
+            height: info.width,
+            ..*info
+        })
+    }
+}
+
+/// Applies `transform` to `frame` of the format `info`, writing the planes to
+/// `buf`. The frame is split back out with `split_planes(buf)` on the format
+/// returned by [`Transform::apply_to`].
+pub fn transform_frame(info: &FrameInfo, frame: &Frame, transform: Transform, buf: &mut Vec<u8>) {
+    let bytewidth = info.bytewidth();
+    let (luma_len, chroma_len) = info.plane_lens();
+    let (chroma_width, chroma_height) = info.chroma_dims();
+    buf.clear();
+    buf.reserve(luma_len + 2 * chroma_len);
+    let planes = [
+        (frame.y, info.width, info.height),
+        (frame.u, chroma_width, chroma_height),
+        (frame.v, chroma_width, chroma_height),
+    ];
+    for (plane, width, height) in planes {
+        let (out_width, out_height) = match transform {
+            Transform::Rot90 => (height, width),
+            _ => (width, height),
+        };
+        for y in 0..out_height {
+            for x in 0..out_width {
+                let (src_x, src_y) = match transform {
+                    Transform::Rot90 => (y, height - 1 - x),
+                    Transform::Rot180 => (width - 1 - x, height - 1 - y),
+                    Transform::HFlip => (width - 1 - x, y),
+                    Transform::VFlip => (x, height - 1 - y),
+                };
+                let i = (src_y * width + src_x) * bytewidth;
+                buf.extend_from_slice(&plane[i..i + bytewidth]);
+            }
+        }
+    }
+}
+
+/// A rectangle of a frame in luma samples, for scoring part of the picture.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Crop {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Crop {
+    /// Format of frames after the crop, or an error if the rectangle is not
+    /// inside the frame or its corner would split chroma samples.
+    pub fn apply_to(self, info: &FrameInfo) -> Result<FrameInfo, String> {
+        if self.width == 0
+            || self.height == 0
+            || self.x + self.width > info.width
+            || self.y + self.height > info.height
+        {
+            return Err(format!(
+                "{}x{} at {},{} is not inside the {}x{} frame",
+                self.width, self.height, self.x, self.y, info.width, info.height
+            ));
+        }
+        // Chroma of interlaced 4:2:0 alternates between fields every row, so
+        // a crop moves down in steps of two rows of each field
+        let y_step = if info.interlaced && info.ydec == 1 {
+            4
+        } else {
+            1 << info.ydec
+        };
+        let x_step = 1 << info.xdec;
+        if !self.x.is_multiple_of(x_step) || !self.y.is_multiple_of(y_step) {
+            return Err(format!(
+                "The corner must be on a multiple of {} across and {} down to keep chroma aligned",
+                x_step, y_step
+            ));
+        }
+        Ok(FrameInfo {
+            width: self.width,
+            height: self.height,
+            ..*info
+        })
+    }
+}
+
+/// Copies the rectangle `crop` of `frame` of the format `info` to `buf`. The
+/// frame is split back out with `split_planes(buf)` on the format returned by
+/// [`Crop::apply_to`].
+pub fn crop_frame(info: &FrameInfo, frame: &Frame, crop: Crop, buf: &mut Vec<u8>) {
+    let bytewidth = info.bytewidth();
+    let to = crop.apply_to(info).unwrap();
+    let (luma_len, chroma_len) = to.plane_lens();
+    let chroma_width = info.chroma_dims().0;
+    let (to_chroma_width, to_chroma_height) = to.chroma_dims();
+    buf.clear();
+    buf.reserve(luma_len + 2 * chroma_len);
+    let planes = [
+        (frame.y, info.width, crop.x, crop.y, crop.width, crop.height),
+        (
+            frame.u,
+            chroma_width,
+            crop.x >> info.xdec,
+            crop.y >> info.ydec,
+            to_chroma_width,
+            to_chroma_height,
+        ),
+        (
+            frame.v,
+            chroma_width,
+            crop.x >> info.xdec,
+            crop.y >> info.ydec,
+            to_chroma_width,
+            to_chroma_height,
+        ),
+    ];
+    for (plane, stride, x, y, width, height) in planes {
+        for row in y..y + height {
+            let start = (row * stride + x) * bytewidth;
+            buf.extend_from_slice(&plane[start..start + width * bytewidth]);
+        }
+    }
+}